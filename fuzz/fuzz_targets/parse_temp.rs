@@ -0,0 +1,29 @@
+//! The validating temperature parser against a string-based model: it must
+//! accept exactly `-?d{1,2}.d` and read it as tenths of a degree, and must
+//! not panic on anything else.
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use onebrc::fuzzing;
+
+fuzz_target!(|bytes: &[u8]| {
+    assert_eq!(fuzzing::parse_temp_checked(bytes), model(bytes));
+});
+
+fn model(bytes: &[u8]) -> Option<i32> {
+    let text = std::str::from_utf8(bytes).ok()?;
+    let digits = text.strip_prefix('-').unwrap_or(text);
+    let (whole, fraction) = digits.split_once('.')?;
+    let valid = (1..=2).contains(&whole.len())
+        && fraction.len() == 1
+        && whole
+            .bytes()
+            .chain(fraction.bytes())
+            .all(|b| b.is_ascii_digit());
+    if !valid {
+        return None;
+    }
+    let value: i32 = format!("{}{}", whole, fraction).parse().ok()?;
+    Some(if text.starts_with('-') { -value } else { value })
+}
@@ -0,0 +1,57 @@
+//! The line splitter and the `Records` parser against the lines the input
+//! was rendered from. Input is well-formed, as both assume: names free of
+//! `;` and `\n` and temperatures shaped `-?d{1,2}.d`, with or without a
+//! final newline.
+
+#![no_main]
+
+use arbitrary::Arbitrary;
+use libfuzzer_sys::fuzz_target;
+use onebrc::{fuzzing, Records};
+
+#[derive(Arbitrary, Debug)]
+struct Input {
+    lines: Vec<(Vec<u8>, i16)>,
+    final_newline: bool,
+}
+
+fuzz_target!(|input: Input| {
+    let mut data = Vec::new();
+    let mut expected = Vec::new();
+    for (name, temp) in input.lines {
+        let name: Vec<u8> = name
+            .into_iter()
+            .filter(|&b| b != b';' && b != b'\n')
+            .collect();
+        let temp = i32::from(temp) % 1000;
+        let sign = if temp < 0 { "-" } else { "" };
+        let text = format!("{}{}.{}", sign, temp.abs() / 10, temp.abs() % 10);
+        data.extend_from_slice(&name);
+        data.push(b';');
+        data.extend_from_slice(text.as_bytes());
+        data.push(b'\n');
+        expected.push((name, text.into_bytes(), temp));
+    }
+    if !input.final_newline {
+        data.pop();
+    }
+
+    let mut split = Vec::new();
+    fuzzing::for_each_record(&data, |name, temp| {
+        split.push((name.to_vec(), temp.to_vec()))
+    });
+    let want: Vec<_> = expected
+        .iter()
+        .map(|(name, text, _)| (name.clone(), text.clone()))
+        .collect();
+    assert_eq!(split, want);
+
+    let parsed: Vec<_> = Records::new(&data)
+        .map(|(name, temp)| (name.to_vec(), temp))
+        .collect();
+    let want: Vec<_> = expected
+        .into_iter()
+        .map(|(name, _, temp)| (name, temp))
+        .collect();
+    assert_eq!(parsed, want);
+});
@@ -0,0 +1,89 @@
+//! The station table against a `BTreeMap`, fed either one lookup at a time
+//! or through the batched and interleaved line loops. Tables start small so
+//! that they grow, and some names may be placed by a perfect hash first as
+//! with `--stations`.
+
+#![no_main]
+
+use std::collections::BTreeMap;
+
+use arbitrary::Arbitrary;
+use libfuzzer_sys::fuzz_target;
+use onebrc::fuzzing::{self, Station};
+
+#[derive(Arbitrary, Debug)]
+struct Input {
+    /// Initial slots, as a power of two.
+    slots_log2: u8,
+    batch: u8,
+    interleave: bool,
+    through_lines: bool,
+    /// Station names; updates and listed stations index into them.
+    names: Vec<Vec<u8>>,
+    listed: Vec<u8>,
+    updates: Vec<(u8, i16)>,
+}
+
+fuzz_target!(|input: Input| {
+    let names: Vec<Vec<u8>> = input
+        .names
+        .into_iter()
+        .map(|name| {
+            let mut name: Vec<u8> = name
+                .into_iter()
+                .filter(|&b| b != b';' && b != b'\n')
+                .collect();
+            name.truncate(fuzzing::MAX_NAME_LEN);
+            name
+        })
+        .filter(|name| !name.is_empty())
+        .collect();
+    if names.is_empty() {
+        return;
+    }
+    let name = |i: u8| &names[i as usize % names.len()];
+
+    let slots = 1 << (input.slots_log2 % 8 + 1);
+    let mut table = fuzzing::table(slots, input.batch.into(), input.interleave);
+    let mut listed: Vec<Box<[u8]>> = input.listed.iter().map(|&i| name(i)[..].into()).collect();
+    listed.sort_unstable();
+    listed.dedup();
+    if !listed.is_empty() {
+        fuzzing::seed_stations(&mut table, listed);
+    }
+
+    let mut model: BTreeMap<Vec<u8>, Station> = BTreeMap::new();
+    let mut lines = Vec::new();
+    for &(i, temp) in &input.updates {
+        let name = name(i);
+        let temp = i32::from(temp) % 1000;
+        if input.through_lines {
+            let sign = if temp < 0 { "-" } else { "" };
+            lines.extend_from_slice(name);
+            lines.extend_from_slice(
+                format!(";{}{}.{}\n", sign, temp.abs() / 10, temp.abs() % 10).as_bytes(),
+            );
+        } else {
+            fuzzing::lookup_or_insert(&mut table, name, temp);
+        }
+        let station = model.entry(name.clone()).or_insert_with(|| Station {
+            name: name.clone(),
+            min: i32::MAX,
+            max: i32::MIN,
+            sum: 0,
+            count: 0,
+        });
+        station.min = station.min.min(temp);
+        station.max = station.max.max(temp);
+        station.sum += i64::from(temp);
+        station.count += 1;
+    }
+    if input.through_lines {
+        fuzzing::aggregate_lines(&lines, &mut table);
+    }
+
+    assert_eq!(
+        fuzzing::stations(&table),
+        model.into_values().collect::<Vec<_>>()
+    );
+});
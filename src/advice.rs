@@ -0,0 +1,191 @@
+//! `--fadvise` and `--readahead`: advice to the kernel on how a local input
+//! is read. The default readahead window is sized for far smaller reads than
+//! a scan of gigabytes, so `--readahead` asks for the window of the file
+//! ahead of where the workers have got to, in the mapped and read backends.
+
+use std::fs::File;
+use std::io::{self, Read};
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use crate::cli::{Fadvise, Options};
+
+/// Whether this platform has `posix_fadvise`, which both flags need.
+pub const SUPPORTED: bool = cfg!(any(
+    target_os = "linux",
+    target_os = "android",
+    target_os = "freebsd",
+    target_os = "dragonfly",
+    target_os = "illumos"
+));
+
+enum Advice {
+    WillNeed,
+    NoReuse,
+    DontNeed,
+}
+
+/// Gives `advice` about `len` bytes of `file` from `offset`, 0 meaning to the
+/// end of the file.
+#[cfg(any(
+    target_os = "linux",
+    target_os = "android",
+    target_os = "freebsd",
+    target_os = "dragonfly",
+    target_os = "illumos"
+))]
+fn fadvise(file: &File, offset: u64, len: u64, advice: Advice) -> io::Result<()> {
+    use std::os::unix::io::AsRawFd;
+    let advice = match advice {
+        Advice::WillNeed => libc::POSIX_FADV_WILLNEED,
+        Advice::NoReuse => libc::POSIX_FADV_NOREUSE,
+        Advice::DontNeed => libc::POSIX_FADV_DONTNEED,
+    };
+    let offset = libc::off_t::try_from(offset).unwrap_or(libc::off_t::MAX);
+    let len = libc::off_t::try_from(len).unwrap_or(0);
+    // SAFETY: only advice about the pages of an open fd
+    match unsafe { libc::posix_fadvise(file.as_raw_fd(), offset, len, advice) } {
+        0 => Ok(()),
+        e => Err(io::Error::from_raw_os_error(e)),
+    }
+}
+
+#[cfg(not(any(
+    target_os = "linux",
+    target_os = "android",
+    target_os = "freebsd",
+    target_os = "dragonfly",
+    target_os = "illumos"
+)))]
+fn fadvise(_file: &File, _offset: u64, _len: u64, _advice: Advice) -> io::Result<()> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "posix_fadvise is not available here",
+    ))
+}
+
+/// Drops the cached pages of `file`, for `--evict-cache`.
+pub fn drop_cached(file: &File) -> io::Result<()> {
+    if !SUPPORTED && cfg!(target_os = "macos") {
+        return Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "posix_fadvise is not available here; `sudo purge` empties the whole cache",
+        ));
+    }
+    fadvise(file, 0, 0, Advice::DontNeed)
+}
+
+/// Gives the `--fadvise` advice about all `len` bytes of `file`.
+pub fn advise(file: &File, len: u64, opts: &Options) {
+    let advice = match opts.fadvise {
+        Some(Fadvise::WillNeed) => Advice::WillNeed,
+        Some(Fadvise::NoReuse) => Advice::NoReuse,
+        None => return,
+    };
+    if let Err(e) = fadvise(file, 0, len, advice) {
+        eprintln!("warning: --fadvise: {}", e);
+    }
+}
+
+/// `--readahead` over the bytes of a file from offset `origin` to `end`.
+/// Whoever reaches an offset asks for the window from there unless it has
+/// mostly been asked for already, so that consecutive chunks and reads do
+/// not each make a call.
+pub struct Prefetch {
+    file: File,
+    /// Address of the mapping of the bytes from `origin`, if they are mapped.
+    base: usize,
+    origin: u64,
+    end: u64,
+    window: u64,
+    /// Offset up to which the file has been asked for.
+    asked: AtomicU64,
+}
+
+impl Prefetch {
+    fn new(file: &File, base: usize, origin: u64, end: u64, opts: &Options) -> Option<Self> {
+        let window = opts.readahead?;
+        let file = file
+            .try_clone()
+            .map_err(|e| eprintln!("warning: --readahead: {}", e))
+            .ok()?;
+        Some(Self {
+            file,
+            base,
+            origin,
+            end,
+            window,
+            asked: AtomicU64::new(origin),
+        })
+    }
+
+    /// Prefetching for `mapping`, the bytes of `file` from offset `origin`.
+    pub fn mapped(file: &File, mapping: &[u8], origin: u64, opts: &Options) -> Option<Self> {
+        let base = mapping.as_ptr() as usize;
+        Self::new(file, base, origin, origin + mapping.len() as u64, opts)
+    }
+
+    /// Prefetching for reads of `file` from offset `start` to `end`.
+    pub fn read(file: &File, start: u64, end: u64, opts: &Options) -> Option<Self> {
+        Self::new(file, 0, start, end, opts)
+    }
+
+    /// Asks for the window from the start of `chunk`, if it lies in the
+    /// mapping: decompressed and transcoded buffers are not the file.
+    #[inline]
+    pub fn reached(&self, chunk: &[u8]) {
+        let addr = chunk.as_ptr() as usize;
+        let len = self.end - self.origin;
+        if addr < self.base || (addr - self.base) as u64 >= len {
+            return;
+        }
+        self.ahead(self.origin + (addr - self.base) as u64);
+    }
+
+    /// Asks for the window from `offset`.
+    pub fn ahead(&self, offset: u64) {
+        let asked = self.asked.load(Ordering::Relaxed);
+        if offset + self.window / 2 < asked {
+            return;
+        }
+        let from = offset.max(asked);
+        let to = (offset + self.window).min(self.end);
+        if to <= from {
+            return;
+        }
+        let claimed = self
+            .asked
+            .compare_exchange(asked, to, Ordering::Relaxed, Ordering::Relaxed);
+        if claimed.is_ok() {
+            let _ = fadvise(&self.file, from, to - from, Advice::WillNeed);
+        }
+    }
+}
+
+/// `inner`, which reads `file` from offset `start` to `end`, with
+/// `--readahead` asking for the window past each read.
+pub struct Readahead<R> {
+    inner: R,
+    prefetch: Option<Prefetch>,
+    offset: u64,
+}
+
+impl<R: Read> Readahead<R> {
+    pub fn new(inner: R, file: &File, start: u64, end: u64, opts: &Options) -> Self {
+        Self {
+            inner,
+            prefetch: Prefetch::read(file, start, end, opts),
+            offset: start,
+        }
+    }
+}
+
+impl<R: Read> Read for Readahead<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        self.offset += n as u64;
+        if let Some(prefetch) = &self.prefetch {
+            prefetch.ahead(self.offset);
+        }
+        Ok(n)
+    }
+}
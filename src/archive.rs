@@ -0,0 +1,86 @@
+#[cfg(feature = "archive")]
+use std::io::Read;
+
+/// Bytes needed to recognise a tar header.
+pub const TAR_SNIFF_LEN: usize = 512;
+
+pub fn is_zip(data: &[u8]) -> bool {
+    data.starts_with(b"PK\x03\x04")
+}
+
+/// Recognises a POSIX/GNU tar stream by the `ustar` magic in its first header.
+pub fn is_tar(head: &[u8]) -> bool {
+    head.len() >= 262 && &head[257..262] == b"ustar"
+}
+
+/// Shell-style glob match supporting `*` and `?`. `*` also matches `/`, so
+/// `*.txt` selects text files in any directory of the archive.
+#[cfg(feature = "archive")]
+pub fn glob_match(pattern: &[u8], name: &[u8]) -> bool {
+    let (mut p, mut n) = (0, 0);
+    // Position of the last `*` and the name index it is currently matched up to
+    let mut star: Option<(usize, usize)> = None;
+    while n < name.len() {
+        match pattern.get(p) {
+            Some(b'*') => {
+                star = Some((p, n));
+                p += 1;
+            }
+            Some(&c) if c == b'?' || c == name[n] => {
+                p += 1;
+                n += 1;
+            }
+            _ => match star {
+                Some((sp, sn)) => {
+                    star = Some((sp, sn + 1));
+                    p = sp + 1;
+                    n = sn + 1;
+                }
+                None => return false,
+            },
+        }
+    }
+    pattern[p..].iter().all(|&c| c == b'*')
+}
+
+/// Calls `f` with a decompressed stream for every regular file in the zip
+/// archive whose path matches `pattern`. Returns the number of members visited.
+#[cfg(feature = "archive")]
+pub fn for_each_zip_member(
+    data: &[u8],
+    pattern: &str,
+    mut f: impl FnMut(&mut dyn Read),
+) -> Result<usize, String> {
+    let mut zip = zip::ZipArchive::new(std::io::Cursor::new(data)).map_err(|e| e.to_string())?;
+    let mut visited = 0;
+    for i in 0..zip.len() {
+        let mut member = zip.by_index(i).map_err(|e| e.to_string())?;
+        if member.is_file() && glob_match(pattern.as_bytes(), member.name_raw()) {
+            f(&mut member);
+            visited += 1;
+        }
+    }
+    Ok(visited)
+}
+
+/// Calls `f` for every regular file in the tar stream whose path matches
+/// `pattern`. Members are read in archive order without extracting anything.
+#[cfg(feature = "archive")]
+pub fn for_each_tar_member<R: Read>(
+    reader: R,
+    pattern: &str,
+    mut f: impl FnMut(&mut dyn Read),
+) -> Result<usize, String> {
+    let mut tar = tar::Archive::new(reader);
+    let mut visited = 0;
+    for member in tar.entries().map_err(|e| e.to_string())? {
+        let mut member = member.map_err(|e| e.to_string())?;
+        if member.header().entry_type().is_file()
+            && glob_match(pattern.as_bytes(), &member.path_bytes())
+        {
+            f(&mut member);
+            visited += 1;
+        }
+    }
+    Ok(visited)
+}
@@ -0,0 +1,101 @@
+//! A deliberately naive aggregation, for `--baseline` and `--verify`: lines
+//! through a `BufReader`, stations in a `HashMap`, temperatures through
+//! `str::parse`. It shares nothing with the fast path except the output, so
+//! it serves as both a correctness oracle and the reference for speedups.
+
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, Read};
+
+use crate::cli::{Exit, Failure, Options};
+use crate::stats;
+use crate::{key_fn, Aggregator, StationStats, StationTable};
+
+/// Stations reported by [`verify`] before it gives up listing them.
+const MAX_REPORTED: usize = 10;
+
+/// Aggregates `opts.file_path`, a plain UTF-8 file, grouped by `--group-by`.
+pub fn aggregate(opts: &Options) -> io::Result<HashMap<Vec<u8>, StationStats>> {
+    let key = key_fn(opts);
+    let scale = stats::scale(opts.decimals);
+    let mut stations: HashMap<Vec<u8>, StationStats> = HashMap::new();
+    let file = File::open(&opts.file_path)?;
+    let lines = BufReader::new(file.take(opts.length.unwrap_or(u64::MAX))).lines();
+    let limit = opts.limit.map_or(usize::MAX, |rows| rows as usize);
+    for (number, line) in lines.take(limit).enumerate() {
+        let line = line?;
+        if opts.skip_comments && (line.is_empty() || line == "\r" || line.starts_with('#')) {
+            continue;
+        }
+        let invalid = || {
+            let msg = format!("line {} is not 'name;temperature': {}", number + 1, line);
+            io::Error::new(io::ErrorKind::InvalidData, msg)
+        };
+        let (name, temp) = line.rsplit_once(';').ok_or_else(invalid)?;
+        let temp: f64 = temp.parse().map_err(|_| invalid())?;
+        let name = key
+            .as_ref()
+            .map_or(name.as_bytes(), |key| key(name.as_bytes()));
+        stations
+            .entry(name.to_vec())
+            .or_default()
+            .update((temp * scale).round() as i32);
+    }
+    Ok(stations)
+}
+
+/// Aggregates the input again with [`aggregate`] and fails, listing the
+/// stations that differ from `table` on stderr, if any do.
+pub fn verify(table: &StationTable, opts: &Options) -> Result<(), Failure> {
+    let mut expected =
+        aggregate(opts).map_err(|e| Failure::io(format!("{}: {}", opts.file_path, e)))?;
+    let scale = stats::scale(opts.decimals);
+    let describe = |s: &StationStats| {
+        format!(
+            "min {}, max {}, mean {}, count {}",
+            s.min as f64 / scale,
+            s.max as f64 / scale,
+            s.sum as f64 / s.count as f64 / scale,
+            s.count
+        )
+    };
+
+    let mut diffs = Vec::new();
+    let mut total = 0;
+    for (name, stats) in table.iter() {
+        total += 1;
+        let name_str = String::from_utf8_lossy(name);
+        match expected.remove(name) {
+            Some(want) if want == *stats => {}
+            Some(want) => diffs.push(format!(
+                "{}: {}, but the baseline has {}",
+                name_str,
+                describe(stats),
+                describe(&want)
+            )),
+            None => diffs.push(format!("{}: missing from the baseline", name_str)),
+        }
+    }
+    total += expected.len();
+    for name in expected.keys() {
+        diffs.push(format!(
+            "{}: only in the baseline",
+            String::from_utf8_lossy(name)
+        ));
+    }
+    if diffs.is_empty() {
+        return Ok(());
+    }
+    diffs.sort();
+    for diff in diffs.iter().take(MAX_REPORTED) {
+        eprintln!("  {}", diff);
+    }
+    Err(Failure::new(
+        Exit::Mismatch,
+        format!(
+            "--verify: {} of {} stations differ from the baseline",
+            diffs.len(),
+            total
+        ),
+    ))
+}
@@ -0,0 +1,542 @@
+//! `onebrc bench FILE`: times repeated runs of the aggregation and writes a
+//! JSON record of them, with the build, machine and settings they ran with,
+//! so that performance can be tracked across commits. `--compare` reads an
+//! earlier record and flags a slowdown of the median past `--threshold`.
+//!
+//! Every run after the first finds the input in the page cache. For cold
+//! numbers, `--evict-cache` drops it before each run; the system-wide
+//! alternative is `sync; echo 3 | sudo tee /proc/sys/vm/drop_caches` on
+//! Linux, or `sudo purge` on macOS.
+
+use std::fs::{self, File};
+use std::io::{self, Write};
+use std::thread;
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+
+use crate::cli::{self, Exit, Fadvise, Io, Options};
+use crate::{advice, aggregate_and_report, calibrate, local_input_size, map};
+
+/// `git describe` of the tree the binary was built from, if it was a
+/// checkout.
+const GIT_DESCRIBE: &str = env!("ONEBRC_GIT_DESCRIBE");
+
+/// Features that change the hot loop, recorded with the timings.
+const FEATURES: [(&str, bool); 5] = [
+    ("fast-unsafe", cfg!(feature = "fast-unsafe")),
+    ("hashbrown", cfg!(feature = "hashbrown")),
+    ("std_map", cfg!(feature = "std_map")),
+    ("portable_simd", cfg!(feature = "portable_simd")),
+    ("rvv", cfg!(feature = "rvv")),
+];
+
+/// Drops the pages of the input from the page cache for `--evict-cache`, so
+/// that the next run reads it from the device. Dirty pages are written back
+/// first, as only clean ones can be dropped.
+pub fn evict_input(opts: &Options) {
+    let path = &opts.file_path;
+    if local_input_size(opts).is_none() {
+        cli::fail(Exit::Usage, "--evict-cache needs a local input file");
+    }
+    File::open(path)
+        .and_then(|file| {
+            file.sync_data()?;
+            advice::drop_cached(&file)
+        })
+        .unwrap_or_else(|e| cli::fatal(&format!("--evict-cache: {}: {}", path, e)));
+}
+
+/// The JSON a record is made of.
+#[derive(Debug, Clone, PartialEq)]
+enum Json {
+    Null,
+    Bool(bool),
+    Number(f64),
+    String(String),
+    Array(Vec<Json>),
+    Object(Vec<(String, Json)>),
+}
+
+impl Json {
+    fn text(s: &str) -> Json {
+        Json::String(s.to_string())
+    }
+
+    fn get(&self, key: &str) -> Option<&Json> {
+        match self {
+            Json::Object(members) => members.iter().find(|(k, _)| k == key).map(|(_, v)| v),
+            _ => None,
+        }
+    }
+
+    fn number(&self, key: &str) -> Option<f64> {
+        match self.get(key)? {
+            Json::Number(n) => Some(*n),
+            _ => None,
+        }
+    }
+
+    fn write(&self, indent: usize, out: &mut dyn Write) -> io::Result<()> {
+        match self {
+            Json::Null => write!(out, "null"),
+            Json::Bool(b) => write!(out, "{}", b),
+            Json::Number(n) => write!(out, "{}", n),
+            Json::String(s) => write!(out, "{}", crate::format::json_string(s)),
+            Json::Array(items) => {
+                write!(out, "[")?;
+                for (i, item) in items.iter().enumerate() {
+                    if i > 0 {
+                        write!(out, ", ")?;
+                    }
+                    item.write(indent, out)?;
+                }
+                write!(out, "]")
+            }
+            Json::Object(members) => {
+                writeln!(out, "{{")?;
+                for (i, (key, value)) in members.iter().enumerate() {
+                    write!(
+                        out,
+                        "{:indent$}{}: ",
+                        "",
+                        crate::format::json_string(key),
+                        indent = indent + 2
+                    )?;
+                    value.write(indent + 2, out)?;
+                    writeln!(out, "{}", if i + 1 < members.len() { "," } else { "" })?;
+                }
+                write!(out, "{:indent$}}}", "", indent = indent)
+            }
+        }
+    }
+}
+
+/// A reader of the JSON [`Json::write`] produces, and of any other.
+struct Parser<'a> {
+    s: &'a [u8],
+    pos: usize,
+}
+
+impl Parser<'_> {
+    fn parse(text: &str) -> Result<Json, String> {
+        let mut parser = Parser {
+            s: text.as_bytes(),
+            pos: 0,
+        };
+        let value = parser.value()?;
+        parser.skip_whitespace();
+        if parser.pos < parser.s.len() {
+            return Err(parser.error("trailing characters"));
+        }
+        Ok(value)
+    }
+
+    fn error(&self, msg: &str) -> String {
+        format!("{} at byte {}", msg, self.pos)
+    }
+
+    fn skip_whitespace(&mut self) {
+        while self.s.get(self.pos).is_some_and(u8::is_ascii_whitespace) {
+            self.pos += 1;
+        }
+    }
+
+    /// Consumes `b` after any whitespace, if it comes next.
+    fn eat(&mut self, b: u8) -> bool {
+        self.skip_whitespace();
+        let found = self.s.get(self.pos) == Some(&b);
+        self.pos += found as usize;
+        found
+    }
+
+    fn expect(&mut self, b: u8) -> Result<(), String> {
+        match self.eat(b) {
+            true => Ok(()),
+            false => Err(self.error(&format!("expected '{}'", b as char))),
+        }
+    }
+
+    fn value(&mut self) -> Result<Json, String> {
+        self.skip_whitespace();
+        let rest = &self.s[self.pos..];
+        for (literal, value) in [
+            ("null", Json::Null),
+            ("true", Json::Bool(true)),
+            ("false", Json::Bool(false)),
+        ] {
+            if rest.starts_with(literal.as_bytes()) {
+                self.pos += literal.len();
+                return Ok(value);
+            }
+        }
+        match rest.first() {
+            Some(b'"') => self.string().map(Json::String),
+            Some(b'[') => {
+                self.pos += 1;
+                let mut items = Vec::new();
+                if !self.eat(b']') {
+                    loop {
+                        items.push(self.value()?);
+                        if self.eat(b']') {
+                            break;
+                        }
+                        self.expect(b',')?;
+                    }
+                }
+                Ok(Json::Array(items))
+            }
+            Some(b'{') => {
+                self.pos += 1;
+                let mut members = Vec::new();
+                if !self.eat(b'}') {
+                    loop {
+                        self.skip_whitespace();
+                        let key = self.string()?;
+                        self.expect(b':')?;
+                        members.push((key, self.value()?));
+                        if self.eat(b'}') {
+                            break;
+                        }
+                        self.expect(b',')?;
+                    }
+                }
+                Ok(Json::Object(members))
+            }
+            Some(b'-' | b'0'..=b'9') => {
+                let len = rest
+                    .iter()
+                    .take_while(|b| matches!(b, b'-' | b'+' | b'.' | b'e' | b'E' | b'0'..=b'9'))
+                    .count();
+                let number = std::str::from_utf8(&rest[..len])
+                    .ok()
+                    .and_then(|n| n.parse().ok())
+                    .ok_or_else(|| self.error("invalid number"))?;
+                self.pos += len;
+                Ok(Json::Number(number))
+            }
+            _ => Err(self.error("expected a value")),
+        }
+    }
+
+    fn string(&mut self) -> Result<String, String> {
+        if self.s.get(self.pos) != Some(&b'"') {
+            return Err(self.error("expected a string"));
+        }
+        self.pos += 1;
+        let mut out = Vec::new();
+        loop {
+            let b = *self
+                .s
+                .get(self.pos)
+                .ok_or_else(|| self.error("unterminated string"))?;
+            self.pos += 1;
+            match b {
+                b'"' => break,
+                b'\\' => {
+                    let escape = self.s.get(self.pos).copied();
+                    self.pos += 1;
+                    let c = match escape {
+                        Some(b'"') => '"',
+                        Some(b'\\') => '\\',
+                        Some(b'/') => '/',
+                        Some(b'b') => '\u{8}',
+                        Some(b'f') => '\u{c}',
+                        Some(b'n') => '\n',
+                        Some(b'r') => '\r',
+                        Some(b't') => '\t',
+                        Some(b'u') => {
+                            let hex = self.s.get(self.pos..self.pos + 4);
+                            self.pos += 4;
+                            // Surrogate halves are not paired up, only replaced
+                            hex.and_then(|hex| std::str::from_utf8(hex).ok())
+                                .and_then(|hex| u32::from_str_radix(hex, 16).ok())
+                                .map(|code| char::from_u32(code).unwrap_or('\u{fffd}'))
+                                .ok_or_else(|| self.error("invalid \\u escape"))?
+                        }
+                        _ => return Err(self.error("invalid escape")),
+                    };
+                    out.extend_from_slice(c.encode_utf8(&mut [0; 4]).as_bytes());
+                }
+                b => out.push(b),
+            }
+        }
+        String::from_utf8(out).map_err(|_| self.error("string is not UTF-8"))
+    }
+}
+
+/// The CPU's model name, where the system says.
+fn cpu_model() -> Option<String> {
+    let info = fs::read_to_string("/proc/cpuinfo").ok()?;
+    info.lines()
+        .filter_map(|line| line.split_once(':'))
+        .find(|(key, _)| matches!(key.trim(), "model name" | "Model" | "uarch"))
+        .map(|(_, value)| value.trim().to_string())
+}
+
+/// Worker threads of a run with `opts`.
+fn threads(opts: &Options) -> usize {
+    match opts.threads {
+        0 => thread::available_parallelism().map_or(1, |n| n.get()),
+        n => n,
+    }
+}
+
+/// The settings a run's speed depends on.
+fn config(opts: &Options) -> Json {
+    let threads = threads(opts);
+    let stats: Vec<&str> = opts.stats.iter().map(|stat| stat.name()).collect();
+    let features = FEATURES
+        .iter()
+        .filter(|(_, enabled)| *enabled)
+        .map(|(name, _)| Json::text(name))
+        .collect();
+    Json::Object(vec![
+        (
+            "io".to_string(),
+            Json::text(match opts.io {
+                Io::Mmap => "mmap",
+                Io::Read => "read",
+            }),
+        ),
+        (
+            "fadvise".to_string(),
+            Json::text(match opts.fadvise {
+                Some(Fadvise::WillNeed) => "willneed",
+                Some(Fadvise::NoReuse) => "noreuse",
+                None => "none",
+            }),
+        ),
+        (
+            "readahead".to_string(),
+            opts.readahead
+                .map_or(Json::Null, |bytes| Json::Number(bytes as f64)),
+        ),
+        ("threads".to_string(), Json::Number(threads as f64)),
+        (
+            "chunk_size".to_string(),
+            Json::Number(opts.chunk_size as f64),
+        ),
+        (
+            "table_size".to_string(),
+            opts.table_size
+                .map_or(Json::Null, |slots| Json::Number(slots as f64)),
+        ),
+        ("batch".to_string(), Json::Number(opts.batch as f64)),
+        ("interleave".to_string(), Json::Bool(opts.interleave)),
+        ("two_pass".to_string(), Json::Bool(opts.two_pass)),
+        ("auto_tune".to_string(), Json::Bool(opts.auto_tune)),
+        ("processes".to_string(), Json::Number(opts.processes as f64)),
+        ("gpu".to_string(), Json::Bool(opts.gpu)),
+        ("engine".to_string(), Json::text(opts.engine.name())),
+        ("deterministic".to_string(), Json::Bool(opts.deterministic)),
+        ("evict_cache".to_string(), Json::Bool(opts.evict_cache)),
+        ("stats".to_string(), Json::text(&stats.join(","))),
+        ("decimals".to_string(), Json::Number(opts.decimals as f64)),
+        (
+            "map".to_string(),
+            Json::text(if map::ENABLED { map::NAME } else { "table" }),
+        ),
+        ("features".to_string(), Json::Array(features)),
+    ])
+}
+
+fn median(sorted: &[f64]) -> f64 {
+    let mid = sorted.len() / 2;
+    if sorted.len().is_multiple_of(2) {
+        (sorted[mid - 1] + sorted[mid]) / 2.0
+    } else {
+        sorted[mid]
+    }
+}
+
+/// Runs `onebrc bench`, writing the record of its runs to `out`.
+pub fn run(opts: &Options, out: &mut dyn Write) -> io::Result<()> {
+    let bytes = local_input_size(opts);
+    let calibration = opts.calibrate.then(|| {
+        if bytes.is_none() {
+            cli::fail(Exit::Usage, "--calibrate needs a local input file");
+        }
+        calibrate::measure(&opts.file_path, threads(opts))
+            .unwrap_or_else(|e| cli::fatal(&format!("{}: {}", opts.file_path, e)))
+    });
+    let mut timings = Vec::with_capacity(opts.runs);
+    for _ in 0..opts.runs {
+        if opts.evict_cache {
+            evict_input(opts);
+        }
+        let start = Instant::now();
+        aggregate_and_report(opts, &mut io::sink()).unwrap_or_else(|e| e.exit());
+        timings.push(start.elapsed().as_secs_f64());
+    }
+    let mut sorted = timings.clone();
+    sorted.sort_by(f64::total_cmp);
+    let best = sorted[0];
+    let median = median(&sorted);
+    let mean = timings.iter().sum::<f64>() / timings.len() as f64;
+    let throughput = bytes.map(|bytes| bytes as f64 / (1 << 20) as f64 / median);
+    let round = |seconds: f64| (seconds * 1e6).round() / 1e6;
+    let rate = |mib: f64| Json::Number((mib * 10.0).round() / 10.0);
+
+    let unix_time = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |since| since.as_secs());
+    let record = Json::Object(vec![
+        ("version".to_string(), Json::text(env!("CARGO_PKG_VERSION"))),
+        (
+            "git".to_string(),
+            Some(GIT_DESCRIBE)
+                .filter(|describe| !describe.is_empty())
+                .map_or(Json::Null, Json::text),
+        ),
+        ("unix_time".to_string(), Json::Number(unix_time as f64)),
+        (
+            "cpu".to_string(),
+            cpu_model().map_or(Json::Null, Json::String),
+        ),
+        (
+            "cpus".to_string(),
+            Json::Number(thread::available_parallelism().map_or(1, |n| n.get()) as f64),
+        ),
+        ("input".to_string(), Json::text(&opts.file_path)),
+        (
+            "bytes".to_string(),
+            bytes.map_or(Json::Null, |bytes| Json::Number(bytes as f64)),
+        ),
+        ("config".to_string(), config(opts)),
+        (
+            "timings".to_string(),
+            Json::Array(timings.iter().map(|&t| Json::Number(round(t))).collect()),
+        ),
+        ("best_seconds".to_string(), Json::Number(round(best))),
+        ("median_seconds".to_string(), Json::Number(round(median))),
+        ("mean_seconds".to_string(), Json::Number(round(mean))),
+        (
+            "throughput_mib_s".to_string(),
+            throughput.map_or(Json::Null, rate),
+        ),
+    ]);
+    let record = match (record, &calibration) {
+        (Json::Object(mut members), Some(calibration)) => {
+            let (device, memory) = calibration.lower_bounds(bytes.unwrap_or(0));
+            members.push((
+                "calibration".to_string(),
+                Json::Object(vec![
+                    ("device_read_mib_s".to_string(), rate(calibration.device)),
+                    ("device_cold".to_string(), Json::Bool(calibration.cold)),
+                    ("memory_read_mib_s".to_string(), rate(calibration.read)),
+                    ("memory_copy_mib_s".to_string(), rate(calibration.copy)),
+                    ("memory_triad_mib_s".to_string(), rate(calibration.triad)),
+                    (
+                        "lower_bound_device_seconds".to_string(),
+                        Json::Number(round(device)),
+                    ),
+                    (
+                        "lower_bound_memory_seconds".to_string(),
+                        Json::Number(round(memory)),
+                    ),
+                ]),
+            ));
+            Json::Object(members)
+        }
+        (record, _) => record,
+    };
+    record.write(0, out)?;
+    writeln!(out)?;
+
+    eprint!(
+        "bench: {} run{}, best {:.3} s, median {:.3} s, mean {:.3} s",
+        timings.len(),
+        if timings.len() == 1 { "" } else { "s" },
+        best,
+        median,
+        mean
+    );
+    match throughput {
+        Some(mib) => eprintln!(", {:.0} MiB/s at the median", mib),
+        None => eprintln!(),
+    }
+    if let Some(calibration) = &calibration {
+        let (device, memory) = calibration.lower_bounds(bytes.unwrap_or(0));
+        let cached = if calibration.cold {
+            ""
+        } else {
+            ", maybe cached"
+        };
+        eprintln!(
+            "calibrate: device reads {:.0} MiB/s{}",
+            calibration.device, cached
+        );
+        eprintln!(
+            "calibrate: memory {:.0} MiB/s read, {:.0} copy, {:.0} triad (threads: {})",
+            calibration.read,
+            calibration.copy,
+            calibration.triad,
+            threads(opts)
+        );
+        eprintln!(
+            "calibrate: at best {:.3} s from the device, {:.3} s from memory, {:.1}x under the median",
+            device,
+            memory,
+            median / memory
+        );
+    }
+    if let Some(path) = &opts.compare {
+        let previous =
+            fs::read_to_string(path).unwrap_or_else(|e| cli::fatal(&format!("{}: {}", path, e)));
+        let previous = Parser::parse(&previous)
+            .unwrap_or_else(|e| cli::fail(Exit::Data, &format!("{}: {}", path, e)));
+        if let Some(msg) = compare(&record, &previous, path, opts.threshold) {
+            out.flush()?;
+            cli::fail(Exit::Regression, &msg);
+        }
+    }
+    Ok(())
+}
+
+/// Prints how `record` differs from `previous`, returning the error to fail
+/// with if its median is more than `threshold` percent slower.
+fn compare(record: &Json, previous: &Json, path: &str, threshold: f64) -> Option<String> {
+    match previous.get("git") {
+        Some(Json::String(git)) => eprintln!("compared with {} ({}):", path, git),
+        _ => eprintln!("compared with {}:", path),
+    }
+    let mut regression = None;
+    for (key, label) in [
+        ("best_seconds", "best"),
+        ("median_seconds", "median"),
+        ("mean_seconds", "mean"),
+    ] {
+        let (Some(now), Some(before)) = (record.number(key), previous.number(key)) else {
+            continue;
+        };
+        let delta = (now - before) / before * 100.0;
+        let flagged = key == "median_seconds" && delta > threshold;
+        eprintln!(
+            "  {:<8} {:>9.3} s -> {:>9.3} s  {:+6.1}%{}",
+            label,
+            before,
+            now,
+            delta,
+            if flagged { "  regression" } else { "" }
+        );
+        if flagged {
+            regression = Some(format!(
+                "the median is {:.1}% slower than in {}, over the {}% threshold",
+                delta, path, threshold
+            ));
+        }
+    }
+    for key in ["cpu", "cpus", "bytes"] {
+        if record.get(key) != previous.get(key) {
+            eprintln!("  note: {} differs from the earlier record", key);
+        }
+    }
+    if let (Some(Json::Object(now)), Some(before)) = (record.get("config"), previous.get("config"))
+    {
+        for (key, value) in now {
+            if before.get(key) != Some(value) {
+                eprintln!("  note: config {} differs from the earlier record", key);
+            }
+        }
+    }
+    regression
+}
@@ -0,0 +1,113 @@
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+
+/// Time limit of a run with `--max-seconds`. Aggregation asks it before every
+/// chunk and skips the rest of the input once it has run out, counting the
+/// bytes it did aggregate so that the partial results can say what they
+/// cover.
+pub struct Budget {
+    seconds: f64,
+    deadline: Instant,
+    total: AtomicU64,
+    covered: AtomicU64,
+    expired: AtomicBool,
+}
+
+impl Budget {
+    pub fn new(seconds: f64) -> Self {
+        Self {
+            seconds,
+            deadline: Instant::now() + Duration::from_secs_f64(seconds),
+            total: AtomicU64::new(0),
+            covered: AtomicU64::new(0),
+            expired: AtomicBool::new(false),
+        }
+    }
+
+    /// Whether a chunk of `bytes` may still be aggregated, counting it if so.
+    pub fn admit(&self, bytes: usize) -> bool {
+        if self.expired() {
+            return false;
+        }
+        if Instant::now() >= self.deadline {
+            self.expired.store(true, Ordering::Relaxed);
+            return false;
+        }
+        self.covered.fetch_add(bytes as u64, Ordering::Relaxed);
+        true
+    }
+
+    /// Whether some input was skipped for lack of time.
+    pub fn expired(&self) -> bool {
+        self.expired.load(Ordering::Relaxed)
+    }
+
+    /// Records the input size, when it is known up front.
+    pub fn set_total(&self, bytes: u64) {
+        self.total.store(bytes, Ordering::Relaxed);
+    }
+
+    /// One line for stderr marking the results as partial.
+    pub fn summary(&self) -> String {
+        let mib = |bytes: u64| bytes as f64 / (1 << 20) as f64;
+        let covered = self.covered.load(Ordering::Relaxed);
+        let covers = match self.total.load(Ordering::Relaxed) {
+            0 => format!("the first {:.1} MiB of the input", mib(covered)),
+            total => format!(
+                "{:.1}% of the input ({:.1} of {:.1} MiB)",
+                covered as f64 * 100.0 / total as f64,
+                mib(covered),
+                mib(total)
+            ),
+        };
+        format!(
+            "partial results: --max-seconds {} ran out, covering {}",
+            self.seconds, covers
+        )
+    }
+}
+
+/// Row limit of a run with `--limit`, for streamed input, which arrives in
+/// order: each chunk is cut after the rows that are left. Mapped input is
+/// cut to its first rows up front instead.
+pub struct RowLimit {
+    remaining: AtomicU64,
+}
+
+impl RowLimit {
+    pub fn new(rows: u64) -> Self {
+        Self {
+            remaining: AtomicU64::new(rows),
+        }
+    }
+
+    /// The rows of `data` that are still within the limit, counting them.
+    pub fn admit<'a>(&self, data: &'a [u8]) -> &'a [u8] {
+        let remaining = self.remaining.load(Ordering::Relaxed);
+        let data = first_rows(data, remaining);
+        self.remaining
+            .store(remaining - count_rows(data), Ordering::Relaxed);
+        data
+    }
+
+    /// Whether no more rows will be aggregated.
+    pub fn reached(&self) -> bool {
+        self.remaining.load(Ordering::Relaxed) == 0
+    }
+}
+
+/// The lines of `data`, counting a last one without a newline.
+pub fn count_rows(data: &[u8]) -> u64 {
+    memchr::memchr_iter(b'\n', data).count() as u64
+        + u64::from(!data.is_empty() && !data.ends_with(b"\n"))
+}
+
+/// The first `rows` lines of `data`, newlines included.
+pub fn first_rows(data: &[u8], rows: u64) -> &[u8] {
+    match rows {
+        0 => &[],
+        _ => memchr::memchr_iter(b'\n', data)
+            .nth(rows as usize - 1)
+            .map_or(data, |i| &data[..=i]),
+    }
+}
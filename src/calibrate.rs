@@ -0,0 +1,136 @@
+//! `bench --calibrate`: how fast this machine could run at best, from the
+//! sequential read speed of the input's device and a STREAM-like measure of
+//! memory bandwidth, so that timings from very different hardware can be
+//! put side by side. A run can go no faster than reading its input once,
+//! from the device when the page cache is cold and from memory when warm.
+
+use std::fs::File;
+use std::hint::black_box;
+use std::io::{self, Read};
+use std::thread;
+use std::time::Instant;
+
+use crate::advice::drop_cached;
+
+/// At most this much of the input is read to time the device.
+const READ_LIMIT: u64 = 1 << 30;
+const READ_BUF_SIZE: usize = 8 << 20;
+
+/// Elements of each array the memory kernels run over, 128 MiB of them:
+/// far more than any cache.
+const ELEMENTS: usize = 16 << 20;
+/// Repetitions of each kernel, of which the fastest counts.
+const REPS: usize = 5;
+
+fn mib_per_s(bytes: u64, seconds: f64) -> f64 {
+    bytes as f64 / (1 << 20) as f64 / seconds
+}
+
+/// What [`measure`] found, in MiB/s.
+pub struct Calibration {
+    /// Sequential reads of the input, and whether its pages could be
+    /// dropped from the cache first.
+    pub device: f64,
+    pub cold: bool,
+    /// Memory bandwidth summing one array, copying one to another, and
+    /// computing `a = b + s * c` across all worker threads.
+    pub read: f64,
+    pub copy: f64,
+    pub triad: f64,
+}
+
+impl Calibration {
+    /// The least time a run over `bytes` of input could take, from the
+    /// device and from memory.
+    pub fn lower_bounds(&self, bytes: u64) -> (f64, f64) {
+        let mib = bytes as f64 / (1 << 20) as f64;
+        (mib / self.device, mib / self.read)
+    }
+}
+
+/// Measures the device of the file at `path` and memory with `threads`
+/// workers.
+pub fn measure(path: &str, threads: usize) -> io::Result<Calibration> {
+    let (device, cold) = device_read(path)?;
+    let (read, copy, triad) = memory_bandwidth(threads);
+    Ok(Calibration {
+        device,
+        cold,
+        read,
+        copy,
+        triad,
+    })
+}
+
+/// Reads the start of the file at `path` after dropping it from the page
+/// cache where the system allows.
+fn device_read(path: &str) -> io::Result<(f64, bool)> {
+    let mut file = File::open(path)?;
+    let cold = file.sync_data().and_then(|()| drop_cached(&file)).is_ok();
+    let mut buf = vec![0u8; READ_BUF_SIZE];
+    let mut total = 0u64;
+    let start = Instant::now();
+    while total < READ_LIMIT {
+        match file.read(&mut buf)? {
+            0 => break,
+            n => total += n as u64,
+        }
+    }
+    Ok((mib_per_s(total, start.elapsed().as_secs_f64()), cold))
+}
+
+/// Runs `kernel` `REPS` times, returning the fastest time in seconds.
+fn fastest(mut kernel: impl FnMut()) -> f64 {
+    (0..REPS)
+        .map(|_| {
+            let start = Instant::now();
+            kernel();
+            start.elapsed().as_secs_f64()
+        })
+        .fold(f64::INFINITY, f64::min)
+}
+
+/// Read, copy and triad bandwidths in MiB/s.
+fn memory_bandwidth(threads: usize) -> (f64, f64, f64) {
+    let part = ELEMENTS.div_ceil(threads);
+    let mut a = vec![0.0f64; ELEMENTS];
+    let b = vec![1.0f64; ELEMENTS];
+    let c = vec![2.0f64; ELEMENTS];
+    let array = (ELEMENTS * size_of::<f64>()) as u64;
+
+    let read = fastest(|| {
+        thread::scope(|scope| {
+            for b in b.chunks(part) {
+                scope.spawn(move || {
+                    // Integer xor, so that the loop vectorizes
+                    black_box(b.iter().fold(0u64, |acc, x| acc ^ x.to_bits()))
+                });
+            }
+        })
+    });
+    let copy = fastest(|| {
+        thread::scope(|scope| {
+            for (a, b) in a.chunks_mut(part).zip(b.chunks(part)) {
+                scope.spawn(move || a.copy_from_slice(b));
+            }
+        })
+    });
+    let scalar = black_box(3.0);
+    let triad = fastest(|| {
+        thread::scope(|scope| {
+            for ((a, b), c) in a.chunks_mut(part).zip(b.chunks(part)).zip(c.chunks(part)) {
+                scope.spawn(move || {
+                    for ((a, b), c) in a.iter_mut().zip(b).zip(c) {
+                        *a = b + scalar * c;
+                    }
+                });
+            }
+        })
+    });
+    black_box(&a);
+    (
+        mib_per_s(array, read),
+        mib_per_s(2 * array, copy),
+        mib_per_s(3 * array, triad),
+    )
+}
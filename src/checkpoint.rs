@@ -0,0 +1,134 @@
+//! `--checkpoint` and `--resume`: the input offset reached and the table so
+//! far, written every `--checkpoint-every` bytes, so that an interrupted run
+//! of hours over a huge remote file can continue from where it stopped
+//! instead of starting over. Offsets are those of the input as stored, so
+//! only uncompressed UTF-8 text can be checkpointed.
+
+use std::fs::{self, File};
+use std::io::{self, Write};
+
+use crate::cli::{Exit, Failure, Options};
+use crate::format::thousands;
+use crate::{partial, Aggregator, StationTable};
+
+/// Starts every checkpoint, followed by a version byte, the offset and the
+/// input (its length, then the path), then a `--format partial` dump.
+const MAGIC: &[u8; 8] = b"1BRCCKPT";
+const VERSION: u8 = 1;
+
+/// Where and how often a run writes its checkpoints.
+pub struct Checkpoints {
+    path: String,
+    every: u64,
+    /// Offset of the first byte the run aggregates.
+    start: u64,
+    /// Offset past which the next checkpoint is due.
+    next: u64,
+    opts: Options,
+}
+
+impl Checkpoints {
+    /// The checkpoints `opts` asks for, if any. With `--resume`, `table` is
+    /// filled from the checkpoint, and the run goes on at its offset and
+    /// writes the next ones to the same file unless `--checkpoint` is given.
+    pub fn setup<A: Aggregator>(
+        opts: &Options,
+        table: &mut StationTable<A>,
+    ) -> Result<Option<Self>, Failure> {
+        let Some(path) = opts.checkpoint.as_ref().or(opts.resume.as_ref()) else {
+            return Ok(None);
+        };
+        if !A::default().encode(&mut Vec::new()) {
+            return Err(Failure::usage(
+                "this accumulator has no binary encoding for --checkpoint",
+            ));
+        }
+        let mut start = 0;
+        if let Some(resume) = &opts.resume {
+            let data = fs::read(resume).map_err(|e| Failure::io(format!("{}: {}", resume, e)))?;
+            start = load(&data, opts, table)
+                .map_err(|e| Failure::new(Exit::Data, format!("{}: {}", resume, e)))?;
+            eprintln!("resuming {} at byte {}", opts.file_path, thousands(start));
+        }
+        Ok(Some(Checkpoints {
+            path: path.clone(),
+            every: opts.checkpoint_every,
+            start,
+            next: start + opts.checkpoint_every,
+            opts: opts.clone(),
+        }))
+    }
+
+    pub fn start(&self) -> u64 {
+        self.start
+    }
+
+    pub fn every(&self) -> u64 {
+        self.every
+    }
+
+    /// Moves the start past `bytes` that are not aggregated, such as a byte
+    /// order mark.
+    pub fn skip(&mut self, bytes: u64) {
+        self.start += bytes;
+        self.next += bytes;
+    }
+
+    /// Notes that `table` holds every line before `offset`, writing a
+    /// checkpoint if one is due. A failed write is reported and the run
+    /// goes on, to try again at the next.
+    pub fn reached<A: Aggregator>(&mut self, offset: u64, table: &StationTable<A>) {
+        if offset < self.next {
+            return;
+        }
+        self.next = offset + self.every;
+        if let Err(e) = self.write(offset, table) {
+            eprintln!("warning: writing checkpoint {}: {}", self.path, e);
+        }
+    }
+
+    /// Writes the checkpoint next to its file and renames it into place, so
+    /// that an interruption midway leaves the previous one intact.
+    fn write<A: Aggregator>(&self, offset: u64, table: &StationTable<A>) -> io::Result<()> {
+        let input = self.opts.file_path.as_bytes();
+        let mut buf = MAGIC.to_vec();
+        buf.push(VERSION);
+        buf.extend_from_slice(&offset.to_le_bytes());
+        buf.extend_from_slice(&(input.len() as u32).to_le_bytes());
+        buf.extend_from_slice(input);
+        // Checked in `setup`
+        buf.extend(partial::encode_dump(table, &self.opts).unwrap());
+
+        let tmp = format!("{}.tmp", self.path);
+        let mut file = File::create(&tmp)?;
+        file.write_all(&buf)?;
+        file.sync_all()?;
+        fs::rename(&tmp, &self.path)
+    }
+}
+
+/// Merges the table of a checkpoint into `table`, returning its offset.
+fn load<A: Aggregator>(
+    data: &[u8],
+    opts: &Options,
+    table: &mut StationTable<A>,
+) -> Result<u64, String> {
+    let mut body = data.strip_prefix(MAGIC).ok_or("not a checkpoint")?;
+    let truncated = || "truncated checkpoint".to_string();
+    let [version] = partial::take(&mut body).ok_or_else(truncated)?;
+    if version != VERSION {
+        return Err(format!("unsupported checkpoint version {}", version));
+    }
+    let offset = u64::from_le_bytes(partial::take(&mut body).ok_or_else(truncated)?);
+    let len = u32::from_le_bytes(partial::take(&mut body).ok_or_else(truncated)?);
+    let (input, dump) = body.split_at_checked(len as usize).ok_or_else(truncated)?;
+    if input != opts.file_path.as_bytes() {
+        return Err(format!(
+            "checkpoint of {}, not {}",
+            String::from_utf8_lossy(input),
+            opts.file_path
+        ));
+    }
+    partial::merge_dump(dump, opts, table)?;
+    Ok(offset)
+}
@@ -0,0 +1,1739 @@
+use std::env;
+use std::fmt;
+use std::io;
+use std::process;
+
+use crate::advice;
+use crate::compress::Compression;
+use crate::config;
+use crate::decode::Encoding;
+use crate::stats::{Kind, Stat};
+use crate::KeyFn;
+
+/// Order in which stations are printed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Order {
+    /// Raw byte order of the station names (fast, the 1BRC spec order).
+    Bytes,
+    /// Unicode collation for the given locale (requires the `collation` feature).
+    Collate,
+    /// The order in which the stations first appear in the input.
+    Input,
+}
+
+/// Shape of the printed results.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    /// The 1BRC `{name=min/mean/max, ...}` line.
+    Spec,
+    /// `name\tmin/mean/max\0` records, for `xargs -0` and other tools that
+    /// take any byte in a name but NUL.
+    Print0,
+    Csv,
+    /// A JSON array of per-station objects.
+    Json,
+    /// A FeatureCollection of stations located through `--geo`.
+    GeoJson,
+    /// A GitHub-flavored Markdown table.
+    Markdown,
+    /// Padded columns for reading in a terminal.
+    Table,
+    /// SQL that loads the results into a table.
+    Sql,
+    /// A table in the SQLite database at `--output`.
+    Sqlite,
+    /// A typed Parquet file at `--output`.
+    Parquet,
+    /// The aggregate itself in binary, for `merge`.
+    Partial,
+}
+
+impl Format {
+    /// Binary formats that write the `--output` file themselves.
+    pub fn owns_output(self) -> bool {
+        matches!(self, Format::Sqlite | Format::Parquet)
+    }
+}
+
+/// Statements written by `--format sql`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SqlStyle {
+    /// Multi-row `INSERT`s, for any database.
+    Insert,
+    /// A Postgres `COPY ... FROM stdin` block, for psql.
+    Copy,
+}
+
+/// What happens to stations missing from `--stations`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Unknown {
+    /// Aggregate them too, in the general table.
+    Insert,
+    /// Drop their lines.
+    Skip,
+    /// Stop with an error naming the station.
+    Error,
+}
+
+/// Stations `generate` picks from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Names {
+    /// The cities of the 1BRC generator.
+    Cities,
+    /// Names made to be a worst case for the station table.
+    Adversarial,
+}
+
+/// How local files are read.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Io {
+    /// Map the file and aggregate it in place.
+    Mmap,
+    /// Read the file on a dedicated thread into buffers the parser consumes.
+    Read,
+}
+
+/// How the workers over an uncompressed UTF-8 file are organised.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Engine {
+    /// A pool of threads taking chunks of the mapping in turn.
+    Pool,
+    /// A thread per core reading its own share of the file, with nothing
+    /// shared until the final merge.
+    Tpc,
+    /// A rayon parallel iterator over the chunks of the mapping.
+    Rayon,
+    /// A reader thread queueing chunks for parser threads.
+    Pipeline,
+}
+
+impl Engine {
+    pub fn name(self) -> &'static str {
+        match self {
+            Engine::Pool => "pool",
+            Engine::Tpc => "tpc",
+            Engine::Rayon => "rayon",
+            Engine::Pipeline => "pipeline",
+        }
+    }
+}
+
+/// Advice about the pages of a local file given with `--fadvise`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Fadvise {
+    /// Start reading the whole file into the page cache.
+    WillNeed,
+    /// Each page is read once, so it need not stay cached.
+    NoReuse,
+}
+
+/// How station names that are not valid UTF-8 are printed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InvalidUtf8 {
+    /// Replace each invalid byte with a `\xNN` escape.
+    Escape,
+    /// Replace invalid sequences with U+FFFD.
+    Lossy,
+}
+
+/// When `--color` highlights the printed results.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Color {
+    /// When stdout is a terminal and `NO_COLOR` is not set.
+    Auto,
+    Always,
+    Never,
+}
+
+impl Color {
+    /// Settles `Auto` for output to a terminal or not.
+    pub fn resolve(self, terminal: bool) -> Color {
+        match self {
+            Color::Auto if terminal && env::var_os("NO_COLOR").is_none() => Color::Always,
+            Color::Auto => Color::Never,
+            color => color,
+        }
+    }
+}
+
+/// How station names are turned into the key results are grouped by.
+#[derive(Clone)]
+pub enum GroupBy {
+    /// Every distinct name is its own group.
+    Station,
+    /// The part of the name before the first occurrence of the separator,
+    /// e.g. `Country/City` grouped by country.
+    Prefix(String),
+    /// The name up to the first space.
+    FirstWord,
+    /// The `;`-separated columns of the name from `.0` to `.1`, counted
+    /// from 0, e.g. station and sensor of a `station;sensor;temp` row.
+    Columns(usize, usize),
+    /// A key function supplied by a library user.
+    Custom(KeyFn),
+}
+
+impl fmt::Debug for GroupBy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            GroupBy::Station => write!(f, "Station"),
+            GroupBy::Prefix(sep) => f.debug_tuple("Prefix").field(sep).finish(),
+            GroupBy::FirstWord => write!(f, "FirstWord"),
+            GroupBy::Columns(first, last) => {
+                f.debug_tuple("Columns").field(first).field(last).finish()
+            }
+            GroupBy::Custom(_) => write!(f, "Custom(..)"),
+        }
+    }
+}
+
+/// What the invocation does.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Command {
+    /// Aggregate an input file and print the results.
+    Aggregate,
+    /// Accept `station;temp` lines on a socket and answer snapshot queries.
+    Ingest,
+    /// Answer aggregation requests on a Unix socket, caching the tables.
+    Daemon,
+    /// Consume `station;temp` messages from a Kafka topic.
+    Kafka,
+    /// Combine `--format partial` dumps and print the results.
+    Merge,
+    /// Report invalid and suspicious lines of an input file.
+    Quality,
+    /// Time repeated aggregations of an input file.
+    Bench,
+    /// Write a measurements file of random readings.
+    Generate,
+    /// Print a completion script for a shell.
+    Completions(Shell),
+}
+
+/// Shells `onebrc completions` writes a script for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Shell {
+    Bash,
+    Zsh,
+    Fish,
+}
+
+#[derive(Debug, Clone)]
+pub struct Options {
+    pub command: Command,
+    pub file_path: String,
+    pub order: Order,
+    pub locale: String,
+    pub invalid_utf8: InvalidUtf8,
+    /// Highlighting of extremes and warnings in spec and table output. `main`
+    /// settles `Auto` before anything is printed.
+    pub color: Color,
+    /// Leave the input mapped once it is aggregated. `main` sets it for a
+    /// single aggregation, which the process exits right after, so that a
+    /// large file is not unmapped first; the daemon and `bench` aggregate
+    /// again and unmap each input.
+    pub keep_mapped: bool,
+    /// Statistics printed for each station, in order.
+    pub stats: Vec<Stat>,
+    /// Fraction digits of the input temperatures, which are parsed exactly
+    /// as integers scaled by `10^decimals`.
+    pub decimals: u32,
+    /// Skip blank lines and lines starting with `#` in the input.
+    pub skip_comments: bool,
+    /// Station names may be quoted, as `"Foo;Bar";12.3`, with `""` for a
+    /// quote inside.
+    pub quoted_names: bool,
+    pub group_by: GroupBy,
+    /// Printed between the columns of a key from a wide row instead of `;`.
+    pub key_separator: Option<String>,
+    pub format: Format,
+    /// Range in degrees of the global histogram, if requested.
+    pub histogram: Option<(f64, f64)>,
+    pub histogram_only: bool,
+    /// Count the readings outside `plausible` for each station and list them
+    /// on stderr at the end.
+    pub sanity_check: bool,
+    /// Range in degrees of the temperatures `--sanity-check` accepts.
+    pub plausible: (f64, f64),
+    /// Standard deviations beyond which a station's min or max is flagged.
+    pub flag_outliers: Option<f64>,
+    /// List each station's first and last reading and where they are.
+    pub first_last: bool,
+    /// Show a live dashboard on stderr while aggregating.
+    pub tui: bool,
+    /// Print the execution plan to stderr before running.
+    pub explain: bool,
+    /// Print peak memory and the table sizes used to stderr after running.
+    pub run_stats: bool,
+    /// Print hardware counters of the aggregation to stderr (Linux only).
+    pub perf: bool,
+    /// Write a pprof CPU profile of the run to this file.
+    pub profile: Option<String>,
+    /// Bytes of a local input to read, which a raw block device needs since
+    /// it has no file size to go by.
+    pub length: Option<u64>,
+    /// Byte range of the input whose lines are aggregated, for sharding a
+    /// file across runs.
+    pub start_byte: u64,
+    pub end_byte: Option<u64>,
+    /// File the offset reached and the table so far are written to every
+    /// `checkpoint_every` bytes of input.
+    pub checkpoint: Option<String>,
+    pub checkpoint_every: u64,
+    /// Checkpoint an interrupted run continues from.
+    pub resume: Option<String>,
+    /// Rows after which the rest of the input is skipped.
+    pub limit: Option<u64>,
+    /// Time limit after which the rest of the input is skipped.
+    pub max_seconds: Option<f64>,
+    /// Where the chunks of mapped input and the thread aggregating each are
+    /// recorded, or replayed from.
+    pub record_schedule: Option<String>,
+    pub replay_schedule: Option<String>,
+    /// Deal fixed chunks to the workers in turn and merge in worker order.
+    pub deterministic: bool,
+    /// Rows between snapshots of the results so far, and the file they are
+    /// appended to instead of stdout.
+    pub emit_every: Option<u64>,
+    pub emit_to: Option<String>,
+    /// Aggregate with the naive implementation in `baseline` instead.
+    pub baseline: bool,
+    /// Check the results against the naive implementation.
+    pub verify: bool,
+    pub io: Io,
+    /// Advice about the input's pages, and bytes of it to ask for ahead of
+    /// the workers.
+    pub fadvise: Option<Fadvise>,
+    pub readahead: Option<u64>,
+    /// Worker threads for in-memory inputs; 0 means one per CPU.
+    pub threads: usize,
+    /// Bytes of input handed to a worker at a time.
+    pub chunk_size: usize,
+    /// Initial table slots, a power of two; `None` picks by input size.
+    pub table_size: Option<usize>,
+    /// Lines parsed ahead of their table updates, 1 to 64.
+    pub batch: usize,
+    /// Parse two halves of each chunk in lockstep.
+    pub interleave: bool,
+    /// Collect station names from a sample of the input before aggregating.
+    pub two_pass: bool,
+    /// File listing the expected stations, looked up with a perfect hash.
+    pub stations: Option<String>,
+    pub unknown_stations: Unknown,
+    /// Calibrate threads and chunk size on the start of the input.
+    pub auto_tune: bool,
+    /// Parse uncompressed UTF-8 files on the GPU.
+    pub gpu: bool,
+    pub engine: Engine,
+    /// Child processes to split uncompressed files across; 1 means none.
+    pub processes: usize,
+    /// File the results are written to instead of stdout.
+    pub output: Option<String>,
+    /// CSV of station locations joined into structured output.
+    pub geo: Option<String>,
+    /// Suffix temperatures in `--format table` with their unit.
+    pub units: bool,
+    /// Table `--format sql` loads into, and how.
+    pub sql_table: String,
+    pub sql_style: SqlStyle,
+    /// Input encoding; `None` means detect from the byte order mark.
+    pub encoding: Option<Encoding>,
+    /// Glob selecting which members of a zip/tar input are aggregated.
+    pub members: String,
+    /// Parallel ranged requests used for http(s) and s3 inputs.
+    pub connections: usize,
+    /// Socket address for `ingest`, as `unix:PATH` or `tcp:HOST:PORT`.
+    pub listen: String,
+    /// Where `ingest` and `kafka` log the lines they skip.
+    pub rejects: Option<String>,
+    /// Dumps combined by `merge`.
+    pub partials: Vec<String>,
+    /// Address for the ingest server's Arrow Flight endpoint, if enabled.
+    pub flight: Option<String>,
+    pub brokers: Vec<String>,
+    pub topic: String,
+    pub group: String,
+    /// Seconds between snapshots printed by long-running consumers.
+    pub snapshot_every: u64,
+    /// Aggregations `bench` times.
+    pub runs: usize,
+    /// Drop the input from the page cache before reading it.
+    pub evict_cache: bool,
+    /// Measure the device and memory before `bench` times its runs.
+    pub calibrate: bool,
+    /// Earlier `bench` record to compare with.
+    pub compare: Option<String>,
+    /// Percentage by which the median of `bench` may be slower than in
+    /// `compare` before it fails.
+    pub threshold: f64,
+    /// Lines `generate` writes, and the seed of their random readings.
+    pub rows: u64,
+    pub seed: u64,
+    /// Files `generate` splits its rows across.
+    pub shards: usize,
+    /// Fraction of the lines `generate` writes malformed.
+    pub corrupt_rate: f64,
+    pub names: Names,
+    /// Format and level `generate` compresses its output with.
+    pub compress: Option<(Compression, u32)>,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Self {
+            command: Command::Aggregate,
+            file_path: "measurements.txt".to_string(),
+            order: Order::Bytes,
+            locale: "und".to_string(),
+            invalid_utf8: InvalidUtf8::Escape,
+            color: Color::Auto,
+            keep_mapped: false,
+            stats: vec![Stat::Min, Stat::Mean, Stat::Max],
+            decimals: 1,
+            skip_comments: false,
+            quoted_names: false,
+            group_by: GroupBy::Station,
+            key_separator: None,
+            format: Format::Spec,
+            sanity_check: false,
+            plausible: (-90.0, 60.0),
+            histogram: None,
+            histogram_only: false,
+            flag_outliers: None,
+            first_last: false,
+            tui: false,
+            explain: false,
+            run_stats: false,
+            perf: false,
+            profile: None,
+            length: None,
+            start_byte: 0,
+            end_byte: None,
+            checkpoint: None,
+            checkpoint_every: 1 << 30,
+            resume: None,
+            limit: None,
+            max_seconds: None,
+            record_schedule: None,
+            replay_schedule: None,
+            deterministic: false,
+            emit_every: None,
+            emit_to: None,
+            baseline: false,
+            verify: false,
+            io: Io::Mmap,
+            fadvise: None,
+            readahead: None,
+            threads: 0,
+            chunk_size: crate::MAPPED_CHUNK_SIZE,
+            table_size: None,
+            batch: crate::BATCH_SIZE,
+            interleave: false,
+            two_pass: false,
+            stations: None,
+            unknown_stations: Unknown::Insert,
+            auto_tune: false,
+            gpu: false,
+            engine: Engine::Pool,
+            processes: 1,
+            output: None,
+            geo: None,
+            units: false,
+            sql_table: "station_stats".to_string(),
+            sql_style: SqlStyle::Insert,
+            encoding: None,
+            members: "*".to_string(),
+            connections: 4,
+            listen: "unix:/tmp/1brc.sock".to_string(),
+            rejects: None,
+            partials: Vec::new(),
+            flight: None,
+            brokers: vec!["localhost:9092".to_string()],
+            topic: "measurements".to_string(),
+            group: "onebrc".to_string(),
+            snapshot_every: 10,
+            runs: 5,
+            evict_cache: false,
+            calibrate: false,
+            compare: None,
+            threshold: 5.0,
+            rows: 1_000_000_000,
+            seed: 1,
+            shards: 1,
+            corrupt_rate: 0.0,
+            names: Names::Cities,
+            compress: None,
+        }
+    }
+}
+
+pub(crate) struct Flag {
+    pub name: &'static str,
+    pub value: Option<&'static str>,
+    pub help: &'static str,
+}
+
+/// The subcommands, with what they do, for shell completion.
+pub(crate) const COMMANDS: &[(&str, &str)] = &[
+    (
+        "ingest",
+        "accept station;temp lines on a socket and answer snapshot queries",
+    ),
+    (
+        "daemon",
+        "aggregate files for requests on a Unix socket, caching the results",
+    ),
+    ("kafka", "consume station;temp messages from a Kafka topic"),
+    (
+        "merge",
+        "combine --format partial dumps and print the results",
+    ),
+    (
+        "quality",
+        "report invalid and suspicious lines of an input file",
+    ),
+    (
+        "bench",
+        "time repeated runs and write a JSON record of them",
+    ),
+    (
+        "generate",
+        "write a measurements file of random readings, as the 1BRC generator",
+    ),
+    ("completions", "print a bash, zsh or fish completion script"),
+];
+
+pub(crate) const FLAGS: &[Flag] = &[
+    Flag {
+        name: "order",
+        value: Some("bytes|collate|input"),
+        help: "station output order (default: bytes)",
+    },
+    Flag {
+        name: "locale",
+        value: Some("TAG"),
+        help: "BCP 47 locale used by --order collate (default: und)",
+    },
+    Flag {
+        name: "invalid-utf8",
+        value: Some("escape|lossy"),
+        help: "how to print names that are not UTF-8 (default: escape)",
+    },
+    Flag {
+        name: "color",
+        value: Some("auto|always|never"),
+        help: "highlight extremes and warnings in spec and table output (default: auto)",
+    },
+    Flag {
+        name: "stats",
+        value: Some("LIST"),
+        help: "stats to print: min,max,mean,count,stddev,median,min_count,max_count (default: min,mean,max)",
+    },
+    Flag {
+        name: "decimals",
+        value: Some("N"),
+        help: "fraction digits of the input temperatures, 1-3 (default: 1)",
+    },
+    Flag {
+        name: "skip-comments",
+        value: None,
+        help: "skip blank lines and lines starting with # in the input",
+    },
+    Flag {
+        name: "quoted-names",
+        value: None,
+        help: "read names in double quotes, which may hold ';', as in \"Foo;Bar\";12.3 (\"\" for a quote)",
+    },
+    Flag {
+        name: "group-by",
+        value: Some("station|prefix:SEP|first-word|columns:LIST"),
+        help: "key stations are rolled up by; columns:1,2 keys rows like station;sensor;temp by both (default: station)",
+    },
+    Flag {
+        name: "key-separator",
+        value: Some("SEP"),
+        help: "print SEP between the columns of a key from a wide row instead of ';'",
+    },
+    Flag {
+        name: "format",
+        value: Some("spec|print0|csv|json|geojson|markdown|table|sql|sqlite|parquet|partial"),
+        help: "output format (default: spec, the 1BRC brace format)",
+    },
+    Flag {
+        name: "print0",
+        value: None,
+        help: "short for --format print0: a STATION<TAB>STATS record per station, ended by NUL",
+    },
+    Flag {
+        name: "output",
+        value: Some("FILE"),
+        help: "write the results to FILE instead of stdout (required by sqlite, parquet)",
+    },
+    Flag {
+        name: "geo",
+        value: Some("FILE"),
+        help: "CSV of name,lat,lon[,elevation] joined into all formats but spec and partial",
+    },
+    Flag {
+        name: "sql-table",
+        value: Some("NAME"),
+        help: "sql/sqlite: table the results are loaded into (default: station_stats)",
+    },
+    Flag {
+        name: "sql-style",
+        value: Some("insert|copy"),
+        help: "sql: INSERT statements or a Postgres COPY block (default: insert)",
+    },
+    Flag {
+        name: "units",
+        value: None,
+        help: "table: print temperatures with a °C suffix",
+    },
+    Flag {
+        name: "histogram",
+        value: Some("LO:HI"),
+        help: "also print a histogram of all readings from LO to HI degrees",
+    },
+    Flag {
+        name: "histogram-only",
+        value: None,
+        help: "print only the histogram (default range: -99.9:99.9)",
+    },
+    Flag {
+        name: "sanity-check",
+        value: None,
+        help: "count readings outside a plausible range per station and list them on stderr",
+    },
+    Flag {
+        name: "plausible",
+        value: Some("LO:HI"),
+        help: "range in degrees --sanity-check accepts (default: -90:60)",
+    },
+    Flag {
+        name: "flag-outliers",
+        value: Some("K"),
+        help: "list stations whose min or max is over K stddevs from the mean",
+    },
+    Flag {
+        name: "first-last",
+        value: None,
+        help: "also list each station's first and last reading, with their byte offsets, to spot drift",
+    },
+    Flag {
+        name: "tui",
+        value: None,
+        help: "show live progress and the hottest/coldest stations on stderr",
+    },
+    Flag {
+        name: "io",
+        value: Some("mmap|read"),
+        help: "how local files are read (default: mmap)",
+    },
+    Flag {
+        name: "fadvise",
+        value: Some("willneed|noreuse|none"),
+        help: "advise the kernel how the pages of a local file are used (default: none)",
+    },
+    Flag {
+        name: "readahead",
+        value: Some("KB"),
+        help: "ask for the next KB KiB of a local file ahead of the workers",
+    },
+    Flag {
+        name: "threads",
+        value: Some("N"),
+        help: "worker threads for uncompressed files (default: one per CPU)",
+    },
+    Flag {
+        name: "chunk-size",
+        value: Some("BYTES"),
+        help: "input handed to a worker at a time, e.g. 4M (default: 16M)",
+    },
+    Flag {
+        name: "table-size",
+        value: Some("SLOTS"),
+        help: "initial table slots, a power of two (default: 65536)",
+    },
+    Flag {
+        name: "batch",
+        value: Some("N"),
+        help: "lines parsed ahead of their table lookups, up to 64 (default: 16)",
+    },
+    Flag {
+        name: "interleave",
+        value: None,
+        help: "parse two halves of each chunk in lockstep instead of in batches",
+    },
+    Flag {
+        name: "two-pass",
+        value: None,
+        help: "find the stations in a sample first, then aggregate into a table sized for them",
+    },
+    Flag {
+        name: "stations",
+        value: Some("FILE"),
+        help: "expected stations, one per line (e.g. weather_stations.csv), for a perfect hash",
+    },
+    Flag {
+        name: "unknown-stations",
+        value: Some("insert|skip|error"),
+        help: "what to do with stations not in --stations (default: insert)",
+    },
+    Flag {
+        name: "auto-tune",
+        value: None,
+        help: "pick threads and chunk size by timing the start of the input",
+    },
+    Flag {
+        name: "engine",
+        value: Some("pool|tpc|rayon|pipeline"),
+        help: "workers for uncompressed UTF-8 files: a thread pool, a thread per core, rayon, or a reader feeding parsers (default: pool)",
+    },
+    Flag {
+        name: "processes",
+        value: Some("N"),
+        help: "split uncompressed files across N forked processes",
+    },
+    Flag {
+        name: "gpu",
+        value: None,
+        help: "find lines and parse temperatures on the GPU (experimental)",
+    },
+    Flag {
+        name: "explain",
+        value: None,
+        help: "print the execution plan (I/O, threads, table, memory) first",
+    },
+    Flag {
+        name: "run-stats",
+        value: None,
+        help: "print peak RSS, the table and name arena sizes, and rows, time and probes per worker, afterwards",
+    },
+    Flag {
+        name: "perf",
+        value: None,
+        help: "print cycles, instructions, IPC, branch and LLC misses of the aggregation (Linux)",
+    },
+    Flag {
+        name: "profile",
+        value: Some("FILE"),
+        help: "write a pprof CPU profile of the run to FILE (`profiling` feature)",
+    },
+    Flag {
+        name: "length",
+        value: Some("BYTES"),
+        help: "read only the first BYTES of the input; needed for a block device like /dev/nvme0n1p3",
+    },
+    Flag {
+        name: "start-byte",
+        value: Some("OFFSET"),
+        help: "aggregate only the lines starting at or after OFFSET, e.g. 1G",
+    },
+    Flag {
+        name: "end-byte",
+        value: Some("OFFSET"),
+        help: "aggregate only the lines starting before OFFSET",
+    },
+    Flag {
+        name: "checkpoint",
+        value: Some("FILE"),
+        help: "write the offset reached and the table so far to FILE as the run goes, for --resume",
+    },
+    Flag {
+        name: "checkpoint-every",
+        value: Some("SIZE"),
+        help: "input aggregated between checkpoints (default: 1G)",
+    },
+    Flag {
+        name: "resume",
+        value: Some("FILE"),
+        help: "continue an interrupted run from its checkpoint FILE, checkpointing there too",
+    },
+    Flag {
+        name: "limit",
+        value: Some("N"),
+        help: "aggregate only the first N rows, e.g. for smoke tests on a large file",
+    },
+    Flag {
+        name: "max-seconds",
+        value: Some("SECS"),
+        help: "stop after SECS seconds and print results for the input covered so far",
+    },
+    Flag {
+        name: "emit-every",
+        value: Some("N"),
+        help: "also write the results so far after every N rows, e.g. 100000000",
+    },
+    Flag {
+        name: "emit-to",
+        value: Some("FILE"),
+        help: "append the --emit-every snapshots to FILE instead of stdout",
+    },
+    Flag {
+        name: "record-schedule",
+        value: Some("FILE"),
+        help: "debug: write the chunks the input is split into and the thread taking each to FILE",
+    },
+    Flag {
+        name: "replay-schedule",
+        value: Some("FILE"),
+        help: "debug: split the input and hand its chunks to threads as recorded in FILE",
+    },
+    Flag {
+        name: "deterministic",
+        value: None,
+        help: "debug: deal fixed chunks to the threads in turn, so runs differ only by real bugs",
+    },
+    Flag {
+        name: "baseline",
+        value: None,
+        help: "aggregate with the simple reference implementation instead",
+    },
+    Flag {
+        name: "verify",
+        value: None,
+        help: "also aggregate with the reference implementation and fail on any difference",
+    },
+    Flag {
+        name: "input-encoding",
+        value: Some("auto|utf-8|utf-16le|utf-16be|LABEL"),
+        help: "input text encoding, e.g. latin1 (default: auto, from the BOM)",
+    },
+    Flag {
+        name: "members",
+        value: Some("GLOB"),
+        help: "archive members to aggregate (default: *)",
+    },
+    Flag {
+        name: "connections",
+        value: Some("N"),
+        help: "parallel range requests for http(s) and s3 inputs (default: 4)",
+    },
+    Flag {
+        name: "listen",
+        value: Some("unix:PATH|tcp:ADDR"),
+        help: "ingest, daemon: socket to listen on (default: unix:/tmp/1brc.sock)",
+    },
+    Flag {
+        name: "rejects",
+        value: Some("FILE"),
+        help: "ingest, kafka: append each line skipped as malformed to FILE, with where and why",
+    },
+    Flag {
+        name: "flight",
+        value: Some("HOST:PORT"),
+        help: "ingest: also serve results over Arrow Flight",
+    },
+    Flag {
+        name: "brokers",
+        value: Some("HOST:PORT,..."),
+        help: "kafka: bootstrap brokers (default: localhost:9092)",
+    },
+    Flag {
+        name: "topic",
+        value: Some("NAME"),
+        help: "kafka: topic to consume (default: measurements)",
+    },
+    Flag {
+        name: "group",
+        value: Some("NAME"),
+        help: "kafka: consumer group for committed offsets (default: onebrc)",
+    },
+    Flag {
+        name: "snapshot-every",
+        value: Some("SECS"),
+        help: "kafka: seconds between printed snapshots (default: 10)",
+    },
+    Flag {
+        name: "runs",
+        value: Some("N"),
+        help: "bench: aggregations to time (default: 5)",
+    },
+    Flag {
+        name: "evict-cache",
+        value: None,
+        help: "drop the input from the page cache first (before each bench run), for cold timings",
+    },
+    Flag {
+        name: "calibrate",
+        value: None,
+        help: "bench: also measure device read speed and memory bandwidth, and the least time possible",
+    },
+    Flag {
+        name: "compare",
+        value: Some("FILE"),
+        help: "bench: earlier record to print deltas against, failing on a regression",
+    },
+    Flag {
+        name: "threshold",
+        value: Some("PCT"),
+        help: "bench: slowdown of the median --compare counts as a regression (default: 5)",
+    },
+    Flag {
+        name: "rows",
+        value: Some("N"),
+        help: "generate: lines to write (default: 1000000000)",
+    },
+    Flag {
+        name: "shards",
+        value: Some("N"),
+        help: "generate: split the lines across N files, FILE with -0, -1, ... before its extensions",
+    },
+    Flag {
+        name: "seed",
+        value: Some("N"),
+        help: "generate: seed of the random readings, the same for the same file (default: 1)",
+    },
+    Flag {
+        name: "corrupt-rate",
+        value: Some("P"),
+        help: "generate: fraction of lines written malformed, e.g. 0.001 (default: 0)",
+    },
+    Flag {
+        name: "names",
+        value: Some("cities|adversarial"),
+        help: "generate: the 1BRC cities, or names made to be a worst case for the station table (default: cities)",
+    },
+    Flag {
+        name: "compress",
+        value: Some("gzip|zstd|xz|bzip2[:LEVEL]"),
+        help: "generate: compress the output, e.g. zstd:3 (default level: gzip 6, zstd 3, xz 6, bzip2 9)",
+    },
+    Flag {
+        name: "config",
+        value: Some("FILE"),
+        help: "defaults for these flags, as key = value lines (default: ~/.config/1brc.toml)",
+    },
+    Flag {
+        name: "help",
+        value: None,
+        help: "print this help and exit",
+    },
+];
+
+pub fn usage() -> String {
+    let mut out = String::from(
+        "usage: onebrc [OPTIONS] [FILE|-]\n       onebrc ingest [--listen ADDR] [OPTIONS]\n       onebrc daemon [--listen unix:PATH]\n       onebrc kafka [--brokers LIST] [--topic NAME] [OPTIONS]\n       onebrc merge [OPTIONS] DUMP...\n       onebrc quality [OPTIONS] [FILE]\n       onebrc bench [--runs N] [--compare FILE] [OPTIONS] [FILE]\n       onebrc generate [--rows N] [--shards N] [--names cities|adversarial] [--corrupt-rate P]\n                       [--compress FORMAT[:LEVEL]] [FILE|-]\n       onebrc completions bash|zsh|fish\n\noptions:\n",
+    );
+    for flag in FLAGS {
+        let left = match flag.value {
+            Some(v) => format!("--{} <{}>", flag.name, v),
+            None => format!("--{}", flag.name),
+        };
+        if left.len() > 30 {
+            out.push_str(&format!("  {}\n  {:<30} {}\n", left, "", flag.help));
+        } else {
+            out.push_str(&format!("  {:<30} {}\n", left, flag.help));
+        }
+    }
+    out.push_str(
+        "\nEach option can also be set in the environment, as ONEBRC_ and its name in capitals\nwith '_' for '-' (ONEBRC_TABLE_SIZE=4096, ONEBRC_UNITS=1). The command line overrides\nthe environment, which overrides the config file (also taken from ONEBRC_CONFIG).\n\nexit status: 0 success, 1 usage error, 2 I/O error, 3 invalid input data,\n4 --verify mismatch, 5 worker or GPU failure, 6 bench regression.\n",
+    );
+    out
+}
+
+/// The exit status of a failed run, so that scripts can branch on the kind
+/// of failure instead of matching stderr. A successful run exits with 0.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Exit {
+    /// Invalid options, or an input or option this build cannot handle.
+    Usage = 1,
+    /// Reading the input or writing the results failed.
+    Io = 2,
+    /// The input broke a rule the run enforces, such as a station missing
+    /// from `--stations` under `--unknown-stations error`, or a corrupt dump.
+    Data = 3,
+    /// `--verify` found results that differ from the baseline.
+    Mismatch = 4,
+    /// A worker process or the GPU failed.
+    Internal = 5,
+    /// `bench --compare` found the median slower than `--threshold` allows.
+    Regression = 6,
+}
+
+/// Prints `msg` with the usage text to stderr and exits.
+pub fn usage_error(msg: &str) -> ! {
+    eprintln!("error: {}\n\n{}", msg, usage());
+    process::exit(Exit::Usage as i32);
+}
+
+/// Prints `msg` to stderr and exits with the status of an I/O failure.
+pub fn fatal(msg: &str) -> ! {
+    fail(Exit::Io, msg)
+}
+
+/// Prints `msg` to stderr and exits with `status`.
+pub fn fail(status: Exit, msg: &str) -> ! {
+    eprintln!("error: {}", msg);
+    process::exit(status as i32);
+}
+
+/// Why an aggregation failed, with the status the command line exits with
+/// for it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Failure {
+    pub status: Exit,
+    pub message: String,
+}
+
+impl Failure {
+    pub fn new(status: Exit, message: impl Into<String>) -> Self {
+        Self {
+            status,
+            message: message.into(),
+        }
+    }
+
+    pub fn usage(message: impl Into<String>) -> Self {
+        Self::new(Exit::Usage, message)
+    }
+
+    pub fn io(message: impl Into<String>) -> Self {
+        Self::new(Exit::Io, message)
+    }
+
+    /// The failure behind an error writing the results: one the report met
+    /// itself, such as an unknown `--locale`, or else an I/O failure.
+    pub fn writing(e: io::Error) -> Self {
+        match e.get_ref().and_then(|inner| inner.downcast_ref::<Failure>()) {
+            Some(failure) => failure.clone(),
+            None => Self::io(format!("writing output: {}", e)),
+        }
+    }
+
+    /// Prints the failure and exits with its status, as [`fail`] does.
+    pub fn exit(&self) -> ! {
+        fail(self.status, &self.message)
+    }
+}
+
+impl fmt::Display for Failure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+impl std::error::Error for Failure {}
+
+impl From<Failure> for io::Error {
+    fn from(failure: Failure) -> Self {
+        io::Error::other(failure)
+    }
+}
+
+/// Parses the `LIST` of `--group-by columns:LIST`, adjacent columns counted
+/// from 1, as keys are a part of the row's name.
+fn parse_columns(list: &str) -> Result<GroupBy, String> {
+    let columns = list
+        .split(',')
+        .map(|column| match column.parse::<usize>() {
+            Ok(n) if n > 0 => Ok(n - 1),
+            _ => Err(format!("invalid column '{}' in --group-by columns:{}", column, list)),
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    if columns.windows(2).any(|pair| pair[1] != pair[0] + 1) {
+        return Err(format!(
+            "--group-by columns:{} must list adjacent columns in order, e.g. 1,2",
+            list
+        ));
+    }
+    Ok(GroupBy::Columns(columns[0], columns[columns.len() - 1]))
+}
+
+fn parse_count(name: &str, value: &str) -> Result<usize, String> {
+    match value.parse::<usize>() {
+        Ok(n) if n > 0 => Ok(n),
+        _ => Err(format!(
+            "--{} expects a positive integer, got '{}'",
+            name, value
+        )),
+    }
+}
+
+/// Parses a slot count, a power of two.
+fn parse_table_size(name: &str, value: &str) -> Result<usize, String> {
+    match value.parse::<usize>() {
+        Ok(n) if n.is_power_of_two() && n > 1 => Ok(n),
+        _ => Err(format!(
+            "--{} expects a power of two of at least 2, got '{}'",
+            name, value
+        )),
+    }
+}
+
+/// Parses `LO:HI` in degrees, e.g. `-10:35.5`.
+fn parse_range(name: &str, value: &str) -> Result<(f64, f64), String> {
+    let invalid = || format!("invalid --{} range '{}', expected LO:HI", name, value);
+    let (lo, hi) = value.split_once(':').ok_or_else(invalid)?;
+    let degrees = |s: &str| match s.trim().parse::<f64>() {
+        Ok(v) if v.abs() < 100.0 => Ok(v),
+        _ => Err(invalid()),
+    };
+    let (lo, hi) = (degrees(lo)?, degrees(hi)?);
+    if lo > hi {
+        return Err(invalid());
+    }
+    Ok((lo, hi))
+}
+
+/// Parses a byte count with an optional binary `K`, `M` or `G` suffix.
+fn parse_size(name: &str, value: &str) -> Result<usize, String> {
+    let (digits, shift) = match value.as_bytes().last() {
+        Some(b'K' | b'k') => (&value[..value.len() - 1], 10),
+        Some(b'M' | b'm') => (&value[..value.len() - 1], 20),
+        Some(b'G' | b'g') => (&value[..value.len() - 1], 30),
+        _ => (value, 0),
+    };
+    match digits.parse::<usize>() {
+        Ok(n) if n > 0 => n
+            .checked_mul(1 << shift)
+            .ok_or_else(|| format!("--{} value '{}' is too large", name, value)),
+        _ => Err(format!(
+            "--{} expects a size like 4096, 64K or 16M, got '{}'",
+            name, value
+        )),
+    }
+}
+
+/// A byte offset: a size as for [`parse_size`], or 0.
+fn parse_offset(name: &str, value: &str) -> Result<u64, String> {
+    match value {
+        "0" => Ok(0),
+        _ => parse_size(name, value).map(|n| n as u64),
+    }
+}
+
+/// Parses `FORMAT[:LEVEL]` of `--compress`, e.g. `zstd:3`.
+fn parse_compression(value: &str) -> Result<(Compression, u32), String> {
+    let (name, level) = match value.split_once(':') {
+        Some((name, level)) => (name, Some(level)),
+        None => (value, None),
+    };
+    let format = Compression::from_name(name)
+        .ok_or_else(|| format!("invalid --compress format '{}'", name))?;
+    let (levels, default) = format.levels();
+    let level = match level {
+        None => default,
+        Some(level) => match level.parse::<u32>() {
+            Ok(n) if levels.contains(&n) => n,
+            _ => {
+                return Err(format!(
+                    "--compress {} expects a level from {} to {}, got '{}'",
+                    name,
+                    levels.start(),
+                    levels.end(),
+                    level
+                ))
+            }
+        },
+    };
+    Ok((format, level))
+}
+
+fn parse_stats(value: &str) -> Result<Vec<Stat>, String> {
+    value
+        .split(',')
+        .map(|name| Stat::from_name(name).ok_or_else(|| format!("unknown statistic '{}'", name)))
+        .collect()
+}
+
+/// Sets the option of flag `name` to `value`, which is empty for flags that
+/// take none.
+fn apply_flag(
+    opts: &mut Options,
+    plausible: &mut Option<(f64, f64)>,
+    name: &str,
+    value: String,
+) -> Result<(), String> {
+    match name {
+        "order" => {
+            opts.order = match value.as_str() {
+                "bytes" => Order::Bytes,
+                "collate" => Order::Collate,
+                "input" => Order::Input,
+                _ => return Err(format!("invalid --order value '{}'", value)),
+            }
+        }
+        "locale" => opts.locale = value,
+        "color" => {
+            opts.color = match value.as_str() {
+                "auto" => Color::Auto,
+                "always" => Color::Always,
+                "never" => Color::Never,
+                _ => return Err(format!("invalid --color value '{}'", value)),
+            }
+        }
+        "invalid-utf8" => {
+            opts.invalid_utf8 = match value.as_str() {
+                "escape" => InvalidUtf8::Escape,
+                "lossy" => InvalidUtf8::Lossy,
+                _ => return Err(format!("invalid --invalid-utf8 value '{}'", value)),
+            }
+        }
+        "stats" => opts.stats = parse_stats(&value)?,
+        "decimals" => {
+            opts.decimals = match value.parse::<u32>() {
+                Ok(n @ 1..=3) => n,
+                _ => return Err(format!("--decimals expects 1, 2 or 3, got '{}'", value)),
+            }
+        }
+        "skip-comments" => opts.skip_comments = true,
+        "quoted-names" => opts.quoted_names = true,
+        "group-by" => {
+            opts.group_by = match value.as_str() {
+                "station" => GroupBy::Station,
+                "first-word" => GroupBy::FirstWord,
+                v if v.starts_with("columns:") => parse_columns(&v["columns:".len()..])?,
+                v => match v.strip_prefix("prefix:") {
+                    Some(sep) if !sep.is_empty() => GroupBy::Prefix(sep.to_string()),
+                    _ => return Err(format!("invalid --group-by value '{}'", value)),
+                },
+            }
+        }
+        "key-separator" => {
+            if value.is_empty() {
+                return Err("--key-separator expects a separator".to_string());
+            }
+            opts.key_separator = Some(value);
+        }
+        "format" => {
+            opts.format = match value.as_str() {
+                "spec" => Format::Spec,
+                "print0" => Format::Print0,
+                "csv" => Format::Csv,
+                "json" => Format::Json,
+                "geojson" => Format::GeoJson,
+                "markdown" => Format::Markdown,
+                "table" => Format::Table,
+                "sql" => Format::Sql,
+                "sqlite" => Format::Sqlite,
+                "parquet" => Format::Parquet,
+                "partial" => Format::Partial,
+                _ => return Err(format!("invalid --format value '{}'", value)),
+            }
+        }
+        "print0" => opts.format = Format::Print0,
+        "output" => opts.output = Some(value),
+        "geo" => opts.geo = Some(value),
+        "units" => opts.units = true,
+        "sql-table" => {
+            let identifier = |part: &str| {
+                part.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+                    && part.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+            };
+            if !value.split('.').all(identifier) {
+                return Err(format!(
+                    "--sql-table expects a name like stats or schema.stats, got '{}'",
+                    value
+                ));
+            }
+            opts.sql_table = value;
+        }
+        "sql-style" => {
+            opts.sql_style = match value.as_str() {
+                "insert" => SqlStyle::Insert,
+                "copy" => SqlStyle::Copy,
+                _ => return Err(format!("invalid --sql-style value '{}'", value)),
+            }
+        }
+        "histogram" => opts.histogram = Some(parse_range(name, &value)?),
+        "histogram-only" => opts.histogram_only = true,
+        "sanity-check" => opts.sanity_check = true,
+        "plausible" => *plausible = Some(parse_range(name, &value)?),
+        "tui" => opts.tui = true,
+        "explain" => opts.explain = true,
+        "run-stats" => opts.run_stats = true,
+        "perf" => opts.perf = true,
+        "profile" => opts.profile = Some(value),
+        "length" => opts.length = Some(parse_size(name, &value)? as u64),
+        "start-byte" => opts.start_byte = parse_offset(name, &value)?,
+        "end-byte" => opts.end_byte = Some(parse_offset(name, &value)?),
+        "checkpoint" => opts.checkpoint = Some(value),
+        "checkpoint-every" => opts.checkpoint_every = parse_size(name, &value)? as u64,
+        "resume" => opts.resume = Some(value),
+        "record-schedule" => opts.record_schedule = Some(value),
+        "replay-schedule" => opts.replay_schedule = Some(value),
+        "deterministic" => opts.deterministic = true,
+        "emit-every" => opts.emit_every = Some(parse_count(name, &value)? as u64),
+        "emit-to" => opts.emit_to = Some(value),
+        "limit" => opts.limit = Some(parse_count(name, &value)? as u64),
+        "max-seconds" => {
+            opts.max_seconds = match value.parse::<f64>() {
+                Ok(seconds) if seconds > 0.0 && seconds.is_finite() => Some(seconds),
+                _ => {
+                    return Err(format!(
+                        "--max-seconds expects a positive number, got '{}'",
+                        value
+                    ))
+                }
+            }
+        }
+        "baseline" => opts.baseline = true,
+        "verify" => opts.verify = true,
+        "io" => {
+            opts.io = match value.as_str() {
+                "mmap" => Io::Mmap,
+                "read" => Io::Read,
+                _ => return Err(format!("invalid --io value '{}'", value)),
+            }
+        }
+        "fadvise" => {
+            opts.fadvise = match value.as_str() {
+                "willneed" => Some(Fadvise::WillNeed),
+                "noreuse" => Some(Fadvise::NoReuse),
+                "none" => None,
+                _ => return Err(format!("invalid --fadvise value '{}'", value)),
+            }
+        }
+        "readahead" => opts.readahead = Some(parse_count(name, &value)? as u64 * 1024),
+        "threads" => opts.threads = parse_count(name, &value)?,
+        "chunk-size" => opts.chunk_size = parse_size(name, &value)?,
+        "table-size" => opts.table_size = Some(parse_table_size(name, &value)?),
+        "batch" => {
+            opts.batch = match parse_count(name, &value)? {
+                n if n <= crate::MAX_BATCH => n,
+                _ => return Err(format!("--batch is at most {}", crate::MAX_BATCH)),
+            }
+        }
+        "interleave" => opts.interleave = true,
+        "two-pass" => opts.two_pass = true,
+        "stations" => opts.stations = Some(value),
+        "unknown-stations" => {
+            opts.unknown_stations = match value.as_str() {
+                "insert" => Unknown::Insert,
+                "skip" => Unknown::Skip,
+                "error" => Unknown::Error,
+                _ => return Err(format!("invalid --unknown-stations value '{}'", value)),
+            }
+        }
+        "auto-tune" => opts.auto_tune = true,
+        "gpu" => opts.gpu = true,
+        "engine" => {
+            opts.engine = match value.as_str() {
+                "pool" => Engine::Pool,
+                "tpc" => Engine::Tpc,
+                "rayon" => Engine::Rayon,
+                "pipeline" => Engine::Pipeline,
+                _ => return Err(format!("invalid --engine value '{}'", value)),
+            }
+        }
+        "processes" => opts.processes = parse_count(name, &value)?,
+        "flag-outliers" => {
+            opts.flag_outliers = match value.parse::<f64>() {
+                Ok(k) if k > 0.0 => Some(k),
+                _ => {
+                    return Err(format!(
+                        "--flag-outliers expects a positive number, got '{}'",
+                        value
+                    ))
+                }
+            }
+        }
+        "first-last" => opts.first_last = true,
+        "input-encoding" => {
+            opts.encoding = match value.as_str() {
+                "auto" => None,
+                label => Some(Encoding::from_label(label)?),
+            }
+        }
+        "members" => opts.members = value,
+        "connections" => opts.connections = parse_count(name, &value)?,
+        "listen" => opts.listen = value,
+        "rejects" => opts.rejects = Some(value),
+        "flight" => opts.flight = Some(value),
+        "brokers" => opts.brokers = value.split(',').map(str::to_string).collect(),
+        "topic" => opts.topic = value,
+        "group" => opts.group = value,
+        "snapshot-every" => opts.snapshot_every = parse_count(name, &value)? as u64,
+        "runs" => opts.runs = parse_count(name, &value)?,
+        "evict-cache" => opts.evict_cache = true,
+        "calibrate" => opts.calibrate = true,
+        "compare" => opts.compare = Some(value),
+        "threshold" => {
+            opts.threshold = match value.parse::<f64>() {
+                Ok(pct) if pct >= 0.0 && pct.is_finite() => pct,
+                _ => return Err(format!("--threshold expects a percentage, got '{}'", value)),
+            }
+        }
+        "rows" => opts.rows = parse_count(name, &value)? as u64,
+        "shards" => opts.shards = parse_count(name, &value)?,
+        "seed" => {
+            opts.seed = value
+                .parse()
+                .map_err(|_| format!("--seed expects an integer, got '{}'", value))?
+        }
+        "corrupt-rate" => {
+            opts.corrupt_rate = match value.parse::<f64>() {
+                Ok(p) if (0.0..=1.0).contains(&p) => p,
+                _ => {
+                    return Err(format!(
+                        "--corrupt-rate expects a fraction from 0 to 1, got '{}'",
+                        value
+                    ))
+                }
+            }
+        }
+        "compress" => opts.compress = Some(parse_compression(&value)?),
+        "names" => {
+            opts.names = match value.as_str() {
+                "cities" => Names::Cities,
+                "adversarial" => Names::Adversarial,
+                _ => return Err(format!("invalid --names value '{}'", value)),
+            }
+        }
+        // Read before all other flags, by parse_args
+        "config" => {}
+        "help" => {
+            print!("{}", usage());
+            process::exit(0);
+        }
+        _ => unreachable!("flag table and match are out of sync"),
+    }
+    Ok(())
+}
+
+/// The `--config` file named on the command line. It is read before the
+/// other flags, so that they override it.
+fn config_flag(args: &[String]) -> Result<Option<String>, String> {
+    let mut path = None;
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        if arg == "--config" {
+            path = Some(args.next().ok_or("--config requires a value")?.clone());
+        } else if let Some(value) = arg.strip_prefix("--config=") {
+            path = Some(value.to_string());
+        }
+    }
+    Ok(path)
+}
+
+/// Applies the config file at `path` as if its settings were flags given
+/// before the command line's.
+fn apply_config(
+    opts: &mut Options,
+    plausible: &mut Option<(f64, f64)>,
+    path: &str,
+) -> Result<(), String> {
+    for config::Entry { line, key, value } in config::load(path)? {
+        let error = |msg: String| format!("{}:{}: {}", path, line, msg);
+        let flag = FLAGS
+            .iter()
+            .find(|f| f.name == key && !matches!(f.name, "config" | "help"))
+            .ok_or_else(|| error(format!("unknown option '{}'", key)))?;
+        let value = match (flag.value, value) {
+            (None, config::Value::Bool(true)) => String::new(),
+            (None, config::Value::Bool(false)) => continue,
+            (None, config::Value::Text(_)) => {
+                return Err(error(format!("{} expects true or false", key)))
+            }
+            (Some(_), config::Value::Text(value)) => value,
+            (Some(_), config::Value::Bool(_)) => {
+                return Err(error(format!("{} expects a value, not true or false", key)))
+            }
+        };
+        apply_flag(opts, plausible, flag.name, value).map_err(error)?;
+    }
+    Ok(())
+}
+
+/// The environment variable mirroring flag `name`, e.g. `ONEBRC_TABLE_SIZE`
+/// for `--table-size`.
+fn env_var(name: &str) -> String {
+    format!("ONEBRC_{}", name.to_ascii_uppercase().replace('-', "_"))
+}
+
+/// Applies the `ONEBRC_*` variables that are set and not empty, over the
+/// config file and under the command line. Flags without a value are set
+/// with `1` or `true`.
+fn apply_env(opts: &mut Options, plausible: &mut Option<(f64, f64)>) -> Result<(), String> {
+    for flag in FLAGS {
+        if matches!(flag.name, "config" | "help") {
+            continue;
+        }
+        let var = env_var(flag.name);
+        let value = match env::var(&var) {
+            Ok(value) if !value.is_empty() => value,
+            Ok(_) | Err(env::VarError::NotPresent) => continue,
+            Err(env::VarError::NotUnicode(_)) => return Err(format!("{} is not UTF-8", var)),
+        };
+        let value = match (flag.value, value.as_str()) {
+            (None, "1" | "true") => String::new(),
+            (None, "0" | "false") => continue,
+            (None, _) => return Err(format!("{} expects 1, true, 0 or false", var)),
+            (Some(_), _) => value,
+        };
+        apply_flag(opts, plausible, flag.name, value).map_err(|e| format!("{}: {}", var, e))?;
+    }
+    Ok(())
+}
+
+pub fn parse_args<I: Iterator<Item = String>>(args: I) -> Result<Options, String> {
+    let args: Vec<String> = args.collect();
+    let mut opts = Options::default();
+    let mut positional = Vec::new();
+    let mut plausible = None;
+    let config = match config_flag(&args)? {
+        Some(path) => Some(path),
+        None => match env::var("ONEBRC_CONFIG") {
+            Ok(path) if !path.is_empty() => Some(path),
+            _ => config::default_path().map(|path| path.to_string_lossy().into_owned()),
+        },
+    };
+    if let Some(path) = config {
+        apply_config(&mut opts, &mut plausible, &path)?;
+    }
+    apply_env(&mut opts, &mut plausible)?;
+
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        let Some(long) = arg.strip_prefix("--") else {
+            positional.push(arg);
+            continue;
+        };
+        let (name, inline) = match long.split_once('=') {
+            Some((n, v)) => (n, Some(v.to_string())),
+            None => (long, None),
+        };
+        let flag = FLAGS
+            .iter()
+            .find(|f| f.name == name)
+            .ok_or_else(|| format!("unknown option --{}", name))?;
+        let value = match (flag.value, inline) {
+            (Some(_), Some(v)) => v,
+            (Some(_), None) => args
+                .next()
+                .ok_or_else(|| format!("--{} requires a value", name))?,
+            (None, Some(_)) => return Err(format!("--{} does not take a value", name)),
+            (None, None) => String::new(),
+        };
+
+        apply_flag(&mut opts, &mut plausible, name, value)?;
+    }
+
+    let command = match positional.first().map(String::as_str) {
+        Some("ingest") => Some(Command::Ingest),
+        Some("daemon") => Some(Command::Daemon),
+        Some("kafka") => Some(Command::Kafka),
+        Some("merge") => Some(Command::Merge),
+        Some("quality") => Some(Command::Quality),
+        Some("bench") => Some(Command::Bench),
+        Some("generate") => Some(Command::Generate),
+        Some("completions") => {
+            let shell = match positional.get(1).map(String::as_str) {
+                Some("bash") => Shell::Bash,
+                Some("zsh") => Shell::Zsh,
+                Some("fish") => Shell::Fish,
+                _ => return Err("completions expects a shell: bash, zsh or fish".to_string()),
+            };
+            if positional.len() > 2 {
+                return Err("completions expects only the shell".to_string());
+            }
+            opts.command = Command::Completions(shell);
+            return Ok(opts);
+        }
+        _ => None,
+    };
+    if let Some(command) = command {
+        opts.command = command;
+        positional.remove(0);
+    }
+
+    if let Some(range) = plausible {
+        if !opts.sanity_check {
+            return Err("--plausible requires --sanity-check".to_string());
+        }
+        opts.plausible = range;
+    }
+    if opts.order == Order::Input && (opts.processes > 1 || opts.two_pass || opts.baseline) {
+        return Err(
+            "--order input cannot be combined with --processes, --two-pass or --baseline"
+                .to_string(),
+        );
+    }
+    if opts.sanity_check && (opts.processes > 1 || opts.gpu || opts.baseline) {
+        return Err(
+            "--sanity-check cannot be combined with --processes, --gpu or --baseline".to_string(),
+        );
+    }
+    if opts.histogram_only && opts.histogram.is_none() {
+        opts.histogram = Some((-99.9, 99.9));
+    }
+    if opts.histogram.is_some()
+        && (opts.processes > 1 || opts.gpu || opts.baseline || opts.command == Command::Merge)
+    {
+        return Err(
+            "--histogram cannot be combined with --processes, --gpu, --baseline or merge"
+                .to_string(),
+        );
+    }
+    if opts.quoted_names
+        && (matches!(opts.group_by, GroupBy::Columns(..)) || opts.key_separator.is_some())
+    {
+        return Err("--quoted-names cannot be combined with --group-by columns or --key-separator, as names may hold ';'".to_string());
+    }
+    if opts.gpu && (opts.skip_comments || opts.quoted_names) {
+        return Err("--skip-comments and --quoted-names cannot be combined with --gpu".to_string());
+    }
+    if opts.gpu && matches!(opts.group_by, GroupBy::Columns(..)) {
+        return Err("--group-by columns cannot be combined with --gpu, which ends names at their first ';'".to_string());
+    }
+    if opts.two_pass && opts.stations.is_some() {
+        return Err("--two-pass cannot be combined with --stations".to_string());
+    }
+    if opts.processes > 1 && opts.tui {
+        return Err("--processes cannot be combined with --tui".to_string());
+    }
+    if opts.end_byte.is_some_and(|end| end <= opts.start_byte) {
+        return Err("--end-byte must be greater than --start-byte".to_string());
+    }
+    if opts.format.owns_output() && opts.output.is_none() {
+        return Err("--format sqlite and parquet require --output FILE".to_string());
+    }
+    if opts.output.is_some()
+        && matches!(
+            opts.command,
+            Command::Ingest | Command::Daemon | Command::Kafka | Command::Generate
+        )
+    {
+        return Err("--output applies only to aggregate, merge, quality and bench".to_string());
+    }
+    if opts.command == Command::Bench && opts.format.owns_output() {
+        return Err("bench cannot time --format sqlite or parquet".to_string());
+    }
+    if opts.perf && !cfg!(target_os = "linux") {
+        return Err("--perf reads Linux perf events and is not available here".to_string());
+    }
+    if (opts.fadvise.is_some() || opts.readahead.is_some()) && !advice::SUPPORTED {
+        return Err("--fadvise and --readahead need posix_fadvise, which is not available here".to_string());
+    }
+    if opts.units && opts.format != Format::Table {
+        return Err("--units applies only to --format table".to_string());
+    }
+    if opts.format == Format::Partial && (opts.histogram.is_some() || opts.flag_outliers.is_some())
+    {
+        return Err(
+            "--format partial cannot be combined with --histogram or --flag-outliers".to_string(),
+        );
+    }
+    if opts.first_last
+        && (opts.format == Format::Partial
+            || opts.histogram.is_some()
+            || opts.processes > 1
+            || opts.gpu
+            || matches!(opts.engine, Engine::Tpc | Engine::Pipeline)
+            || opts.baseline
+            || opts.checkpoint.is_some()
+            || opts.resume.is_some())
+    {
+        return Err("--first-last cannot be combined with --format partial, --histogram, --processes, --gpu, --engine tpc or pipeline, --baseline, --checkpoint or --resume".to_string());
+    }
+    if opts.engine != Engine::Pool
+        && (opts.io == Io::Read
+            || opts.processes > 1
+            || opts.gpu
+            || opts.auto_tune
+            || opts.order == Order::Input
+            || opts.record_schedule.is_some()
+            || opts.replay_schedule.is_some())
+    {
+        return Err(format!("--engine {} cannot be combined with --io read, --processes, --gpu, --auto-tune, --order input or --record-/--replay-schedule", opts.engine.name()));
+    }
+    if opts.max_seconds.is_some() && (opts.processes > 1 || opts.gpu) {
+        return Err("--max-seconds cannot be combined with --processes or --gpu".to_string());
+    }
+    if opts.checkpoint.is_some() || opts.resume.is_some() {
+        if opts.command != Command::Aggregate {
+            return Err("--checkpoint and --resume apply only to aggregating an input".to_string());
+        }
+        if opts.two_pass
+            || opts.limit.is_some()
+            || opts.max_seconds.is_some()
+            || opts.order == Order::Input
+            || opts.baseline
+            || opts.verify
+        {
+            return Err("--checkpoint and --resume cannot be combined with --two-pass, --limit, --max-seconds, --order input, --baseline or --verify".to_string());
+        }
+    }
+    if opts.record_schedule.is_some() || opts.replay_schedule.is_some() {
+        if opts.record_schedule.is_some() && opts.replay_schedule.is_some() {
+            return Err("--record-schedule cannot be combined with --replay-schedule".to_string());
+        }
+        if opts.command != Command::Aggregate {
+            return Err(
+                "--record-schedule and --replay-schedule apply only to aggregating an input"
+                    .to_string(),
+            );
+        }
+        if opts.processes > 1 || opts.gpu || opts.baseline {
+            return Err("--record-schedule and --replay-schedule cannot be combined with --processes, --gpu or --baseline".to_string());
+        }
+    }
+    if opts.emit_to.is_some() && opts.emit_every.is_none() {
+        return Err("--emit-to requires --emit-every".to_string());
+    }
+    if opts.emit_every.is_some() {
+        if opts.command != Command::Aggregate {
+            return Err("--emit-every applies only to aggregating an input".to_string());
+        }
+        if opts.format.owns_output() || opts.checkpoint.is_some() || opts.resume.is_some() {
+            return Err("--emit-every cannot be combined with --format sqlite or parquet, --checkpoint or --resume".to_string());
+        }
+    }
+    if opts.deterministic
+        && (opts.record_schedule.is_some()
+            || opts.replay_schedule.is_some()
+            || opts.auto_tune
+            || opts.max_seconds.is_some()
+            || opts.processes > 1
+            || opts.gpu
+            || opts.engine != Engine::Pool)
+    {
+        return Err("--deterministic cannot be combined with --record-/--replay-schedule, --auto-tune, --max-seconds, --processes, --gpu or --engine".to_string());
+    }
+    if opts.baseline || opts.verify {
+        let flag = if opts.baseline {
+            "--baseline"
+        } else {
+            "--verify"
+        };
+        if opts.baseline && opts.verify {
+            return Err("--baseline cannot be combined with --verify".to_string());
+        }
+        if !matches!(Kind::for_stats(&opts.stats), Kind::Count | Kind::Basic) {
+            return Err(format!("{} supports only --stats min,max,mean,count", flag));
+        }
+        if opts.max_seconds.is_some() {
+            return Err(format!("{} cannot be combined with --max-seconds", flag));
+        }
+        if opts.start_byte > 0 || opts.end_byte.is_some() {
+            return Err(format!(
+                "{} cannot be combined with --start-byte or --end-byte",
+                flag
+            ));
+        }
+        if opts.baseline && opts.format == Format::Partial {
+            return Err("--baseline cannot write --format partial".to_string());
+        }
+        if opts.histogram.is_some() || opts.flag_outliers.is_some() {
+            return Err(format!(
+                "{} cannot be combined with --histogram or --flag-outliers",
+                flag
+            ));
+        }
+    }
+
+    if opts.command == Command::Merge {
+        if positional.is_empty() {
+            return Err("merge expects at least one --format partial dump".to_string());
+        }
+        opts.partials = positional;
+        return Ok(opts);
+    }
+    match positional.len() {
+        0 => {}
+        1 => opts.file_path = positional.pop().unwrap(),
+        _ => return Err("expected at most one input file".to_string()),
+    }
+    Ok(opts)
+}
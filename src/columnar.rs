@@ -0,0 +1,82 @@
+//! `--format parquet`: the results as a Parquet file at `--output`, typed so
+//! that DuckDB and other engines load them without casts. Temperatures are
+//! decimals with the input's number of fraction digits, so they read back
+//! exactly as the text formats print them.
+
+use std::fs::File;
+use std::io;
+use std::sync::Arc;
+
+use arrow_array::{ArrayRef, Decimal128Array, Float64Array, RecordBatch, StringArray, UInt64Array};
+use arrow_schema::{DataType, Field, Schema};
+use parquet::arrow::ArrowWriter;
+use parquet::basic::Compression;
+use parquet::file::properties::WriterProperties;
+
+use crate::cli::Options;
+use crate::display_name;
+use crate::format::Row;
+use crate::geo::{GeoTable, Location};
+use crate::stats::{Stat, Summary};
+
+/// Digits of the temperature decimals. Nine is the most Parquet stores as a
+/// 32-bit integer, and leaves room for any temperature with three decimals.
+const PRECISION: u8 = 9;
+
+/// Writes `rows` to a new Parquet file at `path`, replacing any file there.
+pub fn write<A: Summary>(
+    rows: &[Row<A>],
+    opts: &Options,
+    geo: Option<&GeoTable>,
+    path: &str,
+) -> io::Result<()> {
+    let mut fields = vec![Field::new("station", DataType::Utf8, false)];
+    let mut columns: Vec<ArrayRef> = vec![Arc::new(StringArray::from_iter_values(
+        rows.iter()
+            .map(|(name, _)| display_name(name, opts.invalid_utf8)),
+    ))];
+    for &stat in &opts.stats {
+        let values = rows.iter().map(|(_, stats)| stats.value(stat));
+        let column: ArrayRef = if stat.is_count() {
+            Arc::new(UInt64Array::from_iter_values(values.map(|v| v as u64)))
+        } else {
+            // Rounded as `format::write_stat` prints them, in units of the last digit
+            Arc::new(
+                Decimal128Array::from_iter_values(values.map(|v| (v + 0.5).floor() as i128))
+                    .with_precision_and_scale(PRECISION, opts.decimals as i8)
+                    .map_err(io::Error::other)?,
+            )
+        };
+        fields.push(Field::new(stat.name(), column.data_type().clone(), false));
+        columns.push(column);
+    }
+    if let Some(geo) = geo {
+        let located: Vec<Option<&Location>> = rows.iter().map(|(name, _)| geo.get(name)).collect();
+        let column = |coordinate: fn(&Location) -> Option<f64>| -> ArrayRef {
+            Arc::new(Float64Array::from_iter(
+                located.iter().map(|loc| loc.and_then(coordinate)),
+            ))
+        };
+        for (name, column) in [
+            ("lat", column(|loc| Some(loc.lat))),
+            ("lon", column(|loc| Some(loc.lon))),
+            ("elevation", column(|loc| loc.elevation)),
+        ] {
+            fields.push(Field::new(name, DataType::Float64, true));
+            columns.push(column);
+        }
+    }
+
+    let batch =
+        RecordBatch::try_new(Arc::new(Schema::new(fields)), columns).map_err(io::Error::other)?;
+    let props = WriterProperties::builder()
+        .set_compression(Compression::SNAPPY)
+        .build();
+    let file =
+        File::create(path).map_err(|e| io::Error::new(e.kind(), format!("{}: {}", path, e)))?;
+    let mut writer =
+        ArrowWriter::try_new(file, batch.schema(), Some(props)).map_err(io::Error::other)?;
+    writer.write(&batch).map_err(io::Error::other)?;
+    writer.close().map_err(io::Error::other)?;
+    Ok(())
+}
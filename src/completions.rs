@@ -0,0 +1,198 @@
+//! `onebrc completions SHELL`: a completion script for bash, zsh or fish,
+//! generated from the option table so that it never falls behind the flags.
+
+use std::io::{self, Write};
+
+use crate::cli::{Flag, Shell, COMMANDS, FLAGS};
+
+/// How a flag's value is completed.
+enum Value {
+    None,
+    File,
+    /// One of the lowercase words of `a|b|c`, less an optional suffix such as
+    /// the `[:LEVEL]` of `gzip|zstd[:LEVEL]`. Placeholders among them, such as
+    /// `LABEL` or `prefix:SEP`, are typed by hand.
+    Choices(Vec<&'static str>),
+    Free,
+}
+
+fn value(flag: &Flag) -> Value {
+    match flag.value {
+        None => Value::None,
+        Some("FILE") => Value::File,
+        Some(value) => {
+            let words: Vec<&str> = value
+                .split('|')
+                .map(|word| word.split_once('[').map_or(word, |(word, _)| word))
+                .filter(|word| {
+                    word.bytes()
+                        .all(|b| b.is_ascii_lowercase() || b.is_ascii_digit() || b == b'-')
+                })
+                .collect();
+            match words.len() {
+                0 | 1 => Value::Free,
+                _ => Value::Choices(words),
+            }
+        }
+    }
+}
+
+const SHELLS: [&str; 3] = ["bash", "zsh", "fish"];
+
+pub fn write(shell: Shell, out: &mut dyn Write) -> io::Result<()> {
+    match shell {
+        Shell::Bash => write_bash(out),
+        Shell::Zsh => write_zsh(out),
+        Shell::Fish => write_fish(out),
+    }
+}
+
+fn write_bash(out: &mut dyn Write) -> io::Result<()> {
+    let commands: Vec<&str> = COMMANDS.iter().map(|(name, _)| *name).collect();
+    let flags: Vec<String> = FLAGS
+        .iter()
+        .map(|flag| format!("--{}", flag.name))
+        .collect();
+    writeln!(out, "_onebrc() {{")?;
+    writeln!(out, "    local cur=\"${{COMP_WORDS[COMP_CWORD]}}\"")?;
+    writeln!(out, "    local prev=\"${{COMP_WORDS[COMP_CWORD-1]}}\"")?;
+    writeln!(out, "    case \"$prev\" in")?;
+    let mut free = Vec::new();
+    for flag in FLAGS {
+        match value(flag) {
+            Value::None => {}
+            Value::File => writeln!(
+                out,
+                "        --{}) COMPREPLY=($(compgen -f -- \"$cur\")); return ;;",
+                flag.name
+            )?,
+            Value::Choices(words) => writeln!(
+                out,
+                "        --{}) COMPREPLY=($(compgen -W \"{}\" -- \"$cur\")); return ;;",
+                flag.name,
+                words.join(" ")
+            )?,
+            Value::Free => free.push(format!("--{}", flag.name)),
+        }
+    }
+    writeln!(out, "        {}) return ;;", free.join("|"))?;
+    writeln!(out, "    esac")?;
+    writeln!(out, "    if [[ \"$cur\" == --* ]]; then")?;
+    writeln!(
+        out,
+        "        COMPREPLY=($(compgen -W \"{}\" -- \"$cur\"))",
+        flags.join(" ")
+    )?;
+    writeln!(
+        out,
+        "    elif [[ $COMP_CWORD -eq 2 && \"${{COMP_WORDS[1]}}\" == completions ]]; then"
+    )?;
+    writeln!(
+        out,
+        "        COMPREPLY=($(compgen -W \"{}\" -- \"$cur\"))",
+        SHELLS.join(" ")
+    )?;
+    writeln!(out, "    elif [[ $COMP_CWORD -eq 1 ]]; then")?;
+    writeln!(
+        out,
+        "        COMPREPLY=($(compgen -W \"{}\" -- \"$cur\") $(compgen -f -- \"$cur\"))",
+        commands.join(" ")
+    )?;
+    writeln!(out, "    else")?;
+    writeln!(out, "        COMPREPLY=($(compgen -f -- \"$cur\"))")?;
+    writeln!(out, "    fi")?;
+    writeln!(out, "}}")?;
+    writeln!(out, "complete -o filenames -F _onebrc onebrc")
+}
+
+/// Quotes `s` for a single-quoted zsh `_arguments` spec, escaping the
+/// brackets that would end its description.
+fn zsh_description(s: &str) -> String {
+    s.replace('\'', "'\\''")
+        .replace('[', "\\[")
+        .replace(']', "\\]")
+        .replace(':', "\\:")
+}
+
+fn write_zsh(out: &mut dyn Write) -> io::Result<()> {
+    writeln!(out, "#compdef onebrc")?;
+    writeln!(out)?;
+    writeln!(out, "_onebrc_first() {{")?;
+    let commands: Vec<String> = COMMANDS
+        .iter()
+        .map(|(name, help)| format!("{}\\:\"{}\"", name, zsh_description(help)))
+        .collect();
+    writeln!(
+        out,
+        "    _alternative 'commands:command:(({}))' 'files:input:_files'",
+        commands.join(" ")
+    )?;
+    writeln!(out, "}}")?;
+    writeln!(out)?;
+    writeln!(out, "_onebrc() {{")?;
+    writeln!(out, "    if [[ ${{words[2]}} == completions ]]; then")?;
+    writeln!(out, "        _arguments '2:shell:({})'", SHELLS.join(" "))?;
+    writeln!(out, "        return")?;
+    writeln!(out, "    fi")?;
+    writeln!(out, "    _arguments \\")?;
+    for flag in FLAGS {
+        let help = zsh_description(flag.help);
+        let action = match value(flag) {
+            Value::None => {
+                writeln!(out, "        '--{}[{}]' \\", flag.name, help)?;
+                continue;
+            }
+            Value::File => "_files".to_string(),
+            Value::Choices(words) => format!("({})", words.join(" ")),
+            Value::Free => " ".to_string(),
+        };
+        let placeholder = zsh_description(flag.value.unwrap_or_default());
+        writeln!(
+            out,
+            "        '--{}=[{}]:{}:{}' \\",
+            flag.name, help, placeholder, action
+        )?;
+    }
+    writeln!(out, "        '1: :_onebrc_first' \\")?;
+    writeln!(out, "        '*:input:_files'")?;
+    writeln!(out, "}}")?;
+    writeln!(out)?;
+    writeln!(out, "_onebrc \"$@\"")
+}
+
+/// Quotes `s` as a single-quoted fish string.
+fn fish_string(s: &str) -> String {
+    format!("'{}'", s.replace('\\', "\\\\").replace('\'', "\\'"))
+}
+
+fn write_fish(out: &mut dyn Write) -> io::Result<()> {
+    for (name, help) in COMMANDS {
+        writeln!(
+            out,
+            "complete -c onebrc -n __fish_use_subcommand -a {} -d {}",
+            name,
+            fish_string(help)
+        )?;
+    }
+    writeln!(
+        out,
+        "complete -c onebrc -n '__fish_seen_subcommand_from completions' -x -a '{}'",
+        SHELLS.join(" ")
+    )?;
+    for flag in FLAGS {
+        let value = match value(flag) {
+            Value::None => String::new(),
+            Value::File => " -r -F".to_string(),
+            Value::Choices(words) => format!(" -x -a {}", fish_string(&words.join(" "))),
+            Value::Free => " -x".to_string(),
+        };
+        writeln!(
+            out,
+            "complete -c onebrc -l {}{} -d {}",
+            flag.name,
+            value,
+            fish_string(flag.help)
+        )?;
+    }
+    Ok(())
+}
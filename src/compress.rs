@@ -0,0 +1,156 @@
+use std::io::{self, Read, Write};
+use std::ops::RangeInclusive;
+
+/// Compression container detected from the leading magic bytes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Compression {
+    Gzip,
+    Zstd,
+    Xz,
+    Bzip2,
+}
+
+impl Compression {
+    pub fn name(self) -> &'static str {
+        match self {
+            Compression::Gzip => "gzip",
+            Compression::Zstd => "zstd",
+            Compression::Xz => "xz",
+            Compression::Bzip2 => "bzip2",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Compression> {
+        match name {
+            "gzip" => Some(Compression::Gzip),
+            "zstd" => Some(Compression::Zstd),
+            "xz" => Some(Compression::Xz),
+            "bzip2" => Some(Compression::Bzip2),
+            _ => None,
+        }
+    }
+
+    /// Compression levels the encoder takes, and the one used by default.
+    pub fn levels(self) -> (RangeInclusive<u32>, u32) {
+        match self {
+            Compression::Gzip => (0..=9, 6),
+            Compression::Zstd => (1..=22, 3),
+            Compression::Xz => (0..=9, 6),
+            Compression::Bzip2 => (1..=9, 9),
+        }
+    }
+}
+
+/// Identifies a compressed input by its magic number, independent of the file name.
+pub fn sniff(data: &[u8]) -> Option<Compression> {
+    match data {
+        [0x1F, 0x8B, ..] => Some(Compression::Gzip),
+        [0x28, 0xB5, 0x2F, 0xFD, ..] => Some(Compression::Zstd),
+        [0xFD, b'7', b'z', b'X', b'Z', 0x00, ..] => Some(Compression::Xz),
+        [b'B', b'Z', b'h', b'1'..=b'9', ..] => Some(Compression::Bzip2),
+        _ => None,
+    }
+}
+
+/// Returns a streaming decompressor over `reader`, or an error if support for the
+/// format was not compiled in.
+#[cfg_attr(
+    not(any(feature = "gzip", feature = "zstd", feature = "xz", feature = "bzip2")),
+    allow(unused_variables)
+)]
+pub fn decoder<'a, R: Read + 'a>(
+    format: Compression,
+    reader: R,
+) -> Result<Box<dyn Read + 'a>, String> {
+    match format {
+        #[cfg(feature = "gzip")]
+        Compression::Gzip => Ok(Box::new(flate2::read::MultiGzDecoder::new(reader))),
+        #[cfg(feature = "zstd")]
+        Compression::Zstd => Ok(Box::new(
+            zstd::stream::read::Decoder::new(reader).map_err(|e| e.to_string())?,
+        )),
+        #[cfg(feature = "xz")]
+        Compression::Xz => Ok(Box::new(liblzma::read::XzDecoder::new_multi_decoder(
+            reader,
+        ))),
+        #[cfg(feature = "bzip2")]
+        Compression::Bzip2 => Ok(Box::new(bzip2::read::MultiBzDecoder::new(reader))),
+        #[allow(unreachable_patterns)]
+        _ => Err(format!(
+            "input is {}-compressed but support for it was not compiled in",
+            format.name()
+        )),
+    }
+}
+
+/// A compressing writer. Finishing it writes the end of the stream and
+/// flushes the writer under it, which dropping it would do without a word
+/// on failure.
+pub trait Encoder: Write {
+    fn finish(self: Box<Self>) -> io::Result<()>;
+}
+
+#[cfg(feature = "gzip")]
+impl<W: Write> Encoder for flate2::write::GzEncoder<W> {
+    fn finish(self: Box<Self>) -> io::Result<()> {
+        (*self).finish()?.flush()
+    }
+}
+
+#[cfg(feature = "zstd")]
+impl<W: Write> Encoder for zstd::stream::write::Encoder<'_, W> {
+    fn finish(self: Box<Self>) -> io::Result<()> {
+        (*self).finish()?.flush()
+    }
+}
+
+#[cfg(feature = "xz")]
+impl<W: Write> Encoder for liblzma::write::XzEncoder<W> {
+    fn finish(self: Box<Self>) -> io::Result<()> {
+        (*self).finish()?.flush()
+    }
+}
+
+#[cfg(feature = "bzip2")]
+impl<W: Write> Encoder for bzip2::write::BzEncoder<W> {
+    fn finish(self: Box<Self>) -> io::Result<()> {
+        (*self).finish()?.flush()
+    }
+}
+
+/// Returns a streaming compressor at `level` over `writer`, or an error if
+/// support for the format was not compiled in.
+#[cfg_attr(
+    not(any(feature = "gzip", feature = "zstd", feature = "xz", feature = "bzip2")),
+    allow(unused_variables)
+)]
+pub fn encoder<'a, W: Write + 'a>(
+    format: Compression,
+    level: u32,
+    writer: W,
+) -> Result<Box<dyn Encoder + 'a>, String> {
+    match format {
+        #[cfg(feature = "gzip")]
+        Compression::Gzip => Ok(Box::new(flate2::write::GzEncoder::new(
+            writer,
+            flate2::Compression::new(level),
+        ))),
+        #[cfg(feature = "zstd")]
+        Compression::Zstd => Ok(Box::new(
+            zstd::stream::write::Encoder::new(writer, level as i32).map_err(|e| e.to_string())?,
+        )),
+        #[cfg(feature = "xz")]
+        Compression::Xz => Ok(Box::new(liblzma::write::XzEncoder::new(writer, level))),
+        #[cfg(feature = "bzip2")]
+        Compression::Bzip2 => Ok(Box::new(bzip2::write::BzEncoder::new(
+            writer,
+            bzip2::Compression::new(level),
+        ))),
+        #[allow(unreachable_patterns)]
+        _ => Err(format!(
+            "writing {} requires building with the `{}` feature",
+            format.name(),
+            format.name()
+        )),
+    }
+}
@@ -0,0 +1,204 @@
+//! Defaults from a config file, `~/.config/1brc.toml` or the file given with
+//! `--config`, so that cron entries and scripts need not repeat them. Each
+//! key is the name of a flag, with `-` or `_`:
+//!
+//! ```toml
+//! threads = 16
+//! io = "read"
+//! format = "csv"
+//! stats = ["min", "max", "mean", "count"]
+//! sanity-check = true
+//! ```
+//!
+//! Only this much of TOML is read: one `key = value` per line, with string,
+//! integer, float, boolean and single-line array values, and `#` comments.
+
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+
+/// The file read when `--config` is not given, if it exists.
+pub fn default_path() -> Option<PathBuf> {
+    let dir = match env::var_os("XDG_CONFIG_HOME") {
+        Some(dir) if !dir.is_empty() => PathBuf::from(dir),
+        _ => PathBuf::from(env::var_os("HOME")?).join(".config"),
+    };
+    Some(dir.join("1brc.toml")).filter(|path| path.is_file())
+}
+
+/// One setting of a config file.
+pub struct Entry {
+    pub line: usize,
+    /// The flag name, with `_` turned into `-`.
+    pub key: String,
+    pub value: Value,
+}
+
+pub enum Value {
+    Bool(bool),
+    /// A string, number or array, as the flag's value. Arrays are joined with
+    /// commas, as in `--stats min,max`.
+    Text(String),
+}
+
+/// Reads the settings of the config file at `path`.
+pub fn load(path: &str) -> Result<Vec<Entry>, String> {
+    let text = fs::read_to_string(path).map_err(|e| format!("{}: {}", path, e))?;
+    let mut entries: Vec<Entry> = Vec::new();
+    for (i, line) in text.lines().enumerate() {
+        let error = |msg: &str| format!("{}:{}: {}", path, i + 1, msg);
+        let line = strip_comment(line).trim();
+        if line.is_empty() {
+            continue;
+        }
+        if line.starts_with('[') {
+            return Err(error("tables are not supported, only top-level keys"));
+        }
+        let (key, value) = line
+            .split_once('=')
+            .ok_or_else(|| error("expected key = value"))?;
+        let key = key.trim();
+        if key.is_empty()
+            || !key
+                .bytes()
+                .all(|b| b.is_ascii_alphanumeric() || b == b'-' || b == b'_')
+        {
+            return Err(error(&format!("invalid key '{}'", key)));
+        }
+        let key = key.replace('_', "-");
+        if entries.iter().any(|entry| entry.key == key) {
+            return Err(error(&format!("duplicate key '{}'", key)));
+        }
+        let value = parse_value(value.trim()).map_err(|msg| error(&msg))?;
+        entries.push(Entry {
+            line: i + 1,
+            key,
+            value,
+        });
+    }
+    Ok(entries)
+}
+
+/// `line` up to a `#` outside quotes.
+fn strip_comment(line: &str) -> &str {
+    let mut quote = None;
+    let mut escaped = false;
+    for (i, c) in line.char_indices() {
+        match (quote, c) {
+            (Some('"'), '\\') if !escaped => {
+                escaped = true;
+                continue;
+            }
+            (Some(q), c) if c == q && !escaped => quote = None,
+            (None, '"' | '\'') => quote = Some(c),
+            (None, '#') => return &line[..i],
+            _ => {}
+        }
+        escaped = false;
+    }
+    line
+}
+
+fn parse_value(value: &str) -> Result<Value, String> {
+    match value {
+        "true" => return Ok(Value::Bool(true)),
+        "false" => return Ok(Value::Bool(false)),
+        _ => {}
+    }
+    if let Some(items) = value
+        .strip_prefix('[')
+        .and_then(|rest| rest.strip_suffix(']'))
+    {
+        let items = split_items(items)?
+            .into_iter()
+            .map(parse_scalar)
+            .collect::<Result<Vec<_>, _>>()?;
+        return Ok(Value::Text(items.join(",")));
+    }
+    parse_scalar(value).map(Value::Text)
+}
+
+/// The comma-separated items of an array, which may end with a comma.
+fn split_items(items: &str) -> Result<Vec<&str>, String> {
+    let mut split = Vec::new();
+    let (mut start, mut quote) = (0, None);
+    let mut escaped = false;
+    for (i, c) in items.char_indices() {
+        match (quote, c) {
+            (Some('"'), '\\') if !escaped => {
+                escaped = true;
+                continue;
+            }
+            (Some(q), c) if c == q && !escaped => quote = None,
+            (None, '"' | '\'') => quote = Some(c),
+            (None, ',') => {
+                split.push(items[start..i].trim());
+                start = i + 1;
+            }
+            (None, '[' | ']') => return Err("nested arrays are not supported".to_string()),
+            _ => {}
+        }
+        escaped = false;
+    }
+    let last = items[start..].trim();
+    if !last.is_empty() {
+        split.push(last);
+    }
+    if split.iter().any(|item| item.is_empty()) {
+        return Err("empty array item".to_string());
+    }
+    Ok(split)
+}
+
+/// A string, integer or float, as text.
+fn parse_scalar(value: &str) -> Result<String, String> {
+    if let Some(literal) = value
+        .strip_prefix('\'')
+        .and_then(|rest| rest.strip_suffix('\''))
+    {
+        return Ok(literal.to_string());
+    }
+    if let Some(basic) = value
+        .strip_prefix('"')
+        .and_then(|rest| rest.strip_suffix('"'))
+    {
+        return unescape(basic);
+    }
+    let number = value.replace('_', "");
+    if number.parse::<i64>().is_ok() || number.parse::<f64>().is_ok_and(f64::is_finite) {
+        return Ok(number);
+    }
+    Err(format!(
+        "invalid value '{}', expected a string, number, boolean or array",
+        value
+    ))
+}
+
+fn unescape(s: &str) -> Result<String, String> {
+    let mut out = String::with_capacity(s.len());
+    let mut chars = s.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            out.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('"') => out.push('"'),
+            Some('\\') => out.push('\\'),
+            Some('n') => out.push('\n'),
+            Some('t') => out.push('\t'),
+            Some('r') => out.push('\r'),
+            Some(u @ ('u' | 'U')) => {
+                let digits: String = chars.by_ref().take(if u == 'u' { 4 } else { 8 }).collect();
+                let c = u32::from_str_radix(&digits, 16)
+                    .ok()
+                    .and_then(char::from_u32)
+                    .ok_or_else(|| format!("invalid escape '\\{}{}'", u, digits))?;
+                out.push(c);
+            }
+            Some(c) => return Err(format!("invalid escape '\\{}'", c)),
+            None => return Err("string ends with '\\'".to_string()),
+        }
+    }
+    Ok(out)
+}
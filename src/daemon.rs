@@ -0,0 +1,242 @@
+use std::collections::{HashMap, VecDeque};
+use std::fs;
+use std::io::{self, BufRead, BufReader, Write};
+use std::os::unix::fs::{FileTypeExt, MetadataExt};
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+use crate::cli::{self, Command, Engine, Failure, Format, Io, Options, Order};
+use crate::stats::{Counter, Distribution, Extremes, Kind, Moments};
+use crate::{accumulator_kind, measure, report, StationStats, StationTable};
+
+/// Tables kept for later requests; the oldest is dropped to make room.
+const CACHED_TABLES: usize = 64;
+
+/// An aggregated input, with the accumulator its requests needed.
+enum Cached {
+    Count(StationTable<Counter>),
+    Basic(StationTable<StationStats>),
+    Moments(StationTable<Moments>),
+    Distribution(StationTable<Distribution>),
+    Extremes(StationTable<Extremes>),
+}
+
+impl Cached {
+    fn measure(opts: &Options) -> Result<Self, Failure> {
+        Ok(match accumulator_kind(opts) {
+            Kind::Count => Cached::Count(measure(opts)?),
+            Kind::Basic => Cached::Basic(measure(opts)?),
+            Kind::Moments => Cached::Moments(measure(opts)?),
+            Kind::Distribution => Cached::Distribution(measure(opts)?),
+            Kind::Extremes => Cached::Extremes(measure(opts)?),
+        })
+    }
+
+    fn report(&self, opts: &Options, out: &mut dyn Write) -> io::Result<()> {
+        match self {
+            Cached::Count(table) => report(table, opts, out),
+            Cached::Basic(table) => report(table, opts, out),
+            Cached::Moments(table) => report(table, opts, out),
+            Cached::Distribution(table) => report(table, opts, out),
+            Cached::Extremes(table) => report(table, opts, out),
+        }
+    }
+}
+
+/// Tables by [`cache_key`], and the keys from oldest to newest.
+#[derive(Default)]
+struct Cache {
+    tables: HashMap<String, Cached>,
+    order: VecDeque<String>,
+}
+
+/// Runs `onebrc daemon`, which aggregates files for its clients so that each
+/// request costs neither starting a process nor, for an input it has seen,
+/// aggregating again. Each client sends newline-terminated lines:
+///
+/// * the arguments of an aggregation as on the command line, separated by
+///   tabs, e.g. `--stats\tmin,max,count\t/data/measurements.txt`, is
+///   answered `OK <LEN>` and a newline, then the LEN bytes of the results,
+///   or `ERROR <message>`,
+/// * `CLEAR` drops every cached table and replies `OK`.
+///
+/// A table is cached for the file, as long as its size, modification time
+/// and inode are unchanged, and the options that decide what is aggregated:
+/// requests differing only in how the results are printed, or in how the
+/// input is read and split across threads, share it. Requests are parsed as
+/// command lines are, under the config file and environment of the daemon.
+/// What the command line would fail on, such as a malformed line, only fails
+/// the request, answered `ERROR` with the message the command line prints.
+pub fn run(opts: &Options) -> io::Result<()> {
+    let Some(path) = opts.listen.strip_prefix("unix:") else {
+        return Err(io::Error::other(format!(
+            "daemon expects --listen unix:PATH, got '{}'",
+            opts.listen
+        )));
+    };
+    // A stale socket from a previous run would make bind fail; anything else
+    // at the path is left for bind to refuse
+    if fs::symlink_metadata(path).is_ok_and(|meta| meta.file_type().is_socket()) {
+        fs::remove_file(path)?;
+    }
+    let listener = UnixListener::bind(path)?;
+    eprintln!("daemon: listening on {}", opts.listen);
+    let cache = Arc::new(Mutex::new(Cache::default()));
+    for stream in listener.incoming() {
+        let stream = stream?;
+        let cache = cache.clone();
+        thread::spawn(move || serve(stream, &cache));
+    }
+    Ok(())
+}
+
+/// Answers the requests of one client until it disconnects.
+fn serve(stream: UnixStream, cache: &Mutex<Cache>) -> io::Result<()> {
+    let mut writer = stream.try_clone()?;
+    let mut reader = BufReader::new(stream);
+    let mut line = String::new();
+    loop {
+        line.clear();
+        if reader.read_line(&mut line)? == 0 {
+            return Ok(());
+        }
+        match line.trim_end_matches(['\n', '\r']) {
+            "" => continue,
+            "CLEAR" => {
+                *cache.lock().unwrap() = Cache::default();
+                writer.write_all(b"OK\n")?;
+            }
+            request => match answer(request, cache) {
+                Ok(out) => {
+                    writeln!(writer, "OK {}", out.len())?;
+                    writer.write_all(&out)?;
+                }
+                Err(e) => writeln!(writer, "ERROR {}", e.replace('\n', " "))?,
+            },
+        }
+        writer.flush()?;
+    }
+}
+
+/// The results of the aggregation `request` asks for, from the cache or
+/// aggregated and cached now.
+fn answer(request: &str, cache: &Mutex<Cache>) -> Result<Vec<u8>, String> {
+    // --help would print the usage to the daemon's stdout and exit
+    if request.split('\t').any(|arg| arg == "--help") {
+        return Err("requests cannot use --help".to_string());
+    }
+    let mut opts = cli::parse_args(request.split('\t').map(str::to_string))?;
+    check(&opts)?;
+    opts.color = opts.color.resolve(false);
+    let key = cache_key(&opts)?;
+
+    let mut out = Vec::new();
+    // Aggregating is left outside the lock, so that other clients' cached
+    // answers do not wait for it
+    if let Some(cached) = cache.lock().unwrap().tables.get(&key) {
+        cached.report(&opts, &mut out).map_err(|e| e.to_string())?;
+        return Ok(out);
+    }
+    let table = Cached::measure(&opts).map_err(|e| e.to_string())?;
+    table.report(&opts, &mut out).map_err(|e| e.to_string())?;
+
+    let mut cache = cache.lock().unwrap();
+    if cache.tables.insert(key.clone(), table).is_none() {
+        cache.order.push_back(key);
+    }
+    if cache.order.len() > CACHED_TABLES {
+        let oldest = cache.order.pop_front().unwrap();
+        cache.tables.remove(&oldest);
+    }
+    Ok(out)
+}
+
+/// Rejects what a request cannot ask of the daemon: anything but aggregating
+/// a local file, writing elsewhere than the reply, and results that would not
+/// be the same again for the same file.
+fn check(opts: &Options) -> Result<(), String> {
+    if opts.command != Command::Aggregate {
+        return Err("requests can only aggregate a file".to_string());
+    }
+    if opts.file_path == "-" || opts.file_path.contains("://") {
+        return Err("requests need a local file".to_string());
+    }
+    if opts.output.is_some() || opts.format.owns_output() || opts.emit_every.is_some() {
+        return Err("results are only written to the reply, so requests cannot use --output or --emit-every".to_string());
+    }
+    if opts.histogram.is_some()
+        || opts.baseline
+        || opts.verify
+        || opts.max_seconds.is_some()
+        || opts.processes > 1
+        || opts.tui
+        || opts.checkpoint.is_some()
+        || opts.resume.is_some()
+        || opts.record_schedule.is_some()
+        || opts.replay_schedule.is_some()
+        || opts.evict_cache
+    {
+        return Err("requests cannot use --histogram, --baseline, --verify, --max-seconds, --processes, --tui, --checkpoint, --resume, --record-/--replay-schedule or --evict-cache".to_string());
+    }
+    Ok(())
+}
+
+/// What decides the table a request aggregates: the file as it is now and
+/// its options, less those only changing how the table is printed or how
+/// fast it is built.
+fn cache_key(opts: &Options) -> Result<String, String> {
+    let path = &opts.file_path;
+    let meta = fs::metadata(path).map_err(|e| format!("{}: {}", path, e))?;
+    if !meta.is_file() {
+        return Err(format!("{}: not a regular file", path));
+    }
+    let path = fs::canonicalize(path).map_err(|e| format!("{}: {}", path, e))?;
+
+    let defaults = Options::default();
+    let mut what = opts.clone();
+    what.file_path = String::new();
+    // The accumulator stands for the stats: min,max and max,mean,min share
+    // a table
+    what.stats = Vec::new();
+    what.flag_outliers = None;
+    if what.order != Order::Input {
+        what.order = Order::Bytes;
+    }
+    what.locale = defaults.locale;
+    what.invalid_utf8 = defaults.invalid_utf8;
+    what.key_separator = None;
+    what.color = defaults.color;
+    what.format = Format::Spec;
+    what.geo = None;
+    what.units = false;
+    what.sql_table = defaults.sql_table;
+    what.sql_style = defaults.sql_style;
+    what.explain = false;
+    what.run_stats = false;
+    what.perf = false;
+    what.profile = None;
+    what.io = Io::Mmap;
+    what.fadvise = None;
+    what.readahead = None;
+    what.threads = 0;
+    what.chunk_size = defaults.chunk_size;
+    what.table_size = None;
+    what.batch = defaults.batch;
+    what.interleave = false;
+    what.two_pass = false;
+    what.auto_tune = false;
+    what.gpu = false;
+    what.engine = Engine::Pool;
+    what.deterministic = false;
+    Ok(format!(
+        "{}\0{}:{}:{}:{:?}\0{:?}\0{:?}",
+        path.display(),
+        meta.dev(),
+        meta.ino(),
+        meta.len(),
+        meta.modified().ok(),
+        accumulator_kind(opts),
+        what
+    ))
+}
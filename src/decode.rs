@@ -0,0 +1,231 @@
+use std::io::{self, Read};
+
+/// Text encoding of the input file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Encoding {
+    Utf8,
+    Utf16Le,
+    Utf16Be,
+    /// Any other WHATWG encoding, transcoded through encoding_rs.
+    #[cfg(feature = "encoding")]
+    Other(&'static encoding_rs::Encoding),
+}
+
+impl Encoding {
+    pub fn name(self) -> &'static str {
+        match self {
+            Encoding::Utf8 => "UTF-8",
+            Encoding::Utf16Le => "UTF-16LE",
+            Encoding::Utf16Be => "UTF-16BE",
+            #[cfg(feature = "encoding")]
+            Encoding::Other(enc) => enc.name(),
+        }
+    }
+
+    /// Looks up an encoding by name. The built-in UTF variants are always
+    /// available; other labels (`latin1`, `windows-1252`, `koi8-r`, ...) need
+    /// the `encoding` feature.
+    pub fn from_label(label: &str) -> Result<Self, String> {
+        match label.to_ascii_lowercase().as_str() {
+            "utf-8" | "utf8" => return Ok(Encoding::Utf8),
+            "utf-16le" => return Ok(Encoding::Utf16Le),
+            "utf-16be" => return Ok(Encoding::Utf16Be),
+            _ => {}
+        }
+        Self::from_whatwg_label(label)
+    }
+
+    #[cfg(feature = "encoding")]
+    fn from_whatwg_label(label: &str) -> Result<Self, String> {
+        // Note that WHATWG maps `latin1`/`iso-8859-1` to windows-1252, a superset
+        let enc = encoding_rs::Encoding::for_label(label.as_bytes())
+            .ok_or_else(|| format!("unknown input encoding '{}'", label))?;
+        Ok(if enc == encoding_rs::UTF_8 {
+            Encoding::Utf8
+        } else if enc == encoding_rs::UTF_16LE {
+            Encoding::Utf16Le
+        } else if enc == encoding_rs::UTF_16BE {
+            Encoding::Utf16Be
+        } else {
+            Encoding::Other(enc)
+        })
+    }
+
+    #[cfg(not(feature = "encoding"))]
+    fn from_whatwg_label(label: &str) -> Result<Self, String> {
+        Err(format!(
+            "input encoding '{}' requires building with the `encoding` feature",
+            label
+        ))
+    }
+}
+
+/// Detects a byte order mark at the start of `data`. Returns the encoding it
+/// announces and the length of the mark, or `None` if there is no BOM.
+pub fn sniff_bom(data: &[u8]) -> Option<(Encoding, usize)> {
+    match data {
+        [0xEF, 0xBB, 0xBF, ..] => Some((Encoding::Utf8, 3)),
+        [0xFF, 0xFE, ..] => Some((Encoding::Utf16Le, 2)),
+        [0xFE, 0xFF, ..] => Some((Encoding::Utf16Be, 2)),
+        _ => None,
+    }
+}
+
+const RAW_BUF_SIZE: usize = 1 << 20;
+
+/// Transcodes a UTF-16 byte stream to UTF-8 on the fly. Unpaired surrogates and
+/// a dangling odd byte at EOF are replaced with U+FFFD.
+pub struct Utf16Reader<R> {
+    inner: R,
+    big_endian: bool,
+    raw: Vec<u8>,
+    out: Vec<u8>,
+    out_pos: usize,
+    eof: bool,
+}
+
+impl<R: Read> Utf16Reader<R> {
+    pub fn new(inner: R, big_endian: bool) -> Self {
+        Self {
+            inner,
+            big_endian,
+            raw: Vec::with_capacity(RAW_BUF_SIZE),
+            out: Vec::with_capacity(RAW_BUF_SIZE * 3 / 2),
+            out_pos: 0,
+            eof: false,
+        }
+    }
+
+    /// Reads more raw input and decodes as much of it as forms complete code points.
+    fn refill(&mut self) -> io::Result<()> {
+        self.out.clear();
+        self.out_pos = 0;
+
+        while self.out.is_empty() && !self.eof {
+            let start = self.raw.len();
+            self.raw.resize(start + RAW_BUF_SIZE, 0);
+            let n = self.inner.read(&mut self.raw[start..])?;
+            self.raw.truncate(start + n);
+            self.eof = n == 0;
+
+            let big_endian = self.big_endian;
+            let raw = &self.raw;
+            let unit = |i: usize| {
+                let pair = [raw[2 * i], raw[2 * i + 1]];
+                if big_endian {
+                    u16::from_be_bytes(pair)
+                } else {
+                    u16::from_le_bytes(pair)
+                }
+            };
+
+            // Stop before a trailing odd byte or high surrogate unless this is the end
+            let mut units = raw.len() / 2;
+            if !self.eof && units > 0 && (0xD800..0xDC00).contains(&unit(units - 1)) {
+                units -= 1;
+            }
+
+            let decoded = char::decode_utf16((0..units).map(unit))
+                .map(|c| c.unwrap_or(char::REPLACEMENT_CHARACTER));
+            let mut tmp = [0u8; 4];
+            for c in decoded {
+                self.out
+                    .extend_from_slice(c.encode_utf8(&mut tmp).as_bytes());
+            }
+            self.raw.drain(..units * 2);
+
+            if self.eof && !self.raw.is_empty() {
+                self.raw.clear();
+                self.out.extend_from_slice(
+                    char::REPLACEMENT_CHARACTER.encode_utf8(&mut tmp).as_bytes(),
+                );
+            }
+        }
+        Ok(())
+    }
+}
+
+impl<R: Read> Read for Utf16Reader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.out_pos == self.out.len() {
+            self.refill()?;
+        }
+        let n = buf.len().min(self.out.len() - self.out_pos);
+        buf[..n].copy_from_slice(&self.out[self.out_pos..self.out_pos + n]);
+        self.out_pos += n;
+        Ok(n)
+    }
+}
+
+/// Transcodes a stream in any encoding_rs-supported encoding to UTF-8,
+/// chunk by chunk. Malformed sequences become U+FFFD.
+#[cfg(feature = "encoding")]
+pub struct TranscodingReader<R> {
+    inner: R,
+    decoder: encoding_rs::Decoder,
+    raw: Vec<u8>,
+    raw_pos: usize,
+    out: Vec<u8>,
+    out_pos: usize,
+    eof: bool,
+    done: bool,
+}
+
+#[cfg(feature = "encoding")]
+impl<R: Read> TranscodingReader<R> {
+    pub fn new(inner: R, encoding: &'static encoding_rs::Encoding) -> Self {
+        Self {
+            inner,
+            decoder: encoding.new_decoder_without_bom_handling(),
+            raw: vec![0; RAW_BUF_SIZE],
+            raw_pos: RAW_BUF_SIZE,
+            out: Vec::new(),
+            out_pos: 0,
+            eof: false,
+            done: false,
+        }
+    }
+
+    fn refill(&mut self) -> io::Result<()> {
+        self.out_pos = 0;
+        self.out.clear();
+
+        while self.out.is_empty() && !self.done {
+            if self.raw_pos == self.raw.len() && !self.eof {
+                self.raw.resize(RAW_BUF_SIZE, 0);
+                let n = self.inner.read(&mut self.raw)?;
+                self.raw.truncate(n);
+                self.raw_pos = 0;
+                self.eof = n == 0;
+            }
+
+            let src = &self.raw[self.raw_pos..];
+            let cap = self
+                .decoder
+                .max_utf8_buffer_length(src.len())
+                .expect("chunk too large to transcode");
+            self.out.resize(cap, 0);
+            let (result, read, written, _) =
+                self.decoder.decode_to_utf8(src, &mut self.out, self.eof);
+            self.raw_pos += read;
+            self.out.truncate(written);
+            if self.eof && result == encoding_rs::CoderResult::InputEmpty {
+                self.done = true;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(feature = "encoding")]
+impl<R: Read> Read for TranscodingReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.out_pos == self.out.len() {
+            self.refill()?;
+        }
+        let n = buf.len().min(self.out.len() - self.out_pos);
+        buf[..n].copy_from_slice(&self.out[self.out_pos..self.out_pos + n]);
+        self.out_pos += n;
+        Ok(n)
+    }
+}
@@ -0,0 +1,54 @@
+use std::sync::Arc;
+
+use crate::stats::Counter;
+use crate::{aggregate_lines, Aggregator, StationTable};
+
+/// Slices of the input the first pass of `--two-pass` reads, spread evenly
+/// so that stations which only show up late are found too.
+const SAMPLE_SLICES: usize = 16;
+const SAMPLE_SLICE_SIZE: usize = 256 << 10;
+
+/// Collects the distinct keys of a sample of `data`, grouped like `table`.
+pub fn sample_keys<A: Aggregator>(data: &[u8], table: &StationTable<A>) -> Vec<Box<[u8]>> {
+    let mut sample = StationTable::<Counter>::new();
+    sample.key = table.key.clone();
+    sample.decimals = table.decimals;
+    sample.skip_comments = table.skip_comments;
+    let stride = (data.len() / SAMPLE_SLICES).max(SAMPLE_SLICE_SIZE);
+    let mut offset = 0;
+    while offset < data.len() {
+        aggregate_lines(line_slice(data, offset, SAMPLE_SLICE_SIZE), &mut sample);
+        offset += stride;
+    }
+    sample.iter().map(|(key, _)| key.into()).collect()
+}
+
+/// The whole lines of `data` in the `len` bytes from `offset` on, starting
+/// after the line `offset` falls into unless that is the first.
+fn line_slice(data: &[u8], offset: usize, len: usize) -> &[u8] {
+    let start = match offset {
+        0 => 0,
+        _ => memchr::memchr(b'\n', &data[offset - 1..]).map_or(data.len(), |i| offset + i),
+    };
+    let end = match data.get(start + len..) {
+        Some(tail) => memchr::memchr(b'\n', tail).map_or(data.len(), |i| start + len + i + 1),
+        None => data.len(),
+    };
+    &data[start..end]
+}
+
+/// Replaces `table` with one sized for `keys` and holding all of them, for
+/// the second pass of `--two-pass`. Its siblings start out with the same
+/// keys, so lookups of known stations never insert or grow a table.
+pub fn seed<A: Aggregator>(table: &mut StationTable<A>, keys: Vec<Box<[u8]>>) {
+    let slots = slots_for(keys.len());
+    table.known = Some(Arc::from(keys));
+    let schedule = table.schedule.take();
+    *table = table.sibling_with_slots(slots);
+    table.schedule = schedule;
+}
+
+/// Slots for a table holding `stations` so it stays at most a quarter full.
+fn slots_for(stations: usize) -> usize {
+    (stations * 4).next_power_of_two().max(64)
+}
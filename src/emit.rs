@@ -0,0 +1,92 @@
+//! `--emit-every`: a snapshot of the results so far after every N rows, in
+//! the `--format` of the final results, written to stdout or appended to
+//! `--emit-to`, for a view into a long run before it ends. Mapped input is
+//! aggregated in segments of N rows, each in parallel, with the snapshot
+//! taken between them; streamed input is cut after the N-th row of a buffer.
+
+use std::fs::OpenOptions;
+use std::io::{self, Write};
+
+use crate::budget::{count_rows, first_rows};
+use crate::cli::{Failure, Options};
+use crate::{Aggregator, StationTable};
+
+/// Writes the results of a table as the run's final report would.
+pub type Report<A> = fn(&StationTable<A>, &Options, &mut dyn Write) -> io::Result<()>;
+
+pub struct Emitter<A> {
+    every: u64,
+    /// Rows until the next snapshot.
+    left: u64,
+    out: Box<dyn Write + Send>,
+    /// Where `out` writes, for errors.
+    name: String,
+    opts: Options,
+    report: Report<A>,
+}
+
+impl<A: Aggregator> Emitter<A> {
+    /// The emitter `opts` asks for, if any.
+    pub fn setup(opts: &Options, report: Report<A>) -> Result<Option<Self>, Failure> {
+        let Some(every) = opts.emit_every else {
+            return Ok(None);
+        };
+        let (out, name): (Box<dyn Write + Send>, String) = match &opts.emit_to {
+            Some(path) => {
+                let file = OpenOptions::new()
+                    .create(true)
+                    .append(true)
+                    .open(path)
+                    .map_err(|e| Failure::io(format!("{}: {}", path, e)))?;
+                (Box::new(file), path.clone())
+            }
+            None => (Box::new(io::stdout()), "stdout".to_string()),
+        };
+        Ok(Some(Self {
+            every,
+            left: every,
+            out,
+            name,
+            opts: opts.clone(),
+            report,
+        }))
+    }
+
+    /// Writes a snapshot of `table`, failing the run if it cannot.
+    fn emit(&mut self, table: &StationTable<A>) {
+        let mut snapshot = Vec::new();
+        let written = (self.report)(table, &self.opts, &mut snapshot)
+            .and_then(|()| self.out.write_all(&snapshot))
+            .and_then(|()| self.out.flush());
+        if let Err(e) = written {
+            table
+                .fault
+                .raise(Failure::io(format!("--emit-every: {}: {}", self.name, e)));
+        }
+    }
+}
+
+/// Aggregates `data` into `table` with `aggregate`, in segments that end
+/// where a snapshot is due when `table` has an emitter, emitting after each.
+pub fn in_segments<A: Aggregator>(
+    data: &[u8],
+    table: &mut StationTable<A>,
+    mut aggregate: impl FnMut(&[u8], &mut StationTable<A>),
+) {
+    let Some(mut emitter) = table.emit.take() else {
+        aggregate(data, table);
+        return;
+    };
+    let mut rest = data;
+    while !rest.is_empty() && !table.fault.raised() {
+        let segment = first_rows(rest, emitter.left);
+        aggregate(segment, table);
+        rest = &rest[segment.len()..];
+        emitter.left -= count_rows(segment);
+        if emitter.left == 0 {
+            emitter.emit(table);
+            emitter.left = emitter.every;
+        }
+    }
+    table.emit = Some(emitter);
+}
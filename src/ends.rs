@@ -0,0 +1,59 @@
+use std::collections::HashMap;
+
+/// A reading and the byte offset of its line in the input.
+#[derive(Clone, Copy)]
+struct Reading {
+    offset: u64,
+    temp: i32,
+}
+
+/// `--first-last`: the first and last reading of each station, by their
+/// offset in the input as the table's [`Origin`](crate::origin::Origin) gives
+/// it, so that each worker's table can note the readings of its chunks in
+/// any order and the tables merge to the input's.
+#[derive(Default)]
+pub struct Ends {
+    stations: HashMap<Box<[u8]>, (Reading, Reading)>,
+}
+
+impl Ends {
+    /// Records the reading `temp` of `key`, from the line at `offset`.
+    #[inline]
+    pub fn record(&mut self, key: &[u8], offset: u64, temp: i32) {
+        let reading = Reading { offset, temp };
+        match self.stations.get_mut(key) {
+            Some(ends) => Self::widen(ends, reading, reading),
+            None => {
+                self.stations.insert(key.into(), (reading, reading));
+            }
+        }
+    }
+
+    fn widen(ends: &mut (Reading, Reading), first: Reading, last: Reading) {
+        if first.offset < ends.0.offset {
+            ends.0 = first;
+        }
+        if last.offset > ends.1.offset {
+            ends.1 = last;
+        }
+    }
+
+    /// Merges what a worker's table noted into this one.
+    pub fn merge(&mut self, other: &Ends) {
+        for (key, &(first, last)) in &other.stations {
+            match self.stations.get_mut(key) {
+                Some(ends) => Self::widen(ends, first, last),
+                None => {
+                    self.stations.insert(key.clone(), (first, last));
+                }
+            }
+        }
+    }
+
+    /// The first and last reading of `key`, in degrees, with their offsets.
+    pub fn get(&self, key: &[u8], scale: f64) -> Option<((f64, u64), (f64, u64))> {
+        let (first, last) = self.stations.get(key)?;
+        let degrees = |reading: &Reading| (reading.temp as f64 / scale, reading.offset);
+        Some((degrees(first), degrees(last)))
+    }
+}
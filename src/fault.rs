@@ -0,0 +1,38 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+
+use crate::cli::Failure;
+
+/// The first failure met while aggregating, such as a malformed line or a
+/// read error on a worker's thread, shared by every worker's table. Once one
+/// is raised the workers skip the rest of the input, and the run returns it.
+#[derive(Default)]
+pub struct Fault {
+    raised: AtomicBool,
+    first: Mutex<Option<Failure>>,
+}
+
+impl Fault {
+    /// Records `failure`, unless one was raised before.
+    #[cold]
+    #[inline(never)]
+    pub fn raise(&self, failure: Failure) {
+        if self.raised() {
+            return;
+        }
+        self.first.lock().unwrap().get_or_insert(failure);
+        self.raised.store(true, Ordering::Relaxed);
+    }
+
+    /// Whether a failure was raised, so that the input left is not worth
+    /// aggregating.
+    #[inline]
+    pub fn raised(&self) -> bool {
+        self.raised.load(Ordering::Relaxed)
+    }
+
+    /// The failure raised, if any.
+    pub fn take(&self) -> Option<Failure> {
+        self.first.lock().unwrap().take()
+    }
+}
@@ -0,0 +1,176 @@
+use std::net::SocketAddr;
+use std::sync::Arc;
+
+use arrow_array::{ArrayRef, Float64Array, RecordBatch, StringArray, UInt64Array};
+use arrow_flight::encode::FlightDataEncoderBuilder;
+use arrow_flight::flight_service_server::{FlightService, FlightServiceServer};
+use arrow_flight::{
+    Action, ActionType, Criteria, Empty, FlightData, FlightDescriptor, FlightEndpoint, FlightInfo,
+    HandshakeRequest, HandshakeResponse, PollInfo, PutResult, SchemaResult, Ticket,
+};
+use arrow_schema::{ArrowError, DataType, Field, Schema, SchemaRef};
+use futures::stream::{self, BoxStream, StreamExt, TryStreamExt};
+use tonic::{Request, Response, Status, Streaming};
+
+use crate::server::Ingest;
+
+/// Ticket under which the live aggregate is published. `do_get` ignores the
+/// ticket contents, but `list_flights`/`get_flight_info` advertise this one.
+const SNAPSHOT_TICKET: &str = "snapshot";
+
+fn schema() -> SchemaRef {
+    Arc::new(Schema::new(vec![
+        Field::new("station", DataType::Utf8, false),
+        Field::new("min", DataType::Float64, false),
+        Field::new("mean", DataType::Float64, false),
+        Field::new("max", DataType::Float64, false),
+        Field::new("count", DataType::UInt64, false),
+    ]))
+}
+
+/// Copies the current table into a record batch, sorted by station name.
+fn snapshot(state: &Ingest) -> Result<RecordBatch, ArrowError> {
+    let table = state.table.lock().unwrap();
+    let mut rows: Vec<_> = table.iter().collect();
+    rows.sort_unstable_by(|a, b| a.0.cmp(b.0));
+
+    let columns: Vec<ArrayRef> = vec![
+        Arc::new(StringArray::from_iter_values(
+            rows.iter().map(|(name, _)| String::from_utf8_lossy(name)),
+        )),
+        Arc::new(Float64Array::from_iter_values(
+            rows.iter().map(|(_, s)| s.min_f64()),
+        )),
+        Arc::new(Float64Array::from_iter_values(
+            rows.iter().map(|(_, s)| s.mean()),
+        )),
+        Arc::new(Float64Array::from_iter_values(
+            rows.iter().map(|(_, s)| s.max_f64()),
+        )),
+        Arc::new(UInt64Array::from_iter_values(
+            rows.iter().map(|(_, s)| s.count()),
+        )),
+    ];
+    RecordBatch::try_new(schema(), columns)
+}
+
+struct ResultsService {
+    state: Arc<Ingest>,
+}
+
+impl ResultsService {
+    fn flight_info(&self, descriptor: FlightDescriptor) -> Result<FlightInfo, Status> {
+        let info = FlightInfo::new()
+            .try_with_schema(&schema())
+            .map_err(|e| Status::internal(e.to_string()))?
+            .with_descriptor(descriptor)
+            .with_endpoint(FlightEndpoint::new().with_ticket(Ticket::new(SNAPSHOT_TICKET)));
+        Ok(info)
+    }
+}
+
+#[tonic::async_trait]
+impl FlightService for ResultsService {
+    type HandshakeStream = BoxStream<'static, Result<HandshakeResponse, Status>>;
+    type ListFlightsStream = BoxStream<'static, Result<FlightInfo, Status>>;
+    type DoGetStream = BoxStream<'static, Result<FlightData, Status>>;
+    type DoPutStream = BoxStream<'static, Result<PutResult, Status>>;
+    type DoExchangeStream = BoxStream<'static, Result<FlightData, Status>>;
+    type DoActionStream = BoxStream<'static, Result<arrow_flight::Result, Status>>;
+    type ListActionsStream = BoxStream<'static, Result<ActionType, Status>>;
+
+    async fn handshake(
+        &self,
+        _request: Request<Streaming<HandshakeRequest>>,
+    ) -> Result<Response<Self::HandshakeStream>, Status> {
+        Err(Status::unimplemented("no authentication required"))
+    }
+
+    async fn list_flights(
+        &self,
+        _request: Request<Criteria>,
+    ) -> Result<Response<Self::ListFlightsStream>, Status> {
+        let info = self.flight_info(FlightDescriptor::new_path(vec![SNAPSHOT_TICKET.into()]))?;
+        Ok(Response::new(stream::iter([Ok(info)]).boxed()))
+    }
+
+    async fn get_flight_info(
+        &self,
+        request: Request<FlightDescriptor>,
+    ) -> Result<Response<FlightInfo>, Status> {
+        Ok(Response::new(self.flight_info(request.into_inner())?))
+    }
+
+    async fn poll_flight_info(
+        &self,
+        _request: Request<FlightDescriptor>,
+    ) -> Result<Response<PollInfo>, Status> {
+        Err(Status::unimplemented("poll_flight_info"))
+    }
+
+    async fn get_schema(
+        &self,
+        _request: Request<FlightDescriptor>,
+    ) -> Result<Response<SchemaResult>, Status> {
+        Err(Status::unimplemented("use get_flight_info for the schema"))
+    }
+
+    async fn do_get(
+        &self,
+        _request: Request<Ticket>,
+    ) -> Result<Response<Self::DoGetStream>, Status> {
+        let batch = snapshot(&self.state).map_err(|e| Status::internal(e.to_string()))?;
+        let stream = FlightDataEncoderBuilder::new()
+            .with_schema(schema())
+            .build(stream::iter([Ok(batch)]))
+            .map_err(Status::from);
+        Ok(Response::new(stream.boxed()))
+    }
+
+    async fn do_put(
+        &self,
+        _request: Request<Streaming<FlightData>>,
+    ) -> Result<Response<Self::DoPutStream>, Status> {
+        Err(Status::unimplemented("results are read-only"))
+    }
+
+    async fn do_exchange(
+        &self,
+        _request: Request<Streaming<FlightData>>,
+    ) -> Result<Response<Self::DoExchangeStream>, Status> {
+        Err(Status::unimplemented("do_exchange"))
+    }
+
+    async fn do_action(
+        &self,
+        _request: Request<Action>,
+    ) -> Result<Response<Self::DoActionStream>, Status> {
+        Err(Status::unimplemented("no actions"))
+    }
+
+    async fn list_actions(
+        &self,
+        _request: Request<Empty>,
+    ) -> Result<Response<Self::ListActionsStream>, Status> {
+        Ok(Response::new(stream::empty().boxed()))
+    }
+}
+
+/// Serves the ingest table over Arrow Flight on `addr`. Blocks the calling
+/// thread with its own runtime so the socket server stays synchronous.
+pub fn serve(addr: SocketAddr, state: Arc<Ingest>) {
+    let runtime = tokio::runtime::Builder::new_multi_thread()
+        .enable_all()
+        .build()
+        .expect("Failed to start the Flight runtime");
+    let service = FlightServiceServer::new(ResultsService { state });
+    runtime.block_on(async {
+        if let Err(e) = tonic::transport::Server::builder()
+            .add_service(service)
+            .serve(addr)
+            .await
+        {
+            eprintln!("flight: {}", e);
+        }
+    });
+}
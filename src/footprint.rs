@@ -0,0 +1,205 @@
+//! `--run-stats`: the memory a run took, so that the machine for a bigger
+//! dataset can be sized from a smaller one instead of guessed. A mapped
+//! input counts towards the RSS as far as its pages were read. Then what each
+//! worker did: the rows and bytes it aggregated, the time it spent on them and
+//! how far its table had to probe, so that a worker left with more than its
+//! share or a hash that piles stations up shows in the numbers.
+
+use std::io::{self, Write};
+use std::mem::size_of_val;
+use std::time::Duration;
+
+use crate::budget::count_rows;
+use crate::{map, StationTable};
+
+/// `bytes` in the largest unit that keeps it above one.
+pub(crate) fn size(bytes: u64) -> String {
+    match bytes {
+        0..=1023 => format!("{} B", bytes),
+        1024..=0xfffff => format!("{:.1} KiB", bytes as f64 / 1024.0),
+        0x100000..=0x3fff_ffff => format!("{:.1} MiB", bytes as f64 / (1 << 20) as f64),
+        _ => format!("{:.1} GiB", bytes as f64 / (1 << 30) as f64),
+    }
+}
+
+/// The peak resident set sizes, in bytes, of this process and of its largest
+/// `--processes` worker (0 without workers).
+#[cfg(unix)]
+fn peak_rss() -> Option<(u64, u64)> {
+    let max_rss = |who| {
+        let mut usage = std::mem::MaybeUninit::<libc::rusage>::zeroed();
+        // SAFETY: getrusage only writes the struct it is given
+        if unsafe { libc::getrusage(who, usage.as_mut_ptr()) } != 0 {
+            return None;
+        }
+        // SAFETY: initialized by the successful call
+        let max = unsafe { usage.assume_init() }.ru_maxrss as u64;
+        // Bytes on macOS, kilobytes elsewhere
+        Some(if cfg!(target_os = "macos") {
+            max
+        } else {
+            max * 1024
+        })
+    };
+    // illumos and Solaris leave ru_maxrss at 0, which no running process has
+    let own = max_rss(libc::RUSAGE_SELF).filter(|&rss| rss > 0)?;
+    Some((own, max_rss(libc::RUSAGE_CHILDREN)?))
+}
+
+#[cfg(not(unix))]
+fn peak_rss() -> Option<(u64, u64)> {
+    None
+}
+
+/// Writes the peak memory of the run and the sizes `table` ended up with.
+pub fn write_report<A>(table: &StationTable<A>, out: &mut dyn Write) -> io::Result<()> {
+    writeln!(out, "run stats:")?;
+    match peak_rss() {
+        Some((rss, 0)) => writeln!(out, "  peak RSS        {}", size(rss))?,
+        Some((rss, worker)) => writeln!(
+            out,
+            "  peak RSS        {} (largest worker: {})",
+            size(rss),
+            size(worker)
+        )?,
+        None => {}
+    }
+    if map::ENABLED {
+        let names: usize = table.map.keys().map(|name| name.len()).sum();
+        return writeln!(
+            out,
+            "  station map     {} stations, room for {} in a {}; {} of names",
+            table.map.len(),
+            table.map.capacity(),
+            map::NAME,
+            size(names as u64)
+        );
+    }
+    writeln!(
+        out,
+        "  station table   {} of {} slots filled, {}",
+        table.len,
+        table.entries.len(),
+        size(size_of_val(&*table.entries) as u64)
+    )?;
+    writeln!(
+        out,
+        "  name arena      {} used of {} allocated",
+        size(table.names.len() as u64),
+        size(table.names.capacity() as u64)
+    )
+}
+
+/// What a table's worker aggregated, counted as it went.
+#[derive(Default)]
+pub struct Activity {
+    rows: u64,
+    bytes: u64,
+    busy: Duration,
+    /// Ranges this worker split off lagging workers, and their bytes.
+    splits: u64,
+    split_bytes: u64,
+    /// The workers whose tables were merged into this one, in merge order.
+    workers: Vec<Worker>,
+}
+
+/// A worker's totals, with the stations its table ended with and their mean
+/// and longest probe (`None` without the built-in table).
+pub struct Worker {
+    rows: u64,
+    bytes: u64,
+    busy: Duration,
+    splits: u64,
+    split_bytes: u64,
+    stations: usize,
+    probes: Option<(f64, usize)>,
+}
+
+impl Activity {
+    /// Counts `data`, which took `busy` to aggregate.
+    pub fn record(&mut self, data: &[u8], busy: Duration) {
+        self.rows += count_rows(data);
+        self.bytes += data.len() as u64;
+        self.busy += busy;
+    }
+
+    /// Counts a range of `bytes` this worker split off another's.
+    pub fn split_off(&mut self, bytes: usize) {
+        self.splits += 1;
+        self.split_bytes += bytes as u64;
+    }
+
+    /// This worker's totals, its table holding `stations` stations probed
+    /// as `probes` says.
+    pub fn worker(&self, stations: usize, probes: Option<(f64, usize)>) -> Worker {
+        Worker {
+            rows: self.rows,
+            bytes: self.bytes,
+            busy: self.busy,
+            splits: self.splits,
+            split_bytes: self.split_bytes,
+            stations,
+            probes,
+        }
+    }
+
+    /// Notes a worker whose table was merged into this one.
+    pub fn absorb(&mut self, worker: Worker) {
+        self.workers.push(worker);
+    }
+
+    /// Writes a line per worker, `own` (this table's worker) among them if it
+    /// aggregated anything itself or nothing was merged into it.
+    pub fn write_report(&self, own: Worker, out: &mut dyn Write) -> io::Result<()> {
+        let mut workers: Vec<&Worker> = self.workers.iter().collect();
+        if own.rows > 0 || workers.is_empty() {
+            workers.insert(0, &own);
+        }
+        writeln!(out, "worker stats:")?;
+        writeln!(
+            out,
+            "  {:>6} {:>12} {:>10} {:>9} {:>8} {:>13}",
+            "worker", "rows", "bytes", "busy", "stations", "probe avg/max"
+        )?;
+        for (idx, worker) in workers.iter().enumerate() {
+            let probes = match worker.probes {
+                Some((mean, max)) => format!("{:.2} / {}", mean, max),
+                None => "-".to_string(),
+            };
+            writeln!(
+                out,
+                "  {:>6} {:>12} {:>10} {:>7.3} s {:>8} {:>13}",
+                idx,
+                worker.rows,
+                size(worker.bytes),
+                worker.busy.as_secs_f64(),
+                worker.stations,
+                probes
+            )?;
+        }
+        let busy: Vec<f64> = workers.iter().map(|w| w.busy.as_secs_f64()).collect();
+        let mean = busy.iter().sum::<f64>() / busy.len() as f64;
+        let most = busy.iter().cloned().fold(0.0, f64::max);
+        if workers.len() > 1 && mean > 0.0 {
+            writeln!(
+                out,
+                "  busiest worker {:.3} s, {:.2}x the mean of {:.3} s",
+                most,
+                most / mean,
+                mean
+            )?;
+        }
+        let splits: u64 = workers.iter().map(|w| w.splits).sum();
+        if splits > 0 {
+            let bytes = workers.iter().map(|w| w.split_bytes).sum();
+            writeln!(
+                out,
+                "  rebalanced: {} of lagging workers' chunks moved to idle ones, in {} split{}",
+                size(bytes),
+                splits,
+                if splits == 1 { "" } else { "s" }
+            )?;
+        }
+        Ok(())
+    }
+}
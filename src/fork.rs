@@ -0,0 +1,136 @@
+use std::fs::File;
+use std::io::{Read, Write};
+use std::os::unix::io::FromRawFd;
+
+use crate::cli::{Exit, Failure};
+use crate::{line_chunks, partial, process_lines, Aggregator, StationTable};
+
+/// Exit status of a child whose aggregator has no binary encoding, apart
+/// from the [`Exit`] statuses a child may fail with.
+const NO_ENCODING: i32 = 64;
+
+/// The statuses a child exits with when it fails, sending the message.
+const STATUSES: [Exit; 6] = [
+    Exit::Usage,
+    Exit::Io,
+    Exit::Data,
+    Exit::Mismatch,
+    Exit::Internal,
+    Exit::Regression,
+];
+
+/// Aggregates `data` in `processes` forked children, one line-aligned slice
+/// each, for `--processes`. Every child builds its own table and writes it
+/// to a pipe in the [`partial`] encoding; the parent merges them in order.
+/// Separate processes do not share page tables, which on some kernels scales
+/// better than threads faulting in the same mapping. What a child or forking
+/// one fails on is raised as the run's failure.
+pub fn aggregate<A: Aggregator>(
+    data: &[u8],
+    processes: usize,
+    chunk_size: usize,
+    table: &mut StationTable<A>,
+) {
+    let slice_size = data.len().div_ceil(processes).max(1);
+    let mut children = Vec::new();
+    for slice in line_chunks(data, slice_size) {
+        let mut fds = [0; 2];
+        // SAFETY: `fds` has room for the two descriptors pipe() writes
+        if unsafe { libc::pipe(fds.as_mut_ptr()) } != 0 {
+            let e = std::io::Error::last_os_error();
+            table
+                .fault
+                .raise(Failure::new(Exit::Internal, format!("pipe: {}", e)));
+            break;
+        }
+        // SAFETY: the child only aggregates its slice, writes the result and
+        // exits without returning into the caller
+        match unsafe { libc::fork() } {
+            -1 => {
+                let e = std::io::Error::last_os_error();
+                // SAFETY: neither end was handed to a child
+                unsafe {
+                    libc::close(fds[0]);
+                    libc::close(fds[1]);
+                }
+                table
+                    .fault
+                    .raise(Failure::new(Exit::Internal, format!("fork: {}", e)));
+                break;
+            }
+            0 => {
+                // SAFETY: the read end is not used in the child; the write
+                // end is owned by the file from here on
+                unsafe { libc::close(fds[0]) };
+                let pipe = unsafe { File::from_raw_fd(fds[1]) };
+                run_child(slice, chunk_size, table.sibling(), pipe);
+            }
+            pid => {
+                // SAFETY: as above, for the parent's ends
+                unsafe { libc::close(fds[1]) };
+                children.push((pid, unsafe { File::from_raw_fd(fds[0]) }));
+            }
+        }
+    }
+
+    // Every child is reaped, even after one failed
+    let mut encoded = Vec::new();
+    for (pid, mut pipe) in children {
+        encoded.clear();
+        let read = pipe.read_to_end(&mut encoded);
+        let mut status = 0;
+        // SAFETY: `pid` is a child of this process that has not been reaped
+        unsafe { libc::waitpid(pid, &mut status, 0) };
+        let code = libc::WIFEXITED(status).then(|| libc::WEXITSTATUS(status));
+        let failure = match (read, code) {
+            (Err(e), _) => Failure::new(Exit::Internal, format!("reading from child: {}", e)),
+            (Ok(_), Some(0)) => match partial::merge_encoded(&encoded, table) {
+                Some(()) => continue,
+                None => Failure::new(
+                    Exit::Internal,
+                    "child process sent a corrupt partial result",
+                ),
+            },
+            (Ok(_), Some(NO_ENCODING)) => {
+                Failure::usage("--processes requires an aggregator with a binary encoding")
+            }
+            // The child has sent what it failed on, such as a malformed line
+            (Ok(_), Some(code)) if !encoded.is_empty() => {
+                match STATUSES.into_iter().find(|&exit| exit as i32 == code) {
+                    Some(exit) => Failure::new(exit, String::from_utf8_lossy(&encoded)),
+                    None => Failure::new(Exit::Internal, format!("child process {} failed", pid)),
+                }
+            }
+            (Ok(_), _) => Failure::new(Exit::Internal, format!("child process {} failed", pid)),
+        };
+        table.fault.raise(failure);
+    }
+}
+
+fn run_child<A: Aggregator>(
+    slice: &[u8],
+    chunk_size: usize,
+    mut table: StationTable<A>,
+    mut pipe: File,
+) -> ! {
+    table.progress = None;
+    for chunk in line_chunks(slice, chunk_size) {
+        process_lines(chunk, &mut table);
+    }
+    let mut out = Vec::new();
+    let code = match table.fault.take() {
+        Some(failure) => {
+            out = failure.message.into_bytes();
+            failure.status as i32
+        }
+        None if !partial::encode_table(&table, &mut out) => NO_ENCODING,
+        None => 0,
+    };
+    let code = match pipe.write_all(&out) {
+        Ok(()) => code,
+        Err(_) => Exit::Internal as i32,
+    };
+    // SAFETY: exits without running the parent's atexit handlers or
+    // flushing its buffered output a second time
+    unsafe { libc::_exit(code) }
+}
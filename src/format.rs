@@ -0,0 +1,513 @@
+use std::fmt::Write as _;
+use std::io::{self, Write};
+
+use crate::cli::{Color, Options, SqlStyle};
+use crate::display_name;
+use crate::geo::{GeoTable, Location};
+use crate::stats::{self, Stat, Summary};
+
+/// A station ready for output: its display name and accumulator.
+pub type Row<'a, A> = (&'a [u8], &'a A);
+
+/// Prints a [`Summary::value`] in degrees with the input's number of decimals.
+/// Values are rounded half up in units of the last digit, as `Math.round`
+/// in the 1BRC reference, so that ties round towards positive infinity and
+/// nothing prints as `-0.0`. Ties are exact here: the reference sums in
+/// floating point, which can leave a mean just short of one.
+pub fn write_stat(out: &mut dyn Write, stat: Stat, value: f64, opts: &Options) -> io::Result<()> {
+    if stat.is_count() {
+        write!(out, "{}", value)
+    } else {
+        write!(out, "{:.*}", opts.decimals as usize, degrees(value, opts))
+    }
+}
+
+/// A temperature [`Summary::value`] in degrees, rounded as [`write_stat`]
+/// prints it.
+pub fn degrees(value: f64, opts: &Options) -> f64 {
+    (value + 0.5).floor() / stats::scale(opts.decimals)
+}
+
+/// Terminal colors of `--color`: the coldest and hottest values, and
+/// warnings.
+const COLD: &str = "\x1b[1;34m";
+const HOT: &str = "\x1b[1;31m";
+pub const WARNING: &str = "\x1b[33m";
+pub const RESET: &str = "\x1b[0m";
+
+/// The lowest and highest printed value of each temperature statistic, for
+/// `--color`. Spreads and counts are not temperatures to highlight.
+struct Extremes(Vec<Option<(f64, f64)>>);
+
+impl Extremes {
+    fn new<A: Summary>(rows: &[Row<A>], opts: &Options) -> Self {
+        if opts.color != Color::Always || rows.len() < 2 {
+            return Self(Vec::new());
+        }
+        let range = |stat| {
+            rows.iter()
+                .map(|(_, stats)| degrees(stats.value(stat), opts))
+                .fold((f64::INFINITY, f64::NEG_INFINITY), |(lo, hi), v| {
+                    (lo.min(v), hi.max(v))
+                })
+        };
+        Self(
+            opts.stats
+                .iter()
+                .map(|&stat| (!stat.is_count() && stat != Stat::Stddev).then(|| range(stat)))
+                .collect(),
+        )
+    }
+
+    /// The color of `value` of the `i`th statistic, if it is an extreme.
+    fn color(&self, i: usize, value: f64, opts: &Options) -> Option<&'static str> {
+        let (lo, hi) = self.0.get(i).copied().flatten()?;
+        let value = degrees(value, opts);
+        (value == lo)
+            .then_some(COLD)
+            .or((value == hi).then_some(HOT))
+    }
+}
+
+/// The 1BRC `{name=min/mean/max, ...}` format, or with the statistics chosen
+/// by `--stats` in place of min/mean/max.
+pub fn write_spec<A: Summary>(
+    rows: &[Row<A>],
+    opts: &Options,
+    out: &mut dyn Write,
+) -> io::Result<()> {
+    let extremes = Extremes::new(rows, opts);
+    write!(out, "{{")?;
+    for (i, (name, stats)) in rows.iter().enumerate() {
+        if i > 0 {
+            write!(out, ", ")?;
+        }
+        write!(out, "{}=", display_name(name, opts.invalid_utf8))?;
+        for (j, &stat) in opts.stats.iter().enumerate() {
+            if j > 0 {
+                write!(out, "/")?;
+            }
+            let value = stats.value(stat);
+            match extremes.color(j, value, opts) {
+                Some(color) => {
+                    write!(out, "{}", color)?;
+                    write_stat(out, stat, value, opts)?;
+                    write!(out, "{}", RESET)?;
+                }
+                None => write_stat(out, stat, value, opts)?,
+            }
+        }
+    }
+    writeln!(out, "}}")
+}
+
+/// A `name\tstats\0` record per station, with the statistics as in
+/// [`write_spec`], so that names are delimited whatever bytes they hold. A
+/// name may contain tabs too; the statistics follow the last one.
+pub fn write_print0<A: Summary>(
+    rows: &[Row<A>],
+    opts: &Options,
+    out: &mut dyn Write,
+) -> io::Result<()> {
+    for (name, stats) in rows {
+        write!(out, "{}\t", display_name(name, opts.invalid_utf8))?;
+        for (j, &stat) in opts.stats.iter().enumerate() {
+            if j > 0 {
+                write!(out, "/")?;
+            }
+            write_stat(out, stat, stats.value(stat), opts)?;
+        }
+        write!(out, "\0")?;
+    }
+    Ok(())
+}
+
+fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+/// One header line, then a row per station with the `--stats` columns and,
+/// with `--geo`, `lat,lon,elevation` (empty for stations not in the lookup).
+pub fn write_csv<A: Summary>(
+    rows: &[Row<A>],
+    opts: &Options,
+    geo: Option<&GeoTable>,
+    out: &mut dyn Write,
+) -> io::Result<()> {
+    write!(out, "station")?;
+    for stat in &opts.stats {
+        write!(out, ",{}", stat.name())?;
+    }
+    if geo.is_some() {
+        write!(out, ",lat,lon,elevation")?;
+    }
+    writeln!(out)?;
+
+    for (name, stats) in rows {
+        write!(out, "{}", csv_field(&display_name(name, opts.invalid_utf8)))?;
+        for &stat in &opts.stats {
+            write!(out, ",")?;
+            write_stat(out, stat, stats.value(stat), opts)?;
+        }
+        if let Some(geo) = geo {
+            match geo.get(name) {
+                Some(loc) => {
+                    write!(out, ",{},{},", loc.lat, loc.lon)?;
+                    if let Some(elevation) = loc.elevation {
+                        write!(out, "{}", elevation)?;
+                    }
+                }
+                None => write!(out, ",,,")?,
+            }
+        }
+        writeln!(out)?;
+    }
+    Ok(())
+}
+
+/// Escapes what would end a GitHub-flavored Markdown table cell or turn it
+/// into markup.
+fn markdown_cell(field: &str) -> String {
+    let mut out = String::with_capacity(field.len());
+    for c in field.chars() {
+        match c {
+            '|' | '\\' | '*' | '_' | '`' | '[' | ']' | '<' | '>' => {
+                out.push('\\');
+                out.push(c);
+            }
+            '\n' | '\r' => out.push(' '),
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+/// A GitHub-flavored Markdown table with the `--stats` columns, right-aligned,
+/// and with `--geo`, `lat`/`lon`/`elevation` (empty when unknown).
+pub fn write_markdown<A: Summary>(
+    rows: &[Row<A>],
+    opts: &Options,
+    geo: Option<&GeoTable>,
+    out: &mut dyn Write,
+) -> io::Result<()> {
+    let mut columns: Vec<&str> = opts.stats.iter().map(|stat| stat.name()).collect();
+    if geo.is_some() {
+        columns.extend(["lat", "lon", "elevation"]);
+    }
+    write!(out, "| station |")?;
+    for column in &columns {
+        write!(out, " {} |", column)?;
+    }
+    write!(out, "\n| --- |")?;
+    for _ in &columns {
+        write!(out, " ---: |")?;
+    }
+    writeln!(out)?;
+
+    for (name, stats) in rows {
+        write!(
+            out,
+            "| {} |",
+            markdown_cell(&display_name(name, opts.invalid_utf8))
+        )?;
+        for &stat in &opts.stats {
+            write!(out, " ")?;
+            write_stat(out, stat, stats.value(stat), opts)?;
+            write!(out, " |")?;
+        }
+        if let Some(geo) = geo {
+            match geo.get(name) {
+                Some(loc) => {
+                    write!(out, " {} | {} |", loc.lat, loc.lon)?;
+                    match loc.elevation {
+                        Some(elevation) => write!(out, " {} |", elevation)?,
+                        None => write!(out, "  |")?,
+                    }
+                }
+                None => write!(out, "  |  |  |")?,
+            }
+        }
+        writeln!(out)?;
+    }
+    Ok(())
+}
+
+/// `count` with a comma between each group of three digits.
+pub fn thousands(count: u64) -> String {
+    let digits = count.to_string();
+    let mut out = String::with_capacity(digits.len() * 4 / 3);
+    for (i, digit) in digits.chars().enumerate() {
+        if i > 0 && (digits.len() - i).is_multiple_of(3) {
+            out.push(',');
+        }
+        out.push(digit);
+    }
+    out
+}
+
+/// Columns padded to line up for reading in a terminal: counts with
+/// thousands separators, temperatures with ` °C` after them with `--units`,
+/// and with `--geo`, the stations' coordinates.
+pub fn write_table<A: Summary>(
+    rows: &[Row<A>],
+    opts: &Options,
+    geo: Option<&GeoTable>,
+    out: &mut dyn Write,
+) -> io::Result<()> {
+    let mut header = vec!["station".to_string()];
+    header.extend(opts.stats.iter().map(|stat| stat.name().to_string()));
+    if geo.is_some() {
+        header.extend(["lat", "lon", "elevation"].map(String::from));
+    }
+    let extremes = Extremes::new(rows, opts);
+    let mut cells = vec![header];
+    // The color of each cell, after the name
+    let mut colors = vec![Vec::new()];
+    for (name, stats) in rows {
+        let mut row = vec![display_name(name, opts.invalid_utf8).into_owned()];
+        let mut row_colors = Vec::new();
+        for (i, &stat) in opts.stats.iter().enumerate() {
+            let value = stats.value(stat);
+            row_colors.push(extremes.color(i, value, opts));
+            let cell = if stat.is_count() {
+                thousands(value as u64)
+            } else {
+                let mut cell = Vec::new();
+                write_stat(&mut cell, stat, value, opts)?;
+                if opts.units {
+                    cell.extend_from_slice(" °C".as_bytes());
+                }
+                String::from_utf8(cell).unwrap()
+            };
+            row.push(cell);
+        }
+        if let Some(geo) = geo {
+            let loc = geo.get(name);
+            row.push(loc.map_or(String::new(), |loc| loc.lat.to_string()));
+            row.push(loc.map_or(String::new(), |loc| loc.lon.to_string()));
+            let elevation = loc.and_then(|loc| loc.elevation);
+            row.push(elevation.map_or(String::new(), |elevation| elevation.to_string()));
+        }
+        cells.push(row);
+        colors.push(row_colors);
+    }
+
+    let mut widths = vec![0; cells[0].len()];
+    for row in &cells {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.chars().count());
+        }
+    }
+    for (row, colors) in cells.iter().zip(&colors) {
+        // Names are left-aligned, numbers right-aligned
+        write!(out, "{:<1$}", row[0], widths[0])?;
+        for (i, (cell, &width)) in row.iter().zip(&widths).skip(1).enumerate() {
+            match colors.get(i).copied().flatten() {
+                Some(color) => write!(out, "  {}{:>3$}{}", color, cell, RESET, width)?,
+                None => write!(out, "  {:>1$}", cell, width)?,
+            }
+        }
+        writeln!(out)?;
+    }
+    Ok(())
+}
+
+/// Rows per `INSERT` statement of `--format sql`.
+const SQL_BATCH: usize = 1000;
+
+fn sql_string(s: &str) -> String {
+    format!("'{}'", s.replace('\'', "''"))
+}
+
+/// Escapes a field of Postgres `COPY` text format.
+fn copy_field(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '\\' => out.push_str("\\\\"),
+            '\t' => out.push_str("\\t"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+/// Loads the results into `--sql-table`: `INSERT` statements of up to
+/// [`SQL_BATCH`] rows, or with `--sql-style copy`, a Postgres `COPY ... FROM
+/// stdin` block for psql. Columns are the station, the `--stats` values and,
+/// with `--geo`, `lat`/`lon`/`elevation` (NULL when unknown).
+pub fn write_sql<A: Summary>(
+    rows: &[Row<A>],
+    opts: &Options,
+    geo: Option<&GeoTable>,
+    out: &mut dyn Write,
+) -> io::Result<()> {
+    let mut columns = vec!["station"];
+    columns.extend(opts.stats.iter().map(|stat| stat.name()));
+    if geo.is_some() {
+        columns.extend(["lat", "lon", "elevation"]);
+    }
+    let columns = columns.join(", ");
+    let (null, sep) = match opts.sql_style {
+        SqlStyle::Insert => ("NULL", ", "),
+        SqlStyle::Copy => ("\\N", "\t"),
+    };
+    if opts.sql_style == SqlStyle::Copy {
+        writeln!(out, "COPY {} ({}) FROM stdin;", opts.sql_table, columns)?;
+    }
+
+    for (i, (name, stats)) in rows.iter().enumerate() {
+        let station = display_name(name, opts.invalid_utf8);
+        match opts.sql_style {
+            SqlStyle::Insert => {
+                if i % SQL_BATCH == 0 {
+                    writeln!(out, "INSERT INTO {} ({}) VALUES", opts.sql_table, columns)?;
+                }
+                write!(out, "  ({}", sql_string(&station))?;
+            }
+            SqlStyle::Copy => write!(out, "{}", copy_field(&station))?,
+        }
+        for &stat in &opts.stats {
+            write!(out, "{}", sep)?;
+            write_stat(out, stat, stats.value(stat), opts)?;
+        }
+        if let Some(geo) = geo {
+            match geo.get(name) {
+                Some(loc) => {
+                    write!(out, "{}{}{}{}{}", sep, loc.lat, sep, loc.lon, sep)?;
+                    match loc.elevation {
+                        Some(elevation) => write!(out, "{}", elevation)?,
+                        None => write!(out, "{}", null)?,
+                    }
+                }
+                None => write!(out, "{}{}{}{}{}{}", sep, null, sep, null, sep, null)?,
+            }
+        }
+        match opts.sql_style {
+            SqlStyle::Insert if (i + 1) % SQL_BATCH == 0 || i + 1 == rows.len() => {
+                writeln!(out, ");")?
+            }
+            SqlStyle::Insert => writeln!(out, "),")?,
+            SqlStyle::Copy => writeln!(out)?,
+        }
+    }
+    if opts.sql_style == SqlStyle::Copy {
+        writeln!(out, "\\.")?;
+    }
+    Ok(())
+}
+
+pub fn json_string(s: &str) -> String {
+    let mut out = String::with_capacity(s.len() + 2);
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => {
+                let _ = write!(out, "\\u{:04x}", c as u32);
+            }
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+/// Writes `"stat": value` members for the `--stats` columns, comma-separated.
+fn write_json_stats<A: Summary>(stats: &A, opts: &Options, out: &mut dyn Write) -> io::Result<()> {
+    for (i, &stat) in opts.stats.iter().enumerate() {
+        if i > 0 {
+            write!(out, ", ")?;
+        }
+        write!(out, "\"{}\": ", stat.name())?;
+        write_stat(out, stat, stats.value(stat), opts)?;
+    }
+    Ok(())
+}
+
+fn write_json_location(loc: Option<&Location>, out: &mut dyn Write) -> io::Result<()> {
+    match loc {
+        Some(loc) => {
+            write!(
+                out,
+                ", \"lat\": {}, \"lon\": {}, \"elevation\": ",
+                loc.lat, loc.lon
+            )?;
+            match loc.elevation {
+                Some(elevation) => write!(out, "{}", elevation),
+                None => write!(out, "null"),
+            }
+        }
+        None => write!(out, ", \"lat\": null, \"lon\": null, \"elevation\": null"),
+    }
+}
+
+/// A JSON array with one object per station: its name, the `--stats` values
+/// and, with `--geo`, `lat`/`lon`/`elevation` (null when unknown).
+pub fn write_json<A: Summary>(
+    rows: &[Row<A>],
+    opts: &Options,
+    geo: Option<&GeoTable>,
+    out: &mut dyn Write,
+) -> io::Result<()> {
+    writeln!(out, "[")?;
+    for (i, (name, stats)) in rows.iter().enumerate() {
+        write!(
+            out,
+            "  {{\"station\": {}, ",
+            json_string(&display_name(name, opts.invalid_utf8))
+        )?;
+        write_json_stats(*stats, opts, out)?;
+        if let Some(geo) = geo {
+            write_json_location(geo.get(name), out)?;
+        }
+        let sep = if i + 1 < rows.len() { "," } else { "" };
+        writeln!(out, "}}{}", sep)?;
+    }
+    writeln!(out, "]")
+}
+
+/// A GeoJSON FeatureCollection of point features, one per station found in
+/// the `--geo` lookup, with the station name and `--stats` values as
+/// properties. Stations without coordinates are left out.
+pub fn write_geojson<A: Summary>(
+    rows: &[Row<A>],
+    opts: &Options,
+    geo: &GeoTable,
+    out: &mut dyn Write,
+) -> io::Result<()> {
+    writeln!(out, "{{\"type\": \"FeatureCollection\", \"features\": [")?;
+    let mut located = rows
+        .iter()
+        .filter_map(|(name, stats)| Some((name, stats, geo.get(name)?)))
+        .peekable();
+    while let Some((name, stats, loc)) = located.next() {
+        write!(
+            out,
+            "  {{\"type\": \"Feature\", \"geometry\": {{\"type\": \"Point\", \"coordinates\": [{}, {}",
+            loc.lon, loc.lat
+        )?;
+        if let Some(elevation) = loc.elevation {
+            write!(out, ", {}", elevation)?;
+        }
+        write!(
+            out,
+            "]}}, \"properties\": {{\"station\": {}, ",
+            json_string(&display_name(name, opts.invalid_utf8))
+        )?;
+        write_json_stats(*stats, opts, out)?;
+        let sep = if located.peek().is_some() { "," } else { "" };
+        writeln!(out, "}}}}{}", sep)?;
+    }
+    writeln!(out, "]}}")
+}
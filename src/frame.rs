@@ -0,0 +1,42 @@
+use polars::prelude::*;
+
+use crate::cli::Options;
+use crate::read_measurements;
+
+/// Aggregates the measurements at `path` into a DataFrame with one row per
+/// station, sorted by name: `station`, `min`, `mean`, `max` and `count`.
+///
+/// `path` accepts everything the command line does (compressed files,
+/// archives, URLs when those features are enabled). An input the command
+/// line would fail on, such as a missing file or a malformed line, is a
+/// [`PolarsError::ComputeError`] with the message it would print.
+pub fn aggregate_to_dataframe(path: &str) -> PolarsResult<DataFrame> {
+    let opts = Options {
+        file_path: path.to_string(),
+        ..Options::default()
+    };
+    let table =
+        read_measurements(&opts).map_err(|e| PolarsError::ComputeError(e.message.into()))?;
+    let mut rows: Vec<_> = table.iter().collect();
+    rows.sort_unstable_by(|a, b| a.0.cmp(b.0));
+
+    let station: Vec<String> = rows
+        .iter()
+        .map(|(name, _)| String::from_utf8_lossy(name).into_owned())
+        .collect();
+    let min: Vec<f64> = rows.iter().map(|(_, s)| s.min_f64()).collect();
+    let mean: Vec<f64> = rows.iter().map(|(_, s)| s.mean()).collect();
+    let max: Vec<f64> = rows.iter().map(|(_, s)| s.max_f64()).collect();
+    let count: Vec<u64> = rows.iter().map(|(_, s)| s.count()).collect();
+
+    DataFrame::new(
+        rows.len(),
+        vec![
+            Column::new("station".into(), station),
+            Column::new("min".into(), min),
+            Column::new("mean".into(), mean),
+            Column::new("max".into(), max),
+            Column::new("count".into(), count),
+        ],
+    )
+}
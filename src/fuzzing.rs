@@ -0,0 +1,73 @@
+//! Entry points into the crate's internals for the targets in `fuzz/`. Only
+//! built with the `fuzzing` feature; not a stable API.
+
+use crate::cli::Unknown;
+use crate::origin::Origin;
+use crate::{perfect, scan, StationTable};
+
+/// Longest station name a table accepts.
+pub const MAX_NAME_LEN: usize = crate::MAX_NAME_LEN;
+
+/// One station of a table, with its statistics in tenths of a degree.
+#[derive(Debug, PartialEq, Eq)]
+pub struct Station {
+    pub name: Vec<u8>,
+    pub min: i32,
+    pub max: i32,
+    pub sum: i64,
+    pub count: u64,
+}
+
+/// The line splitter of the aggregation, see [`scan::for_each_record`].
+/// Panics on a malformed line.
+pub fn for_each_record<'a>(data: &'a [u8], f: impl FnMut(&'a [u8], &'a [u8])) {
+    if let Err(failure) = scan::for_each_record(data, &Origin::default(), f) {
+        panic!("{}", failure);
+    }
+}
+
+/// The validating temperature parser used for untrusted input.
+pub fn parse_temp_checked(bytes: &[u8]) -> Option<i32> {
+    crate::parse_temp_checked(bytes)
+}
+
+/// An empty table with `slots` slots (a power of two, at least 2), parsing
+/// `batch` lines ahead of their lookups or, with `interleave`, walking
+/// buffers as two halves.
+pub fn table(slots: usize, batch: usize, interleave: bool) -> StationTable {
+    let mut table = StationTable::with_slots(slots);
+    table.batch = batch.clamp(1, crate::MAX_BATCH);
+    table.interleave = interleave;
+    table
+}
+
+/// Replaces `table` with one placing `keys` by a perfect hash, as with
+/// `--stations` and `--unknown-stations insert`.
+pub fn seed_stations(table: &mut StationTable, keys: Vec<Box<[u8]>>) {
+    perfect::seed(table, keys, Unknown::Insert);
+}
+
+pub fn lookup_or_insert(table: &mut StationTable, name: &[u8], temp: i32) {
+    table.lookup_or_insert(name, temp);
+}
+
+/// Aggregates a buffer of well-formed lines into `table`.
+pub fn aggregate_lines(data: &[u8], table: &mut StationTable) {
+    crate::aggregate_lines(data, table);
+}
+
+/// The stations of `table`, sorted by name.
+pub fn stations(table: &StationTable) -> Vec<Station> {
+    let mut stations: Vec<Station> = table
+        .iter()
+        .map(|(name, stats)| Station {
+            name: name.to_vec(),
+            min: stats.min,
+            max: stats.max,
+            sum: stats.sum,
+            count: stats.count,
+        })
+        .collect();
+    stations.sort_by(|a, b| a.name.cmp(&b.name));
+    stations
+}
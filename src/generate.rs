@@ -0,0 +1,799 @@
+//! `onebrc generate [FILE]`: a measurements file as the 1BRC generator
+//! writes one, `--rows` readings of stations picked at random from its list,
+//! each drawn from a normal distribution around the station's mean with a
+//! standard deviation of 10 degrees. The same `--seed` writes the same file.
+//! With FILE `-` the lines go to stdout, for piping into the aggregator or a
+//! compressor without a file in between. `--shards N` splits the rows
+//! across N files named after FILE, e.g. `measurements-07.txt`, one per
+//! host or run of a sharded aggregation. `--compress` writes them gzip,
+//! zstd, xz or bzip2 compressed, as the aggregator reads them.
+//!
+//! `--corrupt-rate` replaces that fraction of the lines with malformed ones,
+//! for exercising the paths that handle dirty input. `--names adversarial`
+//! picks from stations made to be a worst case for the station table
+//! instead; see [`adversarial`].
+
+use std::collections::HashSet;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+
+use crate::cli::{self, Exit, Names, Options};
+use crate::{compress, parallel, MAX_NAME_LEN};
+
+/// Ways a line is corrupted, in the order the summary counts them.
+const CORRUPTIONS: [&str; 4] = [
+    "without ';'",
+    "with a bad number",
+    "with an overlong name",
+    "with invalid UTF-8",
+];
+
+/// Temperatures `--corrupt-rate` writes in place of a number.
+const BAD_NUMBERS: [&[u8]; 5] = [b"", b".", b"--1.0", b"12.3.4", b"N/A"];
+
+/// Bytes `--corrupt-rate` appends to a name to make it invalid UTF-8: a byte
+/// that never appears in it, a sequence cut short and an encoded surrogate.
+const INVALID_UTF8: [&[u8]; 3] = [b"\xff", b"\xc3", b"\xed\xa0\x80"];
+
+/// Stations `--names adversarial` makes, the most the 1BRC rules allow.
+const ADVERSARIAL_STATIONS: usize = 10_000;
+
+/// Emoji for `--names adversarial`, with skin tones, variation selectors and
+/// zero-width joiner sequences among them.
+const EMOJI: [&str; 12] = [
+    "😀",
+    "🔥",
+    "❄\u{fe0f}",
+    "☀",
+    "🌧",
+    "🌡\u{fe0f}",
+    "👍🏽",
+    "🧑🏿",
+    "👩\u{200d}🔬",
+    "🏳\u{fe0f}\u{200d}🌈",
+    "🇮🇸",
+    "🐻\u{200d}❄\u{fe0f}",
+];
+
+/// Bytes of the ASCII names `--names adversarial` makes.
+const ALPHANUMERIC: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789";
+
+/// The splitmix64 generator: fast, and as random as test data needs.
+struct Rng(u64);
+
+impl Rng {
+    fn next(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    /// Uniform in `0..n`.
+    fn below(&mut self, n: usize) -> usize {
+        ((self.next() as u128 * n as u128) >> 64) as usize
+    }
+
+    /// Uniform in `[0, 1)`.
+    fn unit(&mut self) -> f64 {
+        (self.next() >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    /// A random ASCII letter or digit.
+    fn alphanumeric(&mut self) -> char {
+        ALPHANUMERIC[self.below(ALPHANUMERIC.len())] as char
+    }
+
+    /// Standard normal, by the Box-Muller transform.
+    fn normal(&mut self) -> f64 {
+        let u = 1.0 - self.unit();
+        let v = self.unit();
+        (-2.0 * u.ln()).sqrt() * (std::f64::consts::TAU * v).cos()
+    }
+}
+
+/// Writes the file or shards `opts` asks for, and a summary of them to
+/// stderr.
+pub fn run(opts: &Options) -> io::Result<()> {
+    let mut rng = Rng(opts.seed);
+    let stations: Vec<(String, f64)> = match opts.names {
+        Names::Cities => STATIONS
+            .iter()
+            .map(|&(name, mean)| (name.to_string(), mean))
+            .collect(),
+        Names::Adversarial => adversarial(&mut rng),
+    };
+    if let Some((format, level)) = opts.compress {
+        // Fails before any file is created if the format was not compiled in
+        if let Err(e) = compress::encoder(format, level, io::sink()) {
+            cli::fail(Exit::Usage, &e);
+        }
+    }
+    let (corrupted, written) = if opts.shards > 1 {
+        let corrupted = write_shards(opts, &stations, &mut rng)?;
+        let (first, last) = (
+            shard_path(&opts.file_path, 0, opts.shards),
+            shard_path(&opts.file_path, opts.shards - 1, opts.shards),
+        );
+        let written = format!("{} files, {} to {}", opts.shards, first, last);
+        (corrupted, written)
+    } else {
+        let (out, name): (Box<dyn Write>, &str) = match opts.file_path.as_str() {
+            "-" => (Box::new(io::stdout().lock()), "stdout"),
+            path => (
+                Box::new(File::create(path).map_err(|e| named(path, e))?),
+                path,
+            ),
+        };
+        let corrupted =
+            write_file(opts, &stations, opts.rows, &mut rng, out).map_err(|e| named(name, e))?;
+        (corrupted, name.to_string())
+    };
+
+    eprint!("generate: wrote {} rows to {}", opts.rows, written);
+    let total: u64 = corrupted.iter().sum();
+    if opts.corrupt_rate > 0.0 {
+        let counts: Vec<String> = CORRUPTIONS
+            .iter()
+            .zip(corrupted)
+            .map(|(kind, n)| format!("{} {}", n, kind))
+            .collect();
+        eprint!(", {} of them corrupted: {}", total, counts.join(", "));
+    }
+    eprintln!();
+    Ok(())
+}
+
+fn named(name: &str, e: io::Error) -> io::Error {
+    io::Error::new(e.kind(), format!("{}: {}", name, e))
+}
+
+/// `path` with the number of shard `i` of `shards` before its extensions,
+/// e.g. `measurements-07.txt` or `measurements-07.txt.zst`.
+fn shard_path(path: &str, i: usize, shards: usize) -> String {
+    let width = (shards - 1).to_string().len();
+    let path = Path::new(path);
+    let file_name = path.file_name().unwrap_or_default().to_string_lossy();
+    // A leading dot starts a hidden file's name, not an extension
+    let (stem, extensions) = match file_name.char_indices().skip(1).find(|&(_, c)| c == '.') {
+        Some((dot, _)) => file_name.split_at(dot),
+        None => (&file_name[..], ""),
+    };
+    let name = format!("{}-{:0width$}{}", stem, i, extensions, width = width);
+    path.with_file_name(name).to_string_lossy().into_owned()
+}
+
+/// Writes `--rows` across `--shards` files on `--threads` threads. Each
+/// shard has its own seed, drawn from `rng` in order, so that a shard is the
+/// same whichever thread writes it; the first `rows % shards` have a row
+/// more than the rest.
+fn write_shards(
+    opts: &Options,
+    stations: &[(String, f64)],
+    rng: &mut Rng,
+) -> io::Result<[u64; CORRUPTIONS.len()]> {
+    if opts.file_path == "-" {
+        cli::fail(
+            Exit::Usage,
+            "--shards writes files and cannot write to stdout",
+        );
+    }
+    let shards = opts.shards;
+    let seeds: Vec<u64> = (0..shards).map(|_| rng.next()).collect();
+    let next = AtomicUsize::new(0);
+    let write_shard = |i: usize| -> io::Result<[u64; CORRUPTIONS.len()]> {
+        let path = shard_path(&opts.file_path, i, shards);
+        let rows = opts.rows / shards as u64 + u64::from((i as u64) < opts.rows % shards as u64);
+        let file = File::create(&path).map_err(|e| named(&path, e))?;
+        write_file(opts, stations, rows, &mut Rng(seeds[i]), file).map_err(|e| named(&path, e))
+    };
+    let workers = parallel::threads(opts).min(shards);
+    thread::scope(|scope| {
+        let handles: Vec<_> = (0..workers)
+            .map(|_| {
+                scope.spawn(|| -> io::Result<_> {
+                    let mut corrupted = [0u64; CORRUPTIONS.len()];
+                    loop {
+                        let i = next.fetch_add(1, Ordering::Relaxed);
+                        if i >= shards {
+                            return Ok(corrupted);
+                        }
+                        for (total, n) in corrupted.iter_mut().zip(write_shard(i)?) {
+                            *total += n;
+                        }
+                    }
+                })
+            })
+            .collect();
+        let mut corrupted = [0u64; CORRUPTIONS.len()];
+        for handle in handles {
+            for (total, n) in corrupted.iter_mut().zip(handle.join().unwrap()?) {
+                *total += n;
+            }
+        }
+        Ok(corrupted)
+    })
+}
+
+/// Writes `rows` lines picked from `stations` to `out`, compressed if
+/// `--compress` asks for it.
+fn write_file(
+    opts: &Options,
+    stations: &[(String, f64)],
+    rows: u64,
+    rng: &mut Rng,
+    out: impl Write,
+) -> io::Result<[u64; CORRUPTIONS.len()]> {
+    let Some((format, level)) = opts.compress else {
+        let mut out = BufWriter::with_capacity(1 << 20, out);
+        return write_rows(opts, stations, rows, rng, &mut out);
+    };
+    // Checked by `run`
+    let encoder = compress::encoder(format, level, out).unwrap();
+    let mut lines = BufWriter::with_capacity(1 << 20, encoder);
+    let corrupted = write_rows(opts, stations, rows, rng, &mut lines)?;
+    lines.into_inner().map_err(|e| e.into_error())?.finish()?;
+    Ok(corrupted)
+}
+
+/// Writes `rows` lines picked from `stations` to `out`, returning how many
+/// of each of [`CORRUPTIONS`] it wrote.
+fn write_rows(
+    opts: &Options,
+    stations: &[(String, f64)],
+    rows: u64,
+    rng: &mut Rng,
+    out: &mut impl Write,
+) -> io::Result<[u64; CORRUPTIONS.len()]> {
+    let mut corrupted = [0u64; CORRUPTIONS.len()];
+    let mut line = Vec::with_capacity(2 * MAX_NAME_LEN);
+    for _ in 0..rows {
+        let (name, mean) = &stations[rng.below(stations.len())];
+        // In tenths of a degree, kept to the -99.9..99.9 of the format
+        let temp = ((mean + 10.0 * rng.normal()) * 10.0)
+            .round()
+            .clamp(-999.0, 999.0) as i32;
+        line.clear();
+        line.extend_from_slice(name.as_bytes());
+        line.push(b';');
+        write_temp(&mut line, temp);
+        if opts.corrupt_rate > 0.0 && rng.unit() < opts.corrupt_rate {
+            let kind = rng.below(CORRUPTIONS.len());
+            corrupt(&mut line, name.len(), kind, rng);
+            corrupted[kind] += 1;
+        }
+        line.push(b'\n');
+        out.write_all(&line)?;
+    }
+    out.flush()?;
+    Ok(corrupted)
+}
+
+/// Stations whose names are a worst case for the station table, which
+/// hashes the first 8 bytes of a name and its length and keeps names out of
+/// line past a fixed-size entry. In equal parts:
+///
+/// * names of the full 100 bytes,
+/// * groups of 62 names differing only in their last byte,
+/// * names the same 8 bytes start, alike in length too in groups of 16,
+///   which collide in the hash,
+/// * names of emoji, among them sequences of several code points,
+/// * letters under stacks of combining marks, which print alike in many
+///   ways.
+///
+/// Each has a mean temperature from -30 to 40 degrees.
+fn adversarial(rng: &mut Rng) -> Vec<(String, f64)> {
+    let per_kind = ADVERSARIAL_STATIONS / 5;
+    let mut names: Vec<String> = Vec::with_capacity(ADVERSARIAL_STATIONS);
+    let mut seen = HashSet::new();
+    let mut add = |names: &mut Vec<String>, name: String| {
+        debug_assert!(!name.is_empty() && name.len() <= MAX_NAME_LEN);
+        if seen.insert(name.clone()) {
+            names.push(name);
+        }
+    };
+    while names.len() < per_kind {
+        let name = (0..MAX_NAME_LEN).map(|_| rng.alphanumeric()).collect();
+        add(&mut names, name);
+    }
+    while names.len() < 2 * per_kind {
+        let len = 2 + rng.below(MAX_NAME_LEN - 1);
+        let stem: String = (1..len).map(|_| rng.alphanumeric()).collect();
+        for &last in ALPHANUMERIC {
+            add(&mut names, format!("{}{}", stem, last as char));
+        }
+    }
+    names.truncate(2 * per_kind);
+    while names.len() < 3 * per_kind {
+        let len = 9 + rng.below(MAX_NAME_LEN - 8);
+        for _ in 0..16 {
+            let tail: String = (8..len).map(|_| rng.alphanumeric()).collect();
+            add(&mut names, format!("Station {}", tail));
+        }
+    }
+    names.truncate(3 * per_kind);
+    while names.len() < 4 * per_kind {
+        let mut name = String::new();
+        for _ in 0..1 + rng.below(8) {
+            let emoji = EMOJI[rng.below(EMOJI.len())];
+            if name.len() + emoji.len() > MAX_NAME_LEN {
+                break;
+            }
+            name.push_str(emoji);
+        }
+        add(&mut names, name);
+    }
+    while names.len() < ADVERSARIAL_STATIONS {
+        let mut name = String::new();
+        // Combining marks are 2 bytes each in U+0300..U+036F
+        while name.len() < MAX_NAME_LEN - 6 && (name.is_empty() || rng.below(4) > 0) {
+            name.push((b'a' + rng.below(26) as u8) as char);
+            for _ in 0..rng.below(3) {
+                name.push(char::from_u32(0x300 + rng.below(0x70) as u32).unwrap());
+            }
+        }
+        add(&mut names, name);
+    }
+    names
+        .into_iter()
+        .map(|name| {
+            let mean = (rng.unit() * 700.0).round() / 10.0 - 30.0;
+            (name, mean)
+        })
+        .collect()
+}
+
+/// Appends `temp`, in tenths of a degree, as the 1BRC format writes it.
+fn write_temp(line: &mut Vec<u8>, temp: i32) {
+    if temp < 0 {
+        line.push(b'-');
+    }
+    let abs = temp.unsigned_abs();
+    if abs >= 100 {
+        line.push(b'0' + (abs / 100) as u8);
+    }
+    line.extend_from_slice(&[b'0' + (abs / 10 % 10) as u8, b'.', b'0' + (abs % 10) as u8]);
+}
+
+/// Makes `line`, a station name of `name_len` bytes, `;` and a temperature,
+/// malformed in the way `kind` of [`CORRUPTIONS`] names.
+fn corrupt(line: &mut Vec<u8>, name_len: usize, kind: usize, rng: &mut Rng) {
+    match kind {
+        0 => {
+            line.remove(name_len);
+        }
+        1 => {
+            line.truncate(name_len + 1);
+            line.extend_from_slice(BAD_NUMBERS[rng.below(BAD_NUMBERS.len())]);
+        }
+        2 => {
+            let extra = MAX_NAME_LEN + 1 + rng.below(MAX_NAME_LEN) - name_len;
+            line.splice(name_len..name_len, std::iter::repeat_n(b'x', extra));
+        }
+        _ => {
+            let bytes = INVALID_UTF8[rng.below(INVALID_UTF8.len())];
+            line.splice(name_len..name_len, bytes.iter().copied());
+        }
+    }
+}
+
+/// The stations of the 1BRC generator and their mean temperatures.
+const STATIONS: &[(&str, f64)] = &[
+    ("Abha", 18.0),
+    ("Abidjan", 26.0),
+    ("Abéché", 29.4),
+    ("Accra", 26.4),
+    ("Addis Ababa", 16.0),
+    ("Adelaide", 17.3),
+    ("Aden", 29.1),
+    ("Ahvaz", 25.4),
+    ("Albuquerque", 14.0),
+    ("Alexandra", 11.0),
+    ("Alexandria", 20.0),
+    ("Algiers", 18.2),
+    ("Alice Springs", 21.0),
+    ("Almaty", 10.0),
+    ("Amsterdam", 10.2),
+    ("Anadyr", -6.9),
+    ("Anchorage", 2.8),
+    ("Andorra la Vella", 9.8),
+    ("Ankara", 12.0),
+    ("Antananarivo", 17.9),
+    ("Antsiranana", 25.2),
+    ("Arkhangelsk", 1.3),
+    ("Ashgabat", 17.1),
+    ("Asmara", 15.6),
+    ("Assab", 30.5),
+    ("Astana", 3.5),
+    ("Athens", 19.2),
+    ("Atlanta", 17.0),
+    ("Auckland", 15.2),
+    ("Austin", 20.7),
+    ("Baghdad", 22.8),
+    ("Baguio", 19.5),
+    ("Baku", 15.1),
+    ("Baltimore", 13.1),
+    ("Bamako", 27.8),
+    ("Bangkok", 28.6),
+    ("Bangui", 26.0),
+    ("Banjul", 26.0),
+    ("Barcelona", 18.2),
+    ("Bata", 25.1),
+    ("Batumi", 14.0),
+    ("Beijing", 12.9),
+    ("Beirut", 20.9),
+    ("Belgrade", 12.5),
+    ("Belize City", 26.7),
+    ("Benghazi", 19.9),
+    ("Bergen", 7.7),
+    ("Berlin", 10.3),
+    ("Bilbao", 14.7),
+    ("Birao", 26.5),
+    ("Bishkek", 11.3),
+    ("Bissau", 27.0),
+    ("Blantyre", 22.2),
+    ("Bloemfontein", 15.6),
+    ("Boise", 11.4),
+    ("Bordeaux", 14.2),
+    ("Bosaso", 30.0),
+    ("Boston", 10.9),
+    ("Bouaké", 26.0),
+    ("Bratislava", 10.5),
+    ("Brazzaville", 25.0),
+    ("Bridgetown", 27.0),
+    ("Brisbane", 21.4),
+    ("Brussels", 10.5),
+    ("Bucharest", 10.8),
+    ("Budapest", 11.3),
+    ("Bujumbura", 23.8),
+    ("Bulawayo", 18.9),
+    ("Burnie", 13.1),
+    ("Busan", 15.0),
+    ("Cabo San Lucas", 23.9),
+    ("Cairns", 25.0),
+    ("Cairo", 21.4),
+    ("Calgary", 4.4),
+    ("Canberra", 13.1),
+    ("Cape Town", 16.2),
+    ("Changsha", 17.4),
+    ("Charlotte", 16.1),
+    ("Chiang Mai", 25.8),
+    ("Chicago", 9.8),
+    ("Chihuahua", 18.6),
+    ("Chișinău", 10.2),
+    ("Chittagong", 25.9),
+    ("Chongqing", 18.6),
+    ("Christchurch", 12.2),
+    ("City of San Marino", 11.8),
+    ("Colombo", 27.4),
+    ("Columbus", 11.7),
+    ("Conakry", 26.4),
+    ("Copenhagen", 9.1),
+    ("Cotonou", 27.2),
+    ("Cracow", 9.3),
+    ("Da Lat", 17.9),
+    ("Da Nang", 25.8),
+    ("Dakar", 24.0),
+    ("Dallas", 19.0),
+    ("Damascus", 17.0),
+    ("Dampier", 26.4),
+    ("Dar es Salaam", 25.8),
+    ("Darwin", 27.6),
+    ("Denpasar", 23.7),
+    ("Denver", 10.4),
+    ("Detroit", 10.0),
+    ("Dhaka", 25.9),
+    ("Dikson", -11.1),
+    ("Dili", 26.6),
+    ("Djibouti", 29.9),
+    ("Dodoma", 22.7),
+    ("Dolisie", 24.0),
+    ("Douala", 26.7),
+    ("Dubai", 26.9),
+    ("Dublin", 9.8),
+    ("Dunedin", 11.1),
+    ("Durban", 20.6),
+    ("Dushanbe", 14.7),
+    ("Edinburgh", 9.3),
+    ("Edmonton", 4.2),
+    ("El Paso", 18.1),
+    ("Entebbe", 21.0),
+    ("Erbil", 19.5),
+    ("Erzurum", 5.1),
+    ("Fairbanks", -2.3),
+    ("Fianarantsoa", 17.9),
+    ("Flores, Petén", 26.4),
+    ("Frankfurt", 10.6),
+    ("Fresno", 17.9),
+    ("Fukuoka", 17.0),
+    ("Gabès", 19.5),
+    ("Gaborone", 21.0),
+    ("Gagnoa", 26.0),
+    ("Gangtok", 15.2),
+    ("Garissa", 29.3),
+    ("Garoua", 28.3),
+    ("George Town", 27.9),
+    ("Ghanzi", 21.4),
+    ("Gjoa Haven", -14.4),
+    ("Guadalajara", 20.9),
+    ("Guangzhou", 22.4),
+    ("Guatemala City", 20.4),
+    ("Halifax", 7.5),
+    ("Hamburg", 9.7),
+    ("Hamilton", 13.8),
+    ("Hanga Roa", 20.5),
+    ("Hanoi", 23.6),
+    ("Harare", 18.4),
+    ("Harbin", 5.0),
+    ("Hargeisa", 21.7),
+    ("Hat Yai", 27.0),
+    ("Havana", 25.2),
+    ("Helsinki", 5.9),
+    ("Heraklion", 18.9),
+    ("Hiroshima", 16.3),
+    ("Ho Chi Minh City", 27.4),
+    ("Hobart", 12.7),
+    ("Hong Kong", 23.3),
+    ("Honiara", 26.5),
+    ("Honolulu", 25.4),
+    ("Houston", 20.8),
+    ("Ifrane", 11.4),
+    ("Indianapolis", 11.8),
+    ("Iqaluit", -9.3),
+    ("Irkutsk", 1.0),
+    ("Istanbul", 13.9),
+    ("İzmir", 17.9),
+    ("Jacksonville", 20.3),
+    ("Jakarta", 26.7),
+    ("Jayapura", 27.0),
+    ("Jerusalem", 18.3),
+    ("Johannesburg", 15.5),
+    ("Jos", 22.8),
+    ("Juba", 27.8),
+    ("Kabul", 12.1),
+    ("Kampala", 20.0),
+    ("Kandi", 27.7),
+    ("Kankan", 26.5),
+    ("Kano", 26.4),
+    ("Kansas City", 12.5),
+    ("Karachi", 26.0),
+    ("Karonga", 24.4),
+    ("Kathmandu", 18.3),
+    ("Khartoum", 29.9),
+    ("Kingston", 27.4),
+    ("Kinshasa", 25.3),
+    ("Kolkata", 26.7),
+    ("Kuala Lumpur", 27.3),
+    ("Kumasi", 26.0),
+    ("Kunming", 15.7),
+    ("Kuopio", 3.4),
+    ("Kuwait City", 25.7),
+    ("Kyiv", 8.4),
+    ("Kyoto", 15.8),
+    ("La Ceiba", 26.2),
+    ("La Paz", 23.7),
+    ("Lagos", 26.8),
+    ("Lahore", 24.3),
+    ("Lake Havasu City", 23.7),
+    ("Lake Tekapo", 8.7),
+    ("Las Palmas de Gran Canaria", 21.2),
+    ("Las Vegas", 20.3),
+    ("Launceston", 13.1),
+    ("Lhasa", 7.6),
+    ("Libreville", 25.9),
+    ("Lisbon", 17.5),
+    ("Livingstone", 21.8),
+    ("Ljubljana", 10.9),
+    ("Lodwar", 29.3),
+    ("Lomé", 26.9),
+    ("London", 11.3),
+    ("Los Angeles", 18.6),
+    ("Louisville", 13.9),
+    ("Luanda", 25.8),
+    ("Lubumbashi", 20.8),
+    ("Lusaka", 19.9),
+    ("Luxembourg City", 9.3),
+    ("Lviv", 7.8),
+    ("Lyon", 12.5),
+    ("Madrid", 15.0),
+    ("Mahajanga", 26.3),
+    ("Makassar", 26.7),
+    ("Makurdi", 26.0),
+    ("Malabo", 26.3),
+    ("Malé", 28.0),
+    ("Managua", 27.3),
+    ("Manama", 26.5),
+    ("Mandalay", 28.0),
+    ("Mango", 28.1),
+    ("Manila", 28.4),
+    ("Maputo", 22.8),
+    ("Marrakesh", 19.6),
+    ("Marseille", 15.8),
+    ("Maun", 22.4),
+    ("Medan", 26.5),
+    ("Mek'ele", 22.7),
+    ("Melbourne", 15.1),
+    ("Memphis", 17.2),
+    ("Mexicali", 23.1),
+    ("Mexico City", 17.5),
+    ("Miami", 24.9),
+    ("Milan", 13.0),
+    ("Milwaukee", 8.9),
+    ("Minneapolis", 7.8),
+    ("Minsk", 6.7),
+    ("Mogadishu", 27.1),
+    ("Mombasa", 26.3),
+    ("Monaco", 16.4),
+    ("Moncton", 6.1),
+    ("Monterrey", 22.3),
+    ("Montreal", 6.8),
+    ("Moscow", 5.8),
+    ("Mumbai", 27.1),
+    ("Murmansk", 0.6),
+    ("Muscat", 28.0),
+    ("Mzuzu", 17.7),
+    ("N'Djamena", 28.3),
+    ("Naha", 23.1),
+    ("Nairobi", 17.8),
+    ("Nakhon Ratchasima", 27.3),
+    ("Napier", 14.6),
+    ("Napoli", 15.9),
+    ("Nashville", 15.4),
+    ("Nassau", 24.6),
+    ("Ndola", 20.3),
+    ("New Delhi", 25.0),
+    ("New Orleans", 20.7),
+    ("New York City", 12.9),
+    ("Ngaoundéré", 22.0),
+    ("Niamey", 29.3),
+    ("Nicosia", 19.7),
+    ("Niigata", 13.9),
+    ("Nouadhibou", 21.3),
+    ("Nouakchott", 25.7),
+    ("Novosibirsk", 1.7),
+    ("Nuuk", -1.4),
+    ("Odesa", 10.7),
+    ("Odienné", 26.0),
+    ("Oklahoma City", 15.9),
+    ("Omaha", 10.6),
+    ("Oranjestad", 28.1),
+    ("Oslo", 5.7),
+    ("Ottawa", 6.6),
+    ("Ouagadougou", 28.3),
+    ("Ouahigouya", 28.6),
+    ("Ouarzazate", 18.9),
+    ("Oulu", 2.7),
+    ("Palembang", 27.3),
+    ("Palermo", 18.5),
+    ("Palm Springs", 24.5),
+    ("Palmerston North", 13.2),
+    ("Panama City", 28.0),
+    ("Parakou", 26.8),
+    ("Paris", 12.3),
+    ("Perth", 18.7),
+    ("Petropavlovsk-Kamchatsky", 1.9),
+    ("Philadelphia", 13.2),
+    ("Phnom Penh", 28.3),
+    ("Phoenix", 23.9),
+    ("Pittsburgh", 10.8),
+    ("Podgorica", 15.3),
+    ("Pointe-Noire", 26.1),
+    ("Pontianak", 27.7),
+    ("Port Moresby", 26.9),
+    ("Port Sudan", 28.4),
+    ("Port Vila", 24.3),
+    ("Port-Gentil", 26.0),
+    ("Portland (OR)", 12.4),
+    ("Porto", 15.7),
+    ("Prague", 8.4),
+    ("Praia", 24.4),
+    ("Pretoria", 18.2),
+    ("Pyongyang", 10.8),
+    ("Rabat", 17.2),
+    ("Rangpur", 24.4),
+    ("Reggane", 28.3),
+    ("Reykjavík", 4.3),
+    ("Riga", 6.2),
+    ("Riyadh", 26.0),
+    ("Rome", 15.2),
+    ("Roseau", 26.2),
+    ("Rostov-on-Don", 9.9),
+    ("Sacramento", 16.3),
+    ("Saint Petersburg", 5.8),
+    ("Saint-Pierre", 5.7),
+    ("Salt Lake City", 11.6),
+    ("San Antonio", 20.8),
+    ("San Diego", 17.8),
+    ("San Francisco", 14.6),
+    ("San Jose", 16.4),
+    ("San José", 22.6),
+    ("San Juan", 27.2),
+    ("San Salvador", 23.1),
+    ("Sana'a", 20.0),
+    ("Santo Domingo", 25.9),
+    ("Sapporo", 8.9),
+    ("Sarajevo", 10.1),
+    ("Saskatoon", 3.3),
+    ("Seattle", 11.3),
+    ("Ségou", 28.0),
+    ("Seoul", 12.5),
+    ("Seville", 19.2),
+    ("Shanghai", 16.7),
+    ("Singapore", 27.0),
+    ("Skopje", 12.4),
+    ("Sochi", 14.2),
+    ("Sofia", 10.6),
+    ("Sokoto", 28.0),
+    ("Split", 16.1),
+    ("St. John's", 5.0),
+    ("St. Louis", 13.9),
+    ("Stockholm", 6.6),
+    ("Surabaya", 27.1),
+    ("Suva", 25.6),
+    ("Suwałki", 7.2),
+    ("Sydney", 17.7),
+    ("Tabora", 23.0),
+    ("Tabriz", 12.6),
+    ("Taipei", 23.0),
+    ("Tallinn", 6.4),
+    ("Tamale", 27.9),
+    ("Tamanrasset", 21.7),
+    ("Tampa", 22.9),
+    ("Tashkent", 14.8),
+    ("Tauranga", 14.8),
+    ("Tbilisi", 12.9),
+    ("Tegucigalpa", 21.7),
+    ("Tehran", 17.0),
+    ("Tel Aviv", 20.0),
+    ("Thessaloniki", 16.0),
+    ("Thiès", 24.0),
+    ("Tijuana", 17.8),
+    ("Timbuktu", 28.0),
+    ("Tirana", 15.2),
+    ("Toamasina", 23.4),
+    ("Tokyo", 15.4),
+    ("Toliara", 24.1),
+    ("Toluca", 12.4),
+    ("Toronto", 9.4),
+    ("Tripoli", 20.0),
+    ("Tromsø", 2.9),
+    ("Tucson", 20.9),
+    ("Tunis", 18.4),
+    ("Ulaanbaatar", -0.4),
+    ("Upington", 20.4),
+    ("Ürümqi", 7.4),
+    ("Vaduz", 10.1),
+    ("Valencia", 18.3),
+    ("Valletta", 18.8),
+    ("Vancouver", 10.4),
+    ("Veracruz", 25.4),
+    ("Vienna", 10.4),
+    ("Vientiane", 25.9),
+    ("Villahermosa", 27.1),
+    ("Vilnius", 6.0),
+    ("Virginia Beach", 15.8),
+    ("Vladivostok", 4.9),
+    ("Warsaw", 8.5),
+    ("Washington, D.C.", 14.6),
+    ("Wau", 27.8),
+    ("Wellington", 12.9),
+    ("Whitehorse", -0.1),
+    ("Wichita", 13.9),
+    ("Willemstad", 28.0),
+    ("Winnipeg", 3.0),
+    ("Wrocław", 9.6),
+    ("Xi'an", 14.1),
+    ("Yakutsk", -8.8),
+    ("Yangon", 27.5),
+    ("Yaoundé", 23.8),
+    ("Yellowknife", -4.3),
+    ("Yerevan", 12.4),
+    ("Yinchuan", 9.0),
+    ("Zagreb", 10.7),
+    ("Zanzibar City", 26.0),
+    ("Zürich", 9.3),
+];
@@ -0,0 +1,80 @@
+use std::collections::HashMap;
+use std::fs;
+
+/// Coordinates of a station from the `--geo` lookup file.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Location {
+    pub lat: f64,
+    pub lon: f64,
+    pub elevation: Option<f64>,
+}
+
+/// Station name → location, loaded from a CSV of `name,lat,lon[,elevation]`.
+pub struct GeoTable {
+    locations: HashMap<Vec<u8>, Location>,
+}
+
+impl GeoTable {
+    /// Loads the lookup file. The name is everything before the last two (or
+    /// three) fields, so names containing commas need no quoting. A first line
+    /// whose latitude does not parse is taken as a header and skipped.
+    pub fn load(path: &str) -> Result<GeoTable, String> {
+        let data = fs::read(path).map_err(|e| format!("{}: {}", path, e))?;
+        let mut locations = HashMap::new();
+        for (i, line) in data.split(|&b| b == b'\n').enumerate() {
+            let line = line.strip_suffix(b"\r").unwrap_or(line);
+            if line.is_empty() {
+                continue;
+            }
+            match parse_line(line) {
+                Some((name, location)) => {
+                    locations.insert(name.to_vec(), location);
+                }
+                None if i == 0 => {}
+                None => {
+                    return Err(format!(
+                        "{}:{}: expected name,lat,lon[,elevation]",
+                        path,
+                        i + 1
+                    ))
+                }
+            }
+        }
+        Ok(GeoTable { locations })
+    }
+
+    pub fn get(&self, name: &[u8]) -> Option<&Location> {
+        self.locations.get(name)
+    }
+}
+
+fn parse_field(field: &[u8]) -> Option<f64> {
+    std::str::from_utf8(field).ok()?.trim().parse().ok()
+}
+
+/// Splits `name,lat,lon` followed by `extra` more numeric fields off the end of
+/// `line`; an empty extra field reads as `None`.
+fn split_fields(line: &[u8], extra: usize) -> Option<(&[u8], Vec<Option<f64>>)> {
+    let mut fields = line.rsplitn(3 + extra, |&b| b == b',');
+    let mut values = Vec::with_capacity(2 + extra);
+    for _ in 0..2 + extra {
+        values.push(match fields.next()? {
+            b"" => None,
+            field => Some(parse_field(field)?),
+        });
+    }
+    values.reverse();
+    Some((fields.next()?, values))
+}
+
+fn parse_line(line: &[u8]) -> Option<(&[u8], Location)> {
+    // Try with an elevation column first; a name containing commas may make
+    // that split land on the wrong field, in which case it will not parse
+    let (name, values) = split_fields(line, 1).or_else(|| split_fields(line, 0))?;
+    let location = Location {
+        lat: values[0]?,
+        lon: values[1]?,
+        elevation: values.get(2).copied().flatten(),
+    };
+    (!name.is_empty()).then_some((name, location))
+}
@@ -0,0 +1,282 @@
+use std::sync::mpsc;
+use std::time::Instant;
+
+use crate::cli::{Exit, Failure};
+use crate::{line_chunks, Aggregator, StationTable};
+
+/// Input handed to the GPU per dispatch. One invocation per byte, so this
+/// keeps the workgroup count under the 65535 a dispatch may use.
+const CHUNK_SIZE: usize = 8 << 20;
+/// Room for the rest of the line a chunk ends in.
+const MAX_CHUNK: usize = CHUNK_SIZE + 4096;
+const WORKGROUP_SIZE: usize = 256;
+/// Words per record written by the shader: line start, name length and the
+/// parsed temperature.
+const RECORD_WORDS: usize = 3;
+
+/// Every byte that starts a line scans forward for `;` and `\n`, parses the
+/// temperature into fixed-point units and appends a record. Records come out
+/// in no particular order; the CPU reduces them into the table.
+const SHADER: &str = r#"
+struct Params {
+    len: u32,
+    decimals: u32,
+    capacity: u32,
+}
+
+@group(0) @binding(0) var<storage, read> input: array<u32>;
+@group(0) @binding(1) var<storage, read_write> records: array<u32>;
+@group(0) @binding(2) var<storage, read_write> count: atomic<u32>;
+@group(0) @binding(3) var<uniform> params: Params;
+
+fn byte_at(i: u32) -> u32 {
+    return (input[i >> 2u] >> ((i & 3u) * 8u)) & 0xffu;
+}
+
+@compute @workgroup_size(256)
+fn main(@builtin(global_invocation_id) id: vec3<u32>) {
+    let start = id.x;
+    if (start >= params.len || (start != 0u && byte_at(start - 1u) != 10u)) {
+        return;
+    }
+
+    var i = start;
+    while (i < params.len && byte_at(i) != 59u) {
+        i += 1u;
+    }
+    let semi = i;
+    i += 1u;
+
+    var negative = false;
+    if (i < params.len && byte_at(i) == 45u) {
+        negative = true;
+        i += 1u;
+    }
+    var value = 0i;
+    var point = false;
+    var fraction = 0u;
+    while (i < params.len && byte_at(i) != 10u) {
+        let b = byte_at(i);
+        if (b == 46u) {
+            point = true;
+        } else if (!point || fraction < params.decimals) {
+            value = value * 10i + i32(b) - 48i;
+            if (point) {
+                fraction += 1u;
+            }
+        }
+        i += 1u;
+    }
+    for (; fraction < params.decimals; fraction += 1u) {
+        value *= 10i;
+    }
+    if (negative) {
+        value = -value;
+    }
+
+    let slot = atomicAdd(&count, 1u);
+    if (slot < params.capacity) {
+        records[slot * 3u] = start;
+        records[slot * 3u + 1u] = semi - start;
+        records[slot * 3u + 2u] = bitcast<u32>(value);
+    }
+}
+"#;
+
+struct Gpu {
+    device: wgpu::Device,
+    queue: wgpu::Queue,
+    pipeline: wgpu::ComputePipeline,
+    input: wgpu::Buffer,
+    records: wgpu::Buffer,
+    count: wgpu::Buffer,
+    params: wgpu::Buffer,
+    readback: wgpu::Buffer,
+    bind_group: wgpu::BindGroup,
+}
+
+/// Lines a chunk can hold at most; the shortest is `a;0\n`.
+fn capacity(len: usize) -> usize {
+    len / 4 + 1
+}
+
+impl Gpu {
+    fn new() -> Result<Gpu, String> {
+        let instance = wgpu::Instance::new(wgpu::InstanceDescriptor::new_without_display_handle());
+        let adapter =
+            pollster::block_on(instance.request_adapter(&wgpu::RequestAdapterOptions::default()))
+                .map_err(|e| format!("no GPU adapter available: {}", e))?;
+        let (device, queue) =
+            pollster::block_on(adapter.request_device(&wgpu::DeviceDescriptor::default()))
+                .map_err(|e| format!("cannot open the GPU: {}", e))?;
+
+        let module = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("parse"),
+            source: wgpu::ShaderSource::Wgsl(SHADER.into()),
+        });
+        let pipeline = device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+            label: Some("parse"),
+            layout: None,
+            module: &module,
+            entry_point: Some("main"),
+            compilation_options: Default::default(),
+            cache: None,
+        });
+
+        let records_size = (capacity(MAX_CHUNK) * RECORD_WORDS * 4) as u64;
+        let buffer = |label, size, usage| {
+            device.create_buffer(&wgpu::BufferDescriptor {
+                label: Some(label),
+                size,
+                usage,
+                mapped_at_creation: false,
+            })
+        };
+        use wgpu::BufferUsages as U;
+        let input = buffer("input", MAX_CHUNK as u64, U::STORAGE | U::COPY_DST);
+        let records = buffer("records", records_size, U::STORAGE | U::COPY_SRC);
+        let count = buffer("count", 4, U::STORAGE | U::COPY_SRC | U::COPY_DST);
+        let params = buffer("params", 16, U::UNIFORM | U::COPY_DST);
+        let readback = buffer("readback", records_size, U::MAP_READ | U::COPY_DST);
+
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("parse"),
+            layout: &pipeline.get_bind_group_layout(0),
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: input.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: records.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: count.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 3,
+                    resource: params.as_entire_binding(),
+                },
+            ],
+        });
+
+        Ok(Gpu {
+            device,
+            queue,
+            pipeline,
+            input,
+            records,
+            count,
+            params,
+            readback,
+            bind_group,
+        })
+    }
+
+    /// Parses one chunk of complete lines and returns its records.
+    fn parse(&self, chunk: &[u8], decimals: u32) -> Result<Vec<u32>, String> {
+        if chunk.len() > MAX_CHUNK {
+            return Err("input has a line longer than 4 KiB".to_string());
+        }
+        // Buffer writes must be a multiple of four bytes
+        let mut padded = chunk.to_vec();
+        padded.resize(chunk.len().next_multiple_of(4), 0);
+        let max_lines = capacity(chunk.len()) as u32;
+        let params = [chunk.len() as u32, decimals, max_lines, 0];
+        self.queue.write_buffer(&self.input, 0, &padded);
+        self.queue
+            .write_buffer(&self.params, 0, &words_to_bytes(&params));
+        self.queue.write_buffer(&self.count, 0, &[0; 4]);
+
+        let mut encoder = self
+            .device
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor::default());
+        {
+            let mut pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor::default());
+            pass.set_pipeline(&self.pipeline);
+            pass.set_bind_group(0, &self.bind_group, &[]);
+            pass.dispatch_workgroups(chunk.len().div_ceil(WORKGROUP_SIZE) as u32, 1, 1);
+        }
+        encoder.copy_buffer_to_buffer(&self.count, 0, &self.readback, 0, 4);
+        self.queue.submit([encoder.finish()]);
+        let lines = self.read_back(4)?[0];
+        if lines > max_lines {
+            return Err(format!("input has more lines than expected ({})", lines));
+        }
+
+        let size = (lines as usize * RECORD_WORDS * 4) as u64;
+        if size == 0 {
+            return Ok(Vec::new());
+        }
+        let mut encoder = self
+            .device
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor::default());
+        encoder.copy_buffer_to_buffer(&self.records, 0, &self.readback, 0, size);
+        self.queue.submit([encoder.finish()]);
+        self.read_back(size)
+    }
+
+    /// Waits for the GPU and copies the first `size` bytes of the readback
+    /// buffer out as words.
+    fn read_back(&self, size: u64) -> Result<Vec<u32>, String> {
+        let slice = self.readback.slice(..size);
+        let (tx, rx) = mpsc::channel();
+        slice.map_async(wgpu::MapMode::Read, move |result| {
+            let _ = tx.send(result);
+        });
+        self.device
+            .poll(wgpu::PollType::wait_indefinitely())
+            .map_err(|e| e.to_string())?;
+        rx.recv()
+            .map_err(|e| e.to_string())?
+            .map_err(|e| e.to_string())?;
+        let words = {
+            let view = slice.get_mapped_range().map_err(|e| e.to_string())?;
+            view.chunks_exact(4)
+                .map(|w| u32::from_le_bytes(w.try_into().unwrap()))
+                .collect()
+        };
+        self.readback.unmap();
+        Ok(words)
+    }
+}
+
+fn words_to_bytes(words: &[u32]) -> Vec<u8> {
+    words.iter().flat_map(|w| w.to_le_bytes()).collect()
+}
+
+/// Aggregates an in-memory input with delimiter search and temperature
+/// parsing done on the GPU, for `--gpu`. The CPU only looks up each record's
+/// station and updates its statistics.
+pub fn aggregate<A: Aggregator>(data: &[u8], table: &mut StationTable<A>) {
+    let failed = |e: String| Failure::new(Exit::Internal, format!("--gpu: {}", e));
+    let gpu = match Gpu::new() {
+        Ok(gpu) => gpu,
+        Err(e) => {
+            table.fault.raise(failed(e));
+            return;
+        }
+    };
+    let key = table.key.clone();
+    for chunk in line_chunks(data, CHUNK_SIZE) {
+        let start = Instant::now();
+        let records = match gpu.parse(chunk, table.decimals) {
+            Ok(records) => records,
+            Err(e) => {
+                table.fault.raise(failed(e));
+                return;
+            }
+        };
+        for record in records.chunks_exact(RECORD_WORDS) {
+            let (start, len) = (record[0] as usize, record[1] as usize);
+            let name = &chunk[start..start + len];
+            let name = key.as_ref().map_or(name, |key| key(name));
+            table.lookup_or_insert(name, record[2] as i32);
+        }
+        if let Some(progress) = table.progress.clone() {
+            progress.record(chunk.len(), start.elapsed(), table);
+        }
+    }
+}
@@ -0,0 +1,72 @@
+use std::io::{self, Write};
+
+use crate::stats;
+
+/// `--histogram`: how many readings fell on each step of the input's
+/// resolution in a range, in fixed-point units, and how many below and above
+/// it. Each worker's table counts its own readings; the counts are merged
+/// with the tables.
+pub struct Histogram {
+    /// Value counted by `counts[0]`.
+    lo: i32,
+    counts: Vec<u64>,
+    below: u64,
+    above: u64,
+}
+
+impl Histogram {
+    /// Counts over `lo..=hi`.
+    pub fn new(lo: i32, hi: i32) -> Self {
+        Self {
+            lo,
+            counts: vec![0; lo.abs_diff(hi) as usize + 1],
+            below: 0,
+            above: 0,
+        }
+    }
+
+    /// Empty counts over the same range, for a worker's table.
+    pub fn sibling(&self) -> Self {
+        Self {
+            lo: self.lo,
+            counts: vec![0; self.counts.len()],
+            below: 0,
+            above: 0,
+        }
+    }
+
+    #[inline(always)]
+    pub fn count(&mut self, temp: i32) {
+        if temp < self.lo {
+            self.below += 1;
+        } else if let Some(n) = self.counts.get_mut(temp.abs_diff(self.lo) as usize) {
+            *n += 1;
+        } else {
+            self.above += 1;
+        }
+    }
+
+    pub fn merge(&mut self, other: &Histogram) {
+        for (n, theirs) in self.counts.iter_mut().zip(&other.counts) {
+            *n += theirs;
+        }
+        self.below += other.below;
+        self.above += other.above;
+    }
+
+    /// Writes `temp,count` CSV for every step in the range, with `decimals`
+    /// fraction digits, the readings outside it counted on `<lo` and `>hi`
+    /// rows.
+    pub fn write(&self, decimals: u32, out: &mut dyn Write) -> io::Result<()> {
+        let scale = stats::scale(decimals);
+        let degrees = |temp: i64| format!("{:.*}", decimals as usize, temp as f64 / scale);
+        let lo = self.lo as i64;
+        let hi = lo + self.counts.len() as i64 - 1;
+        writeln!(out, "temp,count")?;
+        writeln!(out, "<{},{}", degrees(lo), self.below)?;
+        for (temp, n) in (lo..).zip(&self.counts) {
+            writeln!(out, "{},{}", degrees(temp), n)?;
+        }
+        writeln!(out, ">{},{}", degrees(hi), self.above)
+    }
+}
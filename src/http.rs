@@ -0,0 +1,228 @@
+use std::collections::BTreeMap;
+use std::io::{self, Read};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc::{self, Receiver};
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread;
+
+/// Size of each ranged GET when the server supports `Range` requests.
+const RANGE_SIZE: usize = 32 << 20;
+
+/// Opens `url` as a byte stream from offset `start` on. When the server
+/// advertises byte ranges and a length, the body is fetched as fixed-size
+/// ranges over `connections` parallel requests and reassembled in order;
+/// otherwise, or when the server refuses `HEAD`, it is streamed with a single
+/// GET, which can start past 0 only if the server takes a `Range` header.
+pub fn open(url: &str, connections: usize, start: u64) -> Result<Box<dyn Read + Send>, String> {
+    let agent = ureq::Agent::new_with_defaults();
+    let head = agent.head(url).call().ok();
+    let header = |name: &str| {
+        head.as_ref()?
+            .headers()
+            .get(name)
+            .and_then(|v| v.to_str().ok())
+            .map(str::to_string)
+    };
+    let ranged = header("accept-ranges").is_some_and(|v| v.eq_ignore_ascii_case("bytes"));
+    let len = header("content-length").and_then(|v| v.parse::<usize>().ok());
+
+    match len {
+        Some(len) if ranged && connections > 1 => Ok(Box::new(RangedReader::spawn(
+            agent,
+            url,
+            (start as usize).min(len),
+            len,
+            connections,
+        ))),
+        _ if start == 0 => get(&agent, url, 0).map_err(|e| e.to_string()),
+        // Without HEAD, whether the server takes ranges shows in the reply
+        _ if ranged || head.is_none() => {
+            let resp = agent
+                .get(url)
+                .header("Range", format!("bytes={}-", start))
+                .call()
+                .map_err(|e| e.to_string())?;
+            if resp.status() != 206 {
+                return Err(format!(
+                    "expected 206 Partial Content from byte {}, got {}",
+                    start,
+                    resp.status()
+                ));
+            }
+            Ok(Box::new(resp.into_body().into_reader()))
+        }
+        _ => Err("the server does not accept byte ranges, so the run cannot resume".to_string()),
+    }
+}
+
+/// The whole of `url` with a single GET, less its first `skip` bytes.
+fn get(agent: &ureq::Agent, url: &str, skip: u64) -> io::Result<Box<dyn Read + Send>> {
+    let resp = agent.get(url).call().map_err(io::Error::other)?;
+    let mut reader = resp.into_body().into_reader();
+    if io::copy(&mut (&mut reader).take(skip), &mut io::sink())? != skip {
+        return Err(io::Error::other("body shorter than the bytes already read"));
+    }
+    Ok(Box::new(reader))
+}
+
+/// Fails with [`io::ErrorKind::Unsupported`] when the server answers with
+/// the whole body instead of the range.
+fn fetch_range(agent: &ureq::Agent, url: &str, start: usize, end: usize) -> io::Result<Vec<u8>> {
+    let mut resp = agent
+        .get(url)
+        .header("Range", format!("bytes={}-{}", start, end - 1))
+        .call()
+        .map_err(io::Error::other)?;
+    if resp.status() == 200 {
+        return Err(io::ErrorKind::Unsupported.into());
+    }
+    if resp.status() != 206 {
+        return Err(io::Error::other(format!(
+            "expected 206 Partial Content for range {}-{}, got {}",
+            start,
+            end - 1,
+            resp.status()
+        )));
+    }
+    let body = resp
+        .body_mut()
+        .with_config()
+        .limit((end - start) as u64 + 1)
+        .read_to_vec()
+        .map_err(io::Error::other)?;
+    if body.len() != end - start {
+        return Err(io::Error::other("short range response"));
+    }
+    Ok(body)
+}
+
+/// Reassembles ranges downloaded by worker threads into one in-order stream.
+/// Workers fetch only ranges within `connections` of the one being read, so
+/// a slow range holds the rest back instead of letting them pile up.
+struct RangedReader {
+    rx: Receiver<(usize, io::Result<Vec<u8>>)>,
+    pending: BTreeMap<usize, Vec<u8>>,
+    /// The index of the next range to read, or `None` once the reader is
+    /// gone and the workers should stop.
+    window: Arc<(Mutex<Option<usize>>, Condvar)>,
+    next: usize,
+    ranges: usize,
+    current: Vec<u8>,
+    pos: usize,
+    /// A plain GET taking over from the ranges, if the server ignored them.
+    fallback: Option<Box<dyn Read + Send>>,
+    agent: ureq::Agent,
+    url: String,
+    offset: usize,
+}
+
+impl RangedReader {
+    /// Fetches bytes `offset..len` of `url`.
+    fn spawn(agent: ureq::Agent, url: &str, offset: usize, len: usize, connections: usize) -> Self {
+        let ranges = (len - offset).div_ceil(RANGE_SIZE);
+        let claimed = Arc::new(AtomicUsize::new(0));
+        let window = Arc::new((Mutex::new(Some(0)), Condvar::new()));
+        let (tx, rx) = mpsc::sync_channel(connections);
+
+        for _ in 0..connections.min(ranges) {
+            let (agent, url, claimed, window, tx) = (
+                agent.clone(),
+                url.to_string(),
+                claimed.clone(),
+                window.clone(),
+                tx.clone(),
+            );
+            thread::spawn(move || loop {
+                let idx = claimed.fetch_add(1, Ordering::Relaxed);
+                if idx >= ranges {
+                    break;
+                }
+                let (next, moved) = &*window;
+                let next = moved
+                    .wait_while(next.lock().unwrap(), |next| {
+                        next.is_some_and(|next| idx >= next + connections)
+                    })
+                    .unwrap();
+                if next.is_none() {
+                    break;
+                }
+                drop(next);
+                let start = offset + idx * RANGE_SIZE;
+                let end = (start + RANGE_SIZE).min(len);
+                if tx
+                    .send((idx, fetch_range(&agent, &url, start, end)))
+                    .is_err()
+                {
+                    break;
+                }
+            });
+        }
+
+        Self {
+            rx,
+            pending: BTreeMap::new(),
+            window,
+            next: 0,
+            ranges,
+            current: Vec::new(),
+            pos: 0,
+            fallback: None,
+            agent,
+            url: url.to_string(),
+            offset,
+        }
+    }
+
+    /// Moves the window on to range `next`, or with `None`, stops the workers.
+    fn advance(&self, next: Option<usize>) {
+        let (lock, moved) = &*self.window;
+        *lock.lock().unwrap() = next;
+        moved.notify_all();
+    }
+}
+
+impl Drop for RangedReader {
+    fn drop(&mut self) {
+        self.advance(None);
+    }
+}
+
+impl Read for RangedReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if let Some(fallback) = &mut self.fallback {
+            return fallback.read(buf);
+        }
+        while self.pos == self.current.len() {
+            if self.next == self.ranges {
+                return Ok(0);
+            }
+            while !self.pending.contains_key(&self.next) {
+                let (idx, body) = self
+                    .rx
+                    .recv()
+                    .map_err(|_| io::Error::other("download workers exited early"))?;
+                match body {
+                    Ok(body) => {
+                        self.pending.insert(idx, body);
+                    }
+                    Err(e) if e.kind() == io::ErrorKind::Unsupported => {
+                        // Every range before `next` has been read in full
+                        self.advance(None);
+                        let skip = self.offset + self.next * RANGE_SIZE;
+                        let reader = get(&self.agent, &self.url, skip as u64)?;
+                        return self.fallback.insert(reader).read(buf);
+                    }
+                    Err(e) => return Err(e),
+                }
+            }
+            self.current = self.pending.remove(&self.next).unwrap();
+            self.pos = 0;
+            self.next += 1;
+            self.advance(Some(self.next));
+        }
+        let n = buf.len().min(self.current.len() - self.pos);
+        buf[..n].copy_from_slice(&self.current[self.pos..self.pos + n]);
+        self.pos += n;
+        Ok(n)
+    }
+}
@@ -0,0 +1,69 @@
+use std::io::{self, Write};
+use std::time::{Duration, Instant};
+
+use kafka::consumer::{Consumer, FetchOffset, GroupOffsetStorage};
+
+use crate::cli::Options;
+use crate::rejects::Rejects;
+use crate::seen;
+use crate::{aggregate_untrusted_line, output_results, StationTable};
+
+/// Runs `onebrc kafka`: consumes the topic from the group's committed offset
+/// (or the earliest message for a new group) and prints a snapshot of the
+/// aggregate every `--snapshot-every` seconds. A message value may hold any
+/// number of newline-separated `station;temp` lines. With `--rejects`, lines
+/// that are dropped are logged as `partition@offset:line`.
+pub fn run(opts: &Options) -> Result<(), String> {
+    let rejects = match &opts.rejects {
+        Some(path) => Some(Rejects::open(path).map_err(|e| format!("{}: {}", path, e))?),
+        None => None,
+    };
+    let mut consumer = Consumer::from_hosts(opts.brokers.clone())
+        .with_topic(opts.topic.clone())
+        .with_group(opts.group.clone())
+        .with_fallback_offset(FetchOffset::Earliest)
+        .with_offset_storage(Some(GroupOffsetStorage::Kafka))
+        .create()
+        .map_err(|e| format!("connecting to kafka: {}", e))?;
+
+    let mut table = StationTable::new();
+    table.first_seen = seen::for_order(opts);
+    let interval = Duration::from_secs(opts.snapshot_every);
+    let mut next_snapshot = Instant::now() + interval;
+
+    loop {
+        let sets = consumer.poll().map_err(|e| e.to_string())?;
+        for set in sets.iter() {
+            for message in set.messages() {
+                for (number, line) in (1..).zip(message.value.split(|&b| b == b'\n')) {
+                    if line.is_empty() {
+                        continue;
+                    }
+                    if let (Err(reason), Some(rejects)) =
+                        (aggregate_untrusted_line(&mut table, line), &rejects)
+                    {
+                        let at = format_args!("{}@{}:{}", set.partition(), message.offset, number);
+                        rejects
+                            .record(at, reason, line)
+                            .map_err(|e| e.to_string())?;
+                    }
+                }
+            }
+            consumer
+                .consume_messageset(set)
+                .map_err(|e| e.to_string())?;
+        }
+        if let Some(rejects) = &rejects {
+            rejects.flush().map_err(|e| e.to_string())?;
+        }
+        consumer.commit_consumed().map_err(|e| e.to_string())?;
+
+        if Instant::now() >= next_snapshot {
+            let mut out = io::stdout().lock();
+            output_results(&table, opts, &mut out)
+                .and_then(|()| out.flush())
+                .map_err(|e| e.to_string())?;
+            next_snapshot = Instant::now() + interval;
+        }
+    }
+}
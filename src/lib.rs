@@ -0,0 +1,2369 @@
+//! Aggregation of `station;temperature` measurements into per-station
+//! min/mean/max, as used by the `onebrc` binary.
+
+#![cfg_attr(feature = "portable_simd", feature(portable_simd))]
+
+mod advice;
+mod archive;
+mod baseline;
+pub mod bench;
+mod calibrate;
+mod budget;
+mod checkpoint;
+pub mod cli;
+#[cfg(feature = "parquet")]
+mod columnar;
+pub mod completions;
+mod config;
+mod compress;
+pub mod daemon;
+mod decode;
+mod dictionary;
+mod emit;
+mod ends;
+mod fault;
+#[cfg(feature = "flight")]
+mod flight;
+mod footprint;
+#[cfg(unix)]
+mod fork;
+mod format;
+#[cfg(feature = "fuzzing")]
+#[doc(hidden)]
+pub mod fuzzing;
+#[cfg(feature = "polars")]
+mod frame;
+pub mod generate;
+mod geo;
+mod map;
+#[cfg(feature = "gpu")]
+mod gpu;
+mod histogram;
+#[cfg(feature = "http")]
+mod http;
+#[cfg(feature = "kafka")]
+pub mod kafka_source;
+#[cfg(feature = "s3")]
+mod objstore;
+mod origin;
+mod parallel;
+#[cfg(feature = "rayon")]
+mod pariter;
+mod partial;
+#[cfg(feature = "pipeline")]
+mod pipeline;
+#[cfg(target_os = "linux")]
+mod perf;
+mod perfect;
+mod plan;
+mod pread;
+#[cfg(feature = "profiling")]
+mod profile;
+pub mod progress;
+pub mod quality;
+mod records;
+mod rejects;
+mod sanity;
+mod seen;
+mod results;
+mod scan;
+mod schedule;
+pub mod server;
+#[cfg(feature = "sqlite")]
+mod sqlite;
+pub mod stats;
+#[cfg(feature = "tpc")]
+mod tpc;
+#[cfg(feature = "tui")]
+mod tui;
+mod window;
+
+use std::borrow::Cow;
+use std::cmp::Ordering;
+use std::fs::File;
+use std::os::unix::fs::FileTypeExt;
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::mem::MaybeUninit;
+use std::slice::SliceIndex;
+use std::sync::{mpsc, Arc};
+use std::thread;
+use std::time::Instant;
+
+use budget::{Budget, RowLimit};
+use cli::{Color, Command, Engine, Exit, Failure, Format, GroupBy, InvalidUtf8, Io, Options, Order, Unknown};
+use decode::{Encoding, Utf16Reader};
+use progress::Progress;
+use stats::{Counter, Distribution, Extremes, Kind, Moments, Stat, Summary};
+
+#[cfg(feature = "polars")]
+pub use frame::aggregate_to_dataframe;
+pub use records::Records;
+pub use results::Results;
+
+/// Per-station accumulator driven by the aggregation loop. [`StationStats`]
+/// is the default; implement this to collect other statistics (sum of squares,
+/// last value, ...) with the same parser and table. `Default` is the state
+/// before any measurement; `Clone` lets the state be copied out of a running
+/// aggregation, and `Send` lets worker threads each build their own tables.
+pub trait Aggregator: Default + Clone + Send {
+    /// Records one measurement, in tenths of a degree.
+    fn update(&mut self, temp: i32);
+    /// Folds in the state accumulated for the same station elsewhere.
+    fn merge(&mut self, other: &Self);
+
+    /// Appends a binary encoding of the state to `out`, so that partial
+    /// results from other processes (`--processes`) or hosts (`--format
+    /// partial`) can be merged. Numbers are little-endian, so that a dump
+    /// reads back the same on any host. Returns false if the aggregator has
+    /// no encoding, which is the default.
+    fn encode(&self, _out: &mut Vec<u8>) -> bool {
+        false
+    }
+
+    /// Reads back a state written by [`Aggregator::encode`] from the start of
+    /// `input`, advancing it past the encoding.
+    fn decode(_input: &mut &[u8]) -> Option<Self> {
+        None
+    }
+}
+
+/// Running statistics for one station. Temperatures are kept in tenths of a
+/// degree; with the `serde` feature they are serialized in those units too, so
+/// a round trip is exact.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct StationStats {
+    min: i32,
+    max: i32,
+    sum: i64,
+    count: u64,
+}
+
+impl Default for StationStats {
+    fn default() -> Self {
+        Self {
+            min: i32::MAX,
+            max: i32::MIN,
+            sum: 0,
+            count: 0,
+        }
+    }
+}
+
+impl Aggregator for StationStats {
+    fn update(&mut self, temp: i32) {
+        if temp < self.min {
+            self.min = temp;
+        }
+        if temp > self.max {
+            self.max = temp;
+        }
+        self.sum += temp as i64;
+        self.count += 1;
+    }
+
+    fn merge(&mut self, other: &StationStats) {
+        self.min = self.min.min(other.min);
+        self.max = self.max.max(other.max);
+        self.sum += other.sum;
+        self.count += other.count;
+    }
+
+    fn encode(&self, out: &mut Vec<u8>) -> bool {
+        out.extend_from_slice(&self.min.to_le_bytes());
+        out.extend_from_slice(&self.max.to_le_bytes());
+        out.extend_from_slice(&self.sum.to_le_bytes());
+        out.extend_from_slice(&self.count.to_le_bytes());
+        true
+    }
+
+    fn decode(input: &mut &[u8]) -> Option<Self> {
+        Some(Self {
+            min: i32::from_le_bytes(partial::take(input)?),
+            max: i32::from_le_bytes(partial::take(input)?),
+            sum: i64::from_le_bytes(partial::take(input)?),
+            count: u64::from_le_bytes(partial::take(input)?),
+        })
+    }
+}
+
+/// The degree accessors assume the default one-decimal input; with
+/// `--decimals` use [`Summary::value`] and [`stats::scale`] instead.
+impl StationStats {
+    pub fn count(&self) -> u64 {
+        self.count
+    }
+
+    pub fn mean(&self) -> f64 {
+        self.sum as f64 / self.count as f64 / 10.0
+    }
+
+    pub fn min_f64(&self) -> f64 {
+        self.min as f64 / 10.0
+    }
+
+    pub fn max_f64(&self) -> f64 {
+        self.max as f64 / 10.0
+    }
+}
+
+/// `&slice[index]`, except that the bounds check is left out when built with
+/// the `fast-unsafe` feature. The hot loop indexes through this so that the
+/// cost of its checks can be measured.
+///
+/// # Safety
+///
+/// `index` must be in bounds. For the table that holds by construction:
+/// slots are indexed by a hash masked to the slot count, and names by ranges
+/// recorded when they were appended to the arena. For the input it holds as
+/// long as every line is well-formed (`name;temp` with a `.` in the
+/// temperature), which is what `fast-unsafe` builds trust the input to be;
+/// checked builds stop with an error naming the first malformed line instead.
+#[inline(always)]
+unsafe fn at<T, I: SliceIndex<[T]>>(slice: &[T], index: I) -> &I::Output {
+    if cfg!(feature = "fast-unsafe") {
+        slice.get_unchecked(index)
+    } else {
+        &slice[index]
+    }
+}
+
+/// The `&mut` counterpart of [`at`].
+///
+/// # Safety
+///
+/// As for [`at`].
+#[inline(always)]
+unsafe fn at_mut<T, I: SliceIndex<[T]>>(slice: &mut [T], index: I) -> &mut I::Output {
+    if cfg!(feature = "fast-unsafe") {
+        slice.get_unchecked_mut(index)
+    } else {
+        &mut slice[index]
+    }
+}
+
+/// Parses a temperature like "-12.3" or "4.5" as an i32 scaled by 10 (e.g. -123, 45).
+/// Assumes exactly one decimal digit. Checked builds return `None` for
+/// anything else; `fast-unsafe` builds take the shape on trust.
+#[inline(always)]
+fn parse_temp(bytes: &[u8]) -> Option<i32> {
+    if !cfg!(feature = "fast-unsafe") && !is_temp(bytes) {
+        return None;
+    }
+    // SAFETY: a well-formed temperature has a digit after its '.'
+    let byte = |i: usize| unsafe { *at(bytes, i) };
+    let (negative, start) = if byte(0) == b'-' {
+        (true, 1)
+    } else {
+        (false, 0)
+    };
+
+    let mut value: i32 = 0;
+    let mut i = start;
+    while byte(i) != b'.' {
+        value = value * 10 + (byte(i) - b'0') as i32;
+        i += 1;
+    }
+    // skip '.', parse the single decimal digit
+    value = value * 10 + (byte(i + 1) - b'0') as i32;
+
+    Some(if negative { -value } else { value })
+}
+
+/// Whether `bytes` is shaped like a temperature [`parse_temp`] can read:
+/// `-?d+.d`, with few enough digits to fit an i32, and maybe the `\r` of a
+/// CRLF line ending.
+fn is_temp(bytes: &[u8]) -> bool {
+    let bytes = bytes.strip_suffix(b"\r").unwrap_or(bytes);
+    let digits = bytes.strip_prefix(b"-").unwrap_or(bytes);
+    match digits {
+        [whole @ .., b'.', fraction] => {
+            (1..=MAX_WHOLE_DIGITS).contains(&whole.len())
+                && whole.iter().all(u8::is_ascii_digit)
+                && fraction.is_ascii_digit()
+        }
+        _ => false,
+    }
+}
+
+/// Digits a temperature may have before its `.`, so that it fits an i32
+/// even scaled by `10^3` for `--decimals 3`.
+const MAX_WHOLE_DIGITS: usize = 5;
+
+/// Parses a temperature with `decimals` fraction digits as an i32 scaled by
+/// `10^decimals`, e.g. "-12.34" at two decimals as -1234. Inputs with fewer
+/// digits are padded ("1.5" reads as 150) and extra digits are truncated.
+/// Checked builds return `None` for anything but `-?d+(.d*)?`.
+fn parse_temp_scaled(bytes: &[u8], decimals: u32) -> Option<i32> {
+    let bytes = bytes.strip_suffix(b"\r").unwrap_or(bytes);
+    let (negative, digits) = match bytes.split_first() {
+        Some((b'-', rest)) => (true, rest),
+        _ => (false, bytes),
+    };
+    if !cfg!(feature = "fast-unsafe") {
+        let whole = digits.split(|&b| b == b'.').next().unwrap_or(digits);
+        let valid = (1..=MAX_WHOLE_DIGITS).contains(&whole.len())
+            && digits.iter().filter(|&&b| b == b'.').count() <= 1
+            && digits.iter().all(|&b| b == b'.' || b.is_ascii_digit());
+        if !valid {
+            return None;
+        }
+    }
+
+    let mut value: i32 = 0;
+    let mut fraction: Option<u32> = None;
+    for &b in digits {
+        match (b, fraction) {
+            (b'.', _) => fraction = Some(0),
+            (_, Some(n)) if n == decimals => break,
+            _ => {
+                value = value * 10 + (b - b'0') as i32;
+                fraction = fraction.map(|n| n + 1);
+            }
+        }
+    }
+    value *= 10i32.pow(decimals - fraction.unwrap_or(0));
+
+    Some(if negative { -value } else { value })
+}
+
+/// Like `parse_temp`, but validates the `-?d{1,2}.d` shape first and returns
+/// `None` for anything else. Used where input is untrusted.
+fn parse_temp_checked(bytes: &[u8]) -> Option<i32> {
+    let digits = bytes.strip_prefix(b"-").unwrap_or(bytes);
+    let valid = match digits {
+        [a, b'.', c] => a.is_ascii_digit() && c.is_ascii_digit(),
+        [a, b, b'.', c] => a.is_ascii_digit() && b.is_ascii_digit() && c.is_ascii_digit(),
+        _ => false,
+    };
+    if valid { parse_temp(bytes) } else { None }
+}
+
+/// Validates and aggregates one line from an untrusted source (sockets,
+/// message queues). Returns why the line was dropped instead, if it was:
+/// malformed, a name too long for the table, or a new station once half the
+/// table is occupied.
+fn aggregate_untrusted_line(table: &mut StationTable, line: &[u8]) -> Result<(), &'static str> {
+    let line = line.strip_suffix(b"\r").unwrap_or(line);
+    let semi = memchr::memrchr(b';', line).ok_or("no ';' separator")?;
+    let name = &line[..semi];
+    if name.is_empty() {
+        return Err("empty station name");
+    }
+    if name.len() > MAX_NAME_LEN {
+        return Err("station name over 100 bytes");
+    }
+    if table.len >= TABLE_SIZE / 2 {
+        return Err("table full");
+    }
+    let temp = parse_temp_checked(&line[semi + 1..]).ok_or("temperature not like -12.3")?;
+    table.lookup_or_insert(name, temp);
+    Ok(())
+}
+
+const TABLE_SIZE: usize = 65536; // power of 2, handles up to ~10k stations
+const MAX_NAME_LEN: usize = 100;
+
+/// Inputs smaller than this are read into memory and aggregated on one
+/// thread into a small table, since setting up the mapping, the workers and
+/// a full-size table would cost more than the aggregation itself.
+const SMALL_INPUT: u64 = 4 << 20;
+/// Initial slots of the table used for small inputs.
+const SMALL_TABLE_SIZE: usize = 1024;
+
+fn is_small_input(len: u64) -> bool {
+    len < SMALL_INPUT
+}
+
+/// A table slot. Everything a probe reads sits in one slot, and slots are
+/// aligned to cache lines so that a probe touches exactly one line; the full
+/// name is only read from the arena once the prefix matches.
+#[repr(align(64))]
+struct Entry<A> {
+    /// The first 8 bytes of the name, zero-padded, to rule out most other
+    /// names without reading the arena.
+    prefix: u64,
+    /// Where the name starts in the table's name arena.
+    name_start: u32,
+    name_len: u8,
+    stats: A,
+}
+
+impl<A> Entry<A> {
+    #[inline(always)]
+    fn name<'a>(&self, names: &'a [u8]) -> &'a [u8] {
+        let start = self.name_start as usize;
+        // SAFETY: `names` is the arena of the entry's table, which the name
+        // was appended to
+        unsafe { at(names, start..start + self.name_len as usize) }
+    }
+
+    /// Whether this is the entry for `name`, whose prefix is `prefix`.
+    #[inline(always)]
+    fn is(&self, names: &[u8], name: &[u8], prefix: u64) -> bool {
+        self.prefix == prefix
+            && self.name_len as usize == name.len()
+            // SAFETY: both names are longer than 8 bytes in that branch
+            && (name.len() <= 8
+                || scan::names_equal(unsafe { at(self.name(names), 8..) }, unsafe {
+                    at(name, 8..)
+                }))
+    }
+}
+
+/// The first 8 bytes of `name` in one load, zero-padded.
+#[inline(always)]
+fn name_prefix(name: &[u8]) -> u64 {
+    let mut buf = [0u8; 8];
+    let n = name.len().min(8);
+    buf[..n].copy_from_slice(&name[..n]);
+    u64::from_ne_bytes(buf)
+}
+
+/// Hashes the first 8 bytes of a name, as a `u64`, and its length.
+#[inline(always)]
+fn mix(prefix: u64, len: usize) -> u64 {
+    let mut h = prefix ^ len as u64;
+    // Stafford variant 13 finalizer (used in splitmix64)
+    h ^= h >> 30;
+    h = h.wrapping_mul(0xbf58476d1ce4e5b9);
+    h ^= h >> 27;
+    h = h.wrapping_mul(0x94d049bb133111eb);
+    h ^= h >> 31;
+    h
+}
+
+/// A hash of a station name for splitting stations across shards, files or
+/// hosts: the same on every host and in every release. On little-endian
+/// hosts it is also the hash the station table uses.
+pub fn partition_hash(name: &[u8]) -> u64 {
+    mix(u64::from_le(name_prefix(name)), name.len())
+}
+
+/// Slots for a table, zeroed by the allocator rather than written one by one.
+/// Large allocations come straight from the OS as zero pages, so a new table
+/// costs nothing until stations are inserted and untouched pages are never
+/// faulted in. A zero `name_len` marks a free slot, whose other fields may not
+/// be valid values (a zeroed `A` need not be its default).
+fn empty_entries<A>(slots: usize) -> Box<[MaybeUninit<Entry<A>>]> {
+    Box::new_zeroed_slice(slots)
+}
+
+/// Whether a slot from [`empty_entries`] holds a station.
+#[inline(always)]
+fn is_occupied<A>(slot: &MaybeUninit<Entry<A>>) -> bool {
+    // SAFETY: `name_len` is initialized in every slot, to zero in free ones
+    unsafe { (&raw const (*slot.as_ptr()).name_len).read() != 0 }
+}
+
+/// Maps a station name to the key it is aggregated under, which must be a
+/// subslice of the name (e.g. the part before a `/`).
+pub type KeyFn = Arc<dyn Fn(&[u8]) -> &[u8] + Send + Sync>;
+
+/// Open-addressing table from station name to its statistics.
+pub struct StationTable<A = StationStats> {
+    /// Slots from [`empty_entries`], fully initialized once occupied.
+    entries: Box<[MaybeUninit<Entry<A>>]>,
+    /// Every station name, back to back, so that entries stay small.
+    names: Vec<u8>,
+    /// Holds the stations instead of `entries` when [`map::ENABLED`].
+    map: map::Map<A>,
+    mask: usize,
+    len: usize,
+    /// Derives the grouping key from a station name; `None` groups by name.
+    key: Option<KeyFn>,
+    /// Fraction digits of the input temperatures.
+    decimals: u32,
+    /// Lines parsed ahead of their table updates, at most [`MAX_BATCH`].
+    batch: usize,
+    /// Walk each buffer as two interleaved halves instead of in batches.
+    interleave: bool,
+    /// Leave out blank lines and `#` comments, for `--skip-comments`.
+    skip_comments: bool,
+    /// Keys inserted up front into this table and its siblings, found by the
+    /// first pass of `--two-pass`.
+    known: Option<Arc<[Box<[u8]>]>>,
+    /// Where names start probing with `--stations`, a slot of their own for
+    /// the listed ones; dropped once the table grows.
+    perfect: Option<Arc<perfect::PerfectHash>>,
+    progress: Option<Arc<Progress<A>>>,
+    /// Time limit from `--max-seconds`, shared by every worker's table.
+    budget: Option<Arc<Budget>>,
+    /// Rows left under `--limit`, for streamed input.
+    limit: Option<Arc<RowLimit>>,
+    /// The first failure of the run, shared by every worker's table.
+    fault: Arc<fault::Fault>,
+    /// Readings outside the `--sanity-check` range, found by this table.
+    sanity: Option<sanity::Sanity>,
+    /// Readings counted by temperature for `--histogram`, by this table.
+    histogram: Option<histogram::Histogram>,
+    /// Where the lines aggregated into this table sit in the input.
+    origin: origin::Origin,
+    /// The first and last reading of each station, for `--first-last`.
+    ends: Option<ends::Ends>,
+    /// Where this table first saw each station, for `--order input`.
+    first_seen: Option<seen::FirstSeen>,
+    /// `--checkpoint` state of the table reading the input; never copied to
+    /// its siblings.
+    checkpoint: Option<checkpoint::Checkpoints>,
+    /// Chunks and threads recorded or replayed by `--record-schedule` and
+    /// `--replay-schedule`, held like `checkpoint`.
+    schedule: Option<schedule::Schedule>,
+    /// `--readahead` over the mapped input, shared by every worker's table.
+    prefetch: Option<Arc<advice::Prefetch>>,
+    /// What this table's worker aggregated and the workers merged into it,
+    /// for `--run-stats`.
+    activity: Option<footprint::Activity>,
+    /// `--emit-every` snapshots of the table reading the input, held like
+    /// `checkpoint`.
+    emit: Option<emit::Emitter<A>>,
+}
+
+impl<A> StationTable<A> {
+    /// The station in slot `idx`, if there is one.
+    #[inline(always)]
+    fn slot(&self, idx: usize) -> Option<&Entry<A>> {
+        let slot = &self.entries[idx];
+        // SAFETY: occupied slots are initialized
+        is_occupied(slot).then(|| unsafe { slot.assume_init_ref() })
+    }
+}
+
+impl<A> Drop for StationTable<A> {
+    fn drop(&mut self) {
+        if !std::mem::needs_drop::<A>() {
+            return;
+        }
+        for slot in self.entries.iter_mut().filter(|slot| is_occupied(slot)) {
+            // SAFETY: occupied slots are initialized, and dropped only here
+            unsafe { slot.assume_init_drop() };
+        }
+    }
+}
+
+impl<A: Aggregator> StationTable<A> {
+    fn new() -> Self {
+        Self::with_slots(TABLE_SIZE)
+    }
+
+    /// A table starting out with `slots` entries, a power of two. It doubles
+    /// whenever it becomes half full.
+    fn with_slots(slots: usize) -> Self {
+        let slots = if map::ENABLED { 1 } else { slots };
+        Self {
+            entries: empty_entries(slots),
+            names: Vec::new(),
+            map: map::Map::default(),
+            mask: slots - 1,
+            len: 0,
+            key: None,
+            decimals: 1,
+            batch: BATCH_SIZE,
+            interleave: false,
+            skip_comments: false,
+            known: None,
+            perfect: None,
+            progress: None,
+            budget: None,
+            limit: None,
+            fault: Arc::default(),
+            sanity: None,
+            histogram: None,
+            origin: origin::Origin::default(),
+            ends: None,
+            first_seen: None,
+            checkpoint: None,
+            schedule: None,
+            prefetch: None,
+            activity: None,
+            emit: None,
+        }
+    }
+
+    /// Iterates over the occupied entries in table order.
+    pub fn iter(&self) -> impl Iterator<Item = (&[u8], &A)> {
+        (0..self.entries.len())
+            .filter_map(|idx| self.slot(idx))
+            .map(|entry| (entry.name(&self.names), &entry.stats))
+            .chain(self.map.iter().map(|(name, stats)| (&name[..], stats)))
+    }
+
+    /// Copies the table into a sorted, owned [`Results`].
+    pub fn results(&self) -> Results<A>
+    where
+        A: Clone,
+    {
+        Results::from_table(self)
+    }
+
+    /// An empty table with the same settings, for a worker thread.
+    fn sibling(&self) -> Self {
+        self.sibling_with_slots(self.entries.len())
+    }
+
+    /// An empty table with the same settings and `slots` slots, holding only
+    /// the `known` keys.
+    fn sibling_with_slots(&self, slots: usize) -> Self {
+        let mut table = Self::with_slots(slots);
+        table.key = self.key.clone();
+        table.decimals = self.decimals;
+        table.batch = self.batch;
+        table.interleave = self.interleave;
+        table.skip_comments = self.skip_comments;
+        table.progress = self.progress.clone();
+        table.budget = self.budget.clone();
+        table.limit = self.limit.clone();
+        table.fault = self.fault.clone();
+        table.prefetch = self.prefetch.clone();
+        if self.activity.is_some() {
+            table.activity = Some(footprint::Activity::default());
+        }
+        table.sanity = self.sanity.as_ref().map(sanity::Sanity::sibling);
+        table.histogram = self.histogram.as_ref().map(histogram::Histogram::sibling);
+        table.origin = self.origin;
+        if self.ends.is_some() {
+            table.ends = Some(ends::Ends::default());
+        }
+        if self.first_seen.is_some() {
+            table.first_seen = Some(seen::FirstSeen::default());
+        }
+        table.known = self.known.clone();
+        table.perfect = self.perfect.clone();
+        table.insert_known();
+        table
+    }
+
+    /// Inserts every key in `known` with empty statistics.
+    fn insert_known(&mut self) {
+        if let Some(known) = self.known.clone() {
+            for key in known.iter() {
+                self.entry_mut(key);
+            }
+        }
+    }
+
+    /// Merges the stations of a worker's table into this one.
+    fn absorb(&mut self, other: &StationTable<A>) {
+        for (name, stats) in other.iter() {
+            self.entry_mut(name).merge(stats);
+        }
+        if let (Some(sanity), Some(other)) = (&mut self.sanity, &other.sanity) {
+            sanity.merge(other);
+        }
+        if let (Some(histogram), Some(other)) = (&mut self.histogram, &other.histogram) {
+            histogram.merge(other);
+        }
+        if let (Some(ends), Some(other)) = (&mut self.ends, &other.ends) {
+            ends.merge(other);
+        }
+        if let (Some(seen), Some(other)) = (&mut self.first_seen, &other.first_seen) {
+            seen.merge(other);
+        }
+        if let (Some(activity), Some(worker)) = (&mut self.activity, &other.activity) {
+            activity.absorb(worker.worker(other.len, other.probe_lengths()));
+        }
+    }
+
+    /// The mean and longest probe to each station's slot, `None` without
+    /// the built-in table.
+    fn probe_lengths(&self) -> Option<(f64, usize)> {
+        if map::ENABLED || self.len == 0 {
+            return None;
+        }
+        let (mut total, mut longest) = (0, 0);
+        for idx in 0..self.entries.len() {
+            let Some(entry) = self.slot(idx) else {
+                continue;
+            };
+            let home = self.hash_for(entry.name(&self.names)) & self.mask;
+            let probes = (idx.wrapping_sub(home) & self.mask) + 1;
+            total += probes;
+            longest = longest.max(probes);
+        }
+        Some((total as f64 / self.len as f64, longest))
+    }
+
+    /// Returns the accumulator for `name`, inserting an empty one if needed.
+    fn entry_mut(&mut self, name: &[u8]) -> &mut A {
+        if map::ENABLED {
+            if !self.map.contains_key(name) {
+                self.map.insert(name.into(), A::default());
+                self.len += 1;
+            }
+            return self.map.get_mut(name).unwrap();
+        }
+        let prefix = name_prefix(name);
+        let mut idx = self.hash_for(name) & self.mask;
+        loop {
+            let Some(entry) = self.slot(idx) else {
+                if self.len * 2 > self.mask {
+                    self.grow();
+                    return self.entry_mut(name);
+                }
+                return &mut self.insert_at(idx, name).stats;
+            };
+            if entry.is(&self.names, name, prefix) {
+                // SAFETY: the slot is occupied
+                return unsafe { &mut self.entries[idx].assume_init_mut().stats };
+            }
+            idx = (idx + 1) & self.mask;
+        }
+    }
+
+    /// Stores a new station with empty statistics in the free slot `idx`.
+    #[inline(always)]
+    fn insert_at(&mut self, idx: usize, name: &[u8]) -> &mut Entry<A> {
+        assert!(name.len() <= MAX_NAME_LEN, "station name too long");
+        let name_start = u32::try_from(self.names.len()).expect("too many station names");
+        self.names.extend_from_slice(name);
+        self.len += 1;
+        self.entries[idx].write(Entry {
+            prefix: name_prefix(name),
+            name_start,
+            name_len: name.len() as u8,
+            stats: A::default(),
+        })
+    }
+
+    /// Doubles the number of slots and moves every entry over. Names stay
+    /// where they are in the arena.
+    #[cold]
+    fn grow(&mut self) {
+        let slots = self.entries.len() * 2;
+        let old = std::mem::replace(&mut self.entries, empty_entries(slots));
+        self.mask = slots - 1;
+        self.perfect = None;
+        for slot in old.iter().filter(|slot| is_occupied(slot)) {
+            // SAFETY: the slot is occupied, and the old slots are freed
+            // without dropping their contents
+            let entry = unsafe { slot.assume_init_read() };
+            let mut idx = Self::hash(entry.name(&self.names)) & self.mask;
+            while is_occupied(&self.entries[idx]) {
+                idx = (idx + 1) & self.mask;
+            }
+            self.entries[idx].write(entry);
+        }
+    }
+
+    /// The hash of `name` in this process. It reads the first 8 bytes in
+    /// host order, so it differs between little- and big-endian hosts and
+    /// must not leave memory; [`partition_hash`] is the portable one.
+    #[inline(always)]
+    fn hash(name: &[u8]) -> usize {
+        // First 8 bytes in one load, then a fast finalizer
+        mix(name_prefix(name), name.len()) as usize
+    }
+
+    /// Where `name` starts probing: its perfect slot with `--stations`, its
+    /// [`hash`](Self::hash) otherwise.
+    #[inline(always)]
+    fn hash_for(&self, name: &[u8]) -> usize {
+        match &self.perfect {
+            Some(perfect) => perfect.slot(name),
+            None => Self::hash(name),
+        }
+    }
+
+    #[inline(always)]
+    fn lookup_or_insert(&mut self, name: &[u8], temp: i32) {
+        self.lookup_or_insert_hashed(name, self.hash_for(name), temp);
+    }
+
+    /// Looks up a batch of names hashed by [`hash_for`](Self::hash_for)
+    /// before any of them was looked up. Growing drops the perfect hash, so
+    /// the names after the one that grew the table are hashed again.
+    #[inline(always)]
+    fn lookup_or_insert_batch(&mut self, batch: &[(&[u8], usize, i32)]) {
+        if let Some(sanity) = &mut self.sanity {
+            for &(name, _, temp) in batch {
+                sanity.check(name, temp);
+            }
+        }
+        if let Some(histogram) = &mut self.histogram {
+            for &(name, _, temp) in batch {
+                // Readings --unknown-stations skips are not aggregated
+                let skipped = self.perfect.as_ref().is_some_and(|perfect| {
+                    perfect.unknown == Unknown::Skip && !perfect.contains(name)
+                });
+                if !skipped {
+                    histogram.count(temp);
+                }
+            }
+        }
+        let perfect = self.perfect.is_some();
+        for &(name, hash, temp) in batch {
+            if perfect && self.perfect.is_none() {
+                self.lookup_or_insert(name, temp);
+            } else {
+                self.lookup_or_insert_hashed(name, hash, temp);
+            }
+        }
+    }
+
+    /// Hints the CPU to start loading the slot a name with `hash` (from
+    /// [`hash_for`](Self::hash_for)) probes first, ahead of the lookup.
+    #[inline(always)]
+    fn prefetch(&self, hash: usize) {
+        if map::ENABLED {
+            return;
+        }
+        // SAFETY: the mask keeps the index below the slot count
+        let slot = unsafe { at(&self.entries, hash & self.mask) };
+        scan::prefetch(slot.as_ptr().cast());
+    }
+
+    #[inline(always)]
+    fn lookup_or_insert_hashed(&mut self, name: &[u8], hash: usize, temp: i32) {
+        if map::ENABLED {
+            let inserted = map::update(&mut self.map, name, temp);
+            self.len += inserted as usize;
+            if let Some(seen) = self.first_seen.as_mut().filter(|_| inserted) {
+                seen.insert(name);
+            }
+            return;
+        }
+        let prefix = name_prefix(name);
+        let mut idx = hash & self.mask;
+
+        loop {
+            // SAFETY: the mask keeps `idx` below the slot count
+            if !is_occupied(unsafe { at(&self.entries, idx) }) {
+                if let Some(perfect) = &self.perfect {
+                    if perfect.unknown != Unknown::Insert && !perfect.contains(name) {
+                        unknown_station(name, perfect.unknown, &self.fault);
+                        return;
+                    }
+                }
+                if self.len * 2 > self.mask {
+                    self.grow();
+                    self.lookup_or_insert(name, temp);
+                    return;
+                }
+                // Empty slot — insert new entry
+                self.insert_at(idx, name).stats.update(temp);
+                if let Some(seen) = &mut self.first_seen {
+                    seen.insert(name);
+                }
+                return;
+            }
+            // SAFETY: as above, and the slot is occupied
+            let entry = unsafe { at_mut(&mut self.entries, idx).assume_init_mut() };
+
+            if entry.is(&self.names, name, prefix) {
+                // Found existing entry
+                entry.stats.update(temp);
+                return;
+            }
+
+            // Collision — linear probe
+            idx = (idx + 1) & self.mask;
+        }
+    }
+}
+
+/// Handles a station missing from `--stations` under a policy other than
+/// inserting it, raising `fault` under `--unknown-stations error`.
+#[cold]
+fn unknown_station(name: &[u8], policy: Unknown, fault: &fault::Fault) {
+    if policy == Unknown::Error {
+        fault.raise(Failure::new(
+            Exit::Data,
+            format!(
+                "station '{}' is not in the --stations list",
+                String::from_utf8_lossy(name)
+            ),
+        ));
+    }
+}
+
+/// Maps all of `file`, which is not empty.
+fn mmap_file(file: &File) -> Result<window::Mapping, Failure> {
+    let len = file
+        .metadata()
+        .map_err(|e| Failure::io(format!("reading input: {}", e)))?
+        .len();
+    window::map_whole(file, len)?
+        .ok_or_else(|| Failure::io("input is too large to map in this address space"))
+}
+
+/// Flags of the input mapping. FreeBSD can map in the pages of the file
+/// already cached up front, which saves a fault per page on a warm run.
+#[cfg(target_os = "freebsd")]
+const MAP_FLAGS: libc::c_int = libc::MAP_PRIVATE | libc::MAP_PREFAULT_READ;
+#[cfg(not(target_os = "freebsd"))]
+const MAP_FLAGS: libc::c_int = libc::MAP_PRIVATE;
+
+/// Tells the kernel the mapping at `ptr` is read front to back, so it reads
+/// ahead further. The advice is only a hint: a kernel that rejects it still
+/// serves the mapping.
+#[cfg(any(
+    target_os = "linux",
+    target_os = "android",
+    target_os = "macos",
+    target_os = "freebsd",
+    target_os = "dragonfly",
+    target_os = "openbsd",
+    target_os = "netbsd",
+    target_os = "illumos",
+    target_os = "solaris"
+))]
+fn advise_sequential(ptr: *mut libc::c_void, len: usize) {
+    // SAFETY: `ptr` and `len` are exactly a live mapping
+    unsafe { libc::madvise(ptr, len, libc::MADV_SEQUENTIAL) };
+}
+
+#[cfg(not(any(
+    target_os = "linux",
+    target_os = "android",
+    target_os = "macos",
+    target_os = "freebsd",
+    target_os = "dragonfly",
+    target_os = "openbsd",
+    target_os = "netbsd",
+    target_os = "illumos",
+    target_os = "solaris"
+)))]
+fn advise_sequential(_ptr: *mut libc::c_void, _len: usize) {}
+
+/// Aggregates a buffer of complete lines. The final line may lack its newline.
+/// Once the run has failed, the lines are left out.
+fn process_lines<A: Aggregator>(data: &[u8], table: &mut StationTable<A>) {
+    if table.fault.raised() {
+        return;
+    }
+    table.origin.start(data);
+    if let Some(prefetch) = &table.prefetch {
+        prefetch.reached(data);
+    }
+    let data = match &table.limit {
+        Some(limit) => limit.admit(data),
+        None => data,
+    };
+    if let Some(budget) = &table.budget {
+        if !budget.admit(data.len()) {
+            return;
+        }
+    }
+    emit::in_segments(data, table, aggregate_timed);
+}
+
+/// [`aggregate_lines`], timed for `--tui` and `--run-stats`.
+fn aggregate_timed<A: Aggregator>(data: &[u8], table: &mut StationTable<A>) {
+    if table.progress.is_none() && table.activity.is_none() {
+        aggregate_lines(data, table);
+        return;
+    }
+    let start = Instant::now();
+    aggregate_lines(data, table);
+    let busy = start.elapsed();
+    if let Some(activity) = &mut table.activity {
+        activity.record(data, busy);
+    }
+    if let Some(progress) = table.progress.clone() {
+        progress.record(data.len(), busy, table);
+    }
+}
+
+fn aggregate_lines<A: Aggregator>(data: &[u8], table: &mut StationTable<A>) {
+    if table.skip_comments {
+        without_comments(data, |lines| aggregate_records_of(lines, table));
+        return;
+    }
+    aggregate_records_of(data, table);
+}
+
+/// Notes the readings of `data` for `--first-last`, in a pass of its own to
+/// keep the loop of [`aggregate_records`] as it is without the option.
+fn track_ends<A: Aggregator>(data: &[u8], table: &mut StationTable<A>) {
+    let Some(mut ends) = table.ends.take() else {
+        return;
+    };
+    let (key, decimals, origin) = (table.key.clone(), table.decimals, table.origin);
+    let fault = &table.fault;
+    let scanned = scan::for_each_record(data, &origin, |name, temp| {
+        let temp = match decimals {
+            1 => parse_temp(temp),
+            _ => parse_temp_scaled(temp, decimals),
+        };
+        let Some(temp) = temp else {
+            fault.raise(origin.malformed(name.as_ptr(), BAD_TEMP));
+            return;
+        };
+        let key = key.as_ref().map_or(name, |key| key(name));
+        ends.record(key, origin.offset(name.as_ptr()), temp);
+    });
+    if let Err(failure) = scanned {
+        fault.raise(failure);
+    }
+    table.ends = Some(ends);
+}
+
+/// Calls `f` with each run of lines of `data` between blank lines and lines
+/// starting with `#`, which the scanners cannot take.
+fn without_comments(data: &[u8], mut f: impl FnMut(&[u8])) {
+    let (mut run, mut start) = (0, 0);
+    while start < data.len() {
+        let end = memchr::memchr(b'\n', &data[start..]).map_or(data.len(), |i| start + i + 1);
+        if matches!(data[start], b'\n' | b'\r' | b'#') {
+            if run < start {
+                f(&data[run..start]);
+            }
+            run = end;
+        }
+        start = end;
+    }
+    if run < data.len() {
+        f(&data[run..]);
+    }
+}
+
+/// [`aggregate_lines`] for data of only `name;temp` lines.
+fn aggregate_records_of<A: Aggregator>(data: &[u8], table: &mut StationTable<A>) {
+    if table.ends.is_some() {
+        track_ends(data, table);
+    }
+    // Interleaving meets the second half's stations too early for --order input
+    if table.interleave && table.first_seen.is_none() {
+        aggregate_interleaved(data, table);
+        return;
+    }
+    let decimals = table.decimals;
+    match (table.key.clone(), decimals) {
+        (None, 1) => aggregate_records(data, table, |name| name, parse_temp),
+        (None, _) => aggregate_records(data, table, |name| name, |temp| {
+            parse_temp_scaled(temp, decimals)
+        }),
+        (Some(key), 1) => aggregate_records(data, table, |name| key(name), parse_temp),
+        (Some(key), _) => aggregate_records(data, table, |name| key(name), |temp| {
+            parse_temp_scaled(temp, decimals)
+        }),
+    }
+}
+
+/// Why a line whose temperature [`parse_temp`] cannot read is malformed.
+const BAD_TEMP: &str = "temperature is not a number like -12.3";
+
+/// Lines [`aggregate_records`] parses before updating the table, by default.
+const BATCH_SIZE: usize = 16;
+/// Upper bound for `--batch`, the size of the batch buffer on the stack.
+const MAX_BATCH: usize = 64;
+
+/// The loop behind [`aggregate_lines`]. Lines are scanned and parsed a batch
+/// at a time, prefetching the slot each one hashes to, and only then looked up,
+/// so that cache misses on the table overlap with each other and with parsing.
+#[inline(always)]
+fn aggregate_records<'a, A, K, P>(data: &'a [u8], table: &mut StationTable<A>, key: K, parse: P)
+where
+    A: Aggregator,
+    K: Fn(&'a [u8]) -> &'a [u8],
+    P: Fn(&[u8]) -> Option<i32>,
+{
+    let batch = table.batch;
+    let origin = table.origin;
+    let mut pending: [(&[u8], usize, i32); MAX_BATCH] = [(&[], 0, 0); MAX_BATCH];
+    let mut len = 0;
+    let scanned = scan::for_each_record(data, &origin, |name, temp| {
+        let Some(temp) = parse(temp) else {
+            table.fault.raise(origin.malformed(name.as_ptr(), BAD_TEMP));
+            return;
+        };
+        let name = key(name);
+        let hash = table.hash_for(name);
+        table.prefetch(hash);
+        pending[len] = (name, hash, temp);
+        len += 1;
+        if len == batch {
+            table.lookup_or_insert_batch(&pending[..len]);
+            len = 0;
+        }
+    });
+    table.lookup_or_insert_batch(&pending[..len]);
+    if let Err(failure) = scanned {
+        table.fault.raise(failure);
+    }
+}
+
+/// Aggregates `data` as two halves split at a line boundary, taking one line
+/// from each in turn, for `--interleave`. The two lookups of a step do not
+/// depend on each other, so a cache miss on one overlaps with the other.
+fn aggregate_interleaved<A: Aggregator>(data: &[u8], table: &mut StationTable<A>) {
+    let half = data.len() / 2;
+    let mid = memchr::memchr(b'\n', &data[half..]).map_or(data.len(), |i| half + i + 1);
+    let (first, second) = data.split_at(mid);
+    let mut first = Records::with_decimals(first, table.decimals).within(table.origin);
+    let mut second = Records::with_decimals(second, table.decimals).within(table.origin);
+    let key = table.key.clone();
+    let key = |name| key.as_ref().map_or(name, |key| key(name));
+
+    let (mut a, mut b) = (first.next(), second.next());
+    while let (Some((name_a, temp_a)), Some((name_b, temp_b))) = (a, b) {
+        let (name_a, name_b) = (key(name_a), key(name_b));
+        let (hash_a, hash_b) = (table.hash_for(name_a), table.hash_for(name_b));
+        table.prefetch(hash_a);
+        table.prefetch(hash_b);
+        table.lookup_or_insert_batch(&[(name_a, hash_a, temp_a), (name_b, hash_b, temp_b)]);
+        (a, b) = (first.next(), second.next());
+    }
+    // At most one half has lines left
+    for (name, temp) in a.into_iter().chain(&mut first).chain(b).chain(&mut second) {
+        let name = key(name);
+        table.lookup_or_insert_batch(&[(name, table.hash_for(name), temp)]);
+    }
+    if let Some(failure) = first.failure().or_else(|| second.failure()) {
+        table.fault.raise(failure);
+    }
+}
+
+/// Feeds arbitrarily split chunks of input to the table, carrying an incomplete
+/// trailing line over to the next chunk.
+struct LineFeeder {
+    carry: Vec<u8>,
+}
+
+impl LineFeeder {
+    fn new() -> Self {
+        Self { carry: Vec::new() }
+    }
+
+    fn feed<A: Aggregator>(&mut self, chunk: &[u8], table: &mut StationTable<A>) {
+        let Some(last_nl) = memchr::memrchr(b'\n', chunk) else {
+            self.carry.extend_from_slice(chunk);
+            return;
+        };
+
+        let mut start = 0;
+        if !self.carry.is_empty() {
+            let first_nl = memchr::memchr(b'\n', chunk).unwrap();
+            self.carry.extend_from_slice(&chunk[..=first_nl]);
+            process_lines(&self.carry, table);
+            self.carry.clear();
+            start = first_nl + 1;
+        }
+        process_lines(&chunk[start..=last_nl], table);
+        self.carry.extend_from_slice(&chunk[last_nl + 1..]);
+    }
+
+    fn finish<A: Aggregator>(self, table: &mut StationTable<A>) {
+        process_lines(&self.carry, table);
+    }
+}
+
+const READ_BUF_SIZE: usize = 4 << 20;
+/// Chunks `--engine pipeline` queues for its parsers, per parser.
+const PIPELINE_DEPTH: usize = 2;
+
+/// Buffers cycled through `--engine pipeline` with `workers` parsers: those
+/// queued, one being parsed by each parser and one being read into.
+fn pipeline_buffers(workers: usize) -> usize {
+    workers * (PIPELINE_DEPTH + 1) + 1
+}
+
+/// Buffers cycled between the reading and the parsing thread.
+const READ_BUFFERS: usize = 2;
+
+/// Aggregates a byte stream, for inputs that cannot be mapped directly. The
+/// calling thread only reads (and decompresses or transcodes), filling one
+/// buffer while a parser thread aggregates the other, so that I/O overlaps
+/// with parsing instead of alternating with it.
+fn aggregate_reader<A: Aggregator, R: Read>(
+    mut reader: R,
+    table: &mut StationTable<A>,
+) -> Result<(), Failure> {
+    aggregate_batches(READ_BUFFERS, 1, table, |bufs| {
+        Ok(vec![reader.read(&mut bufs[0])?])
+    })
+}
+
+/// Aggregates what `fill` reads, cycling `buffers` buffers between the
+/// calling thread and a parser thread. `fill` is handed up to `batch` free
+/// buffers at a time and returns the bytes it read into each, in order; a
+/// batch it reads nothing into ends the input.
+fn aggregate_batches<A: Aggregator>(
+    buffers: usize,
+    batch: usize,
+    table: &mut StationTable<A>,
+    mut fill: impl FnMut(&mut [Vec<u8>]) -> io::Result<Vec<usize>>,
+) -> Result<(), Failure> {
+    let (full_tx, full_rx) = mpsc::sync_channel::<(Vec<u8>, usize)>(buffers - 1);
+    let (empty_tx, empty_rx) = mpsc::channel();
+    for _ in 0..buffers {
+        empty_tx.send(vec![0u8; READ_BUF_SIZE]).unwrap();
+    }
+    let budget = table.budget.clone();
+    let limit = table.limit.clone();
+    let fault = table.fault.clone();
+    let mut checkpoint = table.checkpoint.take();
+    thread::scope(|scope| {
+        // Owned here, so that a failed read closes it and the parser stops
+        let full_tx = full_tx;
+        let parser = scope.spawn(move || {
+            let mut feeder = LineFeeder::new();
+            let mut offset = checkpoint.as_ref().map_or(0, |c| c.start());
+            for (buf, n) in full_rx {
+                feeder.feed(&buf[..n], table);
+                offset += n as u64;
+                if let Some(checkpoint) = &mut checkpoint {
+                    // The incomplete line carried over is read again on resume
+                    checkpoint.reached(offset - feeder.carry.len() as u64, table);
+                }
+                // The reader may have stopped already
+                let _ = empty_tx.send(buf);
+            }
+            feeder.finish(table);
+        });
+        // Free buffers a short read left empty, kept for the next batch
+        let mut free = Vec::with_capacity(batch);
+        let mut read = Ok(());
+        'read: loop {
+            if free.is_empty() {
+                match empty_rx.recv() {
+                    Ok(buf) => free.push(buf),
+                    Err(_) => break,
+                }
+            }
+            // Nothing more will be aggregated, so stop reading too
+            if budget.as_ref().is_some_and(|budget| budget.expired())
+                || limit.as_ref().is_some_and(|limit| limit.reached())
+                || fault.raised()
+            {
+                break;
+            }
+            while free.len() < batch {
+                match empty_rx.try_recv() {
+                    Ok(buf) => free.push(buf),
+                    Err(_) => break,
+                }
+            }
+            let lens = match fill(&mut free) {
+                Ok(lens) => lens,
+                Err(e) => {
+                    read = Err(Failure::io(format!("reading input: {}", e)));
+                    break;
+                }
+            };
+            if lens.iter().all(|&n| n == 0) {
+                break;
+            }
+            let lens = lens.into_iter().chain(std::iter::repeat(0));
+            for (buf, n) in std::mem::take(&mut free).into_iter().zip(lens) {
+                if n == 0 {
+                    free.push(buf);
+                } else if full_tx.send((buf, n)).is_err() {
+                    break 'read;
+                }
+            }
+        }
+        drop(full_tx);
+        parallel::join(parser);
+        read
+    })
+}
+
+/// Picks the input encoding from the BOM at the start of `head` and the
+/// `--input-encoding` override. Returns it with the number of BOM bytes to skip;
+/// a BOM announcing another encoding than the one requested is a usage error,
+/// as the text after it would be decoded wrongly either way.
+fn resolve_encoding(head: &[u8], opts: &Options) -> Result<(Encoding, usize), Failure> {
+    match (decode::sniff_bom(head), opts.encoding) {
+        (Some((bom, _)), Some(encoding)) if bom != encoding => Err(Failure::usage(format!(
+            "input starts with a {} byte order mark but --input-encoding is {}",
+            bom.name(),
+            encoding.name()
+        ))),
+        (Some((bom, len)), _) => Ok((bom, len)),
+        (None, encoding) => Ok((encoding.unwrap_or(Encoding::Utf8), 0)),
+    }
+}
+
+/// Aggregates a text stream, transcoding it to UTF-8 first if needed.
+fn aggregate_text<A: Aggregator, R: Read>(
+    reader: R,
+    encoding: Encoding,
+    table: &mut StationTable<A>,
+) -> Result<(), Failure> {
+    match encoding {
+        Encoding::Utf8 => aggregate_reader(reader, table),
+        Encoding::Utf16Le => aggregate_reader(Utf16Reader::new(reader, false), table),
+        Encoding::Utf16Be => aggregate_reader(Utf16Reader::new(reader, true), table),
+        #[cfg(feature = "encoding")]
+        Encoding::Other(enc) => {
+            aggregate_reader(decode::TranscodingReader::new(reader, enc), table)
+        }
+    }
+}
+
+/// Aggregates a decompressed stream, which may be a tar archive or text with a BOM.
+fn aggregate_stream<A: Aggregator, R: Read>(
+    mut reader: R,
+    opts: &Options,
+    table: &mut StationTable<A>,
+) -> Result<(), Failure> {
+    let mut head = Vec::new();
+    (&mut reader)
+        .take(archive::TAR_SNIFF_LEN as u64)
+        .read_to_end(&mut head)
+        .map_err(|e| Failure::io(format!("reading input: {}", e)))?;
+    if archive::is_tar(&head) {
+        return aggregate_tar((&head[..]).chain(reader), opts, table);
+    }
+    let (encoding, bom_len) = resolve_encoding(&head, opts)?;
+    aggregate_text((&head[bom_len..]).chain(reader), encoding, table)
+}
+
+/// Aggregates an archive member, raising what fails it as the run's failure
+/// so that the members after it are left out.
+#[cfg(feature = "archive")]
+fn aggregate_member<A: Aggregator>(member: &mut dyn Read, opts: &Options, table: &mut StationTable<A>) {
+    if table.fault.raised() {
+        return;
+    }
+    if let Err(failure) = aggregate_stream(member, opts, table) {
+        table.fault.raise(failure);
+    }
+}
+
+#[cfg(feature = "archive")]
+fn aggregate_tar<A: Aggregator, R: Read>(
+    reader: R,
+    opts: &Options,
+    table: &mut StationTable<A>,
+) -> Result<(), Failure> {
+    let visited = archive::for_each_tar_member(reader, &opts.members, |member| {
+        aggregate_member(member, opts, table)
+    })
+    .map_err(|e| Failure::io(format!("reading tar archive: {}", e)))?;
+    if visited == 0 {
+        return Err(Failure::usage(format!("no archive members match '{}'", opts.members)));
+    }
+    Ok(())
+}
+
+#[cfg(feature = "archive")]
+fn aggregate_zip<A: Aggregator>(
+    data: &[u8],
+    opts: &Options,
+    table: &mut StationTable<A>,
+) -> Result<(), Failure> {
+    let visited = archive::for_each_zip_member(data, &opts.members, |member| {
+        aggregate_member(member, opts, table)
+    })
+    .map_err(|e| Failure::io(format!("reading zip archive: {}", e)))?;
+    if visited == 0 {
+        return Err(Failure::usage(format!("no archive members match '{}'", opts.members)));
+    }
+    Ok(())
+}
+
+#[cfg(not(feature = "archive"))]
+fn aggregate_tar<A: Aggregator, R: Read>(
+    _reader: R,
+    _opts: &Options,
+    _table: &mut StationTable<A>,
+) -> Result<(), Failure> {
+    Err(Failure::usage("input is a tar archive but the `archive` feature was not compiled in"))
+}
+
+#[cfg(not(feature = "archive"))]
+fn aggregate_zip<A: Aggregator>(
+    _data: &[u8],
+    _opts: &Options,
+    _table: &mut StationTable<A>,
+) -> Result<(), Failure> {
+    Err(Failure::usage("input is a zip archive but the `archive` feature was not compiled in"))
+}
+
+/// Aggregates a raw input stream that may still be compressed.
+fn aggregate_raw_stream<A: Aggregator, R: Read>(
+    mut reader: R,
+    opts: &Options,
+    table: &mut StationTable<A>,
+) -> Result<(), Failure> {
+    if table.checkpoint.is_some() {
+        return aggregate_checkpointed(reader, opts, table);
+    }
+    let mut head = Vec::new();
+    (&mut reader)
+        .take(8)
+        .read_to_end(&mut head)
+        .map_err(|e| Failure::io(format!("reading input: {}", e)))?;
+    if archive::is_zip(&head) {
+        return Err(Failure::usage(
+            "zip archives need random access and can only be read from local files",
+        ));
+    }
+    let reader = (&head[..]).chain(reader);
+    match compress::sniff(&head) {
+        Some(format) => {
+            let reader = compress::decoder(format, reader).map_err(Failure::io)?;
+            aggregate_stream(reader, opts, table)
+        }
+        None => aggregate_stream(reader, opts, table),
+    }
+}
+
+/// Aggregates a stream of uncompressed UTF-8 text under `--checkpoint`, with
+/// offsets counted from its start. A resumed stream starts on a line, so only
+/// a new one is checked for its format and byte order mark.
+fn aggregate_checkpointed<A: Aggregator, R: Read>(
+    mut reader: R,
+    opts: &Options,
+    table: &mut StationTable<A>,
+) -> Result<(), Failure> {
+    let Some(checkpoint) = table.checkpoint.as_mut() else {
+        return Ok(());
+    };
+    let mut head = Vec::new();
+    (&mut reader)
+        .take(archive::TAR_SNIFF_LEN as u64)
+        .read_to_end(&mut head)
+        .map_err(|e| Failure::io(format!("reading input: {}", e)))?;
+    let mut bom_len = 0;
+    if checkpoint.start() == 0 {
+        if archive::is_zip(&head) || compress::sniff(&head).is_some() || archive::is_tar(&head) {
+            return Err(Failure::usage("--checkpoint and --resume need uncompressed text input"));
+        }
+        let encoding;
+        (encoding, bom_len) = resolve_encoding(&head, opts)?;
+        if encoding != Encoding::Utf8 {
+            return Err(Failure::usage("--checkpoint and --resume need UTF-8 input"));
+        }
+        checkpoint.skip(bom_len as u64);
+    }
+    aggregate_reader((&head[bom_len..]).chain(reader), table)
+}
+
+fn is_url(path: &str) -> bool {
+    path.starts_with("http://") || path.starts_with("https://")
+}
+
+#[cfg(feature = "http")]
+fn aggregate_url<A: Aggregator>(opts: &Options, table: &mut StationTable<A>) -> Result<(), Failure> {
+    let start = table.checkpoint.as_ref().map_or(0, |c| c.start());
+    let reader = http::open(&opts.file_path, opts.connections, start)
+        .map_err(|e| Failure::io(format!("{}: {}", opts.file_path, e)))?;
+    aggregate_raw_stream(reader, opts, table)
+}
+
+#[cfg(not(feature = "http"))]
+fn aggregate_url<A: Aggregator>(_opts: &Options, _table: &mut StationTable<A>) -> Result<(), Failure> {
+    Err(Failure::usage("URL inputs require building with the `http` feature"))
+}
+
+#[cfg(feature = "s3")]
+fn aggregate_object<A: Aggregator>(opts: &Options, table: &mut StationTable<A>) -> Result<(), Failure> {
+    let start = table.checkpoint.as_ref().map_or(0, |c| c.start());
+    let reader = objstore::open(&opts.file_path, opts.connections, start)
+        .map_err(|e| Failure::io(format!("{}: {}", opts.file_path, e)))?;
+    aggregate_raw_stream(reader, opts, table)
+}
+
+#[cfg(not(feature = "s3"))]
+fn aggregate_object<A: Aggregator>(_opts: &Options, _table: &mut StationTable<A>) -> Result<(), Failure> {
+    Err(Failure::usage("s3:// inputs require building with the `s3` feature"))
+}
+
+/// Aggregates an in-memory buffer of `station;temp` lines.
+pub fn aggregate_bytes(data: &[u8]) -> Results {
+    aggregate_bytes_with(data)
+}
+
+/// Like [`aggregate_bytes`], accumulating a custom [`Aggregator`] per station.
+pub fn aggregate_bytes_with<A: Aggregator + Clone>(data: &[u8]) -> Results<A> {
+    let mut table = StationTable::new();
+    process_lines(data, &mut table);
+    table.results()
+}
+
+/// Aggregates several buffers as if they were one concatenated input, so a
+/// line may be split across consecutive slices.
+pub fn aggregate_slices<'a, I>(slices: I) -> Results
+where
+    I: IntoIterator<Item = &'a [u8]>,
+{
+    aggregate_slices_with(slices)
+}
+
+/// Like [`aggregate_slices`], accumulating a custom [`Aggregator`] per station.
+pub fn aggregate_slices_with<'a, A, I>(slices: I) -> Results<A>
+where
+    A: Aggregator + Clone,
+    I: IntoIterator<Item = &'a [u8]>,
+{
+    let mut table = StationTable::new();
+    let mut feeder = LineFeeder::new();
+    for slice in slices {
+        feeder.feed(slice, &mut table);
+    }
+    feeder.finish(&mut table);
+    table.results()
+}
+
+/// The key `opts` aggregates station names under: `--group-by`, of the name
+/// inside its quotes with `--quoted-names`.
+fn key_fn(opts: &Options) -> Option<KeyFn> {
+    let key = group_key(&opts.group_by);
+    if !opts.quoted_names {
+        return key;
+    }
+    Some(Arc::new(move |name| {
+        let name = unquote(name);
+        key.as_ref().map_or(name, |key| key(name))
+    }))
+}
+
+/// A `"`-quoted name without its quotes. Quotes inside it stay doubled
+/// until the name is printed.
+fn unquote(name: &[u8]) -> &[u8] {
+    match name {
+        [b'"', inner @ .., b'"'] if !inner.is_empty() => inner,
+        _ => name,
+    }
+}
+
+fn group_key(group_by: &GroupBy) -> Option<KeyFn> {
+    match group_by {
+        GroupBy::Station => None,
+        GroupBy::Prefix(sep) => {
+            let finder = memchr::memmem::Finder::new(sep.as_bytes()).into_owned();
+            // A name starting with the separator keeps its full name, as the
+            // table cannot hold an empty key
+            Some(Arc::new(move |name| match finder.find(name) {
+                Some(i) if i > 0 => &name[..i],
+                _ => name,
+            }))
+        }
+        GroupBy::FirstWord => Some(Arc::new(|name| {
+            match name.iter().position(|&b| b == b' ') {
+                Some(i) if i > 0 => &name[..i],
+                _ => name,
+            }
+        })),
+        &GroupBy::Columns(first, last) => Some(Arc::new(move |name| {
+            let mut start = 0;
+            for _ in 0..first {
+                match memchr::memchr(b';', &name[start..]) {
+                    Some(i) => start += i + 1,
+                    // Too few columns for the key groups by the whole name
+                    None => return name,
+                }
+            }
+            let mut end = start;
+            for column in first..=last {
+                match memchr::memchr(b';', &name[end..]) {
+                    Some(i) if column < last => end += i + 1,
+                    Some(i) => end += i,
+                    None => {
+                        end = name.len();
+                        break;
+                    }
+                }
+            }
+            match &name[start..end] {
+                b"" => name,
+                key => key,
+            }
+        })),
+        GroupBy::Custom(key) => Some(key.clone()),
+    }
+}
+
+/// Aggregates the input named by `opts.file_path`: a local file (possibly
+/// compressed or an archive), an http(s) URL or an `s3://` object. Fails with
+/// what the command line exits on, such as an unreadable file or a malformed
+/// line.
+pub fn read_measurements(opts: &Options) -> Result<StationTable, Failure> {
+    read_measurements_with(opts)
+}
+
+/// Like [`read_measurements`], accumulating a custom [`Aggregator`] per station.
+pub fn read_measurements_with<A: Aggregator>(opts: &Options) -> Result<StationTable<A>, Failure> {
+    read_measurements_observed(opts, None)
+}
+
+/// Initial slots of the table for `opts`: `--table-size`, or fewer than
+/// usual for small inputs.
+fn table_slots(opts: &Options) -> usize {
+    match (opts.table_size, local_input_size(opts)) {
+        (Some(slots), _) => slots,
+        (None, Some(len)) if is_small_input(len) => SMALL_TABLE_SIZE,
+        (None, _) => TABLE_SIZE,
+    }
+}
+
+/// Like [`read_measurements_with`], publishing its advance to `progress`,
+/// which is marked finished on return.
+pub fn read_measurements_observed<A: Aggregator>(
+    opts: &Options,
+    progress: Option<Arc<Progress<A>>>,
+) -> Result<StationTable<A>, Failure> {
+    read_measurements_emitting(opts, progress, None)
+}
+
+/// [`read_measurements_observed`], with `--emit-every` snapshots written by
+/// `emit`.
+fn read_measurements_emitting<A: Aggregator>(
+    opts: &Options,
+    progress: Option<Arc<Progress<A>>>,
+    emit: Option<emit::Emitter<A>>,
+) -> Result<StationTable<A>, Failure> {
+    let mut table = StationTable::with_slots(table_slots(opts));
+    table.key = key_fn(opts);
+    table.decimals = opts.decimals;
+    table.batch = opts.batch;
+    table.interleave = opts.interleave;
+    table.skip_comments = opts.skip_comments;
+    table.progress = progress.clone();
+    table.budget = opts.max_seconds.map(|seconds| Arc::new(Budget::new(seconds)));
+    table.limit = opts.limit.map(|rows| Arc::new(RowLimit::new(rows)));
+    let scale = stats::scale(opts.decimals);
+    table.first_seen = seen::for_order(opts);
+    table.ends = opts.first_last.then(ends::Ends::default);
+    table.sanity = opts.sanity_check.then(|| {
+        let (lo, hi) = opts.plausible;
+        sanity::Sanity::new((lo * scale).round() as i32, (hi * scale).round() as i32)
+    });
+    table.histogram = opts.histogram.map(|(lo, hi)| {
+        histogram::Histogram::new((lo * scale).round() as i32, (hi * scale).round() as i32)
+    });
+    if let Some(path) = &opts.stations {
+        let keys = perfect::load(path, table.key.as_ref()).map_err(Failure::io)?;
+        perfect::seed(&mut table, keys, opts.unknown_stations);
+    }
+    table.checkpoint = checkpoint::Checkpoints::setup(opts, &mut table)?;
+    table.schedule = schedule::Schedule::setup(opts)?;
+    table.emit = emit;
+    if opts.run_stats {
+        table.activity = Some(footprint::Activity::default());
+    }
+    #[cfg(target_os = "linux")]
+    let counters = opts.perf.then(perf::Counters::start).and_then(|started| {
+        started
+            .map_err(|e| eprintln!("warning: {}", e))
+            .ok()
+    });
+    let aggregated = if opts.command == Command::Merge {
+        partial::merge_dumps(opts, &mut table)
+    } else {
+        aggregate_input(opts, &mut table)
+    };
+    // A failure met by a worker stopped the rest, and fails the run
+    if let Err(failure) = aggregated.and_then(|()| table.fault.take().map_or(Ok(()), Err)) {
+        if let Some(progress) = progress {
+            progress.finish();
+        }
+        return Err(failure);
+    }
+    #[cfg(target_os = "linux")]
+    if let Some(counters) = counters {
+        let _ = counters.stop().write(&mut io::stderr().lock());
+    }
+    if let Some(budget) = table.budget.as_ref().filter(|budget| budget.expired()) {
+        eprintln!("{}", budget.summary());
+    }
+    if let Some(sanity) = &table.sanity {
+        let color = opts.color == Color::Always;
+        let _ = sanity.write_report(scale, color, &mut io::stderr().lock());
+    }
+    if opts.run_stats {
+        let _ = footprint::write_report(&table, &mut io::stderr().lock());
+        if let Some(activity) = &table.activity {
+            let own = activity.worker(table.len, table.probe_lengths());
+            let _ = activity.write_report(own, &mut io::stderr().lock());
+        }
+    }
+    if let Some(progress) = progress {
+        progress.finish();
+    }
+    Ok(table)
+}
+
+/// Default chunk size for inputs aggregated straight from memory: the unit
+/// of work handed to threads and of progress reports.
+const MAPPED_CHUNK_SIZE: usize = 16 << 20;
+
+/// Splits `data` into pieces of about `size` bytes that end on line boundaries.
+fn line_chunks(data: &[u8], size: usize) -> impl Iterator<Item = &[u8]> {
+    let mut rest = data;
+    std::iter::from_fn(move || {
+        if rest.is_empty() {
+            return None;
+        }
+        let end = match rest.get(size..) {
+            Some(tail) => memchr::memchr(b'\n', tail).map_or(rest.len(), |i| size + i + 1),
+            None => rest.len(),
+        };
+        let (chunk, tail) = rest.split_at(end);
+        rest = tail;
+        Some(chunk)
+    })
+}
+
+#[cfg(feature = "gpu")]
+fn aggregate_gpu<A: Aggregator>(data: &[u8], table: &mut StationTable<A>) {
+    gpu::aggregate(data, table);
+}
+
+#[cfg(not(feature = "gpu"))]
+fn aggregate_gpu<A: Aggregator>(_data: &[u8], table: &mut StationTable<A>) {
+    table.fault.raise(Failure::usage("--gpu requires building with the `gpu` feature"));
+}
+
+#[cfg(feature = "tpc")]
+fn aggregate_tpc<A: Aggregator>(
+    file: &File,
+    data: &[u8],
+    offset: u64,
+    opts: &Options,
+    table: &mut StationTable<A>,
+) {
+    tpc::aggregate(file, data, offset, opts, table);
+}
+
+#[cfg(not(feature = "tpc"))]
+fn aggregate_tpc<A: Aggregator>(
+    _file: &File,
+    _data: &[u8],
+    _offset: u64,
+    _opts: &Options,
+    table: &mut StationTable<A>,
+) {
+    table.fault.raise(Failure::usage("--engine tpc requires building with the `tpc` feature"));
+}
+
+#[cfg(feature = "rayon")]
+fn aggregate_rayon<A: Aggregator>(data: &[u8], opts: &Options, table: &mut StationTable<A>) {
+    pariter::aggregate(data, opts, table);
+}
+
+#[cfg(not(feature = "rayon"))]
+fn aggregate_rayon<A: Aggregator>(_data: &[u8], _opts: &Options, table: &mut StationTable<A>) {
+    table.fault.raise(Failure::usage("--engine rayon requires building with the `rayon` feature"));
+}
+
+#[cfg(feature = "pipeline")]
+fn aggregate_pipeline<A: Aggregator>(
+    file: &File,
+    data: &[u8],
+    offset: u64,
+    opts: &Options,
+    table: &mut StationTable<A>,
+) {
+    pipeline::aggregate(file, data, offset, opts, table);
+}
+
+#[cfg(not(feature = "pipeline"))]
+fn aggregate_pipeline<A: Aggregator>(
+    _file: &File,
+    _data: &[u8],
+    _offset: u64,
+    _opts: &Options,
+    table: &mut StationTable<A>,
+) {
+    table.fault.raise(Failure::usage(
+        "--engine pipeline requires building with the `pipeline` feature",
+    ));
+}
+
+/// Size of the input when it is a local file, as far as `--length` reads it.
+pub(crate) fn local_input_size(opts: &Options) -> Option<u64> {
+    if is_url(&opts.file_path) || opts.file_path.starts_with("s3://") {
+        return None;
+    }
+    let meta = std::fs::metadata(&opts.file_path).ok()?;
+    // The size of a block device is not in its metadata
+    Some(match opts.length {
+        Some(length) if meta.file_type().is_block_device() => length,
+        Some(length) => length.min(meta.len()),
+        None => meta.len(),
+    })
+}
+
+/// Bytes of the local input `file` to read: its size, or the `--length` of
+/// it. A raw block device needs `--length`, since nothing marks where the
+/// measurements on it end.
+fn input_len(file: &File, opts: &Options) -> Result<u64, Failure> {
+    let open_error = |e: io::Error| Failure::io(format!("{}: {}", opts.file_path, e));
+    let meta = file.metadata().map_err(open_error)?;
+    let size = if meta.file_type().is_block_device() {
+        if opts.length.is_none() {
+            return Err(Failure::usage(format!(
+                "{}: a block device needs --length",
+                opts.file_path
+            )));
+        }
+        let mut device = file;
+        let size = device.seek(SeekFrom::End(0)).map_err(open_error)?;
+        device.rewind().map_err(open_error)?;
+        size
+    } else {
+        meta.len()
+    };
+    match opts.length {
+        Some(length) if length > size => Err(Failure::usage(format!(
+            "--length {} is past the end of {}, {} bytes",
+            length, opts.file_path, size
+        ))),
+        Some(length) => Ok(length),
+        None => Ok(size),
+    }
+}
+
+/// Fails for byte ranges and checkpoints of stdin or a pipe, which can be
+/// read only once and from the start.
+fn reject_offsets_in_pipe<A: Aggregator>(
+    opts: &Options,
+    table: &StationTable<A>,
+) -> Result<(), Failure> {
+    if opts.start_byte > 0 || opts.end_byte.is_some() || table.checkpoint.is_some() {
+        return Err(Failure::usage(
+            "--start-byte, --end-byte, --checkpoint and --resume need a file, not stdin or a pipe",
+        ));
+    }
+    Ok(())
+}
+
+/// Aggregates the input into `table`. Failures met by the workers are
+/// raised into the table's fault rather than returned.
+fn aggregate_input<A: Aggregator>(opts: &Options, table: &mut StationTable<A>) -> Result<(), Failure> {
+    let ranged = opts.start_byte > 0 || opts.end_byte.is_some();
+    if ranged && (is_url(&opts.file_path) || opts.file_path.starts_with("s3://")) {
+        return Err(Failure::usage("--start-byte and --end-byte need a local file"));
+    }
+    if is_url(&opts.file_path) {
+        return aggregate_url(opts, table);
+    }
+    if opts.file_path.starts_with("s3://") {
+        return aggregate_object(opts, table);
+    }
+
+    let open_error = |e: io::Error| Failure::io(format!("{}: {}", opts.file_path, e));
+    if opts.file_path == "-" {
+        reject_offsets_in_pipe(opts, table)?;
+        return aggregate_raw_stream(io::stdin().lock(), opts, table);
+    }
+    let file = File::open(&opts.file_path).map_err(open_error)?;
+    // A pipe has no size to map or split the input by, so it is read as it
+    // comes, as stdin is
+    let file_type = file.metadata().map_err(open_error)?.file_type();
+    if !file_type.is_file() && !file_type.is_block_device() {
+        reject_offsets_in_pipe(opts, table)?;
+        return aggregate_raw_stream(&file, opts, table);
+    }
+    let len = input_len(&file, opts)?;
+    advice::advise(&file, len, opts);
+    // Reads stop at --length, as the mapping does
+    let mut reader = advice::Readahead::new((&file).take(len), &file, 0, len, opts);
+    // Byte ranges and checkpoints need the mapping
+    let checkpointed = table.checkpoint.is_some();
+    if opts.io == Io::Read && !is_small_input(len) && !ranged && !checkpointed {
+        let mut head = Vec::new();
+        (&mut reader)
+            .take(archive::TAR_SNIFF_LEN as u64)
+            .read_to_end(&mut head)
+            .map_err(|e| Failure::io(format!("reading input: {}", e)))?;
+        // Zip archives need random access, so they are mapped regardless
+        if !archive::is_zip(&head) {
+            let reader = (&head[..]).chain(reader);
+            return match compress::sniff(&head) {
+                Some(format) => {
+                    let reader = compress::decoder(format, reader).map_err(Failure::io)?;
+                    aggregate_stream(reader, opts, table)
+                }
+                None => {
+                    if let Some(budget) = &table.budget {
+                        budget.set_total(len);
+                    }
+                    match resolve_encoding(&head, opts)? {
+                        // Plain text is read from the file at offsets instead
+                        (Encoding::Utf8, bom_len) if !archive::is_tar(&head) => {
+                            pread::aggregate(&file, bom_len as u64, len, opts, table)
+                        }
+                        _ => aggregate_stream(reader, opts, table),
+                    }
+                }
+            };
+        }
+    }
+    let mut buf = Vec::new();
+    let mut mapping = None;
+    let data = if is_small_input(len) {
+        reader
+            .read_to_end(&mut buf)
+            .map_err(|e| Failure::io(format!("reading input: {}", e)))?;
+        &buf[..]
+    } else {
+        match window::map_whole(&file, len)? {
+            Some(mapped) => {
+                let data = mapping.insert(mapped).bytes();
+                table.prefetch = advice::Prefetch::mapped(&file, data, 0, opts).map(Arc::new);
+                data
+            }
+            None => return window::aggregate(&file, len, opts, table),
+        }
+    };
+    let offsets = match (ranged, checkpointed) {
+        (true, _) => Some("--start-byte and --end-byte"),
+        (false, true) => Some("--checkpoint and --resume"),
+        (false, false) => None,
+    };
+    let packed = archive::is_zip(data) || compress::sniff(data).is_some() || archive::is_tar(data);
+    if let Some(flags) = offsets.filter(|_| packed) {
+        return Err(Failure::usage(format!("{} need an uncompressed text file", flags)));
+    }
+    if archive::is_zip(data) {
+        aggregate_zip(data, opts, table)?;
+    } else if let Some(format) = compress::sniff(data) {
+        let reader = compress::decoder(format, data).map_err(Failure::io)?;
+        aggregate_stream(reader, opts, table)?;
+    } else if archive::is_tar(data) {
+        aggregate_tar(data, opts, table)?;
+    } else {
+        let (encoding, bom_len) = resolve_encoding(data, opts)?;
+        if let Some(flags) = offsets.filter(|_| encoding != Encoding::Utf8) {
+            return Err(Failure::usage(format!("{} need UTF-8 input", flags)));
+        }
+        let resume = table.checkpoint.as_ref().map_or(0, |c| c.start());
+        let range = byte_range(&data[bom_len..], bom_len as u64, opts.start_byte.max(resume), opts);
+        let base = (range.as_ptr() as usize - data.as_ptr() as usize) as u64;
+        let (whole, data) = (data, range);
+        match encoding {
+            // Uncompressed UTF-8 is aggregated straight from the mapping
+            Encoding::Utf8 => {
+                table.origin.place(whole, 0);
+                // Workers take chunks out of order, so --limit cuts the
+                // mapping instead of counting rows as they are aggregated
+                let data = opts.limit.map_or(data, |rows| budget::first_rows(data, rows));
+                table.limit = None;
+                if let Some(progress) = &table.progress {
+                    progress.set_total(data.len() as u64);
+                }
+                if let Some(budget) = &table.budget {
+                    budget.set_total(data.len() as u64);
+                }
+                let aggregate = |data: &[u8], table: &mut StationTable<A>| {
+                    if opts.gpu {
+                        aggregate_gpu(data, table);
+                    } else if opts.engine == Engine::Tpc {
+                        // `whole` starts at the start of the file, mapped or read
+                        let offset = (data.as_ptr() as usize - whole.as_ptr() as usize) as u64;
+                        aggregate_tpc(&file, data, offset, opts, table);
+                    } else if opts.engine == Engine::Rayon {
+                        aggregate_rayon(data, opts, table);
+                    } else if opts.engine == Engine::Pipeline {
+                        let offset = (data.as_ptr() as usize - whole.as_ptr() as usize) as u64;
+                        aggregate_pipeline(&file, data, offset, opts, table);
+                    } else {
+                        parallel::aggregate_mapped(data, opts, table);
+                    }
+                };
+                match table.checkpoint.take() {
+                    None => emit::in_segments(data, table, aggregate),
+                    Some(mut checkpoint) => {
+                        // A checkpoint's worth at a time, each of them in parallel
+                        let mut offset = base;
+                        for segment in line_chunks(data, checkpoint.every() as usize) {
+                            aggregate(segment, table);
+                            if table.fault.raised() {
+                                break;
+                            }
+                            offset += segment.len() as u64;
+                            checkpoint.reached(offset, table);
+                        }
+                    }
+                }
+            }
+            _ => aggregate_text(data, encoding, table)?,
+        }
+    }
+    // The mapping goes with the input, unless aggregating it is all the
+    // process does
+    table.prefetch = None;
+    if let Some(mapping) = mapping.filter(|_| opts.keep_mapped) {
+        mapping.leak();
+    }
+    Ok(())
+}
+
+/// The lines of `data`, which starts at file offset `base`, that start in
+/// `start`..`--end-byte`. Each offset is moved past the end of the line it
+/// falls into, so adjacent ranges share no line and miss none.
+fn byte_range<'a>(data: &'a [u8], base: u64, start: u64, opts: &Options) -> &'a [u8] {
+    let snap = |offset: u64| {
+        let offset = (offset.saturating_sub(base) as usize).min(data.len());
+        match offset {
+            0 => 0,
+            _ => memchr::memchr(b'\n', &data[offset - 1..]).map_or(data.len(), |i| offset + i),
+        }
+    };
+    let end = opts.end_byte.map_or(data.len(), snap);
+    let start = snap(start).min(end);
+    &data[start..end]
+}
+
+type NameCmp<'a> = Box<dyn Fn(&[u8], &[u8]) -> Ordering + 'a>;
+
+/// Returns a comparator over station names implementing the requested order.
+/// `--order input` uses where `seen` says the stations first appeared.
+fn name_comparator<'a>(
+    opts: &Options,
+    seen: Option<&'a seen::FirstSeen>,
+) -> Result<NameCmp<'a>, Failure> {
+    Ok(match (&opts.order, seen) {
+        (Order::Collate, _) => collator(&opts.locale)?,
+        (Order::Input, Some(seen)) => Box::new(|a, b| seen.cmp(a, b)),
+        (Order::Bytes | Order::Input, _) => Box::new(|a, b| a.cmp(b)),
+    })
+}
+
+#[cfg(feature = "collation")]
+fn collator(locale: &str) -> Result<NameCmp<'static>, Failure> {
+    use icu_collator::{options::CollatorOptions, Collator};
+    use icu_locale_core::Locale;
+
+    let locale: Locale = locale
+        .parse()
+        .map_err(|_| Failure::usage(format!("invalid locale '{}'", locale)))?;
+    let collator = Collator::try_new((&locale).into(), CollatorOptions::default())
+        .map_err(|e| Failure::usage(format!("no collation data: {}", e)))?;
+    Ok(Box::new(move |a, b| collator.compare_utf8(a, b)))
+}
+
+#[cfg(not(feature = "collation"))]
+fn collator(_locale: &str) -> Result<NameCmp<'static>, Failure> {
+    Err(Failure::usage("--order collate requires building with the `collation` feature"))
+}
+
+/// A key for printing, with `--key-separator` between the columns of a key
+/// from a wide row, or the doubled quotes of a `--quoted-names` name undone.
+fn key_name<'a>(name: &'a [u8], opts: &Options) -> Cow<'a, [u8]> {
+    if opts.quoted_names && memchr::memmem::find(name, b"\"\"").is_some() {
+        let mut key = Vec::with_capacity(name.len());
+        let mut rest = name;
+        while let Some(i) = memchr::memmem::find(rest, b"\"\"") {
+            key.extend_from_slice(&rest[..=i]);
+            rest = &rest[i + 2..];
+        }
+        key.extend_from_slice(rest);
+        return Cow::Owned(key);
+    }
+    match &opts.key_separator {
+        Some(sep) if name.contains(&b';') => {
+            let mut key = Vec::with_capacity(name.len());
+            for (i, column) in name.split(|&b| b == b';').enumerate() {
+                if i > 0 {
+                    key.extend_from_slice(sep.as_bytes());
+                }
+                key.extend_from_slice(column);
+            }
+            Cow::Owned(key)
+        }
+        _ => Cow::Borrowed(name),
+    }
+}
+
+/// Converts a station name for printing. Valid UTF-8 is borrowed as-is; anything
+/// else is escaped or replaced according to `mode`.
+fn display_name(name: &[u8], mode: InvalidUtf8) -> Cow<'_, str> {
+    if let Ok(s) = std::str::from_utf8(name) {
+        return Cow::Borrowed(s);
+    }
+    match mode {
+        InvalidUtf8::Lossy => String::from_utf8_lossy(name),
+        InvalidUtf8::Escape => {
+            let mut out = String::with_capacity(name.len() * 2);
+            for chunk in name.utf8_chunks() {
+                out.push_str(chunk.valid());
+                for b in chunk.invalid() {
+                    out.push_str(&format!("\\x{:02X}", b));
+                }
+            }
+            Cow::Owned(out)
+        }
+    }
+}
+
+/// Runs `run` under the `--profile` sampler, if one was asked for, writing
+/// the profile once it returns.
+#[cfg(feature = "profiling")]
+pub fn profiled<T>(opts: &Options, run: impl FnOnce() -> T) -> T {
+    match &opts.profile {
+        Some(path) => profile::record(path, run),
+        None => run(),
+    }
+}
+
+#[cfg(not(feature = "profiling"))]
+pub fn profiled<T>(opts: &Options, run: impl FnOnce() -> T) -> T {
+    if opts.profile.is_some() {
+        cli::usage_error("--profile requires building with the `profiling` feature");
+    }
+    run()
+}
+
+/// Aggregates the input with the cheapest accumulator covering `--stats` and
+/// writes the results. Fails with what the command line exits on.
+pub fn aggregate_and_report(opts: &Options, out: &mut dyn Write) -> Result<(), Failure> {
+    if opts.explain {
+        eprint!("{}", plan::explain(opts));
+    }
+    if opts.histogram_only {
+        // Every reading lands in one entry, keeping the table out of the way
+        let opts = Options {
+            group_by: GroupBy::Custom(Arc::new(|_| b"*")),
+            ..opts.clone()
+        };
+        return report(&measure::<Counter>(&opts)?, &opts, out).map_err(Failure::writing);
+    }
+    if opts.baseline {
+        let stations = baseline::aggregate(opts)
+            .map_err(|e| Failure::io(format!("{}: {}", opts.file_path, e)))?;
+        let rows = stations.iter().map(|(name, stats)| (&name[..], stats));
+        return output_rows(rows, None, opts, out).map_err(Failure::writing);
+    }
+    if opts.verify {
+        let table = measure::<StationStats>(opts)?;
+        baseline::verify(&table, opts)?;
+        return report(&table, opts, out).map_err(Failure::writing);
+    }
+    match accumulator_kind(opts) {
+        Kind::Count => report(&measure::<Counter>(opts)?, opts, out),
+        Kind::Basic => report(&measure::<StationStats>(opts)?, opts, out),
+        Kind::Moments => report(&measure::<Moments>(opts)?, opts, out),
+        Kind::Distribution => report(&measure::<Distribution>(opts)?, opts, out),
+        Kind::Extremes => report(&measure::<Extremes>(opts)?, opts, out),
+    }
+    .map_err(Failure::writing)
+}
+
+/// The accumulator a run needs for its `--stats` and reports.
+fn accumulator_kind(opts: &Options) -> Kind {
+    if opts.flag_outliers.is_some() {
+        // Outliers are measured in standard deviations
+        let stats = [&opts.stats[..], &[Stat::Stddev]].concat();
+        return Kind::for_stats(&stats);
+    }
+    Kind::for_stats(&opts.stats)
+}
+
+/// Aggregates the input, showing the `--tui` dashboard while it runs.
+fn measure<A: Summary + Send + 'static>(opts: &Options) -> Result<StationTable<A>, Failure> {
+    let emit = emit::Emitter::setup(opts, report::<A>)?;
+    if !opts.tui {
+        return read_measurements_emitting(opts, None, emit);
+    }
+    let progress = Arc::new(Progress::default());
+    let dashboard = show_dashboard(progress.clone(), opts)?;
+    let table = read_measurements_emitting(opts, Some(progress), emit);
+    if let Err(e) = dashboard.join().unwrap() {
+        eprintln!("warning: dashboard failed: {}", e);
+    }
+    table
+}
+
+#[cfg(feature = "tui")]
+fn show_dashboard<A: Summary + Send + 'static>(
+    progress: Arc<Progress<A>>,
+    opts: &Options,
+) -> Result<std::thread::JoinHandle<io::Result<()>>, Failure> {
+    let opts = opts.clone();
+    Ok(std::thread::spawn(move || tui::run(progress, &opts)))
+}
+
+#[cfg(not(feature = "tui"))]
+fn show_dashboard<A: Summary + Send + 'static>(
+    _progress: Arc<Progress<A>>,
+    _opts: &Options,
+) -> Result<std::thread::JoinHandle<io::Result<()>>, Failure> {
+    Err(Failure::usage("--tui requires building with the `tui` feature"))
+}
+
+fn report<A: Summary>(
+    table: &StationTable<A>,
+    opts: &Options,
+    out: &mut dyn Write,
+) -> io::Result<()> {
+    if opts.format == Format::Partial {
+        return partial::write_dump(table, opts, out);
+    }
+    if let Some(histogram) = table.histogram.as_ref().filter(|_| opts.histogram_only) {
+        return histogram.write(opts.decimals, out);
+    }
+    output_results(table, opts, out)?;
+    if let Some(k) = opts.flag_outliers {
+        writeln!(out)?;
+        write_outliers(table, k, opts, out)?;
+    }
+    if let Some(ends) = &table.ends {
+        writeln!(out)?;
+        write_first_last(table, ends, opts, out)?;
+    }
+    if let Some(histogram) = &table.histogram {
+        writeln!(out)?;
+        histogram.write(opts.decimals, out)?;
+    }
+    Ok(())
+}
+
+/// Lists the first and last reading of every station and the change between
+/// them, for `--first-last`.
+fn write_first_last<A: Summary>(
+    table: &StationTable<A>,
+    ends: &ends::Ends,
+    opts: &Options,
+    out: &mut dyn Write,
+) -> io::Result<()> {
+    let mut stations: Vec<&[u8]> = table.iter().map(|(name, _)| name).collect();
+    let cmp = name_comparator(opts, table.first_seen.as_ref())?;
+    stations.sort_by(|a, b| cmp(a, b));
+
+    let (scale, decimals) = (stats::scale(opts.decimals), opts.decimals as usize);
+    writeln!(out, "first and last readings:")?;
+    for name in stations {
+        let Some(((first, from), (last, to))) = ends.get(name, scale) else {
+            continue;
+        };
+        writeln!(
+            out,
+            "{}: first {:.*} at byte {}, last {:.*} at byte {}, change {:+.*}",
+            display_name(&key_name(name, opts), opts.invalid_utf8),
+            decimals,
+            first,
+            from,
+            decimals,
+            last,
+            to,
+            decimals,
+            last - first
+        )?;
+    }
+    Ok(())
+}
+
+#[cfg(feature = "sqlite")]
+fn write_sqlite<A: Summary>(
+    rows: &[format::Row<A>],
+    opts: &Options,
+    geo: Option<&geo::GeoTable>,
+) -> io::Result<()> {
+    let path = opts.output.as_deref().expect("--format sqlite requires --output");
+    sqlite::write(rows, opts, geo, path)
+}
+
+#[cfg(not(feature = "sqlite"))]
+fn write_sqlite<A: Summary>(
+    _rows: &[format::Row<A>],
+    _opts: &Options,
+    _geo: Option<&geo::GeoTable>,
+) -> io::Result<()> {
+    Err(Failure::usage("--format sqlite requires building with the `sqlite` feature").into())
+}
+
+#[cfg(feature = "parquet")]
+fn write_parquet<A: Summary>(
+    rows: &[format::Row<A>],
+    opts: &Options,
+    geo: Option<&geo::GeoTable>,
+) -> io::Result<()> {
+    let path = opts.output.as_deref().expect("--format parquet requires --output");
+    columnar::write(rows, opts, geo, path)
+}
+
+#[cfg(not(feature = "parquet"))]
+fn write_parquet<A: Summary>(
+    _rows: &[format::Row<A>],
+    _opts: &Options,
+    _geo: Option<&geo::GeoTable>,
+) -> io::Result<()> {
+    Err(Failure::usage("--format parquet requires building with the `parquet` feature").into())
+}
+
+/// Lists stations whose min or max is more than `k` standard deviations from
+/// their mean, one line per offending extreme.
+fn write_outliers<A: Summary>(
+    table: &StationTable<A>,
+    k: f64,
+    opts: &Options,
+    out: &mut dyn Write,
+) -> io::Result<()> {
+    let mut stations: Vec<(&[u8], &A)> = table.iter().collect();
+    let cmp = name_comparator(opts, table.first_seen.as_ref())?;
+    stations.sort_by(|a, b| cmp(a.0, b.0));
+
+    writeln!(out, "outliers beyond {} standard deviations:", k)?;
+    for (name, stats) in stations {
+        let (mean, sd) = (stats.value(Stat::Mean), stats.value(Stat::Stddev));
+        if sd == 0.0 {
+            continue;
+        }
+        for stat in [Stat::Min, Stat::Max] {
+            let deviations = (stats.value(stat) - mean) / sd;
+            if deviations.abs() > k {
+                if opts.color == Color::Always {
+                    write!(out, "{}", format::WARNING)?;
+                }
+                let name = key_name(name, opts);
+                write!(out, "{}: {} ", display_name(&name, opts.invalid_utf8), stat.name())?;
+                format::write_stat(out, stat, stats.value(stat), opts)?;
+                write!(out, " is {:.1} sd from mean ", deviations)?;
+                format::write_stat(out, Stat::Mean, mean, opts)?;
+                if opts.color == Color::Always {
+                    write!(out, "{}", format::RESET)?;
+                }
+                writeln!(out)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Writes the results in the format chosen by `--format`, sorted by station
+/// name in the `--order` order.
+pub fn output_results<A: Summary>(
+    table: &StationTable<A>,
+    opts: &Options,
+    out: &mut dyn Write,
+) -> io::Result<()> {
+    output_rows(table.iter(), table.first_seen.as_ref(), opts, out)
+}
+
+/// [`output_results`] for stations from anywhere, e.g. `--baseline`.
+fn output_rows<'a, A: Summary + 'a>(
+    rows: impl Iterator<Item = format::Row<'a, A>>,
+    seen: Option<&seen::FirstSeen>,
+    opts: &Options,
+    out: &mut dyn Write,
+) -> io::Result<()> {
+    let mut results: Vec<format::Row<A>> = rows.collect();
+
+    // Sort by station name, byte order unless another order was requested
+    let cmp = name_comparator(opts, seen)?;
+    results.sort_by(|a, b| cmp(a.0, b.0));
+    let names: Vec<Cow<[u8]>> = results.iter().map(|&(name, _)| key_name(name, opts)).collect();
+    let results: Vec<format::Row<A>> = names
+        .iter()
+        .zip(&results)
+        .map(|(name, &(_, stats))| (&name[..], stats))
+        .collect();
+
+    let geo = opts
+        .geo
+        .as_deref()
+        .map(|path| geo::GeoTable::load(path).map_err(Failure::io))
+        .transpose()?;
+    match opts.format {
+        Format::Spec => format::write_spec(&results, opts, out),
+        Format::Print0 => format::write_print0(&results, opts, out),
+        Format::Csv => format::write_csv(&results, opts, geo.as_ref(), out),
+        Format::Json => format::write_json(&results, opts, geo.as_ref(), out),
+        Format::Markdown => format::write_markdown(&results, opts, geo.as_ref(), out),
+        Format::Table => format::write_table(&results, opts, geo.as_ref(), out),
+        Format::Sql => format::write_sql(&results, opts, geo.as_ref(), out),
+        Format::Sqlite => write_sqlite(&results, opts, geo.as_ref()),
+        Format::Parquet => write_parquet(&results, opts, geo.as_ref()),
+        Format::GeoJson => match &geo {
+            Some(geo) => format::write_geojson(&results, opts, geo, out),
+            None => Err(Failure::usage("--format geojson requires --geo FILE").into()),
+        },
+        Format::Partial => unreachable!("partial dumps are written by report"),
+    }
+}
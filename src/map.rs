@@ -0,0 +1,34 @@
+use crate::Aggregator;
+
+/// Stations of a table built with the `hashbrown` or `std_map` feature, which
+/// swap its open-addressing slots for a general-purpose map so the two can be
+/// benchmarked against each other on the same pipeline. Otherwise the map is
+/// never touched (an empty one does not allocate).
+#[cfg(feature = "hashbrown")]
+pub type Map<A> = hashbrown::HashMap<Box<[u8]>, A>;
+#[cfg(not(feature = "hashbrown"))]
+pub type Map<A> = std::collections::HashMap<Box<[u8]>, A>;
+
+/// Whether stations go into a [`Map`] rather than the table's own slots.
+pub const ENABLED: bool = cfg!(any(feature = "hashbrown", feature = "std_map"));
+
+/// The map in use, for `--explain`.
+pub const NAME: &str = if cfg!(feature = "hashbrown") {
+    "hashbrown::HashMap"
+} else {
+    "std::collections::HashMap"
+};
+
+/// Records `temp` for `name`, returning whether it is a new station. Only a
+/// new station allocates its key.
+#[inline(always)]
+pub fn update<A: Aggregator>(map: &mut Map<A>, name: &[u8], temp: i32) -> bool {
+    if let Some(stats) = map.get_mut(name) {
+        stats.update(temp);
+        return false;
+    }
+    let mut stats = A::default();
+    stats.update(temp);
+    map.insert(name.into(), stats);
+    true
+}
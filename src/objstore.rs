@@ -0,0 +1,82 @@
+use std::io::{self, Read};
+use std::sync::mpsc::{self, Receiver};
+use std::thread;
+
+use bytes::Bytes;
+use futures::stream::{self, StreamExt};
+use object_store::aws::AmazonS3Builder;
+use object_store::path::Path;
+use object_store::ObjectStoreExt;
+
+/// Size of each ranged GET issued against the object store.
+const RANGE_SIZE: u64 = 32 << 20;
+
+/// Opens an `s3://bucket/key` object as a byte stream. Credentials and region
+/// come from the usual `AWS_*` environment variables. The object is fetched as
+/// `connections` concurrent ranged GETs from offset `start` on, delivered to
+/// the reader in order.
+pub fn open(url: &str, connections: usize, start: u64) -> Result<Box<dyn Read + Send>, String> {
+    let store = AmazonS3Builder::from_env()
+        .with_url(url)
+        .build()
+        .map_err(|e| e.to_string())?;
+    let key = url
+        .strip_prefix("s3://")
+        .and_then(|rest| rest.split_once('/'))
+        .map(|(_, key)| key)
+        .ok_or_else(|| "expected s3://bucket/key".to_string())?;
+    let path = Path::from(key);
+
+    let runtime = tokio::runtime::Builder::new_multi_thread()
+        .enable_all()
+        .build()
+        .map_err(|e| e.to_string())?;
+    let size = runtime
+        .block_on(store.head(&path))
+        .map_err(|e| e.to_string())?
+        .size;
+
+    // Bounded so that downloads cannot run arbitrarily far ahead of the parser
+    let (tx, rx) = mpsc::sync_channel(connections);
+    thread::spawn(move || {
+        runtime.block_on(async {
+            let ranges = (start.min(size)..size)
+                .step_by(RANGE_SIZE as usize)
+                .map(|start| start..(start + RANGE_SIZE).min(size));
+            let mut chunks = stream::iter(ranges)
+                .map(|range| store.get_range(&path, range))
+                .buffered(connections);
+            while let Some(chunk) = chunks.next().await {
+                let failed = chunk.is_err();
+                if tx.send(chunk.map_err(io::Error::other)).is_err() || failed {
+                    break;
+                }
+            }
+        })
+    });
+
+    Ok(Box::new(ChunkReader {
+        rx,
+        current: Bytes::new(),
+    }))
+}
+
+/// Presents a channel of in-order chunks as a `Read` stream.
+struct ChunkReader {
+    rx: Receiver<io::Result<Bytes>>,
+    current: Bytes,
+}
+
+impl Read for ChunkReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        while self.current.is_empty() {
+            match self.rx.recv() {
+                Ok(chunk) => self.current = chunk?,
+                Err(_) => return Ok(0),
+            }
+        }
+        let n = buf.len().min(self.current.len());
+        buf[..n].copy_from_slice(&self.current.split_to(n));
+        Ok(n)
+    }
+}
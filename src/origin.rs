@@ -0,0 +1,64 @@
+use crate::cli::{Exit, Failure};
+
+/// Where the lines at hand sit in the input, so that a line can be named by
+/// its byte offset. Offsets are taken from the address of a line: in a
+/// mapping or a buffer read at a known offset, from its start; in streamed
+/// input, read in order through one table, from the start of each buffer and
+/// the bytes before it.
+#[derive(Debug, Clone, Copy)]
+pub struct Origin {
+    /// Address of the input's first byte, as seen from the lines at hand.
+    base: usize,
+    /// Bytes of streamed input before the current buffer; `None` for a
+    /// mapping.
+    streamed: Option<u64>,
+}
+
+impl Origin {
+    /// Input read in order from byte `offset` on, until [`place`](Self::place).
+    pub fn streamed_from(offset: u64) -> Self {
+        Self {
+            base: 0,
+            streamed: Some(offset),
+        }
+    }
+
+    /// Notes that lines come from `data`, the bytes of the input from
+    /// `offset` on.
+    pub fn place(&mut self, data: &[u8], offset: u64) {
+        self.base = (data.as_ptr() as usize).wrapping_sub(offset as usize);
+        self.streamed = None;
+    }
+
+    /// Notes that the lines of streamed input that follow are from `data`,
+    /// the buffer after the last.
+    pub fn start(&mut self, data: &[u8]) {
+        if let Some(before) = &mut self.streamed {
+            self.base = (data.as_ptr() as usize).wrapping_sub(*before as usize);
+            *before += data.len() as u64;
+        }
+    }
+
+    /// The offset in the input of the byte at `ptr`.
+    #[inline]
+    pub fn offset(&self, ptr: *const u8) -> u64 {
+        (ptr as usize).wrapping_sub(self.base) as u64
+    }
+
+    /// The failure of the run on the line starting at `line`, malformed as
+    /// `why` says.
+    #[cold]
+    #[inline(never)]
+    pub fn malformed(&self, line: *const u8, why: &str) -> Failure {
+        Failure::new(
+            Exit::Data,
+            format!("line at byte {}: {}", self.offset(line), why),
+        )
+    }
+}
+
+impl Default for Origin {
+    fn default() -> Self {
+        Self::streamed_from(0)
+    }
+}
@@ -0,0 +1,345 @@
+use std::ops::Range;
+use std::panic;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::thread;
+use std::time::Instant;
+
+use crate::cli::Options;
+use crate::schedule::{self, Run, Schedule};
+use crate::{dictionary, is_small_input, line_chunks, process_lines, Aggregator, StationTable};
+
+/// Thread count and chunk size for one part of a mapped input.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Setup {
+    pub threads: usize,
+    pub chunk_size: usize,
+}
+
+/// Upper bound on the input spent calibrating with `--auto-tune`.
+const CALIBRATION_BUDGET: usize = 256 << 20;
+
+/// Chunk sizes tried by `--auto-tune`.
+const CALIBRATION_CHUNKS: [usize; 3] = [4 << 20, 16 << 20, 64 << 20];
+
+/// Bytes a worker aggregates of its chunk at a time, between which an idle
+/// worker may split off the rest.
+const STEP: usize = 1 << 20;
+
+/// The least a worker must have left of its chunk for an idle one to split
+/// it.
+const MIN_SPLIT: usize = 4 * STEP;
+
+/// Resolves `--threads` (0 meaning one per available CPU).
+pub fn threads(opts: &Options) -> usize {
+    match opts.threads {
+        0 => thread::available_parallelism().map_or(1, |n| n.get()),
+        n => n,
+    }
+}
+
+/// Waits for a worker, passing on what it panicked or failed with rather
+/// than a panic of its own.
+pub(crate) fn join<T>(handle: thread::ScopedJoinHandle<'_, T>) -> T {
+    handle.join().unwrap_or_else(|e| panic::resume_unwind(e))
+}
+
+/// Whether `len` bytes are aggregated on one thread: small inputs are, unless
+/// the workers or how they share the input were asked for explicitly.
+pub fn single_threaded(len: u64, opts: &Options) -> bool {
+    is_small_input(len)
+        && opts.threads == 0
+        && opts.processes <= 1
+        && !opts.deterministic
+        && opts.record_schedule.is_none()
+        && opts.replay_schedule.is_none()
+}
+
+/// Aggregates an in-memory input, splitting it into line-aligned chunks that
+/// a pool of threads takes in turn. Each thread fills its own table and the
+/// tables are merged at the end, so threads never contend on the hot path.
+/// Returns the setup used, as tuned with `--auto-tune`.
+pub fn aggregate_mapped<A: Aggregator>(
+    data: &[u8],
+    opts: &Options,
+    table: &mut StationTable<A>,
+) -> Setup {
+    let mut setup = Setup {
+        threads: threads(opts),
+        chunk_size: opts.chunk_size,
+    };
+    if opts.two_pass {
+        let keys = dictionary::sample_keys(data, table);
+        dictionary::seed(table, keys);
+    }
+    if single_threaded(data.len() as u64, opts) {
+        setup.threads = 1;
+    } else if opts.processes > 1 {
+        aggregate_forked(data, opts, table);
+        return setup;
+    }
+    let mut rest = data;
+    if opts.auto_tune && setup.threads > 1 {
+        let (tuned, remainder) = calibrate(rest, setup.threads, table);
+        setup = tuned;
+        rest = remainder;
+    }
+    run(rest, setup, table);
+    setup
+}
+
+#[cfg(unix)]
+fn aggregate_forked<A: Aggregator>(data: &[u8], opts: &Options, table: &mut StationTable<A>) {
+    crate::fork::aggregate(data, opts.processes, opts.chunk_size, table);
+}
+
+#[cfg(not(unix))]
+fn aggregate_forked<A: Aggregator>(_data: &[u8], _opts: &Options, table: &mut StationTable<A>) {
+    let failure = crate::cli::Failure::usage("--processes is only supported on Unix");
+    table.fault.raise(failure);
+}
+
+fn run<A: Aggregator>(data: &[u8], setup: Setup, table: &mut StationTable<A>) {
+    let mut schedule = table.schedule.take();
+    let planned = match &mut schedule {
+        Some(Schedule::Deterministic) => Ok(Some(schedule::deterministic(data, setup))),
+        Some(schedule) => schedule.replay(data),
+        None => Ok(None),
+    };
+    match planned {
+        Ok(Some(run)) => replay(data, run, table),
+        Ok(None) => {
+            // A recorded chunk must be aggregated by a single worker
+            let split = !matches!(schedule, Some(Schedule::Record { .. }));
+            let (chunks, workers, assigned) = share(data, setup, split, table);
+            if let Some(schedule) = &mut schedule {
+                if let Err(failure) =
+                    schedule.record(data, setup.threads, workers, &chunks, &assigned)
+                {
+                    table.fault.raise(failure);
+                }
+            }
+        }
+        Err(failure) => table.fault.raise(failure),
+    }
+    table.schedule = schedule;
+}
+
+fn aggregate_chunk<A: Aggregator>(chunk: &[u8], table: &mut StationTable<A>) {
+    if let Some(seen) = &mut table.first_seen {
+        seen.start_chunk(chunk);
+    }
+    process_lines(chunk, table);
+}
+
+/// Aggregates `data` in chunks that `setup.threads` workers take as they
+/// become free. With `split`, a worker that finds no chunk left takes the
+/// back half of what the most lagging worker has left of its own, so that a
+/// chunk of longer lines or slower pages does not hold up the end of the
+/// run. Returns the chunks with the number of workers and the worker that
+/// took each.
+fn share<'a, A: Aggregator>(
+    data: &'a [u8],
+    setup: Setup,
+    split: bool,
+    table: &mut StationTable<A>,
+) -> (Vec<&'a [u8]>, usize, Vec<usize>) {
+    if let Some(progress) = &table.progress {
+        progress.set_threads(setup.threads);
+    }
+    let chunks: Vec<&[u8]> = line_chunks(data, setup.chunk_size).collect();
+    if setup.threads <= 1 {
+        for chunk in &chunks {
+            aggregate_chunk(chunk, table);
+        }
+        let assigned = vec![0; chunks.len()];
+        return (chunks, 1, assigned);
+    }
+
+    let next = AtomicUsize::new(0);
+    let workers = setup.threads.min(chunks.len());
+    // --order input notes where each chunk starts, so chunks stay whole
+    let split = split && table.first_seen.is_none();
+    let left: Vec<Mutex<Range<usize>>> = (0..workers).map(|_| Mutex::new(0..0)).collect();
+    let partials: Vec<(StationTable<A>, Vec<usize>)> = thread::scope(|scope| {
+        let handles: Vec<_> = (0..workers)
+            .map(|worker| {
+                let mut local = table.sibling();
+                let (chunks, next, left) = (&chunks, &next, &left);
+                scope.spawn(move || {
+                    let mut taken = Vec::new();
+                    loop {
+                        let idx = next.fetch_add(1, Ordering::Relaxed);
+                        match chunks.get(idx) {
+                            Some(chunk) if split => {
+                                let start = chunk.as_ptr() as usize - data.as_ptr() as usize;
+                                *left[worker].lock().unwrap() = start..start + chunk.len();
+                            }
+                            Some(chunk) => aggregate_chunk(chunk, &mut local),
+                            None if split => match steal(data, left, worker) {
+                                Some(bytes) => {
+                                    if let Some(activity) = &mut local.activity {
+                                        activity.split_off(bytes);
+                                    }
+                                }
+                                None => break,
+                            },
+                            None => break,
+                        }
+                        if split {
+                            drain(data, &left[worker], &mut local);
+                        }
+                        if idx < chunks.len() {
+                            taken.push(idx);
+                        }
+                    }
+                    (local, taken)
+                })
+            })
+            .collect();
+        handles.into_iter().map(join).collect()
+    });
+    let mut assigned = vec![0; chunks.len()];
+    for (worker, (partial, taken)) in partials.iter().enumerate() {
+        table.absorb(partial);
+        for &idx in taken {
+            assigned[idx] = worker;
+        }
+    }
+    (chunks, workers, assigned)
+}
+
+/// Aggregates what is left of the range at `left` in steps of [`STEP`],
+/// which an idle worker may cut short between steps.
+fn drain<A: Aggregator>(data: &[u8], left: &Mutex<Range<usize>>, table: &mut StationTable<A>) {
+    loop {
+        let piece = {
+            let mut left = left.lock().unwrap();
+            if left.is_empty() {
+                return;
+            }
+            let end = match data[..left.end].get(left.start + STEP..) {
+                Some(tail) => {
+                    memchr::memchr(b'\n', tail).map_or(left.end, |i| left.start + STEP + i + 1)
+                }
+                None => left.end,
+            };
+            let piece = left.start..end;
+            left.start = end;
+            piece
+        };
+        process_lines(&data[piece], table);
+    }
+}
+
+/// Moves the back half of the largest range another worker has left, if it
+/// is worth splitting, to `worker`'s. Returns the bytes moved.
+fn steal(data: &[u8], left: &[Mutex<Range<usize>>], worker: usize) -> Option<usize> {
+    let (victim, _) = left
+        .iter()
+        .enumerate()
+        .filter(|&(other, _)| other != worker)
+        .map(|(other, range)| (other, range.lock().unwrap().len()))
+        .max_by_key(|&(_, len)| len)?;
+    let mut range = left[victim].lock().unwrap();
+    if range.len() < MIN_SPLIT {
+        return None;
+    }
+    let mid = range.start + range.len() / 2;
+    let mid = mid + memchr::memchr(b'\n', &data[mid..range.end])? + 1;
+    if mid >= range.end {
+        return None;
+    }
+    let end = std::mem::replace(&mut range.end, mid);
+    drop(range);
+    *left[worker].lock().unwrap() = mid..end;
+    Some(end - mid)
+}
+
+/// Aggregates `data` in the chunks of a recorded run, each by its worker, in
+/// input order per worker, merging the workers' tables in the same order.
+fn replay<A: Aggregator>(data: &[u8], run: Run, table: &mut StationTable<A>) {
+    if let Some(progress) = &table.progress {
+        progress.set_threads(run.threads);
+    }
+    if run.threads <= 1 {
+        for &(start, end, _) in &run.chunks {
+            aggregate_chunk(&data[start..end], table);
+        }
+        return;
+    }
+
+    let partials: Vec<StationTable<A>> = thread::scope(|scope| {
+        let handles: Vec<_> = (0..run.workers)
+            .map(|worker| {
+                let mut local = table.sibling();
+                let chunks = &run.chunks;
+                scope.spawn(move || {
+                    for &(start, end, _) in chunks.iter().filter(|chunk| chunk.2 == worker) {
+                        aggregate_chunk(&data[start..end], &mut local);
+                    }
+                    local
+                })
+            })
+            .collect();
+        handles.into_iter().map(join).collect()
+    });
+    for partial in &partials {
+        table.absorb(partial);
+    }
+}
+
+/// Tries combinations of thread count and chunk size on successive slices
+/// from the start of `data`, aggregating them into `table` as it goes, and
+/// returns the fastest setup with the part of `data` not yet processed.
+/// Calibration uses at most [`CALIBRATION_BUDGET`] and a quarter of the input.
+fn calibrate<'a, A: Aggregator>(
+    data: &'a [u8],
+    max_threads: usize,
+    table: &mut StationTable<A>,
+) -> (Setup, &'a [u8]) {
+    let mut thread_counts = vec![1, max_threads / 2, max_threads];
+    thread_counts.retain(|&n| n > 0);
+    thread_counts.dedup();
+    let candidates: Vec<Setup> = thread_counts
+        .iter()
+        .flat_map(|&threads| {
+            CALIBRATION_CHUNKS.iter().map(move |&chunk_size| Setup {
+                threads,
+                chunk_size,
+            })
+        })
+        .collect();
+
+    let budget = CALIBRATION_BUDGET.min(data.len() / 4);
+    let sample = budget / candidates.len();
+    let mut best = (f64::MAX, candidates[candidates.len() - 1]);
+    let mut rest = data;
+    // Inputs too small to calibrate keep the last (widest) candidate
+    if sample == 0 {
+        return (best.1, rest);
+    }
+    for setup in candidates {
+        let end = match rest.get(sample..) {
+            Some(tail) => memchr::memchr(b'\n', tail).map_or(rest.len(), |i| sample + i + 1),
+            None => rest.len(),
+        };
+        let (slice, tail) = rest.split_at(end);
+        rest = tail;
+
+        let start = Instant::now();
+        run(slice, setup, table);
+        let per_byte = start.elapsed().as_secs_f64() / slice.len().max(1) as f64;
+        if per_byte < best.0 {
+            best = (per_byte, setup);
+        }
+    }
+    if table.progress.is_none() {
+        eprintln!(
+            "auto-tune: {} threads, {} MiB chunks",
+            best.1.threads,
+            best.1.chunk_size >> 20
+        );
+    }
+    (best.1, rest)
+}
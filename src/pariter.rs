@@ -0,0 +1,55 @@
+//! `--engine rayon`: the mapping's line-aligned chunks as a rayon parallel
+//! iterator, folded into a table per split and merged. The least code that
+//! aggregates in parallel, kept as a baseline for what the pool in `parallel`
+//! buys over it.
+
+use std::sync::Mutex;
+
+use rayon::prelude::*;
+use rayon::ThreadPoolBuilder;
+
+use crate::cli::{Failure, Options};
+use crate::{dictionary, line_chunks, parallel, process_lines, Aggregator, StationTable};
+
+/// Aggregates an in-memory input on a rayon pool of `--threads` threads.
+pub fn aggregate<A: Aggregator>(data: &[u8], opts: &Options, table: &mut StationTable<A>) {
+    if opts.two_pass {
+        let keys = dictionary::sample_keys(data, table);
+        dictionary::seed(table, keys);
+    }
+    let threads = if parallel::single_threaded(data.len() as u64, opts) {
+        1
+    } else {
+        parallel::threads(opts)
+    };
+    if let Some(progress) = &table.progress {
+        progress.set_threads(threads);
+    }
+    let pool = match ThreadPoolBuilder::new().num_threads(threads).build() {
+        Ok(pool) => pool,
+        Err(e) => {
+            let failure = Failure::io(format!("starting thread pool: {}", e));
+            table.fault.raise(failure);
+            return;
+        }
+    };
+    let chunks: Vec<&[u8]> = line_chunks(data, opts.chunk_size).collect();
+    // Aggregators need only be `Send`, so the splits copy their settings
+    // from a template behind a lock
+    let template = Mutex::new(table.sibling());
+    let partials: Vec<StationTable<A>> = pool.install(|| {
+        chunks
+            .par_iter()
+            .fold(
+                || template.lock().unwrap().sibling(),
+                |mut local, chunk| {
+                    process_lines(chunk, &mut local);
+                    local
+                },
+            )
+            .collect()
+    });
+    for partial in &partials {
+        table.absorb(partial);
+    }
+}
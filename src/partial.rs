@@ -0,0 +1,112 @@
+use std::fs;
+use std::io::{self, Write};
+
+use crate::cli::{Exit, Failure, Options};
+use crate::{accumulator_kind, Aggregator, StationTable};
+
+/// Starts every `--format partial` dump, followed by a version, the
+/// accumulator kind and `--decimals`, one byte each.
+const DUMP_MAGIC: &[u8; 8] = b"1BRCPART";
+/// Version 2 encodes numbers little-endian. Version 1 used the writer's byte
+/// order, which is the same on little-endian hosts, so those still read it.
+const DUMP_VERSION: u8 = 2;
+
+/// Splits `N` bytes off the front of `input`.
+pub fn take<const N: usize>(input: &mut &[u8]) -> Option<[u8; N]> {
+    let (head, rest) = input.split_first_chunk::<N>()?;
+    *input = rest;
+    Some(*head)
+}
+
+/// Appends every station of `table` to `out` as its name length (one byte),
+/// the name and the encoded statistics. Returns false if the aggregator has
+/// no binary encoding.
+pub fn encode_table<A: Aggregator>(table: &StationTable<A>, out: &mut Vec<u8>) -> bool {
+    for (name, stats) in table.iter() {
+        out.push(name.len() as u8);
+        out.extend_from_slice(name);
+        if !stats.encode(out) {
+            return false;
+        }
+    }
+    true
+}
+
+/// Merges stations written by [`encode_table`] into `table`. Returns `None`
+/// if the encoding is truncated or corrupt.
+pub fn merge_encoded<A: Aggregator>(mut input: &[u8], table: &mut StationTable<A>) -> Option<()> {
+    while let Some((&len, rest)) = input.split_first() {
+        let (name, rest) = rest.split_at_checked(len as usize)?;
+        input = rest;
+        let stats = A::decode(&mut input)?;
+        table.entry_mut(name).merge(&stats);
+    }
+    Some(())
+}
+
+/// Encodes `table` as a `--format partial` dump: a header recording what it
+/// was aggregated with, then the stations as [`encode_table`] writes them.
+/// Returns `None` if the aggregator has no binary encoding.
+pub fn encode_dump<A: Aggregator>(table: &StationTable<A>, opts: &Options) -> Option<Vec<u8>> {
+    let mut buf = DUMP_MAGIC.to_vec();
+    buf.extend_from_slice(&[
+        DUMP_VERSION,
+        accumulator_kind(opts) as u8,
+        opts.decimals as u8,
+    ]);
+    encode_table(table, &mut buf).then_some(buf)
+}
+
+/// Writes `table` as a `--format partial` dump.
+pub fn write_dump<A: Aggregator>(
+    table: &StationTable<A>,
+    opts: &Options,
+    out: &mut dyn Write,
+) -> io::Result<()> {
+    let Some(buf) = encode_dump(table, opts) else {
+        let failure =
+            Failure::usage("this accumulator has no binary encoding for --format partial");
+        return Err(failure.into());
+    };
+    out.write_all(&buf)
+}
+
+/// Merges the dumps given to `merge` into `table`. They must have been
+/// written with the same `--decimals` and an accumulator of the same kind,
+/// i.e. `--stats` that needed the same statistics.
+pub fn merge_dumps<A: Aggregator>(
+    opts: &Options,
+    table: &mut StationTable<A>,
+) -> Result<(), Failure> {
+    for path in &opts.partials {
+        let data = fs::read(path).map_err(|e| Failure::io(format!("{}: {}", path, e)))?;
+        merge_dump(&data, opts, table)
+            .map_err(|e| Failure::new(Exit::Data, format!("{}: {}", path, e)))?;
+    }
+    Ok(())
+}
+
+/// Merges one dump written by [`write_dump`] into `table`.
+pub fn merge_dump<A: Aggregator>(
+    data: &[u8],
+    opts: &Options,
+    table: &mut StationTable<A>,
+) -> Result<(), String> {
+    let body = data
+        .strip_prefix(DUMP_MAGIC)
+        .ok_or("not a --format partial dump")?;
+    let (&[version, kind, decimals], body) = body.split_first_chunk().ok_or("truncated dump")?;
+    match version {
+        DUMP_VERSION => {}
+        1 if cfg!(target_endian = "little") => {}
+        1 => return Err("version 1 dumps are readable only on little-endian hosts".to_string()),
+        _ => return Err(format!("unsupported dump version {}", version)),
+    }
+    if kind != accumulator_kind(opts) as u8 {
+        return Err("dumped with --stats needing other statistics".to_string());
+    }
+    if u32::from(decimals) != opts.decimals {
+        return Err(format!("dumped with --decimals {}", decimals));
+    }
+    merge_encoded(body, table).ok_or_else(|| "truncated or corrupt dump".to_string())
+}
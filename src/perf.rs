@@ -0,0 +1,233 @@
+//! `--perf`: hardware counters over the aggregation phase alone, read with
+//! `perf_event_open(2)`, so that changes to the hot loop can be judged by
+//! cycles and misses rather than wall time. Counters follow every thread
+//! and `--processes` worker started while they run.
+
+use std::fs::File;
+use std::io::{self, Read, Write};
+use std::os::unix::io::FromRawFd;
+use std::time::{Duration, Instant};
+
+use crate::format::thousands;
+
+const PERF_TYPE_HARDWARE: u32 = 0;
+const PERF_COUNT_HW_CPU_CYCLES: u64 = 0;
+const PERF_COUNT_HW_INSTRUCTIONS: u64 = 1;
+const PERF_COUNT_HW_CACHE_MISSES: u64 = 3;
+const PERF_COUNT_HW_BRANCH_INSTRUCTIONS: u64 = 4;
+const PERF_COUNT_HW_BRANCH_MISSES: u64 = 5;
+
+const PERF_FORMAT_TOTAL_TIME_ENABLED: u64 = 1 << 0;
+const PERF_FORMAT_TOTAL_TIME_RUNNING: u64 = 1 << 1;
+
+const FLAG_DISABLED: u64 = 1 << 0;
+const FLAG_INHERIT: u64 = 1 << 1;
+const FLAG_EXCLUDE_KERNEL: u64 = 1 << 5;
+const FLAG_EXCLUDE_HV: u64 = 1 << 6;
+
+const PERF_EVENT_IOC_ENABLE: libc::c_ulong = 0x2400;
+const PERF_EVENT_IOC_DISABLE: libc::c_ulong = 0x2401;
+
+/// `struct perf_event_attr` as of its first version, which every kernel
+/// accepts.
+#[repr(C)]
+#[derive(Default)]
+struct Attr {
+    kind: u32,
+    size: u32,
+    config: u64,
+    sample_period: u64,
+    sample_type: u64,
+    read_format: u64,
+    flags: u64,
+    wakeup_events: u32,
+    bp_type: u32,
+    config1: u64,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Event {
+    Cycles,
+    Instructions,
+    Branches,
+    BranchMisses,
+    /// Misses of the last-level cache, in the kernel's generic event.
+    CacheMisses,
+}
+
+const EVENTS: [Event; 5] = [
+    Event::Cycles,
+    Event::Instructions,
+    Event::Branches,
+    Event::BranchMisses,
+    Event::CacheMisses,
+];
+
+impl Event {
+    fn config(self) -> u64 {
+        match self {
+            Event::Cycles => PERF_COUNT_HW_CPU_CYCLES,
+            Event::Instructions => PERF_COUNT_HW_INSTRUCTIONS,
+            Event::Branches => PERF_COUNT_HW_BRANCH_INSTRUCTIONS,
+            Event::BranchMisses => PERF_COUNT_HW_BRANCH_MISSES,
+            Event::CacheMisses => PERF_COUNT_HW_CACHE_MISSES,
+        }
+    }
+
+    fn open(self) -> io::Result<File> {
+        let attr = Attr {
+            kind: PERF_TYPE_HARDWARE,
+            size: std::mem::size_of::<Attr>() as u32,
+            config: self.config(),
+            read_format: PERF_FORMAT_TOTAL_TIME_ENABLED | PERF_FORMAT_TOTAL_TIME_RUNNING,
+            // User space only, which needs no privileges at the default
+            // perf_event_paranoid of 2
+            flags: FLAG_DISABLED | FLAG_INHERIT | FLAG_EXCLUDE_KERNEL | FLAG_EXCLUDE_HV,
+            ..Attr::default()
+        };
+        // SAFETY: `attr` outlives the call, which only reads it
+        let fd = unsafe {
+            libc::syscall(
+                libc::SYS_perf_event_open,
+                &attr as *const Attr,
+                0 as libc::pid_t,
+                -1 as libc::c_int,
+                -1 as libc::c_int,
+                0 as libc::c_ulong,
+            )
+        };
+        if fd < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        // SAFETY: the fd was just opened and is owned by nothing else
+        Ok(unsafe { File::from_raw_fd(fd as libc::c_int) })
+    }
+}
+
+/// Counters running since [`Counters::start`].
+pub struct Counters {
+    events: Vec<(Event, File)>,
+    started: Instant,
+}
+
+impl Counters {
+    /// Opens and starts every counter the CPU has. Fails only if it has
+    /// none, with the reason the first one could not be opened.
+    pub fn start() -> Result<Counters, String> {
+        let mut events = Vec::new();
+        let mut first_error = None;
+        for event in EVENTS {
+            match event.open() {
+                Ok(file) => events.push((event, file)),
+                Err(e) => {
+                    first_error.get_or_insert(e);
+                }
+            }
+        }
+        if events.is_empty() {
+            let e = first_error.expect("no events were tried");
+            return Err(match e.raw_os_error() {
+                Some(libc::ENOENT | libc::ENODEV | libc::EOPNOTSUPP) => {
+                    "--perf: this CPU or VM exposes no hardware counters".to_string()
+                }
+                Some(libc::EACCES | libc::EPERM) => {
+                    format!("--perf: {} (see /proc/sys/kernel/perf_event_paranoid)", e)
+                }
+                _ => format!("--perf: {}", e),
+            });
+        }
+        for (_, file) in &events {
+            ioctl(file, PERF_EVENT_IOC_ENABLE);
+        }
+        Ok(Counters {
+            events,
+            started: Instant::now(),
+        })
+    }
+
+    /// Stops the counters and reads them.
+    pub fn stop(self) -> Report {
+        for (_, file) in &self.events {
+            ioctl(file, PERF_EVENT_IOC_DISABLE);
+        }
+        let elapsed = self.started.elapsed();
+        let mut counts = Vec::new();
+        for (event, mut file) in self.events {
+            let mut buf = [0u8; 24];
+            if file.read_exact(&mut buf).is_err() {
+                continue;
+            }
+            let word = |i: usize| u64::from_ne_bytes(buf[i * 8..i * 8 + 8].try_into().unwrap());
+            let (value, enabled, running) = (word(0), word(1), word(2));
+            if running == 0 {
+                continue;
+            }
+            // Scaled up for the time the counter shared the PMU with others
+            let scaled = running < enabled;
+            let value = (value as f64 * enabled as f64 / running as f64) as u64;
+            counts.push((event, value, scaled));
+        }
+        Report { elapsed, counts }
+    }
+}
+
+fn ioctl(file: &File, request: libc::c_ulong) {
+    use std::os::unix::io::AsRawFd;
+    // SAFETY: the request takes no argument
+    unsafe { libc::ioctl(file.as_raw_fd(), request as _, 0) };
+}
+
+/// The counts of one run of [`Counters`].
+pub struct Report {
+    elapsed: Duration,
+    counts: Vec<(Event, u64, bool)>,
+}
+
+impl Report {
+    fn get(&self, event: Event) -> Option<u64> {
+        self.counts
+            .iter()
+            .find(|(e, _, _)| *e == event)
+            .map(|&(_, value, _)| value)
+    }
+
+    pub fn write(&self, out: &mut dyn Write) -> io::Result<()> {
+        writeln!(
+            out,
+            "perf (aggregation, {:.3} s):",
+            self.elapsed.as_secs_f64()
+        )?;
+        for &(event, value, scaled) in &self.counts {
+            let (name, derived) = match event {
+                Event::Cycles => ("cycles", None),
+                Event::Instructions => (
+                    "instructions",
+                    self.get(Event::Cycles)
+                        .filter(|&cycles| cycles > 0)
+                        .map(|cycles| format!("{:.2} IPC", value as f64 / cycles as f64)),
+                ),
+                Event::Branches => ("branches", None),
+                Event::BranchMisses => (
+                    "branch misses",
+                    self.get(Event::Branches)
+                        .filter(|&branches| branches > 0)
+                        .map(|branches| {
+                            format!("{:.2}% of branches", value as f64 * 100.0 / branches as f64)
+                        }),
+                ),
+                Event::CacheMisses => ("LLC misses", None),
+            };
+            write!(
+                out,
+                "  {:<14} {:>18}",
+                name,
+                format!("{}{}", if scaled { "~" } else { "" }, thousands(value))
+            )?;
+            match derived {
+                Some(derived) => writeln!(out, "  {}", derived)?,
+                None => writeln!(out)?,
+            }
+        }
+        Ok(())
+    }
+}
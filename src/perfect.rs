@@ -0,0 +1,169 @@
+use std::fs;
+use std::sync::Arc;
+
+use crate::cli::Unknown;
+use crate::{Aggregator, KeyFn, StationTable};
+
+/// Stations per bucket of the displacement table, on average.
+const BUCKET_SIZE: usize = 4;
+/// Pilots tried for one bucket before starting over with another seed.
+const MAX_PILOT: u32 = 1 << 16;
+
+/// Collision-free hash of a fixed set of keys onto table slots, built with
+/// hash-and-displace: keys are split into buckets, and each bucket gets the
+/// first "pilot" that moves all its keys onto free slots. A lookup is one
+/// hash, one pilot load and one slot, with no probing and no branches.
+pub struct PerfectHash {
+    seed: u64,
+    pilots: Box<[u32]>,
+    mask: usize,
+    /// The keys, indexed by their slot.
+    keys: Box<[Option<Box<[u8]>>]>,
+    /// What to do with a name outside the set.
+    pub unknown: Unknown,
+}
+
+impl PerfectHash {
+    /// Builds a perfect hash of `keys`, which must be distinct, onto `slots`
+    /// slots (a power of two at least twice the number of keys).
+    pub fn build(keys: Vec<Box<[u8]>>, slots: usize, unknown: Unknown) -> PerfectHash {
+        let buckets = keys.len().div_ceil(BUCKET_SIZE).max(1);
+        for seed in 0.. {
+            if let Some(pilots) = place(&keys, slots, buckets, seed) {
+                let mut perfect = PerfectHash {
+                    seed,
+                    pilots,
+                    mask: slots - 1,
+                    keys: vec![None; slots].into_boxed_slice(),
+                    unknown,
+                };
+                for key in keys {
+                    let slot = perfect.slot(&key);
+                    perfect.keys[slot] = Some(key);
+                }
+                return perfect;
+            }
+        }
+        unreachable!()
+    }
+
+    /// The slot `name` starts probing from: its own one if it is in the set,
+    /// otherwise any slot.
+    #[inline(always)]
+    pub fn slot(&self, name: &[u8]) -> usize {
+        let h = full_hash(name, self.seed);
+        let pilot = self.pilots[bucket(h, self.pilots.len())];
+        displace(h, pilot) & self.mask
+    }
+
+    /// Whether `name` is one of the keys.
+    pub fn contains(&self, name: &[u8]) -> bool {
+        self.keys[self.slot(name)].as_deref() == Some(name)
+    }
+}
+
+/// Assigns a pilot to every bucket, largest buckets first, or returns `None`
+/// if some bucket has no pilot that fits.
+fn place(keys: &[Box<[u8]>], slots: usize, buckets: usize, seed: u64) -> Option<Box<[u32]>> {
+    let mut members: Vec<Vec<u64>> = vec![Vec::new(); buckets];
+    for key in keys {
+        let h = full_hash(key, seed);
+        members[bucket(h, buckets)].push(h);
+    }
+    let mut order: Vec<usize> = (0..buckets).collect();
+    order.sort_by_key(|&b| std::cmp::Reverse(members[b].len()));
+
+    let mut taken = vec![false; slots];
+    let mut pilots = vec![0; buckets].into_boxed_slice();
+    let mut candidate = Vec::with_capacity(BUCKET_SIZE * 4);
+    for b in order.into_iter().filter(|&b| !members[b].is_empty()) {
+        let found = (0..MAX_PILOT).find(|&pilot| {
+            candidate.clear();
+            for &h in &members[b] {
+                let slot = displace(h, pilot) & (slots - 1);
+                if taken[slot] || candidate.contains(&slot) {
+                    return false;
+                }
+                candidate.push(slot);
+            }
+            true
+        })?;
+        for &slot in &candidate {
+            taken[slot] = true;
+        }
+        pilots[b] = found;
+    }
+    Some(pilots)
+}
+
+#[inline(always)]
+fn bucket(h: u64, buckets: usize) -> usize {
+    (((h >> 32) * buckets as u64) >> 32) as usize
+}
+
+#[inline(always)]
+fn displace(h: u64, pilot: u32) -> usize {
+    (h ^ (pilot as u64).wrapping_mul(0x9e3779b97f4a7c15)) as usize
+}
+
+/// Hash of every byte of `name`, unlike the table's own hash, which only
+/// reads the first eight and so cannot tell many names apart.
+#[inline(always)]
+fn full_hash(name: &[u8], seed: u64) -> u64 {
+    let mut h = seed ^ name.len() as u64;
+    let (words, rest) = name.as_chunks::<8>();
+    for word in words {
+        h = (h.rotate_left(5) ^ u64::from_le_bytes(*word)).wrapping_mul(0x517cc1b727220a95);
+    }
+    let mut buf = [0u8; 8];
+    buf[..rest.len()].copy_from_slice(rest);
+    h = (h.rotate_left(5) ^ u64::from_le_bytes(buf)).wrapping_mul(0x517cc1b727220a95);
+    // Stafford variant 13 finalizer
+    h ^= h >> 30;
+    h = h.wrapping_mul(0xbf58476d1ce4e5b9);
+    h ^= h >> 27;
+    h = h.wrapping_mul(0x94d049bb133111eb);
+    h ^ (h >> 31)
+}
+
+/// Reads the station list for `--stations`: one name per line, optionally
+/// followed by `;` and anything else, as in the official
+/// `weather_stations.csv`. Lines starting with `#` are comments. Names are
+/// mapped through `key` and deduplicated.
+pub fn load(path: &str, key: Option<&KeyFn>) -> Result<Vec<Box<[u8]>>, String> {
+    let data = fs::read(path).map_err(|e| format!("{}: {}", path, e))?;
+    let mut keys = Vec::new();
+    for line in data.split(|&b| b == b'\n') {
+        let line = line.strip_suffix(b"\r").unwrap_or(line);
+        if line.is_empty() || line.starts_with(b"#") {
+            continue;
+        }
+        let name = line.split(|&b| b == b';').next().unwrap_or(line);
+        let name = key.map_or(name, |key| key(name));
+        if name.is_empty() || name.len() > crate::MAX_NAME_LEN {
+            return Err(format!(
+                "{}: '{}' is not a valid station name",
+                path,
+                String::from_utf8_lossy(name)
+            ));
+        }
+        keys.push(Box::from(name));
+    }
+    if keys.is_empty() {
+        return Err(format!("{}: no stations listed", path));
+    }
+    keys.sort_unstable();
+    keys.dedup();
+    Ok(keys)
+}
+
+/// Replaces `table` with one sized for `keys` that starts each of them
+/// probing at a slot of its own, for `--stations`. As long as they are not
+/// taken by unknown stations first, which `unknown` may prevent, every
+/// lookup of a listed station hits on the first probe. The table behaves as
+/// an ordinary one once it has to grow.
+pub fn seed<A: Aggregator>(table: &mut StationTable<A>, keys: Vec<Box<[u8]>>, unknown: Unknown) {
+    let slots = (keys.len() * 2 + 1).next_power_of_two();
+    table.perfect = Some(Arc::new(PerfectHash::build(keys, slots, unknown)));
+    *table = table.sibling_with_slots(slots);
+}
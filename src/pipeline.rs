@@ -0,0 +1,138 @@
+//! `--engine pipeline`: a reader thread reads line-aligned chunks of the file
+//! into a bounded channel, parser workers each aggregate the chunks they take
+//! from it into their own table, and the calling thread merges the tables as
+//! the workers finish. Reading never waits on parsing until the channel is
+//! full, so on slow storage the device is kept busy where the mapped design
+//! would stall each worker on its page faults in turn.
+
+use std::fs::File;
+use std::os::unix::fs::FileExt;
+use std::thread;
+
+use crossbeam_channel::{bounded, Receiver, Sender};
+
+use crate::advice::Prefetch;
+use crate::cli::{Exit, Failure, Options};
+use crate::{
+    dictionary, parallel, pipeline_buffers, process_lines, Aggregator, StationTable, PIPELINE_DEPTH,
+};
+
+/// Aggregates `data`, the bytes of `file` from offset `offset`, by reading
+/// them from the file again on a reader thread. `data` is only sampled for
+/// `--two-pass`.
+pub fn aggregate<A: Aggregator>(
+    file: &File,
+    data: &[u8],
+    offset: u64,
+    opts: &Options,
+    table: &mut StationTable<A>,
+) {
+    if opts.two_pass {
+        let keys = dictionary::sample_keys(data, table);
+        dictionary::seed(table, keys);
+    }
+    let workers = parallel::threads(opts);
+    if let Some(progress) = &table.progress {
+        progress.set_threads(workers);
+    }
+    let end = offset + data.len() as u64;
+    let (chunk_tx, chunk_rx) = bounded::<(u64, Vec<u8>)>(workers * PIPELINE_DEPTH);
+    let (free_tx, free_rx) = bounded::<Vec<u8>>(pipeline_buffers(workers));
+    for _ in 0..pipeline_buffers(workers) {
+        free_tx.send(Vec::new()).unwrap();
+    }
+    let (merge_tx, merge_rx) = bounded::<StationTable<A>>(workers);
+
+    thread::scope(|scope| {
+        let prefetch = Prefetch::read(file, offset, end, opts);
+        let size = opts.chunk_size;
+        let fault = table.fault.clone();
+        let reader = scope.spawn(move || {
+            if let Err(failure) = read(file, (offset, end), size, prefetch, free_rx, chunk_tx) {
+                fault.raise(failure);
+            }
+        });
+        let mut parsers = Vec::with_capacity(workers);
+        for _ in 0..workers {
+            let mut local = table.sibling();
+            let (chunks, free, merged) = (chunk_rx.clone(), free_tx.clone(), merge_tx.clone());
+            parsers.push(scope.spawn(move || {
+                for (start, chunk) in chunks {
+                    local.origin.place(&chunk, start);
+                    process_lines(&chunk, &mut local);
+                    // The reader may have finished and dropped its end
+                    let _ = free.send(chunk);
+                }
+                merged.send(local).unwrap();
+            }));
+        }
+        // Only the workers hold the channels now, so should they all fail,
+        // the reader stops rather than wait on them, and the merger closes
+        // once the last of them has sent its table
+        drop((chunk_rx, free_tx, merge_tx));
+        for partial in merge_rx {
+            table.absorb(&partial);
+        }
+        parsers.into_iter().for_each(parallel::join);
+        parallel::join(reader);
+    });
+}
+
+/// Reads the bytes of `file` from `start` to `end` into the free buffers of
+/// `size` bytes, each sent with its offset on cut after its last full line,
+/// which starts the next buffer.
+fn read(
+    file: &File,
+    (start, end): (u64, u64),
+    size: usize,
+    prefetch: Option<Prefetch>,
+    free: Receiver<Vec<u8>>,
+    chunks: Sender<(u64, Vec<u8>)>,
+) -> Result<(), Failure> {
+    let mut offset = start;
+    let mut carry: Vec<u8> = Vec::new();
+    while offset < end {
+        let Ok(mut buf) = free.recv() else {
+            return Ok(());
+        };
+        // Only zeroes what was cut off the buffer when it was last sent
+        buf.resize(size, 0);
+        buf[..carry.len()].copy_from_slice(&carry);
+        let mut filled = carry.len();
+        let buf_start = offset - filled as u64;
+        while filled < size && offset < end {
+            let want = ((end - offset) as usize).min(size - filled);
+            let n = file
+                .read_at(&mut buf[filled..filled + want], offset)
+                .map_err(|e| Failure::io(format!("reading input: {}", e)))?;
+            if n == 0 {
+                return Err(Failure::io("reading input: file ended early"));
+            }
+            filled += n;
+            offset += n as u64;
+            if let Some(prefetch) = &prefetch {
+                prefetch.ahead(offset);
+            }
+        }
+        buf.truncate(filled);
+        carry.clear();
+        if offset < end {
+            let Some(last_nl) = memchr::memrchr(b'\n', &buf) else {
+                return Err(Failure::new(
+                    Exit::Data,
+                    format!(
+                        "line at byte {} is longer than the {} byte --chunk-size",
+                        offset - size as u64,
+                        size
+                    ),
+                ));
+            };
+            carry.extend_from_slice(&buf[last_nl + 1..]);
+            buf.truncate(last_nl + 1);
+        }
+        if chunks.send((buf_start, buf)).is_err() {
+            return Ok(());
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,362 @@
+use std::fmt::Write as _;
+use std::fs::File;
+use std::io::Read;
+use std::mem::size_of;
+
+use crate::cli::{Engine, Fadvise, Io, Options};
+use crate::footprint::size;
+use crate::stats::{Counter, Distribution, Extremes, Kind, Moments};
+use crate::{
+    accumulator_kind, archive, compress, decode, is_small_input, is_url, local_input_size, map,
+    parallel, pipeline_buffers, pread, scan, table_slots, Entry, StationStats, PIPELINE_DEPTH,
+    READ_BUFFERS, READ_BUF_SIZE,
+};
+
+fn mib(bytes: usize) -> f64 {
+    bytes as f64 / (1 << 20) as f64
+}
+
+/// How the input will be read, and the read buffers it streams through (none
+/// when it is aggregated in place).
+fn input_backend(opts: &Options) -> (String, usize) {
+    let path = &opts.file_path;
+    if is_url(path) {
+        let how = format!(
+            "HTTP, up to {} parallel range requests when the server allows",
+            opts.connections
+        );
+        return (how, READ_BUFFERS);
+    }
+    if path.starts_with("s3://") {
+        let how = format!("S3, {} parallel ranged GETs", opts.connections);
+        return (how, READ_BUFFERS);
+    }
+
+    let mut head = Vec::new();
+    let size = match File::open(path) {
+        Ok(file) => {
+            let size = local_input_size(opts).unwrap_or(0);
+            let _ = file
+                .take(archive::TAR_SNIFF_LEN as u64)
+                .read_to_end(&mut head);
+            size
+        }
+        Err(e) => return (format!("{} cannot be opened: {}", path, e), 0),
+    };
+    let small = is_small_input(size);
+    let reader = if small || opts.io == Io::Read {
+        "read"
+    } else {
+        "mmap"
+    };
+    let size = mib(size as usize);
+    if archive::is_zip(&head) {
+        let how = format!(
+            "{} of a {:.1} MiB zip archive, members streamed",
+            // Zip archives need random access, so they are mapped regardless of --io
+            if small { "read" } else { "mmap" },
+            size
+        );
+        return (how, READ_BUFFERS);
+    }
+    if let Some(format) = compress::sniff(&head) {
+        let how = format!(
+            "{} of {:.1} MiB {} data, decompressed as a stream",
+            reader,
+            size,
+            format.name()
+        );
+        return (how, READ_BUFFERS);
+    }
+    if archive::is_tar(&head) {
+        return (
+            format!(
+                "{} of a {:.1} MiB tar archive, members streamed",
+                reader, size
+            ),
+            READ_BUFFERS,
+        );
+    }
+    match decode::sniff_bom(&head)
+        .map(|(encoding, _)| encoding)
+        .or(opts.encoding)
+    {
+        Some(decode::Encoding::Utf8) | None if opts.io == Io::Read && !small => (
+            format!(
+                "preadv of {:.1} MiB UTF-8, up to {} buffers a call, parsed as a stream",
+                size,
+                pread::BUFFERS - 1
+            ),
+            pread::BUFFERS,
+        ),
+        Some(decode::Encoding::Utf8) | None if opts.engine == Engine::Pipeline && !small => (
+            format!(
+                "pread of {:.1} MiB UTF-8 on a reader thread, chunks queued for the parsers",
+                size
+            ),
+            0,
+        ),
+        Some(decode::Encoding::Utf8) | None if opts.engine == Engine::Tpc && !small => (
+            format!(
+                "reads of {:.1} MiB UTF-8 on a monoio runtime per core, io_uring where available",
+                size
+            ),
+            0,
+        ),
+        Some(decode::Encoding::Utf8) | None => (
+            format!("{} of {:.1} MiB UTF-8, aggregated in place", reader, size),
+            0,
+        ),
+        Some(encoding) => (
+            format!(
+                "{} of {:.1} MiB {:?} text, transcoded as a stream",
+                reader, size, encoding
+            ),
+            READ_BUFFERS,
+        ),
+    }
+}
+
+fn simd_features() -> String {
+    #[cfg_attr(
+        not(any(
+            target_arch = "x86_64",
+            target_arch = "aarch64",
+            all(target_arch = "riscv64", target_os = "linux")
+        )),
+        allow(unused_mut)
+    )]
+    let mut found: Vec<&str> = Vec::new();
+    #[cfg(target_arch = "x86_64")]
+    {
+        if is_x86_feature_detected!("sse4.2") {
+            found.push("sse4.2");
+        }
+        if is_x86_feature_detected!("avx2") {
+            found.push("avx2");
+        }
+        if is_x86_feature_detected!("avx512bw") {
+            found.push("avx512bw");
+        }
+    }
+    #[cfg(target_arch = "aarch64")]
+    {
+        if std::arch::is_aarch64_feature_detected!("neon") {
+            found.push("neon");
+        }
+    }
+    #[cfg(all(target_arch = "riscv64", target_os = "linux"))]
+    {
+        if scan::riscv::has_vector() {
+            found.push("v");
+        }
+    }
+    if found.is_empty() {
+        "none detected".to_string()
+    } else {
+        found.join(" ")
+    }
+}
+
+/// Describes what a run with `opts` will do, for `--explain`.
+pub fn explain(opts: &Options) -> String {
+    let (backend, read_buffers) = input_backend(opts);
+    let streamed = read_buffers > 0;
+    let kind = accumulator_kind(opts);
+    let (entry_size, accumulator) = match kind {
+        Kind::Count => (size_of::<Entry<Counter>>(), "count"),
+        Kind::Basic => (size_of::<Entry<StationStats>>(), "min/max/sum/count"),
+        Kind::Moments => (
+            size_of::<Entry<Moments>>(),
+            "min/max/sum/count, sum of squares",
+        ),
+        Kind::Distribution => (
+            size_of::<Entry<Distribution>>(),
+            "min/max/sum/count, sum of squares, per-station histogram",
+        ),
+        Kind::Extremes => (
+            size_of::<Entry<Extremes>>(),
+            "min/max/sum/count, readings at the min and max",
+        ),
+    };
+    let single = local_input_size(opts).is_some_and(|len| parallel::single_threaded(len, opts));
+    let threads = if streamed || single || opts.gpu {
+        1
+    } else {
+        parallel::threads(opts)
+    };
+    let forked = !(streamed || single || opts.gpu) && opts.processes > 1;
+    let engine = if streamed || single || opts.gpu {
+        Engine::Pool
+    } else {
+        opts.engine
+    };
+    let tpc = engine == Engine::Tpc;
+    let pipelined = engine == Engine::Pipeline;
+    let tables = if forked { opts.processes } else { threads };
+    let slots = table_slots(opts);
+    let table = slots * entry_size;
+    let buffers = match engine {
+        Engine::Tpc => READ_BUF_SIZE * threads,
+        Engine::Pipeline => opts.chunk_size * pipeline_buffers(threads),
+        _ => READ_BUF_SIZE * read_buffers,
+    };
+
+    let mut plan = String::new();
+    let _ = writeln!(plan, "plan for {}:", opts.file_path);
+    if opts.baseline {
+        let _ = writeln!(
+            plan,
+            "  baseline: BufReader lines, str::parse temperatures, a HashMap of stations"
+        );
+        return plan;
+    }
+    let _ = writeln!(plan, "  input:    {}", backend);
+    if opts.fadvise.is_some() || opts.readahead.is_some() {
+        let mut advice = match opts.fadvise {
+            Some(Fadvise::WillNeed) => "POSIX_FADV_WILLNEED on the whole file".to_string(),
+            Some(Fadvise::NoReuse) => "POSIX_FADV_NOREUSE on the whole file".to_string(),
+            None => "none for the whole file".to_string(),
+        };
+        if let Some(bytes) = opts.readahead {
+            let _ = write!(advice, ", {} KiB asked for ahead of the workers", bytes >> 10);
+        }
+        let _ = writeln!(plan, "  advice:   {}", advice);
+    }
+    if forked {
+        let _ = writeln!(
+            plan,
+            "  threads:  1 in each of {} forked processes, merged through pipes",
+            opts.processes
+        );
+    } else if opts.auto_tune && threads > 1 {
+        let _ = writeln!(
+            plan,
+            "  threads:  up to {}, chosen by timing the start of the input",
+            threads
+        );
+    } else if streamed {
+        let _ = writeln!(plan, "  threads:  1 reading, 1 parsing");
+    } else if tpc {
+        let _ = writeln!(plan, "  threads:  {}, one pinned to each core", threads);
+    } else if pipelined {
+        let _ = writeln!(plan, "  threads:  1 reading, {} parsing, merged as they finish", threads);
+    } else if engine == Engine::Rayon {
+        let _ = writeln!(plan, "  threads:  {} in a rayon pool, a table per split", threads);
+    } else {
+        let _ = writeln!(plan, "  threads:  {}", threads);
+    }
+    if opts.gpu && !streamed {
+        let _ = writeln!(
+            plan,
+            "  gpu:      8 MiB chunks split into lines and parsed on the GPU"
+        );
+    }
+    let chunking = if streamed {
+        format!(
+            "{} x {} read buffers filled ahead of the parser",
+            read_buffers,
+            size(READ_BUF_SIZE as u64)
+        )
+    } else if pipelined {
+        format!(
+            "{} line-aligned chunks, up to {} queued",
+            size(opts.chunk_size as u64),
+            threads * PIPELINE_DEPTH
+        )
+    } else if tpc {
+        format!(
+            "a line-aligned share of the file per thread, read {} at a time",
+            size(READ_BUF_SIZE as u64)
+        )
+    } else if opts.deterministic {
+        format!(
+            "{} line-aligned chunks of the mapping, dealt to the threads in turn",
+            size(opts.chunk_size as u64)
+        )
+    } else {
+        format!(
+            "{} line-aligned chunks of the mapping",
+            size(opts.chunk_size as u64)
+        )
+    };
+    let _ = writeln!(plan, "  chunking: {}", chunking);
+    let scan = if opts.interleave {
+        "memchr delimiter scan, two interleaved cursors per chunk".to_string()
+    } else {
+        format!("{} delimiter scan", scan::implementation())
+    };
+    let _ = writeln!(plan, "  simd:     {}, {}", simd_features(), scan);
+    let slots = if map::ENABLED {
+        format!("a {} per worker, keyed by name", map::NAME)
+    } else if opts.stations.is_some() {
+        format!(
+            "{} B slots per worker, 2 per listed station, placed by a perfect hash",
+            entry_size
+        )
+    } else if opts.two_pass && !streamed {
+        format!(
+            "{} B slots per worker, 4 per station found in a sample of the input",
+            entry_size
+        )
+    } else {
+        format!("{} slots x {} B per worker", slots, entry_size)
+    };
+    let _ = writeln!(plan, "  table:    {}, tracking {}", slots, accumulator);
+    let _ = write!(
+        plan,
+        "  memory:   about {:.1} MiB (table {:.1} MiB, buffers {:.1} MiB)",
+        mib(table * tables + buffers),
+        mib(table * tables),
+        mib(buffers)
+    );
+    if kind == Kind::Distribution {
+        let _ = write!(
+            plan,
+            " plus up to 32 KiB per station, or about 20 B per distinct reading once they span more"
+        );
+    }
+    let _ = writeln!(plan);
+    if opts.start_byte > 0 || opts.end_byte.is_some() {
+        let end = opts
+            .end_byte
+            .map_or("the end".to_string(), |end| end.to_string());
+        let _ = writeln!(
+            plan,
+            "  range:    lines starting in bytes {} to {}",
+            opts.start_byte, end
+        );
+    }
+    if let Some(rows) = opts.limit {
+        let _ = writeln!(plan, "  rows:     the first {} only", rows);
+    }
+    if let Some(seconds) = opts.max_seconds {
+        let _ = writeln!(
+            plan,
+            "  limit:    {} s, then partial results for the input covered",
+            seconds
+        );
+    }
+    if opts.sanity_check {
+        let (lo, hi) = opts.plausible;
+        let _ = writeln!(
+            plan,
+            "  sanity:   readings outside {}..{} counted per station",
+            lo, hi
+        );
+    }
+    if let Some((lo, hi)) = opts.histogram {
+        let _ = writeln!(
+            plan,
+            "  buckets:  readings counted per step of {}..{} for --histogram",
+            lo, hi
+        );
+    }
+    if opts.verify {
+        let _ = writeln!(
+            plan,
+            "  verify:   then again with --baseline, failing on any difference"
+        );
+    }
+    plan
+}
@@ -0,0 +1,117 @@
+//! The read backend for plain local text. Rather than a `read` per buffer,
+//! one `preadv` fills every buffer the parser has handed back, so that when
+//! parsing runs ahead of the device the reader asks for several buffers'
+//! worth at once and keeps more I/O in flight, with fewer system calls.
+
+use std::fs::File;
+use std::io;
+use std::os::unix::io::AsRawFd;
+
+use crate::advice::Prefetch;
+use crate::cli::{Failure, Options};
+use crate::origin::Origin;
+use crate::{aggregate_batches, Aggregator, StationTable};
+
+/// Buffers cycled between the reader and the parser, all but the one being
+/// parsed free to be filled by a single call.
+pub const BUFFERS: usize = 4;
+
+/// Reads `file` from `offset` into `bufs` in order, returning the bytes read.
+#[cfg(any(
+    target_os = "linux",
+    target_os = "android",
+    target_os = "macos",
+    target_os = "freebsd",
+    target_os = "dragonfly",
+    target_os = "openbsd",
+    target_os = "netbsd",
+    target_os = "illumos"
+))]
+fn preadv(file: &File, offset: u64, bufs: &mut [&mut [u8]]) -> io::Result<usize> {
+    let iov: Vec<libc::iovec> = bufs
+        .iter_mut()
+        .map(|buf| libc::iovec {
+            iov_base: buf.as_mut_ptr().cast(),
+            iov_len: buf.len(),
+        })
+        .collect();
+    #[cfg(all(target_os = "linux", target_env = "gnu"))]
+    let (preadv, offset) = (libc::preadv64, offset as libc::off64_t);
+    #[cfg(not(all(target_os = "linux", target_env = "gnu")))]
+    let (preadv, offset) = (
+        libc::preadv,
+        libc::off_t::try_from(offset).map_err(|_| io::Error::from(io::ErrorKind::InvalidInput))?,
+    );
+    // SAFETY: each iovec covers a live buffer of `bufs`, borrowed mutably
+    let n = unsafe {
+        preadv(
+            file.as_raw_fd(),
+            iov.as_ptr(),
+            iov.len() as libc::c_int,
+            offset,
+        )
+    };
+    if n < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(n as usize)
+}
+
+/// Elsewhere only the first buffer is filled, with `pread`.
+#[cfg(not(any(
+    target_os = "linux",
+    target_os = "android",
+    target_os = "macos",
+    target_os = "freebsd",
+    target_os = "dragonfly",
+    target_os = "openbsd",
+    target_os = "netbsd",
+    target_os = "illumos"
+)))]
+fn preadv(file: &File, offset: u64, bufs: &mut [&mut [u8]]) -> io::Result<usize> {
+    use std::os::unix::fs::FileExt;
+    file.read_at(bufs[0], offset)
+}
+
+/// Aggregates the bytes of `file` from `start` to `end`, plain UTF-8 text.
+pub fn aggregate<A: Aggregator>(
+    file: &File,
+    start: u64,
+    end: u64,
+    opts: &Options,
+    table: &mut StationTable<A>,
+) -> Result<(), Failure> {
+    let prefetch = Prefetch::read(file, start, end, opts);
+    table.origin = Origin::streamed_from(start);
+    let mut offset = start;
+    aggregate_batches(BUFFERS, BUFFERS - 1, table, |bufs| {
+        let left = end - offset;
+        let mut slices: Vec<&mut [u8]> = Vec::with_capacity(bufs.len());
+        let mut asked = 0;
+        for buf in bufs.iter_mut() {
+            let len = (buf.len() as u64).min(left - asked) as usize;
+            if len == 0 {
+                break;
+            }
+            slices.push(&mut buf[..len]);
+            asked += len as u64;
+        }
+        if slices.is_empty() {
+            return Ok(Vec::new());
+        }
+        let mut n = preadv(file, offset, &mut slices)? as u64;
+        offset += n;
+        if let Some(prefetch) = &prefetch {
+            prefetch.ahead(offset);
+        }
+        // The buffers are filled in order, the last one read maybe in part
+        Ok(bufs
+            .iter()
+            .map(|buf| {
+                let filled = n.min(buf.len() as u64);
+                n -= filled;
+                filled as usize
+            })
+            .collect())
+    })
+}
@@ -0,0 +1,32 @@
+//! `--profile FILE`: a CPU profile of the run in pprof's protobuf format, for
+//! `go tool pprof` or any viewer that reads it, sampled from inside the
+//! process so that no external profiler has to be set up.
+
+use std::fs;
+
+use pprof::protos::Message;
+use pprof::ProfilerGuardBuilder;
+
+use crate::cli::{self, Exit};
+
+/// Samples per second. Not a round number, so that sampling does not fall
+/// in step with periodic work.
+const FREQUENCY: i32 = 997;
+
+/// Runs `run` under the sampler and writes its profile to `path`.
+pub fn record<T>(path: &str, run: impl FnOnce() -> T) -> T {
+    let guard = ProfilerGuardBuilder::default()
+        .frequency(FREQUENCY)
+        .blocklist(&["libc", "libgcc", "pthread", "vdso"])
+        .build()
+        .unwrap_or_else(|e| cli::fail(Exit::Internal, &format!("--profile: {}", e)));
+    let result = run();
+    let profile = guard
+        .report()
+        .build()
+        .and_then(|report| report.pprof())
+        .unwrap_or_else(|e| cli::fail(Exit::Internal, &format!("--profile: {}", e)));
+    fs::write(path, profile.encode_to_vec())
+        .unwrap_or_else(|e| cli::fatal(&format!("{}: {}", path, e)));
+    result
+}
@@ -0,0 +1,105 @@
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use crate::{Aggregator, StationTable};
+
+/// Copy of the table's stations at one point during the run.
+pub type Snapshot<A> = Vec<(Vec<u8>, A)>;
+
+/// Counters a running aggregation publishes for live displays such as
+/// `--tui`. The aggregating threads update them after every chunk, and copy
+/// their table into `snapshot` when an observer has asked for one. With
+/// several threads a snapshot only covers the chunks seen by one of them.
+pub struct Progress<A> {
+    started: Instant,
+    total: AtomicU64,
+    done: AtomicU64,
+    busy_ns: AtomicU64,
+    threads: AtomicU64,
+    finished: AtomicBool,
+    snapshot_wanted: AtomicBool,
+    snapshot: Mutex<Option<Snapshot<A>>>,
+}
+
+impl<A: Aggregator> Default for Progress<A> {
+    fn default() -> Self {
+        Self {
+            started: Instant::now(),
+            total: AtomicU64::new(0),
+            done: AtomicU64::new(0),
+            busy_ns: AtomicU64::new(0),
+            threads: AtomicU64::new(1),
+            finished: AtomicBool::new(false),
+            snapshot_wanted: AtomicBool::new(false),
+            snapshot: Mutex::new(None),
+        }
+    }
+}
+
+impl<A: Aggregator> Progress<A> {
+    pub fn elapsed(&self) -> Duration {
+        self.started.elapsed()
+    }
+
+    /// Input size in bytes, or 0 when it is not known up front (streams).
+    pub fn total(&self) -> u64 {
+        self.total.load(Ordering::Relaxed)
+    }
+
+    /// Bytes of (decoded) input aggregated so far.
+    pub fn done(&self) -> u64 {
+        self.done.load(Ordering::Relaxed)
+    }
+
+    /// Time spent aggregating, as opposed to waiting on I/O or decoding,
+    /// summed over all threads.
+    pub fn busy(&self) -> Duration {
+        Duration::from_nanos(self.busy_ns.load(Ordering::Relaxed))
+    }
+
+    /// Number of threads aggregating at the moment.
+    pub fn threads(&self) -> u64 {
+        self.threads.load(Ordering::Relaxed)
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.finished.load(Ordering::Acquire)
+    }
+
+    /// Asks the aggregating thread to publish a copy of the table after its
+    /// current chunk; collect it with [`Progress::take_snapshot`].
+    pub fn request_snapshot(&self) {
+        self.snapshot_wanted.store(true, Ordering::Relaxed);
+    }
+
+    pub fn take_snapshot(&self) -> Option<Snapshot<A>> {
+        self.snapshot.lock().unwrap().take()
+    }
+
+    pub(crate) fn set_total(&self, bytes: u64) {
+        self.total.store(bytes, Ordering::Relaxed);
+    }
+
+    pub(crate) fn set_threads(&self, threads: usize) {
+        self.threads.store(threads as u64, Ordering::Relaxed);
+    }
+
+    pub(crate) fn finish(&self) {
+        self.finished.store(true, Ordering::Release);
+    }
+
+    /// Accounts for one processed chunk of `bytes`.
+    pub(crate) fn record(&self, bytes: usize, busy: Duration, table: &StationTable<A>) {
+        self.done.fetch_add(bytes as u64, Ordering::Relaxed);
+        self.busy_ns
+            .fetch_add(busy.as_nanos() as u64, Ordering::Relaxed);
+        if self.snapshot_wanted.swap(false, Ordering::Relaxed) {
+            let entries = table
+                .iter()
+                .map(|(name, stats)| (name.to_vec(), stats.clone()))
+                .collect();
+            *self.snapshot.lock().unwrap() = Some(entries);
+        }
+    }
+}
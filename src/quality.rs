@@ -0,0 +1,305 @@
+//! `onebrc quality FILE`: a data-quality report instead of the aggregate.
+//! Every line is checked against the 1BRC format, and the station names that
+//! parse are compared for spellings that differ only in case or diacritics.
+
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{self, Read, Write};
+use std::thread;
+
+use crate::cli::{self, Exit, Options};
+use crate::decode::Encoding;
+use crate::stats::{Counter, Stat, Summary};
+use crate::{
+    archive, compress, is_small_input, line_chunks, mmap_file, parallel, resolve_encoding,
+    table_slots, StationTable, MAX_NAME_LEN,
+};
+
+/// What the report counts, in the order it prints them. Lines with one of
+/// the first five problems are invalid; the rest are only suspicious.
+const CHECKS: [&str; 7] = [
+    "blank line",
+    "no ';' separator",
+    "empty station name",
+    "station name over 100 bytes",
+    "temperature not a number",
+    "temperature outside -99.9..99.9",
+    "station name not UTF-8",
+];
+const INVALID: usize = 5;
+
+/// Lines of streamed input checked at a time.
+const STREAM_BUF_SIZE: usize = 4 << 20;
+
+/// Counts for one part of the input.
+struct Report {
+    lines: u64,
+    /// How often each of [`CHECKS`] failed, and the byte offset at which it
+    /// first did.
+    found: [(u64, Option<u64>); CHECKS.len()],
+    /// The names of the lines that parse, for spelling variants.
+    names: StationTable<Counter>,
+}
+
+impl Report {
+    fn new(opts: &Options) -> Self {
+        Self {
+            lines: 0,
+            found: [(0, None); CHECKS.len()],
+            names: StationTable::with_slots(table_slots(opts)),
+        }
+    }
+
+    fn found(&mut self, check: usize, offset: u64) {
+        let (count, first) = &mut self.found[check];
+        *count += 1;
+        first.get_or_insert(offset);
+    }
+
+    /// Checks the complete lines of `data`, which starts at byte `base` of
+    /// the input.
+    fn check(&mut self, data: &[u8], base: u64) {
+        let mut start = 0;
+        while start < data.len() {
+            let end = memchr::memchr(b'\n', &data[start..]).map_or(data.len(), |i| start + i);
+            let line = &data[start..end];
+            self.check_line(
+                line.strip_suffix(b"\r").unwrap_or(line),
+                base + start as u64,
+            );
+            start = end + 1;
+        }
+    }
+
+    fn check_line(&mut self, line: &[u8], offset: u64) {
+        self.lines += 1;
+        if line.is_empty() {
+            return self.found(0, offset);
+        }
+        let Some(semi) = memchr::memrchr(b';', line) else {
+            return self.found(1, offset);
+        };
+        let (name, temp) = (&line[..semi], &line[semi + 1..]);
+        if name.is_empty() {
+            return self.found(2, offset);
+        }
+        if name.len() > MAX_NAME_LEN {
+            return self.found(3, offset);
+        }
+        // The 1BRC format is always in range, so only others need a closer look
+        if crate::parse_temp_checked(temp).is_none() {
+            match parse_number(temp) {
+                None => return self.found(4, offset),
+                Some(value) if !(-99.9..=99.9).contains(&value) => self.found(5, offset),
+                Some(_) => {}
+            }
+        }
+        if std::str::from_utf8(name).is_err() {
+            self.found(6, offset);
+        }
+        self.names.lookup_or_insert(name, 0);
+    }
+
+    fn merge(&mut self, other: &Report) {
+        self.lines += other.lines;
+        for (mine, theirs) in self.found.iter_mut().zip(&other.found) {
+            mine.0 += theirs.0;
+            mine.1 = match (mine.1, theirs.1) {
+                (Some(a), Some(b)) => Some(a.min(b)),
+                (a, b) => a.or(b),
+            };
+        }
+        self.names.absorb(&other.names);
+    }
+}
+
+/// A decimal number in any precision, e.g. `-123.45` or `7`.
+fn parse_number(bytes: &[u8]) -> Option<f64> {
+    let digits = bytes.strip_prefix(b"-").unwrap_or(bytes);
+    let (int, frac) = match memchr::memchr(b'.', digits) {
+        Some(dot) => (&digits[..dot], Some(&digits[dot + 1..])),
+        None => (digits, None),
+    };
+    let valid = !int.is_empty()
+        && frac != Some(b"")
+        && int
+            .iter()
+            .chain(frac.unwrap_or_default())
+            .all(u8::is_ascii_digit);
+    // Only ASCII digits, '-' and '.' are left, so this is valid UTF-8
+    valid.then(|| std::str::from_utf8(bytes).ok()?.parse().ok())?
+}
+
+/// Runs `onebrc quality`, writing the report for `opts.file_path` to `out`.
+pub fn run(opts: &Options, out: &mut dyn Write) -> io::Result<()> {
+    let (len, mut file) = File::open(&opts.file_path)
+        .and_then(|file| Ok((file.metadata()?.len(), file)))
+        .unwrap_or_else(|e| cli::fatal(&format!("{}: {}", opts.file_path, e)));
+    let mut buf = Vec::new();
+    let mapping;
+    let data = if is_small_input(len) {
+        file.read_to_end(&mut buf)?;
+        &buf[..]
+    } else {
+        mapping = mmap_file(&file).unwrap_or_else(|e| e.exit());
+        mapping.bytes()
+    };
+    if archive::is_zip(data) || archive::is_tar(data) {
+        cli::fail(Exit::Usage, "quality needs a text file, not an archive");
+    }
+    let report = match compress::sniff(data) {
+        Some(format) => {
+            let reader = compress::decoder(format, data).unwrap_or_else(|e| cli::fatal(&e));
+            check_stream(reader, opts)?
+        }
+        None => {
+            let (encoding, bom_len) = resolve_encoding(data, opts).unwrap_or_else(|e| e.exit());
+            if encoding != Encoding::Utf8 {
+                cli::fail(Exit::Usage, "quality reads UTF-8 input only");
+            }
+            check_mapped(&data[bom_len..], bom_len as u64, opts)
+        }
+    };
+    write_report(&report, opts, out)
+}
+
+/// Checks `data` in chunks spread over the worker threads.
+fn check_mapped(data: &[u8], base: u64, opts: &Options) -> Report {
+    let chunks: Vec<&[u8]> = line_chunks(data, opts.chunk_size).collect();
+    let threads = parallel::threads(opts).min(chunks.len()).max(1);
+    let offset = |chunk: &[u8]| base + (chunk.as_ptr() as usize - data.as_ptr() as usize) as u64;
+    let partials: Vec<Report> = thread::scope(|scope| {
+        let handles: Vec<_> = (0..threads)
+            .map(|worker| {
+                let chunks = &chunks;
+                scope.spawn(move || {
+                    let mut report = Report::new(opts);
+                    for chunk in chunks.iter().skip(worker).step_by(threads) {
+                        report.check(chunk, offset(chunk));
+                    }
+                    report
+                })
+            })
+            .collect();
+        handles.into_iter().map(|h| h.join().unwrap()).collect()
+    });
+    let mut report = Report::new(opts);
+    for partial in &partials {
+        report.merge(partial);
+    }
+    report
+}
+
+/// Checks a decompressed stream on the calling thread. Offsets are into the
+/// decompressed text.
+fn check_stream<R: Read>(mut reader: R, opts: &Options) -> io::Result<Report> {
+    let mut report = Report::new(opts);
+    let mut buf = vec![0u8; STREAM_BUF_SIZE];
+    let (mut filled, mut base) = (0, 0);
+    loop {
+        let n = reader.read(&mut buf[filled..])?;
+        filled += n;
+        let complete = match memchr::memrchr(b'\n', &buf[..filled]) {
+            Some(last) if n > 0 => last + 1,
+            _ if n == 0 || filled == buf.len() => filled,
+            _ => continue,
+        };
+        report.check(&buf[..complete], base);
+        buf.copy_within(complete..filled, 0);
+        filled -= complete;
+        base += complete as u64;
+        if n == 0 {
+            return Ok(report);
+        }
+    }
+}
+
+fn write_report(report: &Report, opts: &Options, out: &mut dyn Write) -> io::Result<()> {
+    let invalid: u64 = report.found[..INVALID].iter().map(|(count, _)| count).sum();
+    writeln!(out, "quality of {}:", opts.file_path)?;
+    writeln!(out, "  {:<34}{:>14}", "lines", report.lines)?;
+    writeln!(out, "  {:<34}{:>14}", "invalid lines", invalid)?;
+    for (check, (count, first)) in CHECKS.iter().zip(&report.found) {
+        write!(out, "    {:<32}{:>14}", check, count)?;
+        match first {
+            Some(offset) => writeln!(out, "  first at byte {}", offset)?,
+            None => writeln!(out)?,
+        }
+    }
+    let variants = spelling_variants(&report.names, opts);
+    writeln!(out, "  {:<34}{:>14}", "stations", report.names.len)?;
+    writeln!(out, "  {:<34}{:>14}", "spelling variants", variants.len())?;
+    for group in &variants {
+        let spellings: Vec<String> = group
+            .iter()
+            .map(|(name, count)| format!("{} ({})", name, count))
+            .collect();
+        writeln!(out, "    {}", spellings.join(", "))?;
+    }
+    Ok(())
+}
+
+/// Groups of station names that are spelled differently but compare equal,
+/// each by count and then name, most common group first.
+fn spelling_variants<'a>(
+    names: &'a StationTable<Counter>,
+    opts: &Options,
+) -> Vec<Vec<(&'a str, u64)>> {
+    let fold = folder(&opts.locale);
+    let mut groups: HashMap<Vec<u8>, Vec<(&str, u64)>> = HashMap::new();
+    let mut key = Vec::new();
+    for (name, counter) in names.iter() {
+        let Ok(name) = std::str::from_utf8(name) else {
+            continue;
+        };
+        key.clear();
+        fold(name, &mut key);
+        let count = counter.value(Stat::Count) as u64;
+        groups.entry(key.clone()).or_default().push((name, count));
+    }
+    let mut variants: Vec<_> = groups
+        .into_values()
+        .filter(|group| group.len() > 1)
+        .collect();
+    for group in &mut variants {
+        group.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(b.0)));
+    }
+    variants.sort_by(|a, b| b[0].1.cmp(&a[0].1).then(a[0].0.cmp(b[0].0)));
+    variants
+}
+
+type Fold = Box<dyn Fn(&str, &mut Vec<u8>)>;
+
+/// Maps a name to a key shared by its case and diacritic variants: its
+/// primary-strength collation key for `locale`.
+#[cfg(feature = "collation")]
+fn folder(locale: &str) -> Fold {
+    use icu_collator::options::{CollatorOptions, Strength};
+    use icu_collator::Collator;
+    use icu_locale_core::Locale;
+
+    let locale: Locale = locale
+        .parse()
+        .unwrap_or_else(|_| cli::usage_error(&format!("invalid locale '{}'", locale)));
+    let mut options = CollatorOptions::default();
+    options.strength = Some(Strength::Primary);
+    let collator = Collator::try_new((&locale).into(), options)
+        .unwrap_or_else(|e| cli::usage_error(&format!("no collation data: {}", e)));
+    Box::new(move |name, key| {
+        let Ok(()) = collator.write_sort_key_to(name, key);
+    })
+}
+
+/// Without collation data, only case and combining marks are folded, so
+/// precomposed letters such as `é` are not matched with `e`.
+#[cfg(not(feature = "collation"))]
+fn folder(_locale: &str) -> Fold {
+    Box::new(|name, key| {
+        let mut utf8 = [0; 4];
+        for c in name.chars().flat_map(char::to_lowercase) {
+            if !('\u{300}'..='\u{36f}').contains(&c) {
+                key.extend_from_slice(c.encode_utf8(&mut utf8).as_bytes());
+            }
+        }
+    })
+}
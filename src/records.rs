@@ -0,0 +1,99 @@
+use crate::cli::Failure;
+use crate::origin::Origin;
+use crate::{parse_temp, parse_temp_scaled, scan, BAD_TEMP};
+
+/// Iterator over the `(station, temperature)` records of a buffer, with the
+/// temperature in tenths of a degree (or the scale given to `with_decimals`).
+/// This is the parser the aggregation uses, so it makes the same assumptions:
+/// every line is `name;temp` with the expected number of decimal digits, and
+/// only the last line may lack its newline. Malformed input panics or yields
+/// garbage rather than being reported.
+pub struct Records<'a> {
+    data: &'a [u8],
+    pos: usize,
+    decimals: u32,
+    origin: Option<Origin>,
+    failure: Option<Failure>,
+}
+
+impl<'a> Records<'a> {
+    pub fn new(data: &'a [u8]) -> Self {
+        Self::with_decimals(data, 1)
+    }
+
+    /// Parses temperatures with `decimals` fraction digits, scaled by
+    /// `10^decimals` so that they stay exact.
+    pub fn with_decimals(data: &'a [u8], decimals: u32) -> Self {
+        Self {
+            data,
+            pos: 0,
+            decimals,
+            origin: None,
+            failure: None,
+        }
+    }
+
+    /// Ends at a malformed line instead, keeping the [`failure`](Self::failure)
+    /// naming it by its offset as `origin` gives it.
+    pub(crate) fn within(mut self, origin: Origin) -> Self {
+        self.origin = Some(origin);
+        self
+    }
+
+    /// The malformed line the records ended at, with [`within`](Self::within).
+    pub(crate) fn failure(&mut self) -> Option<Failure> {
+        self.failure.take()
+    }
+
+    /// Ends the records at a malformed line, failing with `failure`.
+    #[cold]
+    fn stop<T>(&mut self, failure: Failure) -> Option<T> {
+        self.pos = self.data.len();
+        self.failure = Some(failure);
+        None
+    }
+}
+
+impl<'a> Iterator for Records<'a> {
+    type Item = (&'a [u8], i32);
+
+    #[inline(always)]
+    fn next(&mut self) -> Option<Self::Item> {
+        let data = self.data;
+        let pos = self.pos;
+        if pos >= data.len() {
+            return None;
+        }
+
+        // SIMD-accelerated delimiter search, for the last `;` of the line so
+        // that a wide row's name has all of its columns
+        let end = memchr::memchr(b'\n', &data[pos..])
+            .map(|i| i + pos)
+            .unwrap_or(data.len());
+        let semi = memchr::memrchr(b';', &data[pos..end]);
+        if let Some(origin) = &self.origin {
+            if let Err(failure) =
+                scan::check_line(data, origin, pos, semi.map_or(0, |i| pos + i + 1))
+            {
+                return self.stop(failure);
+            }
+        }
+        let semi = semi.unwrap() + pos;
+
+        let temp = &data[semi + 1..end];
+        let temp = if self.decimals == 1 {
+            parse_temp(temp)
+        } else {
+            parse_temp_scaled(temp, self.decimals)
+        };
+        let temp = match (temp, &self.origin) {
+            (Some(temp), _) => temp,
+            (None, Some(origin)) => {
+                return self.stop(origin.malformed(data[pos..].as_ptr(), BAD_TEMP))
+            }
+            (None, None) => panic!("{}", BAD_TEMP),
+        };
+        self.pos = end + 1;
+        Some((&data[pos..semi], temp))
+    }
+}
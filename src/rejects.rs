@@ -0,0 +1,34 @@
+use std::fmt::Display;
+use std::fs::{File, OpenOptions};
+use std::io::{self, BufWriter, Write};
+use std::sync::Mutex;
+
+/// The `--rejects` file of `ingest` and `kafka`: every line they skip, as
+/// where it came from, a tab, the reason, a tab and the raw line, so that
+/// the producers sending them can be fixed. Appended to, so that a restarted
+/// collector keeps the earlier ones.
+pub struct Rejects {
+    out: Mutex<BufWriter<File>>,
+}
+
+impl Rejects {
+    pub fn open(path: &str) -> io::Result<Self> {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        Ok(Self {
+            out: Mutex::new(BufWriter::new(file)),
+        })
+    }
+
+    /// Records `line`, found at `location` and skipped for `reason`.
+    pub fn record(&self, location: impl Display, reason: &str, line: &[u8]) -> io::Result<()> {
+        let mut out = self.out.lock().unwrap();
+        write!(out, "{}\t{}\t", location, reason)?;
+        out.write_all(line)?;
+        out.write_all(b"\n")
+    }
+
+    /// Writes out the lines recorded so far.
+    pub fn flush(&self) -> io::Result<()> {
+        self.out.lock().unwrap().flush()
+    }
+}
@@ -0,0 +1,48 @@
+use std::collections::btree_map::{self, BTreeMap};
+
+use crate::{Aggregator, StationStats, StationTable};
+
+/// Final statistics for every station, ordered by name.
+///
+/// Names that are not valid UTF-8 are converted lossily; stations that end up
+/// with the same name after conversion are merged.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(transparent))]
+pub struct Results<A = StationStats> {
+    stations: BTreeMap<String, A>,
+}
+
+impl<A: Aggregator + Clone> Results<A> {
+    pub(crate) fn from_table(table: &StationTable<A>) -> Self {
+        let mut stations: BTreeMap<String, A> = BTreeMap::new();
+        for (name, stats) in table.iter() {
+            match stations.entry(String::from_utf8_lossy(name).into_owned()) {
+                btree_map::Entry::Vacant(slot) => {
+                    slot.insert(stats.clone());
+                }
+                btree_map::Entry::Occupied(mut slot) => slot.get_mut().merge(stats),
+            }
+        }
+        Self { stations }
+    }
+}
+
+impl<A> Results<A> {
+    pub fn get(&self, station: &str) -> Option<&A> {
+        self.stations.get(station)
+    }
+
+    pub fn len(&self) -> usize {
+        self.stations.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.stations.is_empty()
+    }
+
+    /// Iterates over the stations in byte order of their names.
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &A)> {
+        self.stations.iter().map(|(name, stats)| (name.as_str(), stats))
+    }
+}
@@ -0,0 +1,108 @@
+use std::collections::HashMap;
+use std::io::{self, Write};
+
+use crate::format::{RESET, WARNING};
+
+/// Stations listed by [`Sanity::write_report`] before it only counts them.
+const MAX_LISTED: usize = 20;
+
+/// Readings found outside the plausible range at one station.
+#[derive(Clone, Copy)]
+struct Implausible {
+    count: u64,
+    min: i32,
+    max: i32,
+}
+
+/// `--sanity-check`: the plausible temperature range, in the input's
+/// fixed-point units, and the readings of each station that fell outside it.
+/// Each worker's table checks its own readings; the results are merged with
+/// the tables.
+pub struct Sanity {
+    lo: i32,
+    hi: i32,
+    found: HashMap<Box<[u8]>, Implausible>,
+}
+
+impl Sanity {
+    pub fn new(lo: i32, hi: i32) -> Self {
+        Self {
+            lo,
+            hi,
+            found: HashMap::new(),
+        }
+    }
+
+    /// An empty check of the same range, for a worker's table.
+    pub fn sibling(&self) -> Self {
+        Self::new(self.lo, self.hi)
+    }
+
+    #[inline(always)]
+    pub fn check(&mut self, name: &[u8], temp: i32) {
+        if temp < self.lo || temp > self.hi {
+            self.record(name, temp);
+        }
+    }
+
+    #[cold]
+    fn record(&mut self, name: &[u8], temp: i32) {
+        let found = self.found.entry(name.into()).or_insert(Implausible {
+            count: 0,
+            min: temp,
+            max: temp,
+        });
+        found.count += 1;
+        found.min = found.min.min(temp);
+        found.max = found.max.max(temp);
+    }
+
+    pub fn merge(&mut self, other: &Sanity) {
+        for (name, theirs) in &other.found {
+            let mine = self.found.entry(name.clone()).or_insert(Implausible {
+                count: 0,
+                ..*theirs
+            });
+            mine.count += theirs.count;
+            mine.min = mine.min.min(theirs.min);
+            mine.max = mine.max.max(theirs.max);
+        }
+    }
+
+    /// Lists the stations with implausible readings, most of them first,
+    /// with how many there were and their range, for stderr. With `color`,
+    /// the summary line is highlighted when there are any.
+    pub fn write_report(&self, scale: f64, color: bool, out: &mut dyn Write) -> io::Result<()> {
+        let readings: u64 = self.found.values().map(|found| found.count).sum();
+        let (start, end) = match color && readings > 0 {
+            true => (WARNING, RESET),
+            false => ("", ""),
+        };
+        writeln!(
+            out,
+            "{}sanity check: {} readings outside {}..{} at {} stations{}",
+            start,
+            readings,
+            self.lo as f64 / scale,
+            self.hi as f64 / scale,
+            self.found.len(),
+            end
+        )?;
+        let mut stations: Vec<_> = self.found.iter().collect();
+        stations.sort_by(|a, b| b.1.count.cmp(&a.1.count).then(a.0.cmp(b.0)));
+        for (name, found) in stations.iter().take(MAX_LISTED) {
+            writeln!(
+                out,
+                "  {}: {} readings, {} to {}",
+                String::from_utf8_lossy(name),
+                found.count,
+                found.min as f64 / scale,
+                found.max as f64 / scale
+            )?;
+        }
+        if stations.len() > MAX_LISTED {
+            writeln!(out, "  and {} more stations", stations.len() - MAX_LISTED)?;
+        }
+        Ok(())
+    }
+}
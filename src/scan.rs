@@ -0,0 +1,472 @@
+use crate::cli::Failure;
+use crate::origin::Origin;
+use crate::MAX_NAME_LEN;
+
+/// Calls `f(name, temp)` for every `name;temp` line of `data`, like
+/// [`Records`](crate::Records) but finding delimiters with explicit SIMD where
+/// the CPU supports it. One pass over each 64-byte block yields bitmasks of
+/// every `;` and `\n` in it, replacing two `memchr` calls per line. The
+/// implementation is picked at runtime on x86-64 (AVX-512BW, then AVX2) and
+/// is always NEON on aarch64. RISC-V uses the vector extension when built
+/// with the `rvv` feature and the CPU has it. Elsewhere it uses `std::simd`
+/// when built with the `portable_simd` feature, or falls back to `memchr`.
+/// The final line may lack its newline. Unless built with `fast-unsafe`, a
+/// line without a `;` or with a name the table cannot hold stops the scan,
+/// failing with its offset as `origin` gives it.
+#[inline]
+pub fn for_each_record<'a, F: FnMut(&'a [u8], &'a [u8])>(
+    data: &'a [u8],
+    origin: &Origin,
+    f: F,
+) -> Result<(), Failure> {
+    #[cfg(target_arch = "x86_64")]
+    {
+        if is_x86_feature_detected!("avx512bw") {
+            // SAFETY: the CPU supports the instructions the function is compiled for
+            return unsafe { x86::scan_avx512(data, origin, f) };
+        }
+        if is_x86_feature_detected!("avx2") {
+            // SAFETY: as above
+            return unsafe { x86::scan_avx2(data, origin, f) };
+        }
+    }
+    #[cfg(all(target_arch = "riscv64", target_os = "linux", feature = "rvv"))]
+    {
+        if riscv::has_vector() {
+            // SAFETY: the CPU implements the vector extension
+            return unsafe { riscv::scan_rvv(data, origin, f) };
+        }
+    }
+    #[cfg(all(target_arch = "aarch64", target_feature = "neon"))]
+    return arm::scan_neon(data, origin, f);
+    #[cfg(all(
+        not(all(target_arch = "aarch64", target_feature = "neon")),
+        feature = "portable_simd"
+    ))]
+    return portable::scan(data, origin, f);
+    #[cfg(not(any(
+        all(target_arch = "aarch64", target_feature = "neon"),
+        feature = "portable_simd"
+    )))]
+    scan_memchr(data, origin, f)
+}
+
+/// Asks the CPU to start loading the cache line at `ptr`. Only a hint: it
+/// never faults and is a no-op on architectures without a stable instruction.
+#[inline(always)]
+pub fn prefetch(ptr: *const u8) {
+    #[cfg(target_arch = "x86_64")]
+    // SAFETY: prefetching has no side effects, whatever the address
+    unsafe {
+        std::arch::x86_64::_mm_prefetch::<{ std::arch::x86_64::_MM_HINT_T0 }>(ptr.cast());
+    }
+    #[cfg(target_arch = "aarch64")]
+    // SAFETY: as above
+    unsafe {
+        std::arch::asm!(
+            "prfm pldl1keep, [{}]",
+            in(reg) ptr,
+            options(nostack, readonly, preserves_flags)
+        );
+    }
+    #[cfg(not(any(target_arch = "x86_64", target_arch = "aarch64")))]
+    let _ = ptr;
+}
+
+/// Compares a stored station name with one being looked up, both of the
+/// same length.
+#[inline(always)]
+pub fn names_equal(stored: &[u8], name: &[u8]) -> bool {
+    #[cfg(all(target_arch = "aarch64", target_feature = "neon"))]
+    {
+        arm::names_equal(stored, name)
+    }
+    #[cfg(all(
+        not(all(target_arch = "aarch64", target_feature = "neon")),
+        feature = "portable_simd"
+    ))]
+    {
+        portable::names_equal(stored, name)
+    }
+    #[cfg(not(any(
+        all(target_arch = "aarch64", target_feature = "neon"),
+        feature = "portable_simd"
+    )))]
+    {
+        stored == name
+    }
+}
+
+/// Name of the scanner [`for_each_record`] uses on this CPU.
+pub fn implementation() -> &'static str {
+    #[cfg(target_arch = "x86_64")]
+    {
+        if is_x86_feature_detected!("avx512bw") {
+            return "avx512bw";
+        }
+        if is_x86_feature_detected!("avx2") {
+            return "avx2";
+        }
+    }
+    #[cfg(all(target_arch = "riscv64", target_os = "linux", feature = "rvv"))]
+    {
+        if riscv::has_vector() {
+            return "rvv";
+        }
+    }
+    if cfg!(all(target_arch = "aarch64", target_feature = "neon")) {
+        return "neon";
+    }
+    if cfg!(feature = "portable_simd") {
+        return "std::simd";
+    }
+    "memchr"
+}
+
+#[cfg_attr(
+    any(
+        all(target_arch = "aarch64", target_feature = "neon"),
+        feature = "portable_simd"
+    ),
+    allow(dead_code)
+)]
+fn scan_memchr<'a, F: FnMut(&'a [u8], &'a [u8])>(
+    data: &'a [u8],
+    origin: &Origin,
+    mut f: F,
+) -> Result<(), Failure> {
+    let mut pos = 0;
+    while pos < data.len() {
+        let end = memchr::memchr(b'\n', &data[pos..]).map_or(data.len(), |i| i + pos);
+        // The last `;`, as the blocks find it, so that a wide row's name has
+        // all of its columns
+        let temp_start = memchr::memrchr(b';', &data[pos..end]).map_or(0, |i| pos + i + 1);
+        check_line(data, origin, pos, temp_start)?;
+        f(&data[pos..temp_start - 1], &data[temp_start..end]);
+        pos = end + 1;
+    }
+    Ok(())
+}
+
+/// Fails on the line of `data` at `start`, whose temperature starts at
+/// `temp_start` after its last `;`, if it has no `;` there (`temp_start` not
+/// past `start`) or a name the table cannot hold. `fast-unsafe` builds trust
+/// every line to be well-formed.
+#[inline(always)]
+pub(crate) fn check_line(
+    data: &[u8],
+    origin: &Origin,
+    start: usize,
+    temp_start: usize,
+) -> Result<(), Failure> {
+    if cfg!(feature = "fast-unsafe") {
+        return Ok(());
+    }
+    let line = data[start..].as_ptr();
+    if temp_start <= start {
+        return Err(origin.malformed(line, "no ';' separator"));
+    }
+    match temp_start - 1 - start {
+        0 => Err(origin.malformed(line, "empty station name")),
+        len if len > MAX_NAME_LEN => Err(origin.malformed(line, "station name over 100 bytes")),
+        _ => Ok(()),
+    }
+}
+
+/// Walks `data` in 64-byte blocks, using `masks` to find the `;` and `\n`
+/// bytes of each block as bitmasks (bit `i` for byte `i`). The last partial
+/// block is copied into a zero-padded buffer so `masks` always sees 64 bytes.
+#[cfg_attr(
+    not(any(
+        target_arch = "x86_64",
+        target_arch = "aarch64",
+        all(target_arch = "riscv64", target_os = "linux", feature = "rvv"),
+        feature = "portable_simd"
+    )),
+    allow(dead_code)
+)]
+#[inline(always)]
+fn scan_blocks<'a, F, M>(
+    data: &'a [u8],
+    origin: &Origin,
+    mut f: F,
+    mut masks: M,
+) -> Result<(), Failure>
+where
+    F: FnMut(&'a [u8], &'a [u8]),
+    M: FnMut(&[u8; 64]) -> (u64, u64),
+{
+    let mut line_start = 0;
+    // One past the last `;`, so that a line without one shows as not past
+    // its start
+    let mut temp_start = 0;
+    let mut visit = |base: usize, semis: u64, newlines: u64| {
+        let mut bits = semis | newlines;
+        while bits != 0 {
+            let i = bits.trailing_zeros();
+            let pos = base + i as usize;
+            if semis >> i & 1 != 0 {
+                temp_start = pos + 1;
+            } else {
+                check_line(data, origin, line_start, temp_start)?;
+                // SAFETY: a well-formed line has its `;` after its start
+                unsafe {
+                    f(
+                        crate::at(data, line_start..temp_start - 1),
+                        crate::at(data, temp_start..pos),
+                    )
+                };
+                line_start = pos + 1;
+            }
+            bits &= bits - 1;
+        }
+        Ok(())
+    };
+
+    let mut blocks = data.chunks_exact(64);
+    let mut base = 0;
+    for block in &mut blocks {
+        let (semis, newlines) = masks(block.try_into().unwrap());
+        visit(base, semis, newlines)?;
+        base += 64;
+    }
+    let tail = blocks.remainder();
+    if !tail.is_empty() {
+        let mut padded = [0u8; 64];
+        padded[..tail.len()].copy_from_slice(tail);
+        let (semis, newlines) = masks(&padded);
+        visit(base, semis, newlines)?;
+    }
+    if line_start < data.len() {
+        check_line(data, origin, line_start, temp_start)?;
+        // SAFETY: as above
+        unsafe {
+            f(
+                crate::at(data, line_start..temp_start - 1),
+                crate::at(data, temp_start..),
+            )
+        };
+    }
+    Ok(())
+}
+
+#[cfg(target_arch = "x86_64")]
+mod x86 {
+    use std::arch::x86_64::*;
+
+    use super::scan_blocks;
+    use crate::cli::Failure;
+    use crate::origin::Origin;
+
+    #[target_feature(enable = "avx2")]
+    pub unsafe fn scan_avx2<'a, F: FnMut(&'a [u8], &'a [u8])>(
+        data: &'a [u8],
+        origin: &Origin,
+        f: F,
+    ) -> Result<(), Failure> {
+        scan_blocks(data, origin, f, |block| {
+            let semi = _mm256_set1_epi8(b';' as i8);
+            let newline = _mm256_set1_epi8(b'\n' as i8);
+            let ptr = block.as_ptr() as *const __m256i;
+            // SAFETY: the block holds 64 readable bytes; unaligned loads are allowed
+            let (lo, hi) = unsafe { (_mm256_loadu_si256(ptr), _mm256_loadu_si256(ptr.add(1))) };
+            let mask =
+                |v: __m256i, c: __m256i| _mm256_movemask_epi8(_mm256_cmpeq_epi8(v, c)) as u32;
+            let semis = mask(lo, semi) as u64 | (mask(hi, semi) as u64) << 32;
+            let newlines = mask(lo, newline) as u64 | (mask(hi, newline) as u64) << 32;
+            (semis, newlines)
+        })
+    }
+
+    #[target_feature(enable = "avx512bw")]
+    pub unsafe fn scan_avx512<'a, F: FnMut(&'a [u8], &'a [u8])>(
+        data: &'a [u8],
+        origin: &Origin,
+        f: F,
+    ) -> Result<(), Failure> {
+        scan_blocks(data, origin, f, |block| {
+            // SAFETY: the block holds 64 readable bytes; unaligned loads are allowed
+            let v = unsafe { _mm512_loadu_si512(block.as_ptr() as *const __m512i) };
+            let semis = _mm512_cmpeq_epi8_mask(v, _mm512_set1_epi8(b';' as i8));
+            let newlines = _mm512_cmpeq_epi8_mask(v, _mm512_set1_epi8(b'\n' as i8));
+            (semis, newlines)
+        })
+    }
+}
+
+#[cfg(all(target_arch = "aarch64", target_feature = "neon"))]
+mod arm {
+    use std::arch::aarch64::*;
+
+    use super::scan_blocks;
+    use crate::cli::Failure;
+    use crate::origin::Origin;
+
+    /// Bit `i % 8` for lane `i`, to turn comparison results into a bitmask.
+    const LANE_BITS: [u8; 16] = [1, 2, 4, 8, 16, 32, 64, 128, 1, 2, 4, 8, 16, 32, 64, 128];
+
+    /// Packs the comparisons of four 16-byte vectors into one bit per byte,
+    /// as NEON has no equivalent of x86's `movemask`.
+    #[inline(always)]
+    fn movemask(c: [uint8x16_t; 4]) -> u64 {
+        // SAFETY: NEON is enabled for the whole target
+        unsafe {
+            let bits = vld1q_u8(LANE_BITS.as_ptr());
+            let t = c.map(|v| vandq_u8(v, bits));
+            let s0 = vpaddq_u8(t[0], t[1]);
+            let s1 = vpaddq_u8(t[2], t[3]);
+            let s = vpaddq_u8(s0, s1);
+            let s = vpaddq_u8(s, s);
+            vgetq_lane_u64(vreinterpretq_u64_u8(s), 0)
+        }
+    }
+
+    #[inline]
+    pub fn scan_neon<'a, F: FnMut(&'a [u8], &'a [u8])>(
+        data: &'a [u8],
+        origin: &Origin,
+        f: F,
+    ) -> Result<(), Failure> {
+        scan_blocks(data, origin, f, |block| {
+            // SAFETY: the block holds 64 readable bytes; NEON loads need no alignment
+            let v = unsafe { vld1q_u8_x4(block.as_ptr()) };
+            let v = [v.0, v.1, v.2, v.3];
+            // SAFETY: NEON is enabled for the whole target
+            let (semi, newline) = unsafe { (vdupq_n_u8(b';'), vdupq_n_u8(b'\n')) };
+            let semis = movemask(v.map(|x| unsafe { vceqq_u8(x, semi) }));
+            let newlines = movemask(v.map(|x| unsafe { vceqq_u8(x, newline) }));
+            (semis, newlines)
+        })
+    }
+
+    /// Compares 16 bytes at a time, then the remainder bytewise.
+    #[inline(always)]
+    pub fn names_equal(a: &[u8], b: &[u8]) -> bool {
+        let mut i = 0;
+        while i + 16 <= a.len() {
+            // SAFETY: both slices hold at least `i + 16` bytes
+            let equal = unsafe {
+                let x = vld1q_u8(a.as_ptr().add(i));
+                let y = vld1q_u8(b.as_ptr().add(i));
+                vminvq_u8(vceqq_u8(x, y)) == 0xff
+            };
+            if !equal {
+                return false;
+            }
+            i += 16;
+        }
+        a[i..] == b[i..]
+    }
+}
+
+#[cfg(all(target_arch = "riscv64", target_os = "linux"))]
+pub mod riscv {
+    /// `AT_HWCAP` bit for the single-letter `V` extension.
+    const HWCAP_V: libc::c_ulong = 1 << (b'V' - b'A');
+
+    /// Whether the CPU implements the vector extension (RVV 1.0).
+    pub fn has_vector() -> bool {
+        // SAFETY: getauxval has no preconditions
+        unsafe { libc::getauxval(libc::AT_HWCAP) & HWCAP_V != 0 }
+    }
+
+    /// # Safety
+    ///
+    /// The CPU must implement the vector extension; see [`has_vector`].
+    #[cfg(feature = "rvv")]
+    pub unsafe fn scan_rvv<'a, F: FnMut(&'a [u8], &'a [u8])>(
+        data: &'a [u8],
+        origin: &crate::origin::Origin,
+        f: F,
+    ) -> Result<(), crate::cli::Failure> {
+        // SAFETY: guaranteed by the caller
+        super::scan_blocks(data, origin, f, |block| unsafe { masks(block) })
+    }
+
+    /// Compares the 64 bytes of `block` with `;` and `\n` in one vector
+    /// register group. The vector extension guarantees at least 128-bit
+    /// registers, so four of them (LMUL=4) always hold the whole block, and
+    /// the 64 resulting mask bits fit in one 64-bit element. The assembler is
+    /// told about the extension inline because the `v` target feature is not
+    /// available on stable Rust.
+    #[cfg(feature = "rvv")]
+    #[inline(always)]
+    unsafe fn masks(block: &[u8; 64]) -> (u64, u64) {
+        let (semis, newlines): (u64, u64);
+        // SAFETY: reads exactly the 64 bytes of `block`; only clobbers the
+        // vector registers declared below
+        unsafe {
+            std::arch::asm!(
+                ".option push",
+                ".option arch, +v",
+                "vsetvli zero, {len}, e8, m4, ta, ma",
+                "vle8.v v8, ({ptr})",
+                "vmseq.vx v0, v8, {semi}",
+                "vmseq.vx v4, v8, {newline}",
+                "vsetivli zero, 1, e64, m1, ta, ma",
+                "vmv.x.s {semis}, v0",
+                "vmv.x.s {newlines}, v4",
+                ".option pop",
+                len = in(reg) 64usize,
+                ptr = in(reg) block.as_ptr(),
+                semi = in(reg) b';' as usize,
+                newline = in(reg) b'\n' as usize,
+                semis = out(reg) semis,
+                newlines = out(reg) newlines,
+                out("v0") _,
+                out("v4") _,
+                out("v8") _,
+                out("v9") _,
+                out("v10") _,
+                out("v11") _,
+                options(nostack, readonly, pure),
+            );
+        }
+        (semis, newlines)
+    }
+}
+
+/// Architecture-independent versions built on `std::simd`, for targets
+/// without hand-written intrinsics above.
+#[cfg(feature = "portable_simd")]
+mod portable {
+    use std::simd::prelude::*;
+
+    use super::scan_blocks;
+    use crate::cli::Failure;
+    use crate::origin::Origin;
+
+    #[cfg_attr(
+        all(target_arch = "aarch64", target_feature = "neon"),
+        allow(dead_code)
+    )]
+    #[inline]
+    pub fn scan<'a, F: FnMut(&'a [u8], &'a [u8])>(
+        data: &'a [u8],
+        origin: &Origin,
+        f: F,
+    ) -> Result<(), Failure> {
+        scan_blocks(data, origin, f, |block| {
+            let v = u8x64::from_array(*block);
+            let semis = v.simd_eq(u8x64::splat(b';')).to_bitmask();
+            let newlines = v.simd_eq(u8x64::splat(b'\n')).to_bitmask();
+            (semis, newlines)
+        })
+    }
+
+    /// Compares 16 bytes at a time, then the remainder bytewise.
+    #[cfg_attr(
+        all(target_arch = "aarch64", target_feature = "neon"),
+        allow(dead_code)
+    )]
+    #[inline(always)]
+    pub fn names_equal(a: &[u8], b: &[u8]) -> bool {
+        let (a_chunks, a_rest) = a.as_chunks::<16>();
+        let (b_chunks, b_rest) = b.as_chunks::<16>();
+        a_chunks
+            .iter()
+            .zip(b_chunks)
+            .all(|(x, y)| u8x16::from_array(*x) == u8x16::from_array(*y))
+            && a_rest == b_rest
+    }
+}
@@ -0,0 +1,199 @@
+//! `--record-schedule` and `--replay-schedule`: the chunks a mapped input is
+//! split into and the worker thread that aggregated each, written to a file
+//! that a later run follows instead of letting its threads race for work, so
+//! that a bug that depends on how the workers' tables are filled and merged
+//! can be reproduced run after run.
+//!
+//! The file is text: a `run THREADS WORKERS BYTES` line for each parallel
+//! pass over the input (`--auto-tune` and `--checkpoint` make several), then
+//! a `START END WORKER` line for each chunk of the pass in input order, with
+//! offsets relative to the start of the pass.
+//!
+//! `--deterministic` follows a schedule fixed without a file: the pass is cut
+//! into chunks of `--chunk-size` and worker `i` of `n` takes chunks `i`,
+//! `i + n`, ... in input order, so two runs with the same `--threads` fill
+//! and merge the same tables whatever the timing.
+
+use std::fmt::Write as _;
+use std::fs::{self, File};
+use std::io::Write;
+
+use crate::cli::{Exit, Failure, Options};
+use crate::line_chunks;
+use crate::parallel::Setup;
+
+/// One parallel pass over the input, as recorded.
+pub struct Run {
+    pub threads: usize,
+    pub workers: usize,
+    /// Offsets and worker of each chunk, in input order.
+    pub chunks: Vec<(usize, usize, usize)>,
+}
+
+pub enum Schedule {
+    Record {
+        path: String,
+        file: File,
+    },
+    Replay {
+        path: String,
+        runs: std::vec::IntoIter<Run>,
+        done: usize,
+    },
+    Deterministic,
+}
+
+impl Schedule {
+    /// The schedule `opts` asks to record or replay, if any.
+    pub fn setup(opts: &Options) -> Result<Option<Self>, Failure> {
+        if opts.deterministic {
+            return Ok(Some(Schedule::Deterministic));
+        }
+        if let Some(path) = &opts.record_schedule {
+            let io_error = |e| Failure::io(format!("{}: {}", path, e));
+            let mut file = File::create(path).map_err(io_error)?;
+            let header =
+                "# onebrc schedule: run THREADS WORKERS BYTES, then START END WORKER per chunk\n";
+            file.write_all(header.as_bytes()).map_err(io_error)?;
+            return Ok(Some(Schedule::Record {
+                path: path.clone(),
+                file,
+            }));
+        }
+        let Some(path) = &opts.replay_schedule else {
+            return Ok(None);
+        };
+        let text = fs::read_to_string(path).map_err(|e| Failure::io(format!("{}: {}", path, e)))?;
+        let runs =
+            parse(&text).map_err(|e| Failure::new(Exit::Data, format!("{}: {}", path, e)))?;
+        Ok(Some(Schedule::Replay {
+            path: path.clone(),
+            runs: runs.into_iter(),
+            done: 0,
+        }))
+    }
+
+    /// The recorded run to follow over `data`, when replaying. Fails if the
+    /// schedule has none left or its chunks do not split `data` into lines.
+    pub fn replay(&mut self, data: &[u8]) -> Result<Option<Run>, Failure> {
+        let Schedule::Replay { path, runs, done } = self else {
+            return Ok(None);
+        };
+        *done += 1;
+        let mismatch = |msg: String| {
+            Err(Failure::new(
+                Exit::Data,
+                format!("{}: run {}: {}", path, done, msg),
+            ))
+        };
+        let Some(run) = runs.next() else {
+            return mismatch("the schedule has no more runs".to_string());
+        };
+        let mut expected = 0;
+        for &(start, end, worker) in &run.chunks {
+            if start != expected || end <= start || worker >= run.workers.max(1) {
+                return mismatch(format!("chunk {}..{} does not follow on", start, end));
+            }
+            if end > data.len() {
+                return mismatch(format!(
+                    "chunk {}..{} runs past the {} bytes of this pass",
+                    start,
+                    end,
+                    data.len()
+                ));
+            }
+            if end < data.len() && data[end - 1] != b'\n' {
+                return mismatch(format!(
+                    "chunk {}..{} does not end a line of this input",
+                    start, end
+                ));
+            }
+            expected = end;
+        }
+        if expected != data.len() {
+            return mismatch(format!(
+                "covers {} bytes, but this pass has {}",
+                expected,
+                data.len()
+            ));
+        }
+        Ok(Some(run))
+    }
+
+    /// Appends the run that split `data` into `chunks`, each aggregated by
+    /// the worker in `assigned`, when recording.
+    pub fn record(
+        &mut self,
+        data: &[u8],
+        threads: usize,
+        workers: usize,
+        chunks: &[&[u8]],
+        assigned: &[usize],
+    ) -> Result<(), Failure> {
+        let Schedule::Record { path, file } = self else {
+            return Ok(());
+        };
+        let mut text = format!("run {} {} {}\n", threads, workers, data.len());
+        for (chunk, worker) in chunks.iter().zip(assigned) {
+            let start = chunk.as_ptr() as usize - data.as_ptr() as usize;
+            let _ = writeln!(text, "{} {} {}", start, start + chunk.len(), worker);
+        }
+        file.write_all(text.as_bytes())
+            .map_err(|e| Failure::io(format!("{}: {}", path, e)))
+    }
+}
+
+/// The run `--deterministic` follows over `data` with `setup`: its chunks
+/// dealt out to the workers in turn.
+pub fn deterministic(data: &[u8], setup: Setup) -> Run {
+    let mut chunks = Vec::new();
+    for chunk in line_chunks(data, setup.chunk_size) {
+        let start = chunk.as_ptr() as usize - data.as_ptr() as usize;
+        chunks.push((start, start + chunk.len(), chunks.len() % setup.threads.max(1)));
+    }
+    Run {
+        threads: setup.threads,
+        workers: setup.threads.min(chunks.len()),
+        chunks,
+    }
+}
+
+fn parse(text: &str) -> Result<Vec<Run>, String> {
+    let mut runs: Vec<Run> = Vec::new();
+    for (number, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let invalid = || {
+            format!(
+                "line {}: expected a run or a chunk, got '{}'",
+                number + 1,
+                line
+            )
+        };
+        let fields = |line: &str| -> Result<Vec<usize>, String> {
+            let fields: Vec<usize> = line
+                .split_whitespace()
+                .map(|field| field.parse().map_err(|_| invalid()))
+                .collect::<Result<_, _>>()?;
+            if fields.len() != 3 {
+                return Err(invalid());
+            }
+            Ok(fields)
+        };
+        if let Some(rest) = line.strip_prefix("run ") {
+            let run = fields(rest)?;
+            runs.push(Run {
+                threads: run[0],
+                workers: run[1],
+                chunks: Vec::new(),
+            });
+        } else {
+            let chunk = fields(line)?;
+            let run = runs.last_mut().ok_or_else(invalid)?;
+            run.chunks.push((chunk[0], chunk[1], chunk[2]));
+        }
+    }
+    Ok(runs)
+}
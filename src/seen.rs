@@ -0,0 +1,50 @@
+use std::cmp::Ordering;
+use std::collections::HashMap;
+
+use crate::cli::{Options, Order};
+
+/// Where each station of a table first appeared, for `--order input`: the
+/// address of the chunk it was first seen in, then how many stations the
+/// table had seen before it. The chunks of a mapped input are ordered by
+/// address, and a table aggregates its chunks one after the other, so the
+/// smallest position any worker recorded is the first appearance. Streamed
+/// input goes through a single table, whose order is the input's.
+#[derive(Default)]
+pub struct FirstSeen {
+    chunk: u64,
+    stations: HashMap<Box<[u8]>, (u64, u64)>,
+}
+
+/// Tracking for a table printed in the `--order` of `opts`, if it needs any.
+pub fn for_order(opts: &Options) -> Option<FirstSeen> {
+    (opts.order == Order::Input).then(FirstSeen::default)
+}
+
+impl FirstSeen {
+    /// Notes that the following stations come from `chunk`.
+    pub fn start_chunk(&mut self, chunk: &[u8]) {
+        self.chunk = chunk.as_ptr() as u64;
+    }
+
+    /// Records a station the table has just inserted.
+    #[cold]
+    pub fn insert(&mut self, name: &[u8]) {
+        let position = (self.chunk, self.stations.len() as u64);
+        self.stations.entry(name.into()).or_insert(position);
+    }
+
+    /// Merges what a worker's table saw into this one.
+    pub fn merge(&mut self, other: &FirstSeen) {
+        for (name, &theirs) in &other.stations {
+            let mine = self.stations.entry(name.clone()).or_insert(theirs);
+            *mine = (*mine).min(theirs);
+        }
+    }
+
+    /// Orders stations by first appearance. Stations never recorded, such as
+    /// the ones a merged dump brought in, come last, by name.
+    pub fn cmp(&self, a: &[u8], b: &[u8]) -> Ordering {
+        let position = |name| self.stations.get(name).copied().unwrap_or((u64::MAX, 0));
+        position(a).cmp(&position(b)).then_with(|| a.cmp(b))
+    }
+}
@@ -0,0 +1,218 @@
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::TcpListener;
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+use crate::cli::Options;
+use crate::rejects::Rejects;
+use crate::seen;
+use crate::{aggregate_untrusted_line, output_results, StationTable};
+
+/// Shared state of the ingest server: one table fed by every connection.
+pub struct Ingest {
+    pub table: Mutex<StationTable>,
+    rejects: Option<Rejects>,
+}
+
+/// An empty table for the stations of `ingest`.
+fn new_table(opts: &Options) -> StationTable {
+    let mut table = StationTable::new();
+    table.first_seen = seen::for_order(opts);
+    table
+}
+
+/// Runs `onebrc ingest`. Each client streams newline-terminated lines:
+///
+/// * `station;temp` is aggregated into the shared table,
+/// * `SNAPSHOT` replies with the current results in the usual output format,
+/// * `RESET` clears all stations and replies `OK`.
+///
+/// Malformed data lines are ignored so a misbehaving client cannot take the
+/// collector down. Once half the table is occupied further data is dropped
+/// rather than risking a full table. With `--rejects` the dropped lines are
+/// logged, numbered by client and line, e.g. `3:17` for the 17th line of the
+/// third connection.
+pub fn run(opts: &Options) -> io::Result<()> {
+    let rejects = opts.rejects.as_deref().map(Rejects::open).transpose()?;
+    let state = Arc::new(Ingest {
+        table: Mutex::new(new_table(opts)),
+        rejects,
+    });
+    let opts = Arc::new(opts.clone());
+    if let Some(addr) = &opts.flight {
+        start_flight(addr, state.clone())?;
+    }
+
+    if let Some(path) = opts.listen.strip_prefix("unix:") {
+        remove_stale_socket(path)?;
+        let listener = UnixListener::bind(path)?;
+        eprintln!("ingest: listening on {}", opts.listen);
+        let mut client = 0;
+        for stream in listener.incoming() {
+            let Some(stream) = accepted(stream) else {
+                continue;
+            };
+            client += 1;
+            let (state, opts) = (state.clone(), opts.clone());
+            thread::spawn(move || {
+                let served = stream
+                    .try_clone()
+                    .and_then(|reader| serve(reader, stream, client, &state, &opts));
+                report(client, served);
+            });
+        }
+    } else if let Some(addr) = opts.listen.strip_prefix("tcp:") {
+        let listener = TcpListener::bind(addr)?;
+        eprintln!("ingest: listening on {}", opts.listen);
+        let mut client = 0;
+        for stream in listener.incoming() {
+            let Some(stream) = accepted(stream) else {
+                continue;
+            };
+            client += 1;
+            let (state, opts) = (state.clone(), opts.clone());
+            thread::spawn(move || {
+                let served = stream
+                    .try_clone()
+                    .and_then(|reader| serve(reader, stream, client, &state, &opts));
+                report(client, served);
+            });
+        }
+    } else {
+        return Err(io::Error::other(format!(
+            "--listen expects unix:PATH or tcp:HOST:PORT, got '{}'",
+            opts.listen
+        )));
+    }
+    Ok(())
+}
+
+/// Removes the socket file at `path` if it was left by a server that is no
+/// longer running, which would make bind fail. A socket something still
+/// accepts on is left alone and reported as in use.
+fn remove_stale_socket(path: &str) -> io::Result<()> {
+    match UnixStream::connect(path) {
+        Ok(_) => Err(io::Error::new(
+            io::ErrorKind::AddrInUse,
+            format!("socket in use: {}", path),
+        )),
+        Err(e) if e.kind() == io::ErrorKind::ConnectionRefused => std::fs::remove_file(path),
+        Err(_) => Ok(()),
+    }
+}
+
+/// The accepted connection, or `None` after logging why accepting failed;
+/// running out of descriptors or a client hanging up early only costs that
+/// connection.
+fn accepted<S>(stream: io::Result<S>) -> Option<S> {
+    stream
+        .map_err(|e| eprintln!("ingest: accepting a connection: {}", e))
+        .ok()
+}
+
+/// Logs how connection number `client` failed, if it did.
+fn report(client: u64, served: io::Result<()>) {
+    if let Err(e) = served {
+        eprintln!("ingest: client {}: {}", client, e);
+    }
+}
+
+#[cfg(feature = "flight")]
+fn start_flight(addr: &str, state: Arc<Ingest>) -> io::Result<()> {
+    let addr = addr
+        .parse()
+        .map_err(|e| io::Error::other(format!("invalid --flight address '{}': {}", addr, e)))?;
+    eprintln!("ingest: serving Arrow Flight on {}", addr);
+    thread::spawn(move || crate::flight::serve(addr, state));
+    Ok(())
+}
+
+#[cfg(not(feature = "flight"))]
+fn start_flight(_addr: &str, _state: Arc<Ingest>) -> io::Result<()> {
+    Err(io::Error::other(
+        "--flight requires building with the `flight` feature",
+    ))
+}
+
+/// Serves connection number `client` until it disconnects.
+fn serve<R: Read, W: Write>(
+    reader: R,
+    mut writer: W,
+    client: u64,
+    state: &Ingest,
+    opts: &Options,
+) -> io::Result<()> {
+    let mut reader = BufReader::with_capacity(1 << 16, reader);
+    let mut line = Vec::new();
+    let mut lines = Lines { client, count: 0 };
+
+    loop {
+        let buf = reader.fill_buf()?;
+        if buf.is_empty() {
+            return Ok(());
+        }
+
+        // Aggregate every complete line in the buffer under a single lock
+        let Some(last_nl) = memchr::memrchr(b'\n', buf) else {
+            // A partial line; fall back to reading it whole
+            line.clear();
+            if reader.read_until(b'\n', &mut line)? == 0 {
+                return Ok(());
+            }
+            handle_batch(&line, &mut lines, state, opts, &mut writer)?;
+            continue;
+        };
+        let len = last_nl + 1;
+        handle_batch(&buf[..len], &mut lines, state, opts, &mut writer)?;
+        reader.consume(len);
+    }
+}
+
+/// Lines read from one client so far.
+struct Lines {
+    client: u64,
+    count: u64,
+}
+
+fn handle_batch<W: Write>(
+    batch: &[u8],
+    lines: &mut Lines,
+    state: &Ingest,
+    opts: &Options,
+    writer: &mut W,
+) -> io::Result<()> {
+    // Replies are written once the lock is released, so that a client slow
+    // to read them holds up no one else
+    let mut out = Vec::new();
+    let mut table = state.table.lock().unwrap();
+    let batch = batch.strip_suffix(b"\n").unwrap_or(batch);
+    for line in batch.split(|&b| b == b'\n') {
+        lines.count += 1;
+        match line.strip_suffix(b"\r").unwrap_or(line) {
+            b"" => {}
+            b"SNAPSHOT" => output_results(&table, opts, &mut out)?,
+            b"RESET" => {
+                *table = new_table(opts);
+                out.extend_from_slice(b"OK\n");
+            }
+            _ => {
+                if let (Err(reason), Some(rejects)) =
+                    (aggregate_untrusted_line(&mut table, line), &state.rejects)
+                {
+                    rejects.record(
+                        format_args!("{}:{}", lines.client, lines.count),
+                        reason,
+                        line,
+                    )?;
+                }
+            }
+        }
+    }
+    drop(table);
+    writer.write_all(&out)?;
+    match &state.rejects {
+        Some(rejects) => rejects.flush(),
+        None => Ok(()),
+    }
+}
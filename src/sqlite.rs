@@ -0,0 +1,87 @@
+//! `--format sqlite`: the results as a table of the SQLite database at
+//! `--output`, replaced on every run, so that a scheduled job always leaves
+//! the latest aggregate behind for whatever queries it.
+
+use std::io;
+
+use rusqlite::types::Value;
+use rusqlite::{params_from_iter, Connection};
+
+use crate::cli::Options;
+use crate::display_name;
+use crate::format::{self, Row};
+use crate::geo::GeoTable;
+use crate::stats::{Stat, Summary};
+
+/// Writes `rows` to the `--sql-table` of the database at `path`, creating
+/// either when missing. The other tables of the database are left alone.
+pub fn write<A: Summary>(
+    rows: &[Row<A>],
+    opts: &Options,
+    geo: Option<&GeoTable>,
+    path: &str,
+) -> io::Result<()> {
+    let io_error = |e: rusqlite::Error| io::Error::other(format!("{}: {}", path, e));
+    let mut db = Connection::open(path).map_err(io_error)?;
+    let tx = db.transaction().map_err(io_error)?;
+
+    let mut columns = vec![("station", "TEXT PRIMARY KEY NOT NULL")];
+    columns.extend(opts.stats.iter().map(|&stat| {
+        let kind = if stat.is_count() { "INTEGER NOT NULL" } else { "REAL NOT NULL" };
+        (stat.name(), kind)
+    }));
+    if geo.is_some() {
+        columns.extend([("lat", "REAL"), ("lon", "REAL"), ("elevation", "REAL")]);
+    }
+    let definitions: Vec<String> = columns
+        .iter()
+        .map(|(name, kind)| format!("{} {}", name, kind))
+        .collect();
+    let names: Vec<&str> = columns.iter().map(|(name, _)| *name).collect();
+    let placeholders = vec!["?"; columns.len()].join(", ");
+    tx.execute_batch(&format!(
+        "DROP TABLE IF EXISTS {table}; CREATE TABLE {table} ({});",
+        definitions.join(", "),
+        table = opts.sql_table
+    ))
+    .map_err(io_error)?;
+
+    {
+        let mut insert = tx
+            .prepare(&format!(
+                "INSERT INTO {} ({}) VALUES ({})",
+                opts.sql_table,
+                names.join(", "),
+                placeholders
+            ))
+            .map_err(io_error)?;
+        let mut values = Vec::with_capacity(columns.len());
+        for (name, stats) in rows {
+            values.clear();
+            values.push(Value::Text(
+                display_name(name, opts.invalid_utf8).into_owned(),
+            ));
+            values.extend(opts.stats.iter().map(|&stat| {
+                if stat.is_count() {
+                    Value::Integer(stats.value(stat) as i64)
+                } else {
+                    Value::Real(format::degrees(stats.value(stat), opts))
+                }
+            }));
+            if let Some(geo) = geo {
+                match geo.get(name) {
+                    Some(loc) => values.extend([
+                        Value::Real(loc.lat),
+                        Value::Real(loc.lon),
+                        loc.elevation.map_or(Value::Null, Value::Real),
+                    ]),
+                    None => values.extend([Value::Null, Value::Null, Value::Null]),
+                }
+            }
+            insert
+                .execute(params_from_iter(&values))
+                .map_err(io_error)?;
+        }
+    }
+    tx.commit().map_err(io_error)
+}
@@ -0,0 +1,470 @@
+use std::cmp::Ordering;
+use std::collections::BTreeMap;
+use std::sync::Once;
+
+use crate::{partial, Aggregator, StationStats};
+
+/// A statistic that can be requested with `--stats`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Stat {
+    Min,
+    Max,
+    Mean,
+    Count,
+    Stddev,
+    Median,
+    /// Readings equal to the minimum, e.g. many of a sensor clipping at the
+    /// bottom of its range.
+    MinCount,
+    /// Readings equal to the maximum.
+    MaxCount,
+}
+
+impl Stat {
+    pub fn from_name(name: &str) -> Option<Stat> {
+        match name {
+            "min" => Some(Stat::Min),
+            "max" => Some(Stat::Max),
+            "mean" => Some(Stat::Mean),
+            "count" => Some(Stat::Count),
+            "stddev" => Some(Stat::Stddev),
+            "median" => Some(Stat::Median),
+            "min_count" => Some(Stat::MinCount),
+            "max_count" => Some(Stat::MaxCount),
+            _ => None,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Stat::Min => "min",
+            Stat::Max => "max",
+            Stat::Mean => "mean",
+            Stat::Count => "count",
+            Stat::Stddev => "stddev",
+            Stat::Median => "median",
+            Stat::MinCount => "min_count",
+            Stat::MaxCount => "max_count",
+        }
+    }
+
+    /// Whether the statistic is a number of readings rather than a
+    /// temperature.
+    pub fn is_count(self) -> bool {
+        matches!(self, Stat::Count | Stat::MinCount | Stat::MaxCount)
+    }
+}
+
+/// An aggregator that can report the statistics it tracks.
+pub trait Summary: Aggregator {
+    /// Returns `stat` in the input's fixed-point units (tenths of a degree by
+    /// default), or as a plain number for the counts. Only called for statistics
+    /// the aggregator was chosen for; see [`Kind`].
+    fn value(&self, stat: Stat) -> f64;
+}
+
+/// Fixed-point units per degree for inputs with `decimals` fraction digits.
+pub fn scale(decimals: u32) -> f64 {
+    10f64.powi(decimals as i32)
+}
+
+/// The cheapest accumulator that can produce a set of statistics. From
+/// `Count` to `Distribution` each variant tracks a superset of the one
+/// before; `Extremes` adds to `Basic` only. The variant is written into
+/// `--format partial` dumps, so new ones go last.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Kind {
+    Count,
+    Basic,
+    Moments,
+    Distribution,
+    Extremes,
+}
+
+impl Kind {
+    pub fn for_stats(stats: &[Stat]) -> Kind {
+        let ties = stats
+            .iter()
+            .any(|s| matches!(s, Stat::MinCount | Stat::MaxCount));
+        if stats.contains(&Stat::Median) || ties && stats.contains(&Stat::Stddev) {
+            Kind::Distribution
+        } else if ties {
+            Kind::Extremes
+        } else if stats.contains(&Stat::Stddev) {
+            Kind::Moments
+        } else if stats.iter().all(|&s| s == Stat::Count) {
+            Kind::Count
+        } else {
+            Kind::Basic
+        }
+    }
+}
+
+fn untracked(stat: Stat) -> ! {
+    unreachable!("{:?} is not tracked by this accumulator", stat)
+}
+
+impl Summary for StationStats {
+    fn value(&self, stat: Stat) -> f64 {
+        match stat {
+            Stat::Min => self.min as f64,
+            Stat::Max => self.max as f64,
+            Stat::Mean => self.sum as f64 / self.count as f64,
+            Stat::Count => self.count as f64,
+            _ => untracked(stat),
+        }
+    }
+}
+
+/// Only counts measurements, for `--stats count`.
+#[derive(Debug, Clone, Default)]
+pub struct Counter {
+    count: u64,
+}
+
+impl Aggregator for Counter {
+    #[inline(always)]
+    fn update(&mut self, _temp: i32) {
+        self.count += 1;
+    }
+
+    fn merge(&mut self, other: &Self) {
+        self.count += other.count;
+    }
+
+    fn encode(&self, out: &mut Vec<u8>) -> bool {
+        out.extend_from_slice(&self.count.to_le_bytes());
+        true
+    }
+
+    fn decode(input: &mut &[u8]) -> Option<Self> {
+        Some(Self {
+            count: u64::from_le_bytes(partial::take(input)?),
+        })
+    }
+}
+
+impl Summary for Counter {
+    fn value(&self, stat: Stat) -> f64 {
+        match stat {
+            Stat::Count => self.count as f64,
+            _ => untracked(stat),
+        }
+    }
+}
+
+/// Basic statistics plus how many readings were at the minimum and at the
+/// maximum, for `min_count` and `max_count`.
+#[derive(Debug, Clone, Default)]
+pub struct Extremes {
+    basic: StationStats,
+    at_min: u64,
+    at_max: u64,
+}
+
+impl Aggregator for Extremes {
+    #[inline(always)]
+    fn update(&mut self, temp: i32) {
+        match temp.cmp(&self.basic.min) {
+            Ordering::Less => self.at_min = 1,
+            Ordering::Equal => self.at_min += 1,
+            Ordering::Greater => {}
+        }
+        match temp.cmp(&self.basic.max) {
+            Ordering::Greater => self.at_max = 1,
+            Ordering::Equal => self.at_max += 1,
+            Ordering::Less => {}
+        }
+        self.basic.update(temp);
+    }
+
+    fn merge(&mut self, other: &Self) {
+        self.at_min = match other.basic.min.cmp(&self.basic.min) {
+            Ordering::Less => other.at_min,
+            Ordering::Equal => self.at_min + other.at_min,
+            Ordering::Greater => self.at_min,
+        };
+        self.at_max = match other.basic.max.cmp(&self.basic.max) {
+            Ordering::Greater => other.at_max,
+            Ordering::Equal => self.at_max + other.at_max,
+            Ordering::Less => self.at_max,
+        };
+        self.basic.merge(&other.basic);
+    }
+
+    fn encode(&self, out: &mut Vec<u8>) -> bool {
+        self.basic.encode(out);
+        out.extend_from_slice(&self.at_min.to_le_bytes());
+        out.extend_from_slice(&self.at_max.to_le_bytes());
+        true
+    }
+
+    fn decode(input: &mut &[u8]) -> Option<Self> {
+        Some(Self {
+            basic: StationStats::decode(input)?,
+            at_min: u64::from_le_bytes(partial::take(input)?),
+            at_max: u64::from_le_bytes(partial::take(input)?),
+        })
+    }
+}
+
+impl Summary for Extremes {
+    fn value(&self, stat: Stat) -> f64 {
+        match stat {
+            Stat::MinCount => self.at_min as f64,
+            Stat::MaxCount => self.at_max as f64,
+            _ => self.basic.value(stat),
+        }
+    }
+}
+
+/// Basic statistics plus the sum of squares needed for the standard deviation.
+/// The sum of squares is 128-bit so that finer `--decimals` scales cannot
+/// overflow it on billion-row inputs.
+#[derive(Debug, Clone, Default)]
+pub struct Moments {
+    basic: StationStats,
+    sum_sq: i128,
+}
+
+impl Aggregator for Moments {
+    #[inline(always)]
+    fn update(&mut self, temp: i32) {
+        self.basic.update(temp);
+        self.sum_sq += (temp as i64 * temp as i64) as i128;
+    }
+
+    fn merge(&mut self, other: &Self) {
+        self.basic.merge(&other.basic);
+        self.sum_sq += other.sum_sq;
+    }
+
+    fn encode(&self, out: &mut Vec<u8>) -> bool {
+        self.basic.encode(out);
+        out.extend_from_slice(&self.sum_sq.to_le_bytes());
+        true
+    }
+
+    fn decode(input: &mut &[u8]) -> Option<Self> {
+        Some(Self {
+            basic: StationStats::decode(input)?,
+            sum_sq: i128::from_le_bytes(partial::take(input)?),
+        })
+    }
+}
+
+impl Summary for Moments {
+    fn value(&self, stat: Stat) -> f64 {
+        match stat {
+            Stat::Stddev => {
+                // Population standard deviation
+                let n = self.basic.count as f64;
+                let mean = self.basic.value(Stat::Mean);
+                let variance = self.sum_sq as f64 / n - mean * mean;
+                variance.max(0.0).sqrt()
+            }
+            _ => self.basic.value(stat),
+        }
+    }
+}
+
+/// Everything in [`Moments`] plus the count of measurements at each value,
+/// for the median. The counts start out as a histogram with one bucket per
+/// fixed-point unit, covering -99.9..=99.9 at one decimal and growing when a
+/// value falls outside it. Values spread wider than [`DENSE_SPAN`] units, as
+/// finer `--decimals` readily are, are counted in a map instead, so memory
+/// follows the distinct values rather than their span.
+#[derive(Debug, Clone, Default)]
+pub struct Distribution {
+    moments: Moments,
+    /// Value counted by `histogram[0]`.
+    low: i32,
+    histogram: Vec<u64>,
+    /// The counts once they spread too wide for the histogram, which is
+    /// empty from then on.
+    sparse: BTreeMap<i32, u64>,
+}
+
+const INITIAL_SPAN: i32 = 999;
+
+/// Most buckets of a histogram: 32 KiB of counts.
+const DENSE_SPAN: u32 = 4096;
+
+/// Marks an encoded length as the number of `sparse` entries.
+const SPARSE: u32 = 1 << 31;
+
+impl Distribution {
+    fn high(&self) -> i32 {
+        self.low + self.histogram.len() as i32 - 1
+    }
+
+    /// Counts `n` measurements of `temp`, which the histogram does not
+    /// cover.
+    #[cold]
+    fn add(&mut self, temp: i32, n: u64) {
+        if self.sparse.is_empty() && self.cover(temp, temp) {
+            self.histogram[(temp - self.low) as usize] += n;
+        } else {
+            *self.sparse.entry(temp).or_default() += n;
+        }
+    }
+
+    /// Widens the histogram to cover `low..=high`, or moves its counts to
+    /// the map if it would span more than [`DENSE_SPAN`] units. Returns
+    /// whether the histogram is still used.
+    #[cold]
+    fn cover(&mut self, low: i32, high: i32) -> bool {
+        let (new_low, new_high) = if self.histogram.is_empty() {
+            let initial = (low.min(-INITIAL_SPAN), high.max(INITIAL_SPAN));
+            // Far from zero, the histogram starts out at the values alone
+            match initial.0.abs_diff(initial.1) < DENSE_SPAN {
+                true => initial,
+                false => (low, high),
+            }
+        } else {
+            (low.min(self.low), high.max(self.high()))
+        };
+        if new_low.abs_diff(new_high) >= DENSE_SPAN {
+            static WARNED: Once = Once::new();
+            WARNED.call_once(|| {
+                eprintln!(
+                    "warning: readings of a station span over {} steps, so each distinct one is kept for the median; memory grows with them",
+                    DENSE_SPAN
+                )
+            });
+            self.sparse = self.counts().collect();
+            self.histogram = Vec::new();
+            return false;
+        }
+        let mut histogram = vec![0; (new_high - new_low + 1) as usize];
+        if !self.histogram.is_empty() {
+            let start = (self.low - new_low) as usize;
+            histogram[start..start + self.histogram.len()].copy_from_slice(&self.histogram);
+        }
+        self.low = new_low;
+        self.histogram = histogram;
+        true
+    }
+
+    /// The values measured, in order, with how many times each was.
+    fn counts(&self) -> impl Iterator<Item = (i32, u64)> + '_ {
+        let dense = (self.low..).zip(self.histogram.iter().copied());
+        let sparse = self.sparse.iter().map(|(&temp, &n)| (temp, n));
+        dense.filter(|&(_, n)| n > 0).chain(sparse)
+    }
+
+    /// Returns the `k`-th smallest measurement (0-based).
+    fn nth(&self, k: u64) -> i32 {
+        let mut seen = 0;
+        for (temp, n) in self.counts() {
+            seen += n;
+            if seen > k {
+                return temp;
+            }
+        }
+        unreachable!("rank {} is beyond the measurement count", k)
+    }
+
+    /// Returns how many measurements were `temp`.
+    fn at(&self, temp: i32) -> f64 {
+        match self.histogram.is_empty() {
+            true => self.sparse.get(&temp).copied().unwrap_or(0) as f64,
+            false => self.histogram[(temp - self.low) as usize] as f64,
+        }
+    }
+}
+
+impl Aggregator for Distribution {
+    #[inline(always)]
+    fn update(&mut self, temp: i32) {
+        self.moments.update(temp);
+        // Below `low` wraps to past the end
+        let idx = temp.wrapping_sub(self.low) as u32 as usize;
+        match self.histogram.get_mut(idx) {
+            Some(n) => *n += 1,
+            None => self.add(temp, 1),
+        }
+    }
+
+    fn merge(&mut self, other: &Self) {
+        self.moments.merge(&other.moments);
+        if other.histogram.is_empty() {
+            for (&temp, &n) in &other.sparse {
+                self.add(temp, n);
+            }
+            return;
+        }
+        let covered = self.sparse.is_empty()
+            && !self.histogram.is_empty()
+            && other.low >= self.low
+            && other.high() <= self.high();
+        if covered || self.sparse.is_empty() && self.cover(other.low, other.high()) {
+            let start = (other.low - self.low) as usize;
+            for (a, b) in self.histogram[start..].iter_mut().zip(&other.histogram) {
+                *a += b;
+            }
+        } else {
+            for (temp, n) in other.counts() {
+                *self.sparse.entry(temp).or_default() += n;
+            }
+        }
+    }
+
+    fn encode(&self, out: &mut Vec<u8>) -> bool {
+        self.moments.encode(out);
+        out.extend_from_slice(&self.low.to_le_bytes());
+        if self.histogram.is_empty() && !self.sparse.is_empty() {
+            out.extend_from_slice(&(SPARSE | self.sparse.len() as u32).to_le_bytes());
+            for (temp, n) in &self.sparse {
+                out.extend_from_slice(&temp.to_le_bytes());
+                out.extend_from_slice(&n.to_le_bytes());
+            }
+            return true;
+        }
+        out.extend_from_slice(&(self.histogram.len() as u32).to_le_bytes());
+        for n in &self.histogram {
+            out.extend_from_slice(&n.to_le_bytes());
+        }
+        true
+    }
+
+    fn decode(input: &mut &[u8]) -> Option<Self> {
+        let moments = Moments::decode(input)?;
+        let low = i32::from_le_bytes(partial::take(input)?);
+        let len = u32::from_le_bytes(partial::take(input)?);
+        let mut decoded = Self {
+            moments,
+            low,
+            ..Self::default()
+        };
+        if len & SPARSE == 0 {
+            decoded.histogram = (0..len)
+                .map(|_| partial::take(input).map(u64::from_le_bytes))
+                .collect::<Option<_>>()?;
+        } else {
+            decoded.sparse = (0..len & !SPARSE)
+                .map(|_| {
+                    let temp = i32::from_le_bytes(partial::take(input)?);
+                    Some((temp, u64::from_le_bytes(partial::take(input)?)))
+                })
+                .collect::<Option<_>>()?;
+        }
+        Some(decoded)
+    }
+}
+
+impl Summary for Distribution {
+    fn value(&self, stat: Stat) -> f64 {
+        match stat {
+            Stat::Median => {
+                let n = self.moments.basic.count;
+                let lo = self.nth((n - 1) / 2);
+                let hi = self.nth(n / 2);
+                (lo + hi) as f64 / 2.0
+            }
+            Stat::MinCount => self.at(self.moments.basic.min),
+            Stat::MaxCount => self.at(self.moments.basic.max),
+            _ => self.moments.value(stat),
+        }
+    }
+}
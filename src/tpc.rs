@@ -0,0 +1,112 @@
+//! `--engine tpc`: a thread per core, as a comparison point for the pool in
+//! `parallel`. Each thread is pinned to its core and owns a monoio runtime
+//! (with its own io_uring where there is one), a read buffer and a table for
+//! an equal share of the file's lines, which it reads and aggregates alone:
+//! there is no queue of chunks and nothing is shared until the tables are
+//! merged at the end.
+
+use std::fs::File;
+use std::io;
+use std::thread;
+
+use monoio::buf::{IoBufMut, SliceMut};
+use monoio::{FusionDriver, RuntimeBuilder};
+
+use crate::cli::{Failure, Options};
+use crate::origin::Origin;
+use crate::{dictionary, parallel, Aggregator, LineFeeder, StationTable, READ_BUF_SIZE};
+
+/// io_uring submission queue entries per core. Each core has one read in
+/// flight at a time.
+const RING_ENTRIES: u32 = 8;
+
+/// Aggregates `data`, the bytes of `file` from offset `offset`, by reading
+/// them from the file again a share per core. `data` is only used to cut the
+/// shares at line boundaries.
+pub fn aggregate<A: Aggregator>(
+    file: &File,
+    data: &[u8],
+    offset: u64,
+    opts: &Options,
+    table: &mut StationTable<A>,
+) {
+    if opts.two_pass {
+        let keys = dictionary::sample_keys(data, table);
+        dictionary::seed(table, keys);
+    }
+    let cores = if parallel::single_threaded(data.len() as u64, opts) {
+        1
+    } else {
+        parallel::threads(opts)
+    };
+    if let Some(progress) = &table.progress {
+        progress.set_threads(cores);
+    }
+    let shares: Vec<(u64, u64)> = crate::line_chunks(data, data.len().div_ceil(cores).max(1))
+        .map(|share| {
+            let start = offset + (share.as_ptr() as usize - data.as_ptr() as usize) as u64;
+            (start, start + share.len() as u64)
+        })
+        .collect();
+
+    let partials: Vec<Result<StationTable<A>, Failure>> = thread::scope(|scope| {
+        let handles: Vec<_> = shares
+            .iter()
+            .enumerate()
+            .map(|(core, &(start, end))| {
+                let mut local = table.sibling();
+                let file = file.try_clone();
+                scope.spawn(move || {
+                    let read_error = |e| Failure::io(format!("reading input: {}", e));
+                    let file = file.map_err(read_error)?;
+                    // Fewer cores than threads only costs the pinning
+                    let _ = monoio::utils::bind_to_cpu_set([core]);
+                    let mut runtime = RuntimeBuilder::<FusionDriver>::new()
+                        .with_entries(RING_ENTRIES)
+                        .build()
+                        .map_err(|e| Failure::io(format!("starting runtime: {}", e)))?;
+                    runtime
+                        .block_on(read_share(file, start, end, &mut local))
+                        .map_err(read_error)?;
+                    Ok(local)
+                })
+            })
+            .collect();
+        handles.into_iter().map(parallel::join).collect()
+    });
+    for partial in partials {
+        match partial {
+            Ok(partial) => table.absorb(&partial),
+            Err(failure) => table.fault.raise(failure),
+        }
+    }
+}
+
+/// Reads the bytes of `file` from `start` to `end` into one buffer in turn,
+/// aggregating each read before the next.
+async fn read_share<A: Aggregator>(
+    file: File,
+    start: u64,
+    end: u64,
+    table: &mut StationTable<A>,
+) -> io::Result<()> {
+    let file = monoio::fs::File::from_std(file)?;
+    let mut feeder = LineFeeder::new();
+    table.origin = Origin::streamed_from(start);
+    let mut buf = Vec::with_capacity(READ_BUF_SIZE);
+    let mut offset = start;
+    while offset < end {
+        let want = (end - offset).min(READ_BUF_SIZE as u64) as usize;
+        let (read, slice): (_, SliceMut<Vec<u8>>) =
+            file.read_at(buf.slice_mut(0..want), offset).await;
+        buf = slice.into_inner();
+        let n = read?;
+        if n == 0 {
+            return Err(io::ErrorKind::UnexpectedEof.into());
+        }
+        feeder.feed(&buf[..n], table);
+        offset += n as u64;
+    }
+    feeder.finish(table);
+    Ok(())
+}
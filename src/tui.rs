@@ -0,0 +1,138 @@
+use std::io;
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+use ratatui::backend::CrosstermBackend;
+use ratatui::crossterm::execute;
+use ratatui::crossterm::terminal::{EnterAlternateScreen, LeaveAlternateScreen};
+use ratatui::layout::{Constraint, Layout};
+use ratatui::widgets::{Block, Gauge, List, Paragraph};
+use ratatui::{Frame, Terminal};
+
+use crate::cli::Options;
+use crate::display_name;
+use crate::progress::Progress;
+use crate::stats::{self, Stat, Summary};
+
+const REFRESH: Duration = Duration::from_millis(250);
+
+/// Stations shown in each of the hottest and coldest lists.
+const LEADERS: usize = 10;
+
+/// What the dashboard draws, rebuilt from a [`Progress`] every refresh.
+struct View {
+    ratio: Option<f64>,
+    status: String,
+    hottest: Vec<String>,
+    coldest: Vec<String>,
+}
+
+fn mib(bytes: u64) -> f64 {
+    bytes as f64 / (1 << 20) as f64
+}
+
+fn view<A: Summary>(
+    progress: &Progress<A>,
+    opts: &Options,
+    mut leaders: (Vec<String>, Vec<String>),
+) -> View {
+    let (done, total) = (progress.done(), progress.total());
+    let elapsed = progress.elapsed().as_secs_f64().max(1e-3);
+    let throughput = mib(done) / elapsed;
+    let mut status = format!(
+        "{:.0} MiB aggregated in {:.1}s, {:.0} MiB/s\nworker utilization: {:.0}%",
+        mib(done),
+        elapsed,
+        throughput,
+        100.0 * progress.busy().as_secs_f64() / (elapsed * progress.threads() as f64)
+    );
+    let ratio = (total > 0).then(|| (done as f64 / total as f64).min(1.0));
+    if let Some(ratio) = ratio.filter(|&r| r > 0.0) {
+        status.push_str(&format!(
+            "\nremaining: about {:.0}s",
+            elapsed * (1.0 - ratio) / ratio
+        ));
+    }
+
+    if let Some(snapshot) = progress.take_snapshot() {
+        let scale = stats::scale(opts.decimals);
+        let mut by_mean: Vec<(f64, String)> = snapshot
+            .iter()
+            .map(|(name, stats)| {
+                let mean = stats.value(Stat::Mean) / scale;
+                (mean, display_name(name, opts.invalid_utf8).into_owned())
+            })
+            .collect();
+        by_mean.sort_by(|a, b| a.0.total_cmp(&b.0));
+        let line = |(mean, name): &(f64, String)| format!("{:>6.1}  {}", mean, name);
+        leaders = (
+            by_mean.iter().rev().take(LEADERS).map(line).collect(),
+            by_mean.iter().take(LEADERS).map(line).collect(),
+        );
+    }
+    View {
+        ratio,
+        status,
+        hottest: leaders.0,
+        coldest: leaders.1,
+    }
+}
+
+fn draw(frame: &mut Frame, view: &View) {
+    let [gauge, status, lists] = Layout::vertical([
+        Constraint::Length(3),
+        Constraint::Length(5),
+        Constraint::Min(LEADERS as u16 + 2),
+    ])
+    .areas(frame.area());
+    let [hot, cold] =
+        Layout::horizontal([Constraint::Percentage(50), Constraint::Percentage(50)]).areas(lists);
+
+    let label = match view.ratio {
+        Some(ratio) => format!("{:.1}%", ratio * 100.0),
+        None => "input size unknown".to_string(),
+    };
+    frame.render_widget(
+        Gauge::default()
+            .block(Block::bordered().title("progress"))
+            .ratio(view.ratio.unwrap_or(0.0))
+            .label(label),
+        gauge,
+    );
+    frame.render_widget(
+        Paragraph::new(view.status.as_str()).block(Block::bordered().title("throughput")),
+        status,
+    );
+    frame.render_widget(
+        List::new(view.hottest.iter().map(String::as_str))
+            .block(Block::bordered().title("hottest (mean)")),
+        hot,
+    );
+    frame.render_widget(
+        List::new(view.coldest.iter().map(String::as_str))
+            .block(Block::bordered().title("coldest (mean)")),
+        cold,
+    );
+}
+
+/// Draws the dashboard on stderr until `progress` is finished, leaving stdout
+/// free for the results.
+pub fn run<A: Summary>(progress: Arc<Progress<A>>, opts: &Options) -> io::Result<()> {
+    let mut stderr = io::stderr();
+    execute!(stderr, EnterAlternateScreen)?;
+    let mut terminal = Terminal::new(CrosstermBackend::new(stderr))?;
+    terminal.hide_cursor()?;
+
+    let mut leaders = (Vec::new(), Vec::new());
+    while !progress.is_finished() {
+        progress.request_snapshot();
+        let current = view(&progress, opts, leaders);
+        terminal.draw(|frame| draw(frame, &current))?;
+        leaders = (current.hottest, current.coldest);
+        thread::sleep(REFRESH);
+    }
+
+    terminal.show_cursor()?;
+    execute!(terminal.backend_mut(), LeaveAlternateScreen)
+}
@@ -0,0 +1,214 @@
+//! Inputs too large for the address space, such as the full 13 GB
+//! measurements on a 32-bit target, are mapped a window at a time instead of
+//! whole: each window is aggregated like a mapped input and unmapped before
+//! the next, which starts at the line the previous one cut short.
+
+use std::fs::File;
+use std::io::{self, Read, Seek};
+use std::os::unix::io::AsRawFd;
+use std::sync::Arc;
+
+use crate::cli::{Exit, Failure, Options};
+use crate::{
+    advice, advise_sequential, archive, budget, compress, emit, parallel, resolve_encoding,
+    Aggregator, Encoding, StationTable, MAP_FLAGS,
+};
+
+/// Inputs larger than this are windowed even when mapping them whole might
+/// succeed, which on a 32-bit target would leave little address space for
+/// the tables of the worker threads.
+const MAX_WHOLE: Option<u64> = if usize::BITS < 64 {
+    Some(1 << 30)
+} else {
+    None
+};
+
+/// Bytes mapped at a time. A line must fit in one.
+const WINDOW_SIZE: usize = 256 << 20;
+
+/// A read-only mapping of part of a file, unmapped on drop.
+pub struct Mapping {
+    ptr: *mut libc::c_void,
+    len: usize,
+}
+
+impl Mapping {
+    /// Maps `len` bytes of `file` from `offset`, a multiple of the page size.
+    fn new(file: &File, offset: u64, len: usize) -> io::Result<Self> {
+        #[cfg(all(target_os = "linux", target_env = "gnu"))]
+        let (mmap, offset) = (libc::mmap64, offset as libc::off64_t);
+        #[cfg(not(all(target_os = "linux", target_env = "gnu")))]
+        let (mmap, offset) = (
+            libc::mmap,
+            libc::off_t::try_from(offset)
+                .map_err(|_| io::Error::from(io::ErrorKind::InvalidInput))?,
+        );
+        // SAFETY: a fresh read-only mapping that aliases no Rust memory
+        let ptr = unsafe {
+            mmap(
+                std::ptr::null_mut(),
+                len,
+                libc::PROT_READ,
+                MAP_FLAGS,
+                file.as_raw_fd(),
+                offset,
+            )
+        };
+        if ptr == libc::MAP_FAILED {
+            return Err(io::Error::last_os_error());
+        }
+        advise_sequential(ptr, len);
+        Ok(Self { ptr, len })
+    }
+
+    pub fn bytes(&self) -> &[u8] {
+        // SAFETY: the mapping lives as long as `self`
+        unsafe { std::slice::from_raw_parts(self.ptr as *const u8, self.len) }
+    }
+
+    /// Keeps the mapping for the rest of the process.
+    pub fn leak(self) -> &'static [u8] {
+        let mapping = std::mem::ManuallyDrop::new(self);
+        // SAFETY: never unmapped
+        unsafe { std::slice::from_raw_parts(mapping.ptr as *const u8, mapping.len) }
+    }
+}
+
+impl Drop for Mapping {
+    fn drop(&mut self) {
+        // SAFETY: unmaps exactly what `new` mapped, once nothing borrows it
+        unsafe { libc::munmap(self.ptr, self.len) };
+    }
+}
+
+/// Maps all `len` bytes of `file`, or returns `None` when the address space
+/// has no room for them.
+pub fn map_whole(file: &File, len: u64) -> Result<Option<Mapping>, Failure> {
+    if MAX_WHOLE.is_some_and(|max| len > max) {
+        return Ok(None);
+    }
+    let Ok(len) = usize::try_from(len) else {
+        return Ok(None);
+    };
+    match Mapping::new(file, 0, len) {
+        Ok(mapping) => Ok(Some(mapping)),
+        Err(e) if e.raw_os_error() == Some(libc::ENOMEM) => Ok(None),
+        Err(e) => Err(Failure::io(format!("mapping input: {}", e))),
+    }
+}
+
+fn page_size() -> u64 {
+    // SAFETY: sysconf only reads a system constant
+    match unsafe { libc::sysconf(libc::_SC_PAGESIZE) } {
+        size if size > 0 => size as u64,
+        _ => 4096,
+    }
+}
+
+/// Aggregates the `len` bytes of `file`, which would not fit in the address
+/// space mapped whole. Plain UTF-8 text is mapped a window at a time; archives,
+/// compressed files and other encodings are streamed.
+pub fn aggregate<A: Aggregator>(
+    file: &File,
+    len: u64,
+    opts: &Options,
+    table: &mut StationTable<A>,
+) -> Result<(), Failure> {
+    if opts.start_byte > 0 || opts.end_byte.is_some() || table.checkpoint.is_some() {
+        return Err(Failure::usage(
+            "--start-byte, --end-byte, --checkpoint and --resume need the input mapped whole, which is too large for the address space here",
+        ));
+    }
+    let read_error = |e: io::Error| Failure::io(format!("reading input: {}", e));
+    let mut reader = file;
+    reader.rewind().map_err(read_error)?;
+    let mut reader = advice::Readahead::new(reader.take(len), file, 0, len, opts);
+    let mut head = Vec::new();
+    (&mut reader)
+        .take(archive::TAR_SNIFF_LEN as u64)
+        .read_to_end(&mut head)
+        .map_err(read_error)?;
+    if archive::is_zip(&head) {
+        return Err(Failure::usage(
+            "zip archives need the input mapped whole, which is too large for the address space here",
+        ));
+    }
+    let (encoding, bom_len) = resolve_encoding(&head, opts)?;
+    let plain = compress::sniff(&head).is_none() && !archive::is_tar(&head);
+    if !plain || encoding != Encoding::Utf8 {
+        let reader = (&head[..]).chain(reader);
+        return match compress::sniff(&head) {
+            Some(format) => {
+                let reader = compress::decoder(format, reader).map_err(Failure::io)?;
+                crate::aggregate_stream(reader, opts, table)
+            }
+            None => crate::aggregate_stream(reader, opts, table),
+        };
+    }
+
+    if let Some(progress) = &table.progress {
+        progress.set_total(len - bom_len as u64);
+    }
+    if let Some(budget) = &table.budget {
+        budget.set_total(len - bom_len as u64);
+    }
+    // As for a whole mapping, --limit cuts the windows rather than counting
+    // rows as the workers aggregate them
+    table.limit = None;
+    let mut rows_left = opts.limit;
+    // Windows after the first keep the setup it was tuned to and the
+    // stations it was seeded with
+    let mut window_opts = opts.clone();
+    let page = page_size();
+    let mut offset = bom_len as u64;
+    while offset < len && rows_left != Some(0) && !table.fault.raised() {
+        let start = offset - offset % page;
+        let map_len = (len - start).min(WINDOW_SIZE as u64) as usize;
+        let mapping = Mapping::new(file, start, map_len)
+            .map_err(|e| Failure::io(format!("mapping input: {}", e)))?;
+        table.prefetch = advice::Prefetch::mapped(file, mapping.bytes(), start, opts).map(Arc::new);
+        table.origin.place(mapping.bytes(), start);
+        let bytes = &mapping.bytes()[(offset - start) as usize..];
+        let lines = if start + map_len as u64 == len {
+            bytes
+        } else {
+            match memchr::memrchr(b'\n', bytes) {
+                Some(i) => &bytes[..=i],
+                None => {
+                    return Err(Failure::new(
+                        Exit::Data,
+                        format!(
+                            "line at byte {} is longer than the {} MiB mapped at a time",
+                            offset,
+                            WINDOW_SIZE >> 20
+                        ),
+                    ))
+                }
+            }
+        };
+        offset += lines.len() as u64;
+        let lines = match rows_left {
+            Some(rows) => {
+                let cut = budget::first_rows(lines, rows);
+                rows_left = Some(rows - memchr::memchr_iter(b'\n', cut).count() as u64);
+                cut
+            }
+            None => lines,
+        };
+        emit::in_segments(lines, table, |lines, table| {
+            if opts.gpu {
+                crate::aggregate_gpu(lines, table);
+                return;
+            }
+            let setup = parallel::aggregate_mapped(lines, &window_opts, table);
+            if !parallel::single_threaded(lines.len() as u64, opts) {
+                window_opts.threads = setup.threads;
+                window_opts.chunk_size = setup.chunk_size;
+                window_opts.auto_tune = false;
+            }
+            window_opts.two_pass = false;
+        });
+    }
+    table.prefetch = None;
+    Ok(())
+}
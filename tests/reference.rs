@@ -0,0 +1,213 @@
+//! Property tests of the aggregation against a naive reference: random
+//! well-formed inputs go through both, and the statistics (and for the full
+//! pipeline, the formatted output) must be identical.
+
+use std::collections::BTreeMap;
+use std::fmt::Write as _;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use onebrc::cli::parse_args;
+use onebrc::{aggregate_and_report, aggregate_bytes, aggregate_slices, Results};
+use proptest::prelude::*;
+
+/// Min, max and sum in tenths of a degree, and the count, of one station.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Expected {
+    min: i32,
+    max: i32,
+    sum: i64,
+    count: u64,
+}
+
+/// The lines of an input, as names and temperatures in tenths of a degree.
+#[derive(Debug, Clone)]
+struct Input {
+    lines: Vec<(String, i32)>,
+    final_newline: bool,
+}
+
+impl Input {
+    fn render(&self) -> Vec<u8> {
+        let mut data = String::new();
+        for (name, temp) in &self.lines {
+            let _ = writeln!(data, "{};{}", name, degrees((*temp).into()));
+        }
+        if !self.final_newline {
+            data.pop();
+        }
+        data.into_bytes()
+    }
+
+    /// The reference aggregation, over `repeat` copies of the lines.
+    fn aggregate(&self, repeat: u64) -> BTreeMap<String, Expected> {
+        let mut stations = BTreeMap::new();
+        for (name, temp) in &self.lines {
+            let station = stations.entry(name.clone()).or_insert(Expected {
+                min: i32::MAX,
+                max: i32::MIN,
+                sum: 0,
+                count: 0,
+            });
+            station.min = station.min.min(*temp);
+            station.max = station.max.max(*temp);
+            station.sum += i64::from(*temp) * repeat as i64;
+            station.count += repeat;
+        }
+        stations
+    }
+}
+
+/// Tenths of a degree as text, e.g. -5 as "-0.5".
+fn degrees(tenths: i64) -> String {
+    let sign = if tenths < 0 { "-" } else { "" };
+    format!("{}{}.{}", sign, tenths.abs() / 10, tenths.abs() % 10)
+}
+
+/// The 1BRC output format, rendered from the reference with the mean
+/// rounded half up in exact integer arithmetic.
+fn format_spec(stations: &BTreeMap<String, Expected>) -> String {
+    let rows: Vec<String> = stations
+        .iter()
+        .map(|(name, s)| {
+            let count = s.count as i64;
+            let mean = (2 * s.sum + count).div_euclid(2 * count);
+            format!(
+                "{}={}/{}/{}",
+                name,
+                degrees(s.min.into()),
+                degrees(mean),
+                degrees(s.max.into())
+            )
+        })
+        .collect();
+    format!("{{{}}}\n", rows.join(", "))
+}
+
+fn assert_matches(results: &Results, expected: &BTreeMap<String, Expected>) {
+    assert_eq!(results.len(), expected.len());
+    for ((name, stats), (want_name, want)) in results.iter().zip(expected) {
+        assert_eq!(name, want_name);
+        assert_eq!(stats.count(), want.count, "count of {}", name);
+        assert_eq!(stats.min_f64(), want.min as f64 / 10.0, "min of {}", name);
+        assert_eq!(stats.max_f64(), want.max as f64 / 10.0, "max of {}", name);
+        let mean = want.sum as f64 / want.count as f64 / 10.0;
+        assert_eq!(stats.mean(), mean, "mean of {}", name);
+    }
+}
+
+fn input(max_lines: usize) -> impl Strategy<Value = Input> {
+    // A small pool of names so that stations repeat, some sharing their
+    // first eight bytes, which is all the table hashes. No U+FEFF, which
+    // is read as a byte order mark at the start of the input.
+    let names = prop::collection::vec("(Station){0,1}[^;\n\r\u{feff}]{1,16}", 1..50);
+    (names, any::<bool>()).prop_flat_map(move |(names, final_newline)| {
+        let line = (0..names.len(), -999..=999i32);
+        prop::collection::vec(line, 0..max_lines).prop_map(move |lines| Input {
+            lines: lines
+                .into_iter()
+                .map(|(i, temp)| (names[i].clone(), temp))
+                .collect(),
+            final_newline,
+        })
+    })
+}
+
+/// A file under the temporary directory, removed on drop.
+struct TempFile(PathBuf);
+
+impl TempFile {
+    fn new(contents: &[u8]) -> TempFile {
+        static NEXT: AtomicUsize = AtomicUsize::new(0);
+        let name = format!(
+            "onebrc-reference-{}-{}.txt",
+            std::process::id(),
+            NEXT.fetch_add(1, Ordering::Relaxed)
+        );
+        let path = std::env::temp_dir().join(name);
+        std::fs::write(&path, contents).unwrap();
+        TempFile(path)
+    }
+}
+
+impl Drop for TempFile {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.0);
+    }
+}
+
+/// Inputs at least this large are mapped and split between threads.
+const LARGE_INPUT: usize = 4 << 20;
+
+proptest! {
+    #[test]
+    fn bytes_match_reference(input in input(2000)) {
+        let data = input.render();
+        assert_matches(&aggregate_bytes(&data), &input.aggregate(1));
+    }
+
+    #[test]
+    fn slices_match_reference(input in input(500), cuts in prop::collection::vec(any::<usize>(), 0..8)) {
+        let data = input.render();
+        let mut cuts: Vec<usize> = cuts.into_iter().map(|cut| cut % (data.len() + 1)).collect();
+        cuts.sort_unstable();
+        let mut slices = Vec::new();
+        let mut start = 0;
+        for cut in cuts {
+            slices.push(&data[start..cut]);
+            start = cut;
+        }
+        slices.push(&data[start..]);
+        assert_matches(&aggregate_slices(slices), &input.aggregate(1));
+    }
+}
+
+proptest! {
+    // Every case writes and aggregates a few MiB
+    #![proptest_config(ProptestConfig::with_cases(24))]
+
+    #[test]
+    fn pipeline_output_matches_reference(
+        input in input(2000),
+        large in any::<bool>(),
+        threads in 1..4usize,
+        chunk_kib in 16..256usize,
+        batch in 1..=64usize,
+        interleave in any::<bool>(),
+        read in any::<bool>(),
+        table_size_log2 in 1..12u32,
+    ) {
+        let mut block = input.render();
+        if !block.is_empty() && !input.final_newline {
+            block.push(b'\n');
+        }
+        let repeat = match large && !block.is_empty() {
+            true => LARGE_INPUT.div_ceil(block.len()),
+            false => 1,
+        };
+        let mut data = block.repeat(repeat);
+        if !input.final_newline {
+            data.pop();
+        }
+        let file = TempFile::new(&data);
+
+        let args = [
+            format!("--threads={}", threads),
+            format!("--chunk-size={}K", chunk_kib),
+            format!("--batch={}", batch),
+            format!("--table-size={}", 1 << table_size_log2),
+            format!("--io={}", if read { "read" } else { "mmap" }),
+        ];
+        let mut args: Vec<String> = args.into_iter().collect();
+        if interleave {
+            args.push("--interleave".to_string());
+        }
+        args.push(file.0.to_str().unwrap().to_string());
+        let opts = parse_args(args.into_iter()).unwrap();
+
+        let mut out = Vec::new();
+        aggregate_and_report(&opts, &mut out).unwrap();
+        let expected = format_spec(&input.aggregate(repeat as u64));
+        prop_assert_eq!(String::from_utf8(out).unwrap(), expected);
+    }
+}
@@ -0,0 +1,263 @@
+//! Runs the binary over the inputs in `tests/samples` and compares its output
+//! byte for byte with the `.out` file next to each one. The samples follow
+//! the official 1BRC test set (single stations, boundary values and name
+//! lengths, complex UTF-8, rounding ties, 10,000 distinct stations), with
+//! the expected output produced by the 1BRC baseline implementation.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+fn samples() -> Vec<PathBuf> {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/samples");
+    let mut inputs: Vec<PathBuf> = fs::read_dir(dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "txt"))
+        .collect();
+    inputs.sort();
+    assert!(!inputs.is_empty());
+    inputs
+}
+
+/// Runs every sample with `args`, reporting all that differ at once.
+fn check(args: &[&str]) {
+    let mut failed = Vec::new();
+    for input in samples() {
+        let expected = fs::read_to_string(input.with_extension("out")).unwrap();
+        let output = Command::new(env!("CARGO_BIN_EXE_onebrc"))
+            .args(args)
+            .arg(&input)
+            .output()
+            .unwrap();
+        assert!(output.status.success(), "{}: {:?}", input.display(), output);
+        if output.stdout != expected.as_bytes() {
+            failed.push(format!(
+                "{}\n  expected: {}  actual:   {}",
+                input.display(),
+                expected,
+                String::from_utf8_lossy(&output.stdout)
+            ));
+        }
+    }
+    assert!(
+        failed.is_empty(),
+        "output differs for {:?}:\n{}",
+        args,
+        failed.join("\n")
+    );
+}
+
+#[test]
+fn samples_match() {
+    check(&[]);
+}
+
+#[test]
+fn samples_match_streamed() {
+    check(&["--io", "read"]);
+}
+
+#[test]
+fn samples_match_unbatched_interleaved() {
+    check(&["--batch", "1", "--interleave"]);
+}
+
+#[test]
+fn samples_match_growing_table() {
+    check(&["--table-size", "2"]);
+}
+
+/// The workers that aggregated any rows, as listed by `--run-stats`.
+fn busy_workers(stderr: &[u8]) -> usize {
+    let stats = String::from_utf8_lossy(stderr);
+    let workers = stats
+        .lines()
+        .skip_while(|line| !line.starts_with("worker stats:"));
+    workers
+        .filter_map(|line| {
+            let mut fields = line.split_whitespace();
+            fields.next()?.parse::<usize>().ok()?;
+            fields.next()?.parse::<u64>().ok()
+        })
+        .filter(|&rows| rows > 0)
+        .count()
+}
+
+#[test]
+fn samples_match_threaded() {
+    let args = ["--threads", "4", "--chunk-size", "64K"];
+    check(&args);
+    // Only inputs of several chunks can be shared out
+    for input in samples()
+        .iter()
+        .filter(|input| fs::metadata(input).unwrap().len() > 64 << 10)
+    {
+        let output = Command::new(env!("CARGO_BIN_EXE_onebrc"))
+            .args(args)
+            .arg("--run-stats")
+            .arg(input)
+            .output()
+            .unwrap();
+        assert!(output.status.success(), "{}: {:?}", input.display(), output);
+        assert!(
+            busy_workers(&output.stderr) > 1,
+            "{}: {:?}",
+            input.display(),
+            output
+        );
+    }
+}
+
+/// Splits every sample at a few offsets with `--start-byte`/`--end-byte`,
+/// dumps each range with `--format partial` and merges the dumps.
+#[test]
+fn samples_match_sharded() {
+    let dir = std::env::temp_dir().join(format!("onebrc-shards-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    for input in samples() {
+        let len = fs::metadata(&input).unwrap().len();
+        let cuts = [0, 1, len / 3, len / 2 + 1, len];
+        let mut dumps = Vec::new();
+        for (i, range) in cuts.windows(2).enumerate() {
+            let dump = dir.join(format!("{}.part", i));
+            let output = Command::new(env!("CARGO_BIN_EXE_onebrc"))
+                .args(["--format", "partial", "--start-byte"])
+                .arg(range[0].to_string())
+                .arg("--end-byte")
+                .arg(range[1].max(range[0] + 1).to_string())
+                .arg(&input)
+                .output()
+                .unwrap();
+            assert!(output.status.success(), "{}: {:?}", input.display(), output);
+            fs::write(&dump, output.stdout).unwrap();
+            dumps.push(dump);
+        }
+        let output = Command::new(env!("CARGO_BIN_EXE_onebrc"))
+            .arg("merge")
+            .args(&dumps)
+            .output()
+            .unwrap();
+        let expected = fs::read_to_string(input.with_extension("out")).unwrap();
+        assert_eq!(
+            String::from_utf8_lossy(&output.stdout),
+            expected,
+            "{}",
+            input.display()
+        );
+    }
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn samples_match_baseline() {
+    check(&["--baseline"]);
+}
+
+#[test]
+fn samples_verify() {
+    check(&["--verify"]);
+}
+
+#[test]
+fn samples_pass_quality() {
+    for input in samples() {
+        let output = Command::new(env!("CARGO_BIN_EXE_onebrc"))
+            .arg("quality")
+            .arg(&input)
+            .output()
+            .unwrap();
+        assert!(output.status.success(), "{}: {:?}", input.display(), output);
+        let report = String::from_utf8(output.stdout).unwrap();
+        let invalid = report.lines().find(|line| line.contains("invalid lines"));
+        assert!(
+            invalid.is_some_and(|line| line.ends_with(" 0")),
+            "{}:\n{}",
+            input.display(),
+            report
+        );
+    }
+}
+
+#[test]
+fn samples_in_input_order() {
+    for input in samples() {
+        let data = fs::read_to_string(&input).unwrap();
+        let mut expected: Vec<&str> = Vec::new();
+        for line in data.lines() {
+            let name = line.split(';').next().unwrap();
+            if !expected.contains(&name) {
+                expected.push(name);
+            }
+        }
+        let output = Command::new(env!("CARGO_BIN_EXE_onebrc"))
+            .args(["--order", "input", "--threads", "4", "--chunk-size", "4K"])
+            .args(["--format", "csv", "--stats", "count", "--run-stats"])
+            .arg(&input)
+            .output()
+            .unwrap();
+        assert!(output.status.success(), "{}: {:?}", input.display(), output);
+        if data.len() > 4 << 10 {
+            assert!(
+                busy_workers(&output.stderr) > 1,
+                "{}: {:?}",
+                input.display(),
+                output
+            );
+        }
+        let csv = String::from_utf8(output.stdout).unwrap();
+        // Names with commas or quotes are quoted, so split off the count only
+        let names: Vec<String> = csv
+            .lines()
+            .skip(1)
+            .map(|row| row.rsplit_once(',').unwrap().0.to_string())
+            .collect();
+        assert_eq!(names.len(), expected.len(), "{}", input.display());
+        for (name, expected) in names.iter().zip(&expected) {
+            assert!(
+                name == expected || name.trim_matches('"').replace("\"\"", "\"") == *expected,
+                "{}: {} != {}",
+                input.display(),
+                name,
+                expected
+            );
+        }
+    }
+}
+
+/// Appends a malformed line to every sample: the run must stop with the
+/// status of bad data, naming the line by its offset.
+#[test]
+fn samples_with_malformed_line() {
+    let dir = std::env::temp_dir().join(format!("onebrc-malformed-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    for input in samples() {
+        let mut data = fs::read(&input).unwrap();
+        if !data.ends_with(b"\n") {
+            data.push(b'\n');
+        }
+        let offset = data.len();
+        data.extend_from_slice(b"no separator\n");
+        let corrupt = dir.join(input.file_name().unwrap());
+        fs::write(&corrupt, &data).unwrap();
+        let output = Command::new(env!("CARGO_BIN_EXE_onebrc"))
+            .arg(&corrupt)
+            .output()
+            .unwrap();
+        assert_eq!(
+            output.status.code(),
+            Some(3),
+            "{}: {:?}",
+            input.display(),
+            output
+        );
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        assert!(
+            stderr.contains(&format!("line at byte {}: no ';' separator", offset)),
+            "{}: {}",
+            input.display(),
+            stderr
+        );
+    }
+    fs::remove_dir_all(&dir).unwrap();
+}